@@ -0,0 +1,80 @@
+//! Measures datagram round-trip times and stream throughput against an echo server, such as
+//! the one in `examples/echo-server`.
+//!
+//! Usage: `cargo run --example benchmark --features native -- <url> [certificate sha-256]`
+//!
+//! The certificate hash is needed for self-signed servers, see
+//! `NativeOptions::server_certificate_hashes`.
+
+use futures::channel::oneshot;
+use std::rc::Rc;
+use yew::platform::Runtime;
+use yew::Callback;
+use yew_webtransport::bench::{Benchmark, LatencyConfig};
+use yew_webtransport::certhash::CertificateHash;
+use yew_webtransport::native::{NativeOptions, WebTransportService};
+use yew_webtransport::transport::Transport;
+
+const STREAM_SIZES: [usize; 3] = [64 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+
+async fn run(url: String, options: NativeOptions) -> Result<(), String> {
+    let task = WebTransportService::connect_with_options(
+        &url,
+        options,
+        Callback::noop(),
+        Callback::noop(),
+        Callback::noop(),
+        Callback::noop(),
+    )
+    .map_err(|e| e.to_string())?;
+    task.transport.ready().await?;
+    println!("connected to {url}");
+
+    let task = Rc::new(task);
+    let benchmark = Benchmark::new(task.clone());
+    let latency = benchmark.datagram_latency(LatencyConfig::default()).await;
+    println!("datagrams: {latency}");
+    for size in STREAM_SIZES {
+        let throughput = benchmark
+            .stream_throughput(size)
+            .await
+            .map_err(|e| e.to_string())?;
+        println!("stream: {throughput}");
+    }
+    let _ = task.close();
+    Ok(())
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(url) = args.next() else {
+        eprintln!("usage: benchmark <url> [certificate sha-256]");
+        std::process::exit(2);
+    };
+    let mut options = NativeOptions::default();
+    if let Some(hash) = args.next() {
+        match hash.parse::<CertificateHash>() {
+            Ok(hash) => options.server_certificate_hashes.push(hash.into()),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let (done, result) = oneshot::channel();
+    Runtime::default().spawn_pinned(move || async move {
+        let _ = done.send(run(url, options).await);
+    });
+    match futures::executor::block_on(result) {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            eprintln!("benchmark failed: {e}");
+            std::process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("benchmark task stopped unexpectedly");
+            std::process::exit(1);
+        }
+    }
+}
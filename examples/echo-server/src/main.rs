@@ -0,0 +1,98 @@
+//! A WebTransport server echoing everything it receives, for the demos, the benchmark and the
+//! integration tests.
+//!
+//! Datagrams are sent straight back, bidirectional streams are echoed on the same stream and
+//! unidirectional streams are echoed on a new unidirectional stream opened by the server.
+//!
+//! Usage: `cargo run --manifest-path examples/echo-server/Cargo.toml -- [port] [cert.pem key.pem]`
+//!
+//! Without a certificate, the server generates a self-signed one valid for two weeks, the
+//! longest browsers accept, and prints its SHA-256 hash for `serverCertificateHashes`.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use wtransport::endpoint::IncomingSession;
+use wtransport::{Connection, Endpoint, Identity, RecvStream, ServerConfig};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let port: u16 = args.next().unwrap_or_else(|| "4433".into()).parse()?;
+    let identity = match args.next() {
+        Some(cert) => {
+            let key = args.next().context("missing key path")?;
+            Identity::load_pemfiles(cert, key).await?
+        }
+        None => Identity::self_signed(["localhost", "127.0.0.1", "::1"])?,
+    };
+    let certificate = identity
+        .certificate_chain()
+        .as_slice()
+        .first()
+        .context("empty certificate chain")?;
+    let hash: String = Sha256::digest(certificate.der())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    let config = ServerConfig::builder()
+        .with_bind_default(port)
+        .with_identity(identity)
+        .build();
+    let server = Endpoint::server(config)?;
+    println!("echo server listening on https://127.0.0.1:{port}");
+    println!("certificate sha-256: {hash}");
+
+    loop {
+        let incoming = server.accept().await;
+        tokio::spawn(async move {
+            if let Err(e) = handle_session(incoming).await {
+                eprintln!("session ended: {e:?}");
+            }
+        });
+    }
+}
+
+async fn handle_session(incoming: IncomingSession) -> Result<()> {
+    let request = incoming.await?;
+    let connection = request.accept().await?;
+    loop {
+        tokio::select! {
+            stream = connection.accept_bi() => {
+                let (mut send, recv) = stream?;
+                tokio::spawn(async move {
+                    let data = read_to_end(recv).await?;
+                    send.write_all(&data).await?;
+                    send.finish().await?;
+                    anyhow::Ok(())
+                });
+            }
+            stream = connection.accept_uni() => {
+                let recv = stream?;
+                let connection = connection.clone();
+                tokio::spawn(async move { echo_unidirectional(connection, recv).await });
+            }
+            datagram = connection.receive_datagram() => {
+                let datagram = datagram?;
+                connection.send_datagram(datagram.payload())?;
+            }
+        }
+    }
+}
+
+async fn echo_unidirectional(connection: Connection, recv: RecvStream) -> Result<()> {
+    let data = read_to_end(recv).await?;
+    let mut send = connection.open_uni().await?.await?;
+    send.write_all(&data).await?;
+    send.finish().await?;
+    Ok(())
+}
+
+async fn read_to_end(mut recv: RecvStream) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while let Some(read) = recv.read(&mut buffer).await? {
+        data.extend_from_slice(&buffer[..read]);
+    }
+    Ok(data)
+}
@@ -1,42 +1,480 @@
 use chrono::Local;
+use futures::future;
 use gloo_console::log;
-use js_sys::{Boolean, JsString, Reflect, Uint8Array};
-use wasm_bindgen::{JsCast};
-use wasm_bindgen_futures::JsFuture;
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::{Array, ArrayBuffer, Uint8Array};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::File;
+use web_sys::HtmlAnchorElement;
 use web_sys::HtmlInputElement;
+use web_sys::HtmlSelectElement;
 use web_sys::HtmlTextAreaElement;
 use web_sys::KeyboardEvent;
-use web_sys::ReadableStreamDefaultReader;
 use web_sys::WebTransportBidirectionalStream;
-use web_sys::WebTransportCloseInfo;
+use web_sys::WebTransportDatagramDuplexStream;
 use web_sys::WebTransportReceiveStream;
 
 use yew::prelude::*;
 use yew::TargetCast;
 use yew::{html, Component, Context, Html};
-use yew_webtransport::webtransport::process_binary;
-use yew_webtransport::webtransport::{WebTransportService, WebTransportStatus, WebTransportTask};
+use yew_webtransport::bench::{Benchmark, LatencyConfig};
+use yew_webtransport::capture::SessionCapture;
+use yew_webtransport::certhash::CertificateHash;
+use yew_webtransport::channel::DatagramChannel;
+use yew_webtransport::loopback::LoopbackTransport;
+use yew_webtransport::media::{FrameClass, FrameScheduler, FrameSchedulerConfig};
+use yew_webtransport::mux::{MuxChannel, StreamMux};
+use yew_webtransport::reconnect::{ReconnectPolicy, ReconnectState, ReconnectingSession};
+use yew_webtransport::sessions::{SessionState, WebTransportPool};
+use yew_webtransport::time::{PlatformScheduler, Scheduler};
+use yew_webtransport::transport::{Transport, TransportStats};
+use yew_webtransport::upload::encode_segment;
+use yew_webtransport::webtransport::{
+    LabeledStatus, WebTransportError, WebTransportService, WebTransportStatus, WebTransportTask,
+};
 
 const DEFAULT_URL: &str = std::env!("WS_URL");
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// Bytes of the file sent per stream.
+const UPLOAD_CHUNK_SIZE: u64 = 256 * 1024;
+/// Time between two refreshes of the connection state while reconnecting.
+const RECONNECT_TICK: Duration = Duration::from_millis(250);
+/// Time between two pings in continuous mode.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Round-trip times kept, and plotted, per path.
+const PING_HISTORY: usize = 60;
+/// Size of a ping, on either path.
+const PING_SIZE: usize = 32;
+/// Where the settings are kept across page reloads.
+const SETTINGS_KEY: &str = "yew-webtransport-demo.settings";
+/// Where the tabs and their event logs are kept across page reloads.
+const TABS_KEY: &str = "yew-webtransport-demo.tabs";
+/// Lines of each event log kept across page reloads.
+const LOG_HISTORY: usize = 200;
+/// Time between two synthetic video frames, 30 frames per second.
+const MEDIA_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// Size of the synthetic audio frame sent along with every video frame.
+const AUDIO_FRAME_SIZE: usize = 160;
+/// How long each class of synthetic frame may wait to be sent before it is dropped.
+const AUDIO_DEADLINE: Duration = Duration::from_millis(100);
+const KEYFRAME_DEADLINE: Duration = Duration::from_secs(1);
+const DELTA_FRAME_DEADLINE: Duration = Duration::from_millis(300);
 
 pub enum WsAction {
     Connect,
     SendData(),
     SetText(String),
     SetUrl(String),
+    SetCertificateHash(String),
     SetMessageType(WebTransportMessageType),
     Log(String),
     Disconnect,
-    Lost(String),
-    Connected,
+    Lost(Rc<str>, String),
+    Connected(Rc<str>),
 }
 
 pub enum Msg {
     WsAction(WsAction),
-    OnDatagram(Vec<u8>),
-    OnUniStream(WebTransportReceiveStream),
-    OnBidiStream(WebTransportBidirectionalStream),
-    OnMessage(Vec<u8>, WebTransportMessageType),
+    OnDatagram(Rc<str>, Vec<u8>),
+    OnUniStream(Rc<str>, WebTransportReceiveStream),
+    OnBidiStream(Rc<str>, WebTransportBidirectionalStream),
+    OnMessage(Rc<str>, Vec<u8>, WebTransportMessageType),
+    /// Adds a line to the log of the named session.
+    LogTo(Rc<str>, String),
+    AddTab,
+    SelectTab(usize),
+    CloseTab,
+    RemoveTab(usize),
+    /// Time to refresh the statistics of the session with the given number.
+    PollStats(u32),
+    OnStats(TransportStats),
+    /// Bytes were received on the stream with the given number.
+    StreamReceived(u64, usize),
+    /// The stream with the given number is done, successfully or not.
+    StreamClosed(u64),
+    StartUpload(File),
+    UploadProgress(u64),
+    UploadDone(Result<(), String>),
+    CancelUpload,
+    Ping,
+    /// Time for the next ping of continuous mode, if still on for the given session.
+    ContinuousPing(u32),
+    SetContinuousPing(bool),
+    /// Round-trip time of a ping over a datagram, `None` when no answer came back.
+    OnDatagramPing(Option<Duration>),
+    /// Round-trip time of a ping over a bidirectional stream, `None` when it failed.
+    OnStreamPing(Option<Duration>),
+    SetHexView(bool),
+    /// Downloads what the session of the tab shown received, as recorded by its capture.
+    ExportRecording,
+    SetAutoReconnect(bool),
+    /// Whether sessions connected from now on echo locally instead of reaching the server.
+    SetLoopback(bool),
+    /// Time to refresh the connection state while reconnecting.
+    ReconnectTick,
+    SetStressCount(usize),
+    SetStressSize(usize),
+    StartStress,
+    /// A stress test is over: how long it took, or why it stopped.
+    OnStress(Result<Duration, String>),
+    SetKeyframeSize(usize),
+    SetDeltaFrameSize(usize),
+    SetKeyframeInterval(u64),
+    /// Bandwidth given to the synthetic media, in kilobytes per second, 0 for unlimited.
+    SetMediaBandwidth(u64),
+    StartMedia,
+    StopMedia,
+    /// Time to generate the next synthetic frames, for the given session.
+    MediaTick(u32),
+    SetChannelLayer(ChannelLayer),
+    SetChannelId(u64),
+    SetChannelText(String),
+    /// Registers the channel selected, if it is not open yet.
+    OpenChannel,
+    /// Sends the channel text on the channel selected.
+    SendOnChannel,
+    CloseChannel(ChannelLayer, u64),
+    /// A message received on a channel of the given session.
+    OnChannelMessage(u32, ChannelLayer, u64, Vec<u8>),
+}
+
+/// Who opened a stream, and which way its data flows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamDirection {
+    OutgoingUnidirectional,
+    OutgoingBidirectional,
+    IncomingUnidirectional,
+    IncomingBidirectional,
+}
+
+impl StreamDirection {
+    fn name(self) -> &'static str {
+        match self {
+            StreamDirection::OutgoingUnidirectional => "Outgoing, unidirectional",
+            StreamDirection::OutgoingBidirectional => "Outgoing, bidirectional",
+            StreamDirection::IncomingUnidirectional => "Incoming, unidirectional",
+            StreamDirection::IncomingBidirectional => "Incoming, bidirectional",
+        }
+    }
+}
+
+/// A stream being written or read. Browsers don't expose QUIC stream ids, so streams are
+/// numbered in the order the demo opened or accepted them.
+pub struct OpenStream {
+    pub number: u64,
+    /// Tab of the session the stream belongs to.
+    pub tab: Rc<str>,
+    pub direction: StreamDirection,
+    pub bytes_out: usize,
+    pub bytes_in: usize,
+}
+
+impl OpenStream {
+    fn state(&self) -> &'static str {
+        match self.direction {
+            StreamDirection::OutgoingUnidirectional => "Sending",
+            StreamDirection::OutgoingBidirectional => "Waiting for the response",
+            StreamDirection::IncomingUnidirectional | StreamDirection::IncomingBidirectional => {
+                if self.bytes_in == 0 {
+                    "Accepted"
+                } else {
+                    "Receiving"
+                }
+            }
+        }
+    }
+}
+
+/// The routing layer a channel is carried by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayer {
+    /// A [`DatagramChannel`], each message a datagram prefixed with the channel id.
+    Datagram,
+    /// A [`MuxChannel`], each message a frame on the session's [`StreamMux`].
+    Stream,
+}
+
+impl ChannelLayer {
+    fn name(self) -> &'static str {
+        match self {
+            ChannelLayer::Datagram => "Datagram channel",
+            ChannelLayer::Stream => "Stream mux channel",
+        }
+    }
+}
+
+pub enum ChannelHandle {
+    Datagram(DatagramChannel),
+    Stream(MuxChannel),
+}
+
+/// A channel registered on the session shown, with the messages it sent and received.
+pub struct OpenChannel {
+    pub layer: ChannelLayer,
+    pub id: u64,
+    pub handle: ChannelHandle,
+    /// Newest first.
+    pub log: Vec<String>,
+}
+
+impl OpenChannel {
+    fn send(&mut self, data: &[u8]) {
+        match &self.handle {
+            ChannelHandle::Datagram(channel) => channel.send(data),
+            ChannelHandle::Stream(channel) => channel.send(data),
+        }
+        self.record(format!("Sent: {}", String::from_utf8_lossy(data)));
+    }
+
+    fn record(&mut self, text: String) {
+        let text = format!("{}: {}", Local::now().format("%H:%M:%S%.3f"), text);
+        self.log.insert(0, text);
+        self.log.truncate(LOG_HISTORY);
+    }
+}
+
+/// Synthetic media sent the way a video call would: an audio frame and a video frame every
+/// [`MEDIA_FRAME_INTERVAL`], a keyframe every `keyframe_interval` video frames and delta frames
+/// in between, all through a [`FrameScheduler`].
+pub struct MediaGenerator {
+    pub scheduler: FrameScheduler<Rc<dyn Transport>>,
+    /// Video frames generated so far.
+    pub frames: u64,
+    /// Video frames left until the next keyframe.
+    pub until_keyframe: u64,
+}
+
+impl MediaGenerator {
+    /// Queues the frames of the next tick.
+    fn tick(&mut self, keyframe_size: usize, delta_frame_size: usize, keyframe_interval: u64) {
+        self.scheduler.enqueue(
+            FrameClass::Audio,
+            synthetic_frame(FrameClass::Audio, self.frames, AUDIO_FRAME_SIZE),
+            AUDIO_DEADLINE,
+        );
+        let (class, size, deadline) = if self.until_keyframe == 0 {
+            self.until_keyframe = keyframe_interval;
+            (FrameClass::VideoKey, keyframe_size, KEYFRAME_DEADLINE)
+        } else {
+            (
+                FrameClass::VideoDelta,
+                delta_frame_size,
+                DELTA_FRAME_DEADLINE,
+            )
+        };
+        self.scheduler
+            .enqueue(class, synthetic_frame(class, self.frames, size), deadline);
+        self.frames += 1;
+        self.until_keyframe -= 1;
+    }
+}
+
+/// A frame of `size` bytes starting with its class and number, so that it can be told apart
+/// when echoed back.
+fn synthetic_frame(class: FrameClass, number: u64, size: usize) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(size.max(9));
+    frame.push(class.send_order() as u8);
+    frame.extend_from_slice(&number.to_be_bytes());
+    frame.resize(size.max(frame.len()), 0x5a);
+    frame
+}
+
+/// A stress test: `count` messages of `size` bytes, sent as fast as possible.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StressTest {
+    /// Session the test runs on.
+    pub tab: Rc<str>,
+    pub message_type: WebTransportMessageType,
+    pub count: usize,
+    pub size: usize,
+}
+
+impl StressTest {
+    /// Sends every message over `transport`, resolving once they were all sent, or echoed for
+    /// bidirectional streams.
+    async fn run(self, transport: Rc<WebTransportTask>) -> Result<(), String> {
+        let payload = vec![0xa5; self.size];
+        match self.message_type {
+            WebTransportMessageType::Datagram => {
+                write_datagrams(transport.transport.datagrams(), &payload, self.count).await
+            }
+            WebTransportMessageType::UnidirectionalStream => {
+                let sends = (0..self.count)
+                    .map(|_| transport.send_unidirectional_stream_finished(payload.clone()));
+                future::try_join_all(sends)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            WebTransportMessageType::BidirectionalStream => {
+                let requests = (0..self.count).map(|_| transport.request(payload.clone()));
+                let echoed = future::try_join_all(requests)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let lost = echoed.iter().filter(|echo| echo.len() != self.size).count();
+                if lost > 0 {
+                    return Err(format!("{lost} messages were not fully echoed"));
+                }
+                Ok(())
+            }
+            WebTransportMessageType::Unknown => Err("no message type selected".to_string()),
+        }
+    }
+
+    fn report(&self, elapsed: Duration) -> String {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        format!(
+            "Stress test: {} messages of {} bytes over {:?} in {:.3} s, {:.0} messages/s, {:.2} MB/s",
+            self.count,
+            self.size,
+            self.message_type,
+            elapsed.as_secs_f64(),
+            self.count as f64 / secs,
+            (self.count * self.size) as f64 / secs / 1_000_000.0
+        )
+    }
+}
+
+/// Round-trip times measured over one path.
+#[derive(Default)]
+pub struct PingSeries {
+    /// The last [`PING_HISTORY`] round-trip times, oldest first.
+    pub samples: Vec<Duration>,
+    /// Pings left unanswered.
+    pub lost: usize,
+}
+
+impl PingSeries {
+    fn record(&mut self, rtt: Option<Duration>) {
+        let Some(rtt) = rtt else {
+            self.lost += 1;
+            return;
+        };
+        if self.samples.len() == PING_HISTORY {
+            self.samples.remove(0);
+        }
+        self.samples.push(rtt);
+    }
+
+    fn summary(&self) -> String {
+        let ms = |rtt: Duration| rtt.as_secs_f64() * 1000.0;
+        let (Some(min), Some(max)) = (self.samples.iter().min(), self.samples.iter().max()) else {
+            return format!("- ({} lost)", self.lost);
+        };
+        let avg = self.samples.iter().sum::<Duration>() / self.samples.len() as u32;
+        format!(
+            "min {:.1} ms / avg {:.1} ms / max {:.1} ms ({} lost)",
+            ms(*min),
+            ms(avg),
+            ms(*max),
+            self.lost
+        )
+    }
+
+    /// The samples as the points of an SVG polyline `width` by `height`, with `top` at the top.
+    fn points(&self, width: f64, height: f64, top: Duration) -> String {
+        let step = width / (PING_HISTORY - 1) as f64;
+        let top = top.as_secs_f64().max(f64::EPSILON);
+        self.samples
+            .iter()
+            .enumerate()
+            .map(|(i, rtt)| {
+                let y = height - rtt.as_secs_f64() / top * height;
+                format!("{:.1},{:.1}", i as f64 * step, y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A line of the event log, with a hex dump of the data it is about, if any.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub text: String,
+    pub dump: Option<String>,
+}
+
+/// A session of the demo, shown in its own tab.
+pub struct Tab {
+    /// Name of the session in the pool, also its label.
+    pub name: Rc<str>,
+    pub log: Vec<LogEntry>,
+    pub connected: bool,
+    /// The session being kept up, when connected with auto-reconnect on. Other sessions are
+    /// kept in [`Model::pool`].
+    pub reconnecting: Option<ReconnectingSession<WebTransportTask>>,
+    /// When the next attempt to reconnect is due, on the [`PlatformScheduler`] clock.
+    pub retry_at: Option<Duration>,
+    /// Everything the tab's sessions received, for exporting.
+    pub capture: SessionCapture,
+    /// The session, when connected in loopback mode.
+    pub loopback: Option<LoopbackTransport>,
+}
+
+impl Tab {
+    fn new(number: u32) -> Self {
+        Self::restored(SavedTab {
+            name: format!("Session {number}"),
+            log: Vec::new(),
+        })
+    }
+
+    fn restored(saved: SavedTab) -> Self {
+        Self {
+            name: saved.name.into(),
+            log: saved.log,
+            connected: false,
+            reconnecting: None,
+            retry_at: None,
+            capture: SessionCapture::new(),
+            loopback: None,
+        }
+    }
+
+    /// The number the tab was opened with, from its name.
+    fn number(&self) -> u32 {
+        self.name
+            .strip_prefix("Session ")
+            .and_then(|number| number.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// A tab as kept across page reloads: its name and the end of its event log.
+#[derive(Serialize, Deserialize)]
+pub struct SavedTab {
+    pub name: String,
+    pub log: Vec<LogEntry>,
+}
+
+/// The settings kept across page reloads.
+#[derive(Serialize, Deserialize)]
+pub struct Settings {
+    pub endpoint: String,
+    pub message_type: WebTransportMessageType,
+    pub certificate_hash: String,
+    #[serde(default)]
+    pub loopback: bool,
+}
+
+/// A file being uploaded.
+pub struct Upload {
+    /// Session the file is uploaded on.
+    pub tab: Rc<str>,
+    pub name: String,
+    pub size: u64,
+    /// Bytes sent so far.
+    pub sent: u64,
+    /// Set to stop the upload before its next chunk.
+    pub cancelled: Rc<Cell<bool>>,
 }
 
 impl From<WsAction> for Msg {
@@ -45,7 +483,7 @@ impl From<WsAction> for Msg {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WebTransportMessageType {
     Datagram,
     UnidirectionalStream,
@@ -55,12 +493,921 @@ pub enum WebTransportMessageType {
 
 pub struct Model {
     pub fetching: bool,
-    pub transport: Option<WebTransportTask>,
-    pub connected: bool,
-    pub log: Vec<String>,
+    /// Sessions connected without auto-reconnect, by tab name.
+    pub pool: WebTransportPool,
+    pub tabs: Vec<Tab>,
+    /// Index of the tab shown.
+    pub active: usize,
+    /// Tabs opened so far, used to number the next one.
+    pub opened_tabs: u32,
+    /// Whether received messages are shown as a hex dump even when they are valid text.
+    pub hex_view: bool,
     pub endpoint: String,
+    /// SHA-256 hash of the server's certificate, in hex or base64, for self-signed servers.
+    pub certificate_hash: String,
     pub text: String,
     pub message_type: WebTransportMessageType,
+    /// Number of the current session, so that polling for a previous one, or for the session
+    /// of another tab, stops.
+    pub session: u32,
+    pub stats: Option<TransportStats>,
+    /// Streams being written or read, on any tab.
+    pub streams: Vec<OpenStream>,
+    /// Streams opened or accepted so far, used to number the next one.
+    pub streams_seen: u64,
+    pub upload: Option<Upload>,
+    /// Uploads started so far, used as the id of the next one.
+    pub uploads: u64,
+    pub datagram_pings: PingSeries,
+    pub stream_pings: PingSeries,
+    /// Answers still expected from the last ping.
+    pub pinging: usize,
+    pub continuous_ping: bool,
+    pub stress_count: usize,
+    pub stress_size: usize,
+    /// The stress test running, if any.
+    pub stress: Option<StressTest>,
+    pub keyframe_size: usize,
+    pub delta_frame_size: usize,
+    /// Video frames from one keyframe to the next.
+    pub keyframe_interval: u64,
+    /// Bandwidth given to the synthetic media, in kilobytes per second, 0 for unlimited.
+    pub media_bandwidth: u64,
+    /// The synthetic media being sent on the session shown, if any.
+    pub media: Option<MediaGenerator>,
+    pub channel_layer: ChannelLayer,
+    pub channel_id: u64,
+    pub channel_text: String,
+    /// Channels registered on the session shown.
+    pub channels: Vec<OpenChannel>,
+    /// The stream carrying the stream mux channels, opened with the first of them.
+    pub mux: Option<StreamMux>,
+    /// Whether sessions connected from now on are re-established when lost.
+    pub auto_reconnect: bool,
+    /// Whether sessions connected from now on echo locally, with no server, see
+    /// [`LoopbackTransport`].
+    pub loopback: bool,
+    pub reconnect_policy: ReconnectPolicy,
+    pub reconnect_ticking: bool,
+}
+
+impl Model {
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    fn tab_named(&self, name: &str) -> Option<&Tab> {
+        self.tabs.iter().find(|tab| &*tab.name == name)
+    }
+
+    fn tab_named_mut(&mut self, name: &str) -> Option<&mut Tab> {
+        self.tabs.iter_mut().find(|tab| &*tab.name == name)
+    }
+
+    /// The session of `tab`, unless it is not connected or was lost.
+    fn session_of(&self, tab: &Tab) -> Option<Rc<WebTransportTask>> {
+        match &tab.reconnecting {
+            Some(reconnecting) => reconnecting.session(),
+            None => self.pool.get(&tab.name),
+        }
+    }
+
+    /// The session of the tab shown, unless it is a loopback session.
+    fn transport(&self) -> Option<Rc<WebTransportTask>> {
+        self.session_of(self.tab())
+    }
+
+    /// The session of the tab shown, loopback or not, for the features that only need a
+    /// [`Transport`].
+    fn any_transport(&self) -> Option<Rc<dyn Transport>> {
+        match &self.tab().loopback {
+            Some(loopback) => Some(Rc::new(loopback.clone())),
+            None => self
+                .transport()
+                .map(|transport| transport as Rc<dyn Transport>),
+        }
+    }
+
+    /// Whether the session of the tab shown is open, being established, or being
+    /// re-established.
+    fn active(&self) -> bool {
+        self.tab().reconnecting.is_some() || self.any_transport().is_some()
+    }
+
+    /// The state shown in the connection badge of `tab`.
+    fn connection_state(&self, tab: &Tab) -> ReconnectState {
+        if tab.loopback.is_some() {
+            return ReconnectState::Open;
+        }
+        if let Some(reconnecting) = &tab.reconnecting {
+            return reconnecting.state();
+        }
+        match self.pool.state(&tab.name) {
+            Some(SessionState::Connecting) => ReconnectState::Connecting,
+            Some(SessionState::Open) => ReconnectState::Open,
+            Some(SessionState::Closed(_) | SessionState::Failed(_)) | None => {
+                ReconnectState::Closed
+            }
+        }
+    }
+
+    /// Restarts what follows the session of the tab shown, after switching tabs or once the
+    /// session opened.
+    fn follow_active_session(&mut self, ctx: &Context<Self>) {
+        self.session += 1;
+        self.media = None;
+        self.channels.clear();
+        self.mux = None;
+        self.pinging = 0;
+        self.datagram_pings = PingSeries::default();
+        self.stream_pings = PingSeries::default();
+        if !self.tab().connected {
+            self.stats = None;
+            return;
+        }
+        self.stats = Some(TransportStats::default());
+        ctx.link().send_message(Msg::PollStats(self.session));
+        if self.continuous_ping {
+            ctx.link().send_message(Msg::ContinuousPing(self.session));
+        }
+    }
+
+    fn view_tabs(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="tabs">
+                { for self.tabs.iter().enumerate().map(|(index, tab)| {
+                    let state = match self.connection_state(tab) {
+                        ReconnectState::Connecting => "connecting",
+                        ReconnectState::Open => "open",
+                        ReconnectState::Waiting => "waiting",
+                        ReconnectState::GaveUp => "gave-up",
+                        ReconnectState::Closed => "closed",
+                    };
+                    let class = if index == self.active { "tab selected" } else { "tab" };
+                    html! {
+                        <button class={class} onclick={ctx.link().callback(move |_| Msg::SelectTab(index))}>
+                            <span class={format!("dot {state}")}></span>
+                            { &*tab.name }
+                        </button>
+                    }
+                }) }
+                <button class="tab" onclick={ctx.link().callback(|_| Msg::AddTab)}>{"+"}</button>
+                <button class="tab"
+                    disabled={self.tabs.len() == 1}
+                    onclick={ctx.link().callback(|_| Msg::CloseTab)}>{"Close tab"}</button>
+            </div>
+        }
+    }
+
+    fn start_reconnect_ticks(&mut self, ctx: &Context<Self>) {
+        if !self.reconnect_ticking {
+            self.reconnect_ticking = true;
+            ctx.link().send_message(Msg::ReconnectTick);
+        }
+    }
+
+    fn view_connection_state(&self, ctx: &Context<Self>) -> Html {
+        let tab = self.tab();
+        let (class, text) = match self.connection_state(tab) {
+            ReconnectState::Connecting => ("badge connecting", "Connecting".to_string()),
+            ReconnectState::Open => ("badge open", "Open".to_string()),
+            ReconnectState::Waiting => {
+                let left = tab
+                    .retry_at
+                    .map(|retry_at| retry_at.saturating_sub(PlatformScheduler.now()))
+                    .unwrap_or_default();
+                (
+                    "badge waiting",
+                    format!("Reconnecting in {:.1} s", left.as_secs_f64()),
+                )
+            }
+            ReconnectState::GaveUp => ("badge gave-up", "Gave up".to_string()),
+            ReconnectState::Closed => ("badge closed", "Closed".to_string()),
+        };
+        let attempts = match &tab.reconnecting {
+            Some(reconnecting) if reconnecting.attempts() > 0 => format!(
+                " attempt {} of {}, next delay {:?}",
+                reconnecting.attempts(),
+                self.reconnect_policy
+                    .max_attempts
+                    .map_or("unlimited".to_string(), |max| max.to_string()),
+                self.reconnect_policy.delay(reconnecting.attempts() + 1),
+            ),
+            Some(reconnecting) if reconnecting.reconnects() > 0 => {
+                format!(" reconnected {} times", reconnecting.reconnects())
+            }
+            _ => String::new(),
+        };
+        html! {
+            <div class="input-line">
+                <span class={class}>{ text }</span>
+                <span>{ attempts }</span>
+                <input type="checkbox"
+                    id="auto-reconnect"
+                    checked={self.auto_reconnect}
+                    disabled={self.active()}
+                    onchange={ctx.link().callback(|e: Event| {
+                        let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                        Msg::SetAutoReconnect(input.checked())
+                    })}/>
+                <label for="auto-reconnect">{"Reconnect automatically"}</label>
+                <input type="checkbox"
+                    id="loopback"
+                    checked={self.loopback}
+                    disabled={self.active()}
+                    onchange={ctx.link().callback(|e: Event| {
+                        let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                        Msg::SetLoopback(input.checked())
+                    })}/>
+                <label for="loopback">{"Loopback, no server needed"}</label>
+            </div>
+        }
+    }
+
+    /// Adds `text` at the top of the event log of the tab shown, with the time.
+    fn log(&mut self, text: String, dump: Option<String>) {
+        let name = self.tab().name.clone();
+        self.log_to(&name, text, dump);
+    }
+
+    /// Adds `text` at the top of the event log of the tab named `name`, with the time.
+    fn log_to(&mut self, name: &str, text: String, dump: Option<String>) {
+        let Some(tab) = self.tab_named_mut(name) else {
+            return;
+        };
+        let text = format!("{}: {}", Local::now().format("%H:%M:%S%.3f"), text);
+        tab.log.insert(0, LogEntry { text, dump });
+        self.save_tabs();
+    }
+
+    fn save_settings(&self) {
+        let settings = Settings {
+            endpoint: self.endpoint.clone(),
+            message_type: self.message_type.clone(),
+            certificate_hash: self.certificate_hash.clone(),
+            loopback: self.loopback,
+        };
+        if let Err(error) = LocalStorage::set(SETTINGS_KEY, settings) {
+            log!(format!("Failed to save the settings: {error}"));
+        }
+    }
+
+    /// Saves the tabs with the last [`LOG_HISTORY`] lines of their event logs.
+    fn save_tabs(&self) {
+        let tabs: Vec<SavedTab> = self
+            .tabs
+            .iter()
+            .map(|tab| SavedTab {
+                name: tab.name.to_string(),
+                log: tab.log.iter().take(LOG_HISTORY).cloned().collect(),
+            })
+            .collect();
+        if let Err(error) = LocalStorage::set(TABS_KEY, tabs) {
+            log!(format!("Failed to save the event log: {error}"));
+        }
+    }
+
+    /// Downloads the recording of what the tab shown received, replayable with
+    /// `SessionCapture::from_recording`.
+    fn export_recording(&self) -> Result<(), String> {
+        let recording = self.tab().capture.recording().encode();
+        let parts = Array::of1(&Uint8Array::from(&recording[..]));
+        let blob =
+            web_sys::Blob::new_with_u8_array_sequence(&parts).map_err(|e| format!("{e:?}"))?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|e| format!("{e:?}"))?;
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("no document")?;
+        let link: HtmlAnchorElement = document
+            .create_element("a")
+            .map_err(|e| format!("{e:?}"))?
+            .unchecked_into();
+        link.set_href(&url);
+        link.set_download(&format!("{}.capture", self.tab().name));
+        link.click();
+        let _ = web_sys::Url::revoke_object_url(&url);
+        Ok(())
+    }
+
+    /// Reads a stream opened by the server to the end, and logs what it carried as one message.
+    fn read_message(
+        &mut self,
+        ctx: &Context<Self>,
+        name: Rc<str>,
+        stream: WebTransportReceiveStream,
+        message_type: WebTransportMessageType,
+    ) {
+        let Some(tab) = self.tab_named(&name) else {
+            return;
+        };
+        let Some(transport) = self.session_of(tab) else {
+            return;
+        };
+        let capture = tab.capture.clone();
+        let direction = match message_type {
+            WebTransportMessageType::BidirectionalStream => StreamDirection::IncomingBidirectional,
+            _ => StreamDirection::IncomingUnidirectional,
+        };
+        let number = self.track_stream(name.clone(), direction, 0);
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let chunks = received.clone();
+        let on_chunk = ctx
+            .link()
+            .callback(move |bytes| Msg::StreamReceived(number, bytes));
+        let reading = transport.read_incoming(
+            stream.into(),
+            capture.stream(Callback::from(move |chunk: Vec<u8>| {
+                on_chunk.emit(chunk.len());
+                chunks.borrow_mut().extend(chunk)
+            })),
+        );
+        let tab = name.clone();
+        let on_message = ctx
+            .link()
+            .callback(move |data| Msg::OnMessage(tab.clone(), data, message_type.clone()));
+        let on_error = ctx.link().callback(move |error: WebTransportError| {
+            Msg::LogTo(
+                name.clone(),
+                format!("Failed to read an incoming stream: {error}"),
+            )
+        });
+        let on_closed = ctx.link().callback(move |_| Msg::StreamClosed(number));
+        spawn_local(async move {
+            match reading.await {
+                Ok(()) => on_message.emit(received.take()),
+                Err(error) => on_error.emit(error),
+            }
+            on_closed.emit(());
+        });
+    }
+
+    /// Sends the text over a stream, listing the stream as open until it is done.
+    fn send_over_stream(
+        &mut self,
+        ctx: &Context<Self>,
+        transport: &dyn Transport,
+        text: Vec<u8>,
+        message_type: WebTransportMessageType,
+    ) {
+        let direction = match message_type {
+            WebTransportMessageType::BidirectionalStream => StreamDirection::OutgoingBidirectional,
+            _ => StreamDirection::OutgoingUnidirectional,
+        };
+        let number = self.track_stream(self.tab().name.clone(), direction, text.len());
+        let sending = match message_type {
+            WebTransportMessageType::BidirectionalStream => transport.request(text),
+            _ => {
+                let finished = transport.send_unidirectional_stream_finished(text);
+                Box::pin(async move { finished.await.map(|()| Vec::new()) })
+            }
+        };
+        let tab = self.tab().name.clone();
+        let on_response = ctx.link().callback(move |data| {
+            Msg::OnMessage(
+                tab.clone(),
+                data,
+                WebTransportMessageType::BidirectionalStream,
+            )
+        });
+        let on_error = ctx.link().callback(|error: WebTransportError| {
+            WsAction::Log(format!("Failed to send over a stream: {error}"))
+        });
+        let on_received = ctx
+            .link()
+            .callback(move |bytes| Msg::StreamReceived(number, bytes));
+        let on_closed = ctx.link().callback(move |_| Msg::StreamClosed(number));
+        spawn_local(async move {
+            match sending.await {
+                Ok(response) if message_type == WebTransportMessageType::BidirectionalStream => {
+                    on_received.emit(response.len());
+                    on_response.emit(response)
+                }
+                Ok(_) => {}
+                Err(error) => on_error.emit(error),
+            }
+            on_closed.emit(());
+        });
+    }
+
+    /// Sends `file` in chunks of [`UPLOAD_CHUNK_SIZE`], one unidirectional stream after the
+    /// other, each framed with its offset so that the server can put the file back together.
+    fn start_upload(&mut self, ctx: &Context<Self>, file: File) {
+        let Some(transport) = self.transport() else {
+            return;
+        };
+        let transport = transport.transport.clone();
+        let upload_id = self.uploads;
+        self.uploads += 1;
+        let size = file.size() as u64;
+        let cancelled = Rc::new(Cell::new(false));
+        self.upload = Some(Upload {
+            tab: self.tab().name.clone(),
+            name: file.name(),
+            size,
+            sent: 0,
+            cancelled: cancelled.clone(),
+        });
+        let on_progress = ctx.link().callback(Msg::UploadProgress);
+        let on_done = ctx.link().callback(Msg::UploadDone);
+        spawn_local(async move {
+            let mut offset = 0;
+            let result = loop {
+                if cancelled.get() {
+                    break Err("cancelled".to_string());
+                }
+                let end = (offset + UPLOAD_CHUNK_SIZE).min(size);
+                let chunk = match read_chunk(&file, offset, end).await {
+                    Ok(chunk) => chunk,
+                    Err(error) => break Err(error),
+                };
+                let segment = encode_segment(upload_id, offset, size, &chunk);
+                if let Err(error) = WebTransportTask::send_unidirectional_stream_finished(
+                    transport.clone(),
+                    segment,
+                )
+                .await
+                {
+                    break Err(error.to_string());
+                }
+                offset = end;
+                on_progress.emit(offset);
+                if offset >= size {
+                    break Ok(());
+                }
+            };
+            on_done.emit(result);
+        });
+    }
+
+    fn view_upload(&self, ctx: &Context<Self>) -> Html {
+        let uploading = self.upload.is_some();
+        let progress = match self.upload.as_ref() {
+            Some(upload) => html! {
+                <div>
+                    <progress max={upload.size.to_string()} value={upload.sent.to_string()}/>
+                    { format!(" {} ({} of {} bytes) ", upload.name, upload.sent, upload.size) }
+                    <input type="button"
+                        value="Cancel"
+                        onclick={ctx.link().callback(|_| Msg::CancelUpload)}/>
+                </div>
+            },
+            None => html! {},
+        };
+        html! {
+            <div>
+                <input type="file"
+                    disabled={self.transport().is_none() || uploading}
+                    onchange={ctx.link().batch_callback(|e: Event| {
+                        let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                        let file = input.files().and_then(|files| files.get(0));
+                        input.set_value("");
+                        file.map(Msg::StartUpload)
+                    })}/>
+                { progress }
+            </div>
+        }
+    }
+
+    /// Measures the round-trip time over a datagram and over a bidirectional stream at once,
+    /// against the echo server.
+    fn ping(&mut self, ctx: &Context<Self>) {
+        let Some(transport) = self.transport() else {
+            return;
+        };
+        if self.pinging > 0 {
+            return;
+        }
+        self.pinging = 2;
+        let benchmark = Benchmark::new(transport);
+        let datagram = benchmark.datagram_latency(LatencyConfig {
+            probes: 1,
+            payload_size: PING_SIZE,
+            ..LatencyConfig::default()
+        });
+        let on_datagram_ping = ctx.link().callback(Msg::OnDatagramPing);
+        spawn_local(async move { on_datagram_ping.emit(datagram.await.min()) });
+        let stream = benchmark.stream_throughput(PING_SIZE);
+        let on_stream_ping = ctx.link().callback(Msg::OnStreamPing);
+        spawn_local(async move {
+            let rtt = stream
+                .await
+                .ok()
+                .filter(|report| report.received == report.sent)
+                .map(|report| report.elapsed);
+            on_stream_ping.emit(rtt);
+        });
+    }
+
+    fn schedule_ping(&self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let session = self.session;
+        PlatformScheduler.schedule(
+            PING_INTERVAL,
+            Box::new(move || link.send_message(Msg::ContinuousPing(session))),
+        );
+    }
+
+    fn view_ping(&self, ctx: &Context<Self>) -> Html {
+        const WIDTH: f64 = 600.0;
+        const HEIGHT: f64 = 120.0;
+        let top = self
+            .datagram_pings
+            .samples
+            .iter()
+            .chain(&self.stream_pings.samples)
+            .max()
+            .copied()
+            .unwrap_or_default();
+        html! {
+            <div>
+                <div class="input-line">
+                    <input type="button"
+                        value="Ping"
+                        disabled={self.transport().is_none() || self.pinging > 0 || self.stress.is_some()}
+                        onclick={ctx.link().callback(|_| Msg::Ping)}/>
+                    <input type="checkbox"
+                        id="continuous-ping"
+                        checked={self.continuous_ping}
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                            Msg::SetContinuousPing(input.checked())
+                        })}/>
+                    <label for="continuous-ping">{"Ping every second"}</label>
+                </div>
+                <table id="ping">
+                    <tr>
+                        <td class="ping-datagram">{"Datagram"}</td>
+                        <td>{ self.datagram_pings.summary() }</td>
+                    </tr>
+                    <tr>
+                        <td class="ping-stream">{"Bidirectional stream"}</td>
+                        <td>{ self.stream_pings.summary() }</td>
+                    </tr>
+                </table>
+                <svg width={WIDTH.to_string()} height={HEIGHT.to_string()}>
+                    <polyline class="ping-datagram"
+                        fill="none"
+                        points={self.datagram_pings.points(WIDTH, HEIGHT, top)}/>
+                    <polyline class="ping-stream"
+                        fill="none"
+                        points={self.stream_pings.points(WIDTH, HEIGHT, top)}/>
+                </svg>
+            </div>
+        }
+    }
+
+    fn start_stress(&mut self, ctx: &Context<Self>) {
+        let Some(transport) = self.transport() else {
+            return;
+        };
+        if self.stress.is_some() {
+            return;
+        }
+        let test = StressTest {
+            tab: self.tab().name.clone(),
+            message_type: self.message_type.clone(),
+            count: self.stress_count,
+            size: self.stress_size,
+        };
+        self.stress = Some(test.clone());
+        let on_done = ctx.link().callback(Msg::OnStress);
+        spawn_local(async move {
+            let started = PlatformScheduler.now();
+            let result = test.run(transport).await;
+            on_done.emit(result.map(|()| PlatformScheduler.now().saturating_sub(started)));
+        });
+    }
+
+    fn view_stress(&self, ctx: &Context<Self>) -> Html {
+        let number = |e: Event| {
+            let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+            input.value().parse::<usize>().unwrap_or_default()
+        };
+        html! {
+            <div class="input-line">
+                <label for="stress-count">{"Messages:"}</label>
+                <input type="number"
+                    id="stress-count"
+                    min="1"
+                    value={self.stress_count.to_string()}
+                    onchange={ctx.link().callback(move |e| Msg::SetStressCount(number(e)))}/>
+                <label for="stress-size">{"Bytes per message:"}</label>
+                <input type="number"
+                    id="stress-size"
+                    min="1"
+                    value={self.stress_size.to_string()}
+                    onchange={ctx.link().callback(move |e| Msg::SetStressSize(number(e)))}/>
+                <input type="button"
+                    value={if self.stress.is_some() { "Running..." } else { "Run stress test" }}
+                    disabled={self.transport().is_none() || self.stress.is_some()}
+                    onclick={ctx.link().callback(|_| Msg::StartStress)}/>
+            </div>
+        }
+    }
+
+    fn media_config(&self) -> FrameSchedulerConfig {
+        FrameSchedulerConfig {
+            bandwidth: self.media_bandwidth(),
+            ..FrameSchedulerConfig::default()
+        }
+    }
+
+    fn media_bandwidth(&self) -> Option<u64> {
+        (self.media_bandwidth > 0).then_some(self.media_bandwidth * 1000)
+    }
+
+    fn start_media(&mut self, ctx: &Context<Self>) {
+        let Some(transport) = self.any_transport() else {
+            return;
+        };
+        if self.media.is_some() {
+            return;
+        }
+        self.media = Some(MediaGenerator {
+            scheduler: FrameScheduler::new(Rc::new(transport), self.media_config()),
+            frames: 0,
+            until_keyframe: 0,
+        });
+        self.log("Started sending synthetic media".to_string(), None);
+        ctx.link().send_message(Msg::MediaTick(self.session));
+    }
+
+    fn schedule_media_tick(&self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let session = self.session;
+        PlatformScheduler.schedule(
+            MEDIA_FRAME_INTERVAL,
+            Box::new(move || link.send_message(Msg::MediaTick(session))),
+        );
+    }
+
+    fn view_media(&self, ctx: &Context<Self>) -> Html {
+        let number = |e: Event| {
+            let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+            input.value().parse::<u64>().unwrap_or_default()
+        };
+        let stats = self.media.as_ref().map(|media| {
+            let stats = media.scheduler.stats();
+            let row = |name: &str, class: FrameClass| {
+                let class_stats = stats.class(class);
+                html! {
+                    <tr>
+                        <td>{ name.to_string() }</td>
+                        <td>{ class_stats.frames_sent }</td>
+                        <td>{ class_stats.bytes_sent }</td>
+                        <td>{ class_stats.frames_expired }</td>
+                        <td>{ media.scheduler.queued(class) }</td>
+                    </tr>
+                }
+            };
+            html! {
+                <table id="media-stats">
+                    <tr>
+                        <th>{"Class"}</th>
+                        <th>{"Frames sent"}</th>
+                        <th>{"Bytes sent"}</th>
+                        <th>{"Frames expired"}</th>
+                        <th>{"Queued"}</th>
+                    </tr>
+                    { row("Audio", FrameClass::Audio) }
+                    { row("Video keyframes", FrameClass::VideoKey) }
+                    { row("Video delta frames", FrameClass::VideoDelta) }
+                </table>
+            }
+        });
+        html! {
+            <>
+                <div class="input-line">
+                    <label for="keyframe-size">{"Keyframe bytes:"}</label>
+                    <input type="number"
+                        id="keyframe-size"
+                        min="1"
+                        value={self.keyframe_size.to_string()}
+                        onchange={ctx.link().callback(move |e| Msg::SetKeyframeSize(number(e) as usize))}/>
+                    <label for="delta-frame-size">{"Delta frame bytes:"}</label>
+                    <input type="number"
+                        id="delta-frame-size"
+                        min="1"
+                        value={self.delta_frame_size.to_string()}
+                        onchange={ctx.link().callback(move |e| Msg::SetDeltaFrameSize(number(e) as usize))}/>
+                    <label for="keyframe-interval">{"Keyframe every:"}</label>
+                    <input type="number"
+                        id="keyframe-interval"
+                        min="1"
+                        value={self.keyframe_interval.to_string()}
+                        onchange={ctx.link().callback(move |e| Msg::SetKeyframeInterval(number(e)))}/>
+                    <label for="media-bandwidth">{"Bandwidth (kB/s, 0 for unlimited):"}</label>
+                    <input type="number"
+                        id="media-bandwidth"
+                        min="0"
+                        value={self.media_bandwidth.to_string()}
+                        onchange={ctx.link().callback(move |e| Msg::SetMediaBandwidth(number(e)))}/>
+                    if self.media.is_some() {
+                        <input type="button"
+                            value="Stop"
+                            onclick={ctx.link().callback(|_| Msg::StopMedia)}/>
+                    } else {
+                        <input type="button"
+                            value="Start"
+                            disabled={self.any_transport().is_none()}
+                            onclick={ctx.link().callback(|_| Msg::StartMedia)}/>
+                    }
+                </div>
+                { for stats }
+            </>
+        }
+    }
+
+    fn channel_mut(&mut self, layer: ChannelLayer, id: u64) -> Option<&mut OpenChannel> {
+        self.channels
+            .iter_mut()
+            .find(|channel| channel.layer == layer && channel.id == id)
+    }
+
+    /// Registers the channel selected on the session shown, unless it is already open.
+    fn open_channel(&mut self, ctx: &Context<Self>) {
+        let Some(transport) = self.any_transport() else {
+            return;
+        };
+        let (layer, id) = (self.channel_layer, self.channel_id);
+        if self.channel_mut(layer, id).is_some() {
+            return;
+        }
+        let session = self.session;
+        let on_message = ctx
+            .link()
+            .callback(move |data| Msg::OnChannelMessage(session, layer, id, data));
+        let handle = match layer {
+            ChannelLayer::Datagram => {
+                ChannelHandle::Datagram(transport.datagram_channel(id, on_message))
+            }
+            ChannelLayer::Stream => {
+                let mux = self
+                    .mux
+                    .get_or_insert_with(|| StreamMux::open(transport.as_ref()));
+                ChannelHandle::Stream(mux.channel(id, on_message))
+            }
+        };
+        self.channels.push(OpenChannel {
+            layer,
+            id,
+            handle,
+            log: Vec::new(),
+        });
+    }
+
+    fn view_channels(&self, ctx: &Context<Self>) -> Html {
+        let layer_option = |layer: ChannelLayer| {
+            html! {
+                <option selected={self.channel_layer == layer}>{ layer.name() }</option>
+            }
+        };
+        html! {
+            <>
+                <div class="input-line">
+                    <select id="channel-layer"
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input = e.target_dyn_into::<HtmlSelectElement>().unwrap();
+                            if input.selected_index() == 1 {
+                                Msg::SetChannelLayer(ChannelLayer::Stream)
+                            } else {
+                                Msg::SetChannelLayer(ChannelLayer::Datagram)
+                            }
+                        })}>
+                        { layer_option(ChannelLayer::Datagram) }
+                        { layer_option(ChannelLayer::Stream) }
+                    </select>
+                    <label for="channel-id">{"Channel id:"}</label>
+                    <input type="number"
+                        id="channel-id"
+                        min="0"
+                        value={self.channel_id.to_string()}
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                            Msg::SetChannelId(input.value().parse().unwrap_or_default())
+                        })}/>
+                    <input type="button"
+                        value="Open channel"
+                        disabled={self.any_transport().is_none()}
+                        onclick={ctx.link().callback(|_| Msg::OpenChannel)}/>
+                </div>
+                <div class="input-line">
+                    <input type="text"
+                        id="channel-text"
+                        value={self.channel_text.clone()}
+                        onkeyup={ctx.link().callback(|e: KeyboardEvent| {
+                            let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                            Msg::SetChannelText(input.value())
+                        })}/>
+                    <input type="button"
+                        value="Send on channel"
+                        disabled={self.any_transport().is_none()}
+                        onclick={ctx.link().callback(|_| Msg::SendOnChannel)}/>
+                </div>
+                { for self.channels.iter().map(|channel| {
+                    let (layer, id) = (channel.layer, channel.id);
+                    html! {
+                        <div class="channel">
+                            <h3>
+                                { format!("{} {}", layer.name(), id) }
+                                <input type="button"
+                                    value="Close"
+                                    onclick={ctx.link().callback(move |_| Msg::CloseChannel(layer, id))}/>
+                            </h3>
+                            <ul class="channel-log">
+                                { for channel.log.iter().map(|line| html! { <li>{ line }</li> }) }
+                            </ul>
+                        </div>
+                    }
+                }) }
+            </>
+        }
+    }
+
+    /// Lists a stream as open and returns its number.
+    fn track_stream(&mut self, tab: Rc<str>, direction: StreamDirection, bytes_out: usize) -> u64 {
+        self.streams_seen += 1;
+        self.streams.push(OpenStream {
+            number: self.streams_seen,
+            tab,
+            direction,
+            bytes_out,
+            bytes_in: 0,
+        });
+        self.streams_seen
+    }
+
+    fn view_streams(&self) -> Html {
+        if self.streams.is_empty() {
+            return html! { <p>{"No open streams"}</p> };
+        }
+        html! {
+            <table id="streams">
+                <tr>
+                    <th>{"#"}</th>
+                    <th>{"Session"}</th>
+                    <th>{"Direction"}</th>
+                    <th>{"Bytes out"}</th>
+                    <th>{"Bytes in"}</th>
+                    <th>{"State"}</th>
+                </tr>
+                { for self.streams.iter().map(|stream| html! {
+                    <tr>
+                        <td>{ stream.number }</td>
+                        <td>{ stream.tab.to_string() }</td>
+                        <td>{ stream.direction.name() }</td>
+                        <td>{ stream.bytes_out }</td>
+                        <td>{ stream.bytes_in }</td>
+                        <td>{ stream.state() }</td>
+                    </tr>
+                }) }
+            </table>
+        }
+    }
+
+    /// Refreshes the statistics of the current session every [`STATS_INTERVAL`].
+    fn poll_stats(&self, ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let session = self.session;
+        PlatformScheduler.schedule(
+            STATS_INTERVAL,
+            Box::new(move || link.send_message(Msg::PollStats(session))),
+        );
+    }
+
+    fn view_stats(&self) -> Html {
+        let Some(stats) = self.stats.as_ref() else {
+            return html! { <p>{"Not connected"}</p> };
+        };
+        let rtt = match stats.smoothed_rtt {
+            Some(rtt) => format!("{:.1} ms", rtt.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let packet_loss = if stats.packets_sent == 0 {
+            0.0
+        } else {
+            stats.packets_lost as f64 / stats.packets_sent as f64 * 100.0
+        };
+        let row = |name: &str, value: String| {
+            html! { <tr><td>{ name.to_string() }</td><td>{ value }</td></tr> }
+        };
+        html! {
+            <table id="stats">
+                { row("Round-trip time", rtt) }
+                { row("Packet loss", format!("{packet_loss:.2}% ({} of {})", stats.packets_lost, stats.packets_sent)) }
+                { row("Datagrams lost (outgoing)", stats.datagrams_lost_outgoing.to_string()) }
+                { row("Datagrams expired (outgoing)", stats.datagrams_expired_outgoing.to_string()) }
+                { row("Datagrams dropped (incoming)", stats.datagrams_dropped_incoming.to_string()) }
+                { row("Bytes sent", stats.bytes_sent.to_string()) }
+                { row("Bytes received", stats.bytes_received.to_string()) }
+                { row("Open streams", self.streams.len().to_string()) }
+            </table>
+        }
+    }
 }
 
 impl Component for Model {
@@ -68,14 +1415,62 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_ctx: &Context<Self>) -> Self {
+        let settings = LocalStorage::get(SETTINGS_KEY).unwrap_or_else(|_| Settings {
+            endpoint: DEFAULT_URL.to_string(),
+            message_type: WebTransportMessageType::Datagram,
+            certificate_hash: String::new(),
+            loopback: false,
+        });
+        let mut tabs: Vec<Tab> = LocalStorage::get::<Vec<SavedTab>>(TABS_KEY)
+            .unwrap_or_default()
+            .into_iter()
+            .map(Tab::restored)
+            .collect();
+        if tabs.is_empty() {
+            tabs.push(Tab::new(1));
+        }
+        let opened_tabs = tabs.iter().map(Tab::number).max().unwrap_or_default();
         Self {
             fetching: false,
-            transport: None,
-            log: vec![],
-            endpoint: DEFAULT_URL.to_string(),
+            pool: WebTransportPool::new(),
+            tabs,
+            active: 0,
+            opened_tabs,
+            hex_view: false,
+            endpoint: settings.endpoint,
+            certificate_hash: settings.certificate_hash,
             text: "".to_string(),
-            message_type: WebTransportMessageType::Datagram,
-            connected: false,
+            message_type: settings.message_type,
+            session: 0,
+            stats: None,
+            streams: Vec::new(),
+            streams_seen: 0,
+            upload: None,
+            uploads: 0,
+            datagram_pings: PingSeries::default(),
+            stream_pings: PingSeries::default(),
+            pinging: 0,
+            continuous_ping: false,
+            stress_count: 1000,
+            stress_size: 1000,
+            stress: None,
+            keyframe_size: 20_000,
+            delta_frame_size: 2_000,
+            keyframe_interval: 60,
+            media_bandwidth: 0,
+            media: None,
+            channel_layer: ChannelLayer::Datagram,
+            channel_id: 1,
+            channel_text: "Hello".to_string(),
+            channels: Vec::new(),
+            mux: None,
+            auto_reconnect: true,
+            loopback: settings.loopback,
+            reconnect_policy: ReconnectPolicy {
+                max_attempts: Some(10),
+                ..ReconnectPolicy::default()
+            },
+            reconnect_ticking: false,
         }
     }
 
@@ -83,61 +1478,104 @@ impl Component for Model {
         match msg {
             Msg::WsAction(action) => match action {
                 WsAction::Connect => {
-                    let on_datagram = ctx.link().callback(Msg::OnDatagram);
-                    let on_unidirectional_stream = ctx.link().callback(Msg::OnUniStream);
-                    let on_bidirectional_stream = ctx.link().callback(Msg::OnBidiStream);
-                    let notification = ctx.link().batch_callback(|status| match status {
-                        WebTransportStatus::Opened => Some(WsAction::Connected.into()),
-                        WebTransportStatus::Closed(reason) | WebTransportStatus::Error(reason) => {
-                            let formatted_reason = format!("{:?}", reason);
-                            Some(WsAction::Lost(formatted_reason).into())
-                        }
-                    });
-                    let endpoint = self.endpoint.clone();
-                    let task = WebTransportService::connect(
-                        &endpoint,
-                        on_datagram,
-                        on_unidirectional_stream,
-                        on_bidirectional_stream,
-                        notification,
+                    let name = self.tab().name.clone();
+                    let tab = name.clone();
+                    let on_datagram = self.tab().capture.datagrams(
+                        ctx.link()
+                            .callback(move |datagram| Msg::OnDatagram(tab.clone(), datagram)),
                     );
-                    self.transport = match task {
-                        Ok(task) => Some(task),
-                        Err(err) => {
-                            ctx.link().send_message(WsAction::Log(err.to_string()));
-                            None
+                    let tab = name.clone();
+                    let on_unidirectional_stream = ctx
+                        .link()
+                        .callback(move |stream| Msg::OnUniStream(tab.clone(), stream));
+                    let tab = name.clone();
+                    let on_bidirectional_stream = ctx
+                        .link()
+                        .callback(move |stream| Msg::OnBidiStream(tab.clone(), stream));
+                    let notification =
+                        ctx.link()
+                            .batch_callback(|LabeledStatus { label, status }| {
+                                let name = label?;
+                                match status {
+                                    WebTransportStatus::Opened => {
+                                        Some(WsAction::Connected(name).into())
+                                    }
+                                    WebTransportStatus::Closed(reason) => {
+                                        let formatted_reason = format!("{:?}", reason);
+                                        Some(WsAction::Lost(name, formatted_reason).into())
+                                    }
+                                    WebTransportStatus::Error(error) => {
+                                        Some(WsAction::Lost(name, error.to_string()).into())
+                                    }
+                                    WebTransportStatus::StreamError(error) => {
+                                        log!(format!("[{name}] stream error: {error}"));
+                                        None
+                                    }
+                                }
+                            });
+                    if self.loopback {
+                        let tab = name.clone();
+                        let on_unidirectional_stream = ctx.link().callback(move |data| {
+                            Msg::OnMessage(
+                                tab.clone(),
+                                data,
+                                WebTransportMessageType::UnidirectionalStream,
+                            )
+                        });
+                        let label = name.clone();
+                        let notification = notification.reform(move |status| LabeledStatus {
+                            label: Some(label.clone()),
+                            status,
+                        });
+                        let loopback = LoopbackTransport::connect(
+                            on_datagram,
+                            on_unidirectional_stream,
+                            notification,
+                        );
+                        self.tab_mut().loopback = Some(loopback);
+                        return true;
+                    }
+                    let mut builder = WebTransportService::builder(self.endpoint.clone())
+                        .label(&*name)
+                        .on_datagram(on_datagram)
+                        .on_unidirectional_stream(on_unidirectional_stream)
+                        .on_bidirectional_stream(on_bidirectional_stream)
+                        .on_labeled_status(notification);
+                    if !self.certificate_hash.trim().is_empty() {
+                        match self.certificate_hash.parse::<CertificateHash>() {
+                            Ok(hash) => builder = builder.server_certificate_hashes([hash]),
+                            Err(error) => {
+                                ctx.link().send_message(WsAction::Log(error.to_string()));
+                                return false;
+                            }
                         }
-                    };
+                    }
+                    if self.auto_reconnect {
+                        let reconnecting =
+                            ReconnectingSession::connect(builder, self.reconnect_policy.clone());
+                        self.tab_mut().reconnecting = Some(reconnecting);
+                        self.start_reconnect_ticks(ctx);
+                        return true;
+                    }
+                    if let Err(err) = self.pool.connect(&*name, builder) {
+                        ctx.link().send_message(WsAction::Log(err.to_string()));
+                    }
                     true
                 }
                 WsAction::SendData() => {
                     let text = self.text.clone();
                     let message_type = self.message_type.clone();
-                    if let Some(transport) = self.transport.as_ref() {
+                    if let Some(transport) = self.any_transport() {
                         ctx.link().send_message(WsAction::Log(format!(
                             "Sending: {:?} using {:?}",
                             &text, message_type
                         )));
                         let text = text.into_bytes();
                         match message_type {
-                            WebTransportMessageType::Datagram => {
-                                WebTransportTask::send_datagram(transport.transport.clone(), text);
-                            }
-                            WebTransportMessageType::UnidirectionalStream => {
-                                WebTransportTask::send_unidirectional_stream(
-                                    transport.transport.clone(),
-                                    text,
-                                );
-                            }
-                            WebTransportMessageType::BidirectionalStream => {
-                                let on_bidirectional_stream = ctx.link().callback(|d| {
-                                    Msg::OnMessage(d, WebTransportMessageType::BidirectionalStream)
-                                });
-                                WebTransportTask::send_bidirectional_stream(
-                                    transport.transport.clone(),
-                                    text,
-                                    on_bidirectional_stream,
-                                );
+                            WebTransportMessageType::Datagram => transport.send_datagram(text),
+                            WebTransportMessageType::UnidirectionalStream
+                            | WebTransportMessageType::BidirectionalStream => {
+                                self.send_over_stream(ctx, transport.as_ref(), text, message_type);
                             }
                             WebTransportMessageType::Unknown => {}
                         }
@@ -146,9 +1584,17 @@ impl Component for Model {
                     false
                 }
                 WsAction::Disconnect => {
-                    let connection = self.transport.take();
-                    if let Some(connection) = connection {
-                        connection.transport.close()
+                    self.stats = None;
+                    let name = self.tab().name.clone();
+                    let tab = self.tab_mut();
+                    tab.connected = false;
+                    tab.retry_at = None;
+                    if let Some(loopback) = tab.loopback.take() {
+                        let _ = loopback.close();
+                    } else if let Some(reconnecting) = tab.reconnecting.take() {
+                        let _ = reconnecting.close();
+                    } else {
+                        let _ = self.pool.close(&name);
                     }
                     true
                 }
@@ -158,116 +1604,403 @@ impl Component for Model {
                 }
                 WsAction::SetUrl(url) => {
                     self.endpoint = url;
+                    self.save_settings();
+                    true
+                }
+                WsAction::SetCertificateHash(hash) => {
+                    self.certificate_hash = hash;
+                    self.save_settings();
                     true
                 }
                 WsAction::SetMessageType(message_type) => {
                     self.message_type = message_type;
+                    self.save_settings();
                     true
                 }
                 WsAction::Log(text) => {
-                    let text = format!("{}: {}", Local::now().format("%H:%M:%S%.3f"), text);
-                    self.log.splice(0..0, vec![text]);
+                    self.log(text, None);
                     true
                 }
-                WsAction::Connected => {
-                    self.connected = true;
-                    ctx.link()
-                        .send_message(WsAction::Log("Connected".to_string()));
+                WsAction::Connected(name) => {
+                    let Some(tab) = self.tab_named_mut(&name) else {
+                        return false;
+                    };
+                    tab.connected = true;
+                    tab.retry_at = None;
+                    if self.tab().name == name {
+                        self.follow_active_session(ctx);
+                    }
+                    self.log_to(&name, "Connected".to_string(), None);
                     true
                 }
-                WsAction::Lost(reason) => {
-                    self.connected = false;
-                    self.transport = None;
-                    ctx.link()
-                        .send_message(WsAction::Log(format!("Connection lost ({})", reason)));
+                WsAction::Lost(name, reason) => {
+                    let policy = self.reconnect_policy.clone();
+                    let Some(tab) = self.tab_named_mut(&name) else {
+                        return false;
+                    };
+                    tab.connected = false;
+                    let mut lines = vec![format!("Connection lost ({})", reason)];
+                    match tab.reconnecting.as_ref().map(|r| (r.state(), r.attempts())) {
+                        Some((ReconnectState::Waiting, attempts)) => {
+                            let delay = policy.delay(attempts);
+                            tab.retry_at = Some(PlatformScheduler.now() + delay);
+                            lines.push(format!("Reconnecting in {delay:?}"));
+                            self.start_reconnect_ticks(ctx);
+                        }
+                        Some((ReconnectState::GaveUp, attempts)) => {
+                            tab.retry_at = None;
+                            lines.push(format!(
+                                "Gave up reconnecting after {} attempts",
+                                attempts - 1
+                            ));
+                        }
+                        Some(_) => {}
+                        None => {
+                            self.pool.remove(&name);
+                        }
+                    }
+                    if self.tab().name == name {
+                        self.stats = None;
+                    }
+                    for line in lines {
+                        self.log_to(&name, line, None);
+                    }
                     true
                 }
             },
-            Msg::OnMessage(response, message_type) => {
-                let data = String::from_utf8(response).unwrap();
-                ctx.link().send_message(WsAction::Log(format!(
-                    "We received {data:?} through {message_type:?}"
-                )));
+            Msg::OnMessage(name, response, message_type) => {
+                let text = String::from_utf8_lossy(&response);
+                let binary = matches!(text, Cow::Owned(_))
+                    || text.chars().any(|c| c.is_control() && !c.is_whitespace());
+                let dump = (binary || self.hex_view).then(|| hex_dump(&response));
+                let text = format!(
+                    "We received {} bytes {text:?} through {message_type:?}",
+                    response.len()
+                );
+                self.log_to(&name, text, dump);
+                true
+            }
+            Msg::LogTo(name, text) => {
+                self.log_to(&name, text, None);
                 true
             }
-            Msg::OnDatagram(datagram) => {
+            Msg::OnDatagram(name, datagram) => {
                 // With datagrams there's no need to read from a stream, so we just rebroadcast another message.
-                ctx.link()
-                    .send_message(Msg::OnMessage(datagram, WebTransportMessageType::Datagram));
+                ctx.link().send_message(Msg::OnMessage(
+                    name,
+                    datagram,
+                    WebTransportMessageType::Datagram,
+                ));
                 false
             }
-            Msg::OnBidiStream(stream) => {
-                // TODO: Read from the stream and do something useful with the data.
+            Msg::OnBidiStream(name, stream) => {
                 log!("OnBidiStream: ", &stream);
-                let callback = ctx
-                    .link()
-                    .callback(|d| Msg::OnMessage(d, WebTransportMessageType::BidirectionalStream));
-                let readable: ReadableStreamDefaultReader =
-                    stream.readable().get_reader().unchecked_into();
-                wasm_bindgen_futures::spawn_local(async move {
-                    loop {
-                        let read_result = JsFuture::from(readable.read()).await;
-                        match read_result {
-                            Err(e) => {
-                                let mut reason = WebTransportCloseInfo::default();
-                                reason.reason(
-                                    format!("Failed to read incoming datagrams {e:?}").as_str(),
-                                );
-                                break;
-                            }
-                            Ok(result) => {
-                                let done = Reflect::get(&result, &JsString::from("done"))
-                                    .unwrap()
-                                    .unchecked_into::<Boolean>();
-                                if done.is_truthy() {
-                                    break;
-                                }
-                                let value: Uint8Array =
-                                    Reflect::get(&result, &JsString::from("value"))
-                                        .unwrap()
-                                        .unchecked_into();
-                                process_binary(&value, &callback);
-                            }
-                        }
+                self.read_message(
+                    ctx,
+                    name,
+                    stream.readable(),
+                    WebTransportMessageType::BidirectionalStream,
+                );
+                false
+            }
+            Msg::OnUniStream(name, stream) => {
+                log!("OnUniStream: ", &stream);
+                self.read_message(
+                    ctx,
+                    name,
+                    stream,
+                    WebTransportMessageType::UnidirectionalStream,
+                );
+                false
+            }
+            Msg::AddTab => {
+                self.opened_tabs += 1;
+                self.tabs.push(Tab::new(self.opened_tabs));
+                self.active = self.tabs.len() - 1;
+                self.follow_active_session(ctx);
+                self.save_tabs();
+                true
+            }
+            Msg::SelectTab(index) => {
+                if index >= self.tabs.len() || index == self.active {
+                    return false;
+                }
+                self.active = index;
+                self.follow_active_session(ctx);
+                true
+            }
+            Msg::CloseTab => {
+                if self.tabs.len() == 1 {
+                    return false;
+                }
+                ctx.link().send_message(WsAction::Disconnect);
+                ctx.link().send_message(Msg::RemoveTab(self.active));
+                false
+            }
+            Msg::RemoveTab(index) => {
+                self.tabs.remove(index);
+                self.active = self.active.min(self.tabs.len() - 1);
+                self.follow_active_session(ctx);
+                self.save_tabs();
+                true
+            }
+            Msg::PollStats(session) => {
+                let Some(transport) = self.any_transport() else {
+                    return false;
+                };
+                if session != self.session {
+                    return false;
+                }
+                let stats = transport.stats();
+                let on_stats = ctx.link().callback(Msg::OnStats);
+                spawn_local(async move {
+                    if let Ok(stats) = stats.await {
+                        on_stats.emit(stats);
                     }
                 });
+                self.poll_stats(ctx);
+                false
+            }
+            Msg::OnStats(stats) => {
+                if self.any_transport().is_none() {
+                    return false;
+                }
+                self.stats = Some(stats);
                 true
             }
-            Msg::OnUniStream(stream) => {
-                // TODO: Read from the stream and do something useful with the data.
-                log!("OnUniStream: ", &stream);
-                let incoming_datagrams: ReadableStreamDefaultReader =
-                    stream.get_reader().unchecked_into();
-                let callback = ctx
-                    .link()
-                    .callback(|d| Msg::OnMessage(d, WebTransportMessageType::UnidirectionalStream));
-                wasm_bindgen_futures::spawn_local(async move {
-                    loop {
-                        let read_result = JsFuture::from(incoming_datagrams.read()).await;
-                        match read_result {
-                            Err(e) => {
-                                let mut reason = WebTransportCloseInfo::default();
-                                reason.reason(
-                                    format!("Failed to read incoming datagrams {e:?}").as_str(),
-                                );
-                                break;
-                            }
-                            Ok(result) => {
-                                let done = Reflect::get(&result, &JsString::from("done"))
-                                    .unwrap()
-                                    .unchecked_into::<Boolean>();
-                                if done.is_truthy() {
-                                    break;
-                                }
-                                let value: Uint8Array =
-                                    Reflect::get(&result, &JsString::from("value"))
-                                        .unwrap()
-                                        .unchecked_into();
-                                process_binary(&value, &callback);
-                            }
+            Msg::StreamReceived(number, bytes) => {
+                if let Some(stream) = self.streams.iter_mut().find(|s| s.number == number) {
+                    stream.bytes_in += bytes;
+                }
+                true
+            }
+            Msg::StreamClosed(number) => {
+                self.streams.retain(|stream| stream.number != number);
+                true
+            }
+            Msg::StartUpload(file) => {
+                if self.upload.is_some() {
+                    return false;
+                }
+                ctx.link().send_message(WsAction::Log(format!(
+                    "Uploading {} ({} bytes)",
+                    file.name(),
+                    file.size()
+                )));
+                self.start_upload(ctx, file);
+                true
+            }
+            Msg::UploadProgress(sent) => {
+                if let Some(upload) = self.upload.as_mut() {
+                    upload.sent = sent;
+                }
+                true
+            }
+            Msg::UploadDone(result) => {
+                let Some(upload) = self.upload.take() else {
+                    return false;
+                };
+                let text = match result {
+                    Ok(()) => format!("Uploaded {} ({} bytes)", upload.name, upload.size),
+                    Err(error) => format!("Upload of {} stopped: {error}", upload.name),
+                };
+                self.log_to(&upload.tab, text, None);
+                true
+            }
+            Msg::CancelUpload => {
+                if let Some(upload) = self.upload.as_ref() {
+                    upload.cancelled.set(true);
+                }
+                false
+            }
+            Msg::Ping => {
+                self.ping(ctx);
+                true
+            }
+            Msg::ContinuousPing(session) => {
+                if !self.continuous_ping || session != self.session || self.transport().is_none() {
+                    return false;
+                }
+                self.ping(ctx);
+                self.schedule_ping(ctx);
+                true
+            }
+            Msg::SetContinuousPing(continuous) => {
+                let started = continuous && !self.continuous_ping;
+                self.continuous_ping = continuous;
+                if started {
+                    // A new session number stops the pings scheduled before it was turned off.
+                    self.session += 1;
+                    ctx.link().send_message(Msg::ContinuousPing(self.session));
+                    ctx.link().send_message(Msg::PollStats(self.session));
+                }
+                true
+            }
+            Msg::SetAutoReconnect(auto_reconnect) => {
+                self.auto_reconnect = auto_reconnect;
+                true
+            }
+            Msg::SetLoopback(loopback) => {
+                self.loopback = loopback;
+                self.save_settings();
+                true
+            }
+            Msg::ReconnectTick => {
+                let mut reconnecting = false;
+                for tab in &mut self.tabs {
+                    match tab.reconnecting.as_ref().map(ReconnectingSession::state) {
+                        Some(ReconnectState::Waiting) => reconnecting = true,
+                        Some(ReconnectState::Connecting) => {
+                            // A new attempt was made, so a new session may be on its way.
+                            tab.retry_at = None;
+                            reconnecting = true;
                         }
+                        _ => {}
                     }
-                });
+                }
+                if !reconnecting {
+                    self.reconnect_ticking = false;
+                    return true;
+                }
+                let link = ctx.link().clone();
+                PlatformScheduler.schedule(
+                    RECONNECT_TICK,
+                    Box::new(move || link.send_message(Msg::ReconnectTick)),
+                );
+                true
+            }
+            Msg::SetHexView(hex_view) => {
+                self.hex_view = hex_view;
+                true
+            }
+            Msg::ExportRecording => {
+                if let Err(error) = self.export_recording() {
+                    self.log(format!("Failed to export the recording: {error}"), None);
+                    return true;
+                }
+                false
+            }
+            Msg::SetStressCount(count) => {
+                self.stress_count = count.max(1);
+                true
+            }
+            Msg::SetStressSize(size) => {
+                self.stress_size = size.max(1);
+                true
+            }
+            Msg::StartStress => {
+                self.start_stress(ctx);
+                true
+            }
+            Msg::OnStress(result) => {
+                let Some(test) = self.stress.take() else {
+                    return false;
+                };
+                let text = match result {
+                    Ok(elapsed) => test.report(elapsed),
+                    Err(error) => format!("Stress test failed: {error}"),
+                };
+                self.log_to(&test.tab, text, None);
+                true
+            }
+            Msg::SetKeyframeSize(size) => {
+                self.keyframe_size = size.max(1);
+                true
+            }
+            Msg::SetDeltaFrameSize(size) => {
+                self.delta_frame_size = size.max(1);
+                true
+            }
+            Msg::SetKeyframeInterval(interval) => {
+                self.keyframe_interval = interval.max(1);
+                true
+            }
+            Msg::SetMediaBandwidth(bandwidth) => {
+                self.media_bandwidth = bandwidth;
+                if let Some(media) = self.media.as_ref() {
+                    media.scheduler.set_bandwidth(self.media_bandwidth());
+                }
+                true
+            }
+            Msg::StartMedia => {
+                self.start_media(ctx);
+                true
+            }
+            Msg::StopMedia => {
+                if let Some(media) = self.media.take() {
+                    self.log(
+                        format!(
+                            "Stopped sending synthetic media after {} frames",
+                            media.frames
+                        ),
+                        None,
+                    );
+                }
+                true
+            }
+            Msg::MediaTick(session) => {
+                if session != self.session {
+                    return false;
+                }
+                let Some(media) = self.media.as_mut() else {
+                    return false;
+                };
+                media.tick(
+                    self.keyframe_size,
+                    self.delta_frame_size,
+                    self.keyframe_interval,
+                );
+                self.schedule_media_tick(ctx);
+                true
+            }
+            Msg::SetChannelLayer(layer) => {
+                self.channel_layer = layer;
+                true
+            }
+            Msg::SetChannelId(id) => {
+                self.channel_id = id;
+                true
+            }
+            Msg::SetChannelText(text) => {
+                self.channel_text = text;
+                false
+            }
+            Msg::OpenChannel => {
+                self.open_channel(ctx);
+                true
+            }
+            Msg::SendOnChannel => {
+                self.open_channel(ctx);
+                let text = self.channel_text.clone();
+                let (layer, id) = (self.channel_layer, self.channel_id);
+                if let Some(channel) = self.channel_mut(layer, id) {
+                    channel.send(text.as_bytes());
+                }
+                true
+            }
+            Msg::CloseChannel(layer, id) => {
+                self.channels
+                    .retain(|channel| channel.layer != layer || channel.id != id);
+                true
+            }
+            Msg::OnChannelMessage(session, layer, id, data) => {
+                if session != self.session {
+                    return false;
+                }
+                let Some(channel) = self.channel_mut(layer, id) else {
+                    return false;
+                };
+                channel.record(format!("Received: {}", String::from_utf8_lossy(&data)));
+                true
+            }
+            Msg::OnDatagramPing(rtt) => {
+                self.pinging = self.pinging.saturating_sub(1);
+                self.datagram_pings.record(rtt);
+                true
+            }
+            Msg::OnStreamPing(rtt) => {
+                self.pinging = self.pinging.saturating_sub(1);
+                self.stream_pings.record(rtt);
                 true
             }
         }
@@ -282,6 +2015,7 @@ impl Component for Model {
                 <div id="tool">
                     <img class="banner" src="./assets/banner.jpeg"/>
                     <h1>{"Yew-WebTransport test client"}</h1>
+                    { self.view_tabs(ctx) }
                     <div>
                         <h2>{"Establish WebTransport connection"}</h2>
                         <div class="input-line">
@@ -290,7 +2024,7 @@ impl Component for Model {
                                 name="url"
                                 id="url"
                                 value={self.endpoint.clone()}
-                                disabled={self.transport.is_some()}
+                                disabled={self.active()}
                                 onkeyup={ctx.link().callback(|e: KeyboardEvent| {
                                     let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
                                     let text = input.value();
@@ -298,15 +2032,29 @@ impl Component for Model {
                                 })}/>
                             <input type="button"
                                 id="connect"
-                                disabled={self.transport.is_some()}
+                                disabled={self.active()}
                                 value="Connect"
                                 onclick={ctx.link().callback(|_| WsAction::Connect)}/>
                             <input type="button"
                                 id="connect"
-                                disabled={self.transport.is_none()}
+                                disabled={!self.active()}
                                 value="Disconnect"
                                 onclick={ctx.link().callback(|_| WsAction::Disconnect)}/>
                         </div>
+                        <div class="input-line">
+                            <label for="certificate-hash">{"Certificate SHA-256:"}</label>
+                            <input type="text"
+                                name="certificate-hash"
+                                id="certificate-hash"
+                                placeholder="hex or base64, for self-signed servers"
+                                value={self.certificate_hash.clone()}
+                                disabled={self.active()}
+                                onkeyup={ctx.link().callback(|e: KeyboardEvent| {
+                                    let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                                    WsAction::SetCertificateHash(input.value())
+                                })}/>
+                        </div>
+                        { self.view_connection_state(ctx) }
                     </div>
                     <div>
                         <h2>{"Send data over WebTransport"}</h2>
@@ -315,7 +2063,7 @@ impl Component for Model {
                                 let input = e.target_dyn_into::<HtmlTextAreaElement>().unwrap();
                                 let text = input.value();
                                 WsAction::SetText(text)
-                            })} name="data" id="data" disabled={self.transport.is_none()}></textarea>
+                            })} name="data" id="data" disabled={self.any_transport().is_none()}></textarea>
                             <div>
                                 <input type="radio" name="sendtype" id="datagram" onchange={ctx.link().callback(|e: Event|{
                                     let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
@@ -352,15 +2100,64 @@ impl Component for Model {
                             <input type="button"
                                 id="send"
                                 name="send"
-                                disabled={self.transport.is_none()}
+                                disabled={self.any_transport().is_none() || self.stress.is_some()}
                                 value="Send data"
                                 onclick={ctx.link().callback(|_| WsAction::SendData())}/>
                         </form>
                     </div>
+                    <div>
+                        <h2>{"Stress test over the selected message type"}</h2>
+                        { self.view_stress(ctx) }
+                    </div>
+                    <div>
+                        <h2>{"Channels"}</h2>
+                        { self.view_channels(ctx) }
+                    </div>
+                    <div>
+                        <h2>{"Synthetic media, sent by priority"}</h2>
+                        { self.view_media(ctx) }
+                    </div>
+                    <div>
+                        <h2>{"Upload a file over unidirectional streams"}</h2>
+                        { self.view_upload(ctx) }
+                    </div>
+                    <div>
+                        <h2>{"Round-trip time"}</h2>
+                        { self.view_ping(ctx) }
+                    </div>
+                    <div>
+                        <h2>{"Statistics"}</h2>
+                        { self.view_stats() }
+                    </div>
+                    <div>
+                        <h2>{"Open streams"}</h2>
+                        { self.view_streams() }
+                    </div>
                     <div>
                         <h2>{"Event log"}</h2>
+                        <div>
+                            <input type="checkbox"
+                                id="hex-view"
+                                checked={self.hex_view}
+                                onchange={ctx.link().callback(|e: Event| {
+                                    let input = e.target_dyn_into::<HtmlInputElement>().unwrap();
+                                    Msg::SetHexView(input.checked())
+                                })}/>
+                            <label for="hex-view">{"Show received text as hex too"}</label>
+                            <input type="button"
+                                value="Export log"
+                                title="Downloads everything this tab's sessions received, replayable with SessionCapture::from_recording"
+                                onclick={ctx.link().callback(|_| Msg::ExportRecording)}/>
+                        </div>
                         <ul id="event-log">
-                        { for self.log.iter().map(|log| html! { <li>{ log }</li> }) }
+                        { for self.tab().log.iter().map(|entry| html! {
+                            <li>
+                                { &entry.text }
+                                if let Some(dump) = entry.dump.as_ref() {
+                                    <pre class="hex-dump">{ dump }</pre>
+                                }
+                            </li>
+                        }) }
                         </ul>
                     </div>
                 </div>
@@ -369,6 +2166,69 @@ impl Component for Model {
     }
 }
 
+/// Formats `data` 16 bytes per line, as an offset, the bytes in hex, and the printable ones as
+/// ASCII.
+fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {:<47}  |{ascii}|\n",
+            line * 16,
+            hex.join(" ")
+        ));
+    }
+    dump
+}
+
+/// Writes `payload` as `count` datagrams through a single writer, waiting only when the browser
+/// applies backpressure, and until the last one is sent.
+async fn write_datagrams(
+    datagrams: WebTransportDatagramDuplexStream,
+    payload: &[u8],
+    count: usize,
+) -> Result<(), String> {
+    let error = |e: wasm_bindgen::JsValue| format!("{e:?}");
+    let writer = datagrams.writable().get_writer().map_err(error)?;
+    let chunk = Uint8Array::from(payload);
+    let written = async {
+        let mut last = None;
+        for _ in 0..count {
+            JsFuture::from(writer.ready()).await.map_err(error)?;
+            last = Some(writer.write_with_chunk(&chunk));
+        }
+        if let Some(last) = last {
+            JsFuture::from(last).await.map_err(error)?;
+        }
+        Ok(())
+    }
+    .await;
+    writer.release_lock();
+    written
+}
+
+/// Reads the bytes of `file` from `start` to `end`.
+async fn read_chunk(file: &File, start: u64, end: u64) -> Result<Vec<u8>, String> {
+    let slice = file
+        .slice_with_f64_and_f64(start as f64, end as f64)
+        .map_err(|e| format!("{e:?}"))?;
+    let buffer = JsFuture::from(slice.array_buffer())
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+    let buffer: ArrayBuffer = buffer.unchecked_into();
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
 fn main() {
     yew::Renderer::<Model>::new().render();
 }
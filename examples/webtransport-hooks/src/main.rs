@@ -0,0 +1,153 @@
+//! The demo rewritten with function components, as a reference for the hook-based API.
+
+use std::rc::Rc;
+use web_sys::HtmlInputElement;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_webtransport::hooks::{use_datagrams, use_webtransport, WebTransportProvider};
+use yew_webtransport::sessions::SessionState;
+
+const DEFAULT_URL: &str = std::env!("WS_URL");
+
+#[function_component]
+fn App() -> Html {
+    let url = use_state(|| None::<AttrValue>);
+    let input = use_node_ref();
+    let connect = {
+        let url = url.clone();
+        let input = input.clone();
+        Callback::from(move |_| {
+            if let Some(input) = input.cast::<HtmlInputElement>() {
+                url.set(Some(input.value().into()));
+            }
+        })
+    };
+    let disconnect = {
+        let url = url.clone();
+        Callback::from(move |_| url.set(None))
+    };
+    html! {
+        <>
+            <h1>{ "WebTransport hooks" }</h1>
+            <p>
+                <input type="text" ref={input} value={DEFAULT_URL} />
+                if url.is_some() {
+                    <button onclick={disconnect}>{ "Disconnect" }</button>
+                } else {
+                    <button onclick={connect}>{ "Connect" }</button>
+                }
+            </p>
+            if let Some(url) = (*url).clone() {
+                <WebTransportProvider {url}>
+                    <Status />
+                    <Datagrams />
+                    <Request />
+                </WebTransportProvider>
+            }
+        </>
+    }
+}
+
+#[function_component]
+fn Status() -> Html {
+    let session = use_webtransport();
+    let state = match session.state() {
+        SessionState::Connecting => "Connecting".to_string(),
+        SessionState::Open => "Open".to_string(),
+        SessionState::Closed(info) => format!("Closed: {info:?}"),
+        SessionState::Failed(error) => format!("Failed: {error}"),
+    };
+    html! { <p class="state">{ state }</p> }
+}
+
+/// Received messages. A reducer rather than a state, so that datagrams arriving between two
+/// renders all make it into the log.
+#[derive(Default, PartialEq)]
+struct Log {
+    entries: Vec<String>,
+}
+
+impl Reducible for Log {
+    type Action = String;
+
+    fn reduce(self: Rc<Self>, entry: String) -> Rc<Self> {
+        let mut entries = self.entries.clone();
+        entries.push(entry);
+        Rc::new(Self { entries })
+    }
+}
+
+/// Sends datagrams and shows those the server sends back.
+#[function_component]
+fn Datagrams() -> Html {
+    let session = use_webtransport();
+    let log = use_reducer(Log::default);
+    {
+        let log = log.dispatcher();
+        use_datagrams(Callback::from(move |datagram: Vec<u8>| {
+            log.dispatch(String::from_utf8_lossy(&datagram).into_owned())
+        }));
+    }
+    let input = use_node_ref();
+    let send = {
+        let input = input.clone();
+        let session = session.clone();
+        Callback::from(move |_| {
+            if let Some(input) = input.cast::<HtmlInputElement>() {
+                session.send_datagram(input.value().into_bytes());
+            }
+        })
+    };
+    html! {
+        <>
+            <h2>{ "Datagrams" }</h2>
+            <p>
+                <input type="text" ref={input} value="Hello" />
+                <button onclick={send} disabled={!session.is_open()}>{ "Send" }</button>
+            </p>
+            <div class="log">
+                { for log.entries.iter().map(|entry| html! { <div>{ entry }</div> }) }
+            </div>
+        </>
+    }
+}
+
+/// Sends a request on a bidirectional stream and shows the response.
+#[function_component]
+fn Request() -> Html {
+    let session = use_webtransport();
+    let response = use_state(String::new);
+    let input = use_node_ref();
+    let send = {
+        let input = input.clone();
+        let session = session.clone();
+        let response = response.clone();
+        Callback::from(move |_| {
+            let Some(input) = input.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let request = session.request(input.value().into_bytes());
+            let response = response.clone();
+            spawn_local(async move {
+                match request.await {
+                    Ok(data) => response.set(String::from_utf8_lossy(&data).into_owned()),
+                    Err(error) => response.set(format!("Request failed: {error}")),
+                }
+            });
+        })
+    };
+    html! {
+        <>
+            <h2>{ "Request" }</h2>
+            <p>
+                <input type="text" ref={input} value="Hello" />
+                <button onclick={send} disabled={!session.is_open()}>{ "Send" }</button>
+            </p>
+            <div class="log">{ (*response).clone() }</div>
+        </>
+    }
+}
+
+fn main() {
+    yew::Renderer::<App>::new().render();
+}
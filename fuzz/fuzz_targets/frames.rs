@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yew_webtransport::mux::FrameDecoder;
+
+// The first byte sets the chunk size, so that frames get split across pushes.
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk_size, stream)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = FrameDecoder::new();
+    let mut received = 0;
+    let mut taken = 0;
+    for chunk in stream.chunks(usize::from(chunk_size).max(1)) {
+        decoder.push(chunk);
+        received += chunk.len();
+        while let Some((_, payload)) = decoder.next_frame() {
+            taken += 2 + payload.len();
+        }
+        assert!(taken + decoder.buffered() <= received);
+    }
+});
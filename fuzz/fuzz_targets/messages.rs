@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yew_webtransport::reliable::MessageDecoder;
+
+// The first byte sets the chunk size, so that messages get split across pushes.
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk_size, stream)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = MessageDecoder::new();
+    for chunk in stream.chunks(usize::from(chunk_size).max(1)) {
+        decoder.push(chunk);
+        while decoder.next_message().is_some() {}
+    }
+});
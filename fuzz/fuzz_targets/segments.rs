@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yew_webtransport::upload::decode_segment;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(segment) = decode_segment(data) {
+        assert!(segment.offset + segment.data.len() as u64 <= segment.total_len);
+        assert!(segment.data.len() < data.len());
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yew_webtransport::varint;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((value, len)) = varint::decode(data) {
+        assert!(value <= varint::MAX);
+        assert_eq!(len, varint::len_from_first_byte(data[0]));
+        assert!(len <= data.len());
+        let mut encoded = Vec::new();
+        varint::encode(value, &mut encoded);
+        assert_eq!(varint::decode(&encoded), Some((value, encoded.len())));
+    }
+});
@@ -0,0 +1,403 @@
+//! Best-effort reliable delivery over datagrams, for servers that only speak datagrams.
+//!
+//! An [`ArqChannel`] numbers every message it sends on a [`DatagramChannel`] and keeps it until
+//! the peer acknowledges it, retransmitting with exponential backoff when the retransmission
+//! timer fires. Timers follow [RFC 6298](https://www.rfc-editor.org/rfc/rfc6298): they are
+//! derived from the smoothed round-trip time, which is only sampled from messages that were not
+//! retransmitted. A message is given up on after [`ArqConfig::max_retransmissions`], or once
+//! its deadline passed if it has one.
+//!
+//! Each datagram is `kind u8 | sequence varint | payload`, where kind is 0 for data and 1 for
+//! an acknowledgement of `sequence`. Duplicates are filtered out on receipt, so every message
+//! that makes it through is delivered once, but not necessarily in the order it was sent.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::channel::DatagramChannel;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+use crate::varint;
+
+const KIND_DATA: u8 = 0;
+const KIND_ACK: u8 = 1;
+
+/// Tuning for an [`ArqChannel`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArqConfig {
+    /// Retransmission timeout used until the first round-trip time sample.
+    pub initial_rto: Duration,
+    /// Lower bound of the retransmission timeout.
+    pub min_rto: Duration,
+    /// Upper bound of the retransmission timeout, backoff included.
+    pub max_rto: Duration,
+    /// Number of retransmissions after which a message is given up on.
+    pub max_retransmissions: u32,
+    /// Time after which [`ArqChannel::send`] stops retransmitting an unacknowledged message.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for ArqConfig {
+    fn default() -> Self {
+        Self {
+            initial_rto: Duration::from_secs(1),
+            min_rto: Duration::from_millis(200),
+            max_rto: Duration::from_secs(10),
+            max_retransmissions: 5,
+            deadline: None,
+        }
+    }
+}
+
+/// Counters kept by an [`ArqChannel`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArqStats {
+    pub messages_sent: u64,
+    pub messages_acked: u64,
+    pub retransmissions: u64,
+    pub messages_given_up: u64,
+    /// Messages dropped unacknowledged because their deadline passed.
+    pub messages_expired: u64,
+    pub messages_received: u64,
+    pub duplicates_received: u64,
+}
+
+/// Smoothed round-trip time estimation as described in RFC 6298.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RttEstimator {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+}
+
+impl RttEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a round-trip time measurement.
+    pub fn sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let deviation = srtt.max(rtt) - srtt.min(rtt);
+                self.rttvar = self.rttvar * 3 / 4 + deviation / 4;
+                self.srtt = Some(srtt * 7 / 8 + rtt / 8);
+            }
+        }
+    }
+
+    /// The smoothed round-trip time, once there is a sample.
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// The round-trip time variation.
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// The retransmission timeout, `srtt + 4 * rttvar`, or `initial` without samples.
+    pub fn rto(&self, initial: Duration) -> Duration {
+        match self.srtt {
+            Some(srtt) => srtt + self.rttvar * 4,
+            None => initial,
+        }
+    }
+}
+
+struct Unacked {
+    payload: Vec<u8>,
+    first_sent: Duration,
+    retransmissions: u32,
+    deadline: Option<Duration>,
+}
+
+/// Tracks which sequence numbers were received, to filter out duplicates.
+#[derive(Default)]
+struct ReceiveWindow {
+    /// Every sequence number below this one has been received.
+    contiguous: u64,
+    above: BTreeSet<u64>,
+}
+
+impl ReceiveWindow {
+    /// Records `sequence`, returning `false` if it was already received.
+    fn insert(&mut self, sequence: u64) -> bool {
+        if sequence < self.contiguous || !self.above.insert(sequence) {
+            return false;
+        }
+        while self.above.remove(&self.contiguous) {
+            self.contiguous += 1;
+        }
+        true
+    }
+}
+
+struct ArqState {
+    config: ArqConfig,
+    next_sequence: u64,
+    unacked: BTreeMap<u64, Unacked>,
+    received: ReceiveWindow,
+    rtt: RttEstimator,
+    stats: ArqStats,
+    on_message: Callback<Vec<u8>>,
+}
+
+impl ArqState {
+    /// The timeout before retransmitting a message already retransmitted `retransmissions`
+    /// times.
+    fn rto(&self, retransmissions: u32) -> Duration {
+        let rto = self
+            .rtt
+            .rto(self.config.initial_rto)
+            .max(self.config.min_rto);
+        rto.saturating_mul(1 << retransmissions.min(16))
+            .min(self.config.max_rto)
+    }
+}
+
+struct Inner {
+    state: RefCell<ArqState>,
+    channel: DatagramChannel,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl Inner {
+    fn transmit(&self, kind: u8, sequence: u64, payload: &[u8]) {
+        let mut data = Vec::with_capacity(payload.len() + 9);
+        data.push(kind);
+        varint::encode(sequence, &mut data);
+        data.extend_from_slice(payload);
+        self.channel.send(&data);
+    }
+
+    fn arm_timer(self: &Rc<Self>, sequence: u64, retransmissions: u32) {
+        let delay = {
+            let state = self.state.borrow();
+            let rto = state.rto(retransmissions);
+            // Wake up at the deadline to drop the message if it comes first.
+            match state
+                .unacked
+                .get(&sequence)
+                .and_then(|unacked| unacked.deadline)
+            {
+                Some(deadline) => rto.min(deadline.saturating_sub(self.scheduler.now())),
+                None => rto,
+            }
+        };
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            delay,
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.on_timeout(sequence, retransmissions);
+                }
+            }),
+        );
+    }
+
+    fn on_timeout(self: &Rc<Self>, sequence: u64, retransmissions: u32) {
+        let now = self.scheduler.now();
+        let payload = {
+            let mut state = self.state.borrow_mut();
+            let max_retransmissions = state.config.max_retransmissions;
+            let Some(unacked) = state.unacked.get_mut(&sequence) else {
+                return;
+            };
+            // A stale timer from an earlier transmission.
+            if unacked.retransmissions != retransmissions {
+                return;
+            }
+            if unacked.deadline.is_some_and(|deadline| deadline <= now) {
+                state.unacked.remove(&sequence);
+                state.stats.messages_expired += 1;
+                return;
+            }
+            if retransmissions >= max_retransmissions {
+                state.unacked.remove(&sequence);
+                state.stats.messages_given_up += 1;
+                return;
+            }
+            unacked.retransmissions += 1;
+            let payload = unacked.payload.clone();
+            state.stats.retransmissions += 1;
+            payload
+        };
+        self.transmit(KIND_DATA, sequence, &payload);
+        self.arm_timer(sequence, retransmissions + 1);
+    }
+
+    fn receive(&self, data: Vec<u8>) {
+        let Some((&kind, rest)) = data.split_first() else {
+            return;
+        };
+        let Some((sequence, len)) = varint::decode(rest) else {
+            return;
+        };
+        match kind {
+            KIND_DATA => {
+                self.transmit(KIND_ACK, sequence, &[]);
+                let on_message = {
+                    let mut state = self.state.borrow_mut();
+                    if !state.received.insert(sequence) {
+                        state.stats.duplicates_received += 1;
+                        return;
+                    }
+                    state.stats.messages_received += 1;
+                    state.on_message.clone()
+                };
+                on_message.emit(rest[len..].to_vec());
+            }
+            KIND_ACK => {
+                let now = self.scheduler.now();
+                let mut state = self.state.borrow_mut();
+                if let Some(unacked) = state.unacked.remove(&sequence) {
+                    state.stats.messages_acked += 1;
+                    // Karn's algorithm: an ack for a retransmitted message is ambiguous.
+                    if unacked.retransmissions == 0 {
+                        state.rtt.sample(now.saturating_sub(unacked.first_sent));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Acknowledged, retransmitted messages over a datagram channel.
+pub struct ArqChannel {
+    inner: Rc<Inner>,
+}
+
+impl ArqChannel {
+    /// Registers datagram channel `id` on `transport`, timed by the platform timers.
+    pub fn new<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        config: ArqConfig,
+        on_message: Callback<Vec<u8>>,
+    ) -> Self {
+        Self::with_scheduler(
+            transport,
+            id,
+            config,
+            on_message,
+            Rc::new(PlatformScheduler),
+        )
+    }
+
+    /// Registers datagram channel `id` on `transport`, timed by `scheduler`.
+    pub fn with_scheduler<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        config: ArqConfig,
+        on_message: Callback<Vec<u8>>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let receiver = inner.clone();
+            let channel = transport.datagram_channel(
+                id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            Inner {
+                state: RefCell::new(ArqState {
+                    config,
+                    next_sequence: 0,
+                    unacked: BTreeMap::new(),
+                    received: ReceiveWindow::default(),
+                    rtt: RttEstimator::new(),
+                    stats: ArqStats::default(),
+                    on_message,
+                }),
+                channel,
+                scheduler,
+            }
+        });
+        Self { inner }
+    }
+
+    /// Sends `message`, retransmitting it until it is acknowledged or given up on. Returns its
+    /// sequence number.
+    pub fn send(&self, message: &[u8]) -> u64 {
+        let deadline = self.inner.state.borrow().config.deadline;
+        self.send_inner(message, deadline)
+    }
+
+    /// Like [`ArqChannel::send`], but stops retransmitting `message` once `deadline` has
+    /// elapsed without an acknowledgement, counting it in [`ArqStats::messages_expired`].
+    pub fn send_with_deadline(&self, message: &[u8], deadline: Duration) -> u64 {
+        self.send_inner(message, Some(deadline))
+    }
+
+    fn send_inner(&self, message: &[u8], deadline: Option<Duration>) -> u64 {
+        let sequence = {
+            let now = self.inner.scheduler.now();
+            let mut state = self.inner.state.borrow_mut();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.stats.messages_sent += 1;
+            state.unacked.insert(
+                sequence,
+                Unacked {
+                    payload: message.to_vec(),
+                    first_sent: now,
+                    retransmissions: 0,
+                    deadline: deadline.map(|deadline| now + deadline),
+                },
+            );
+            sequence
+        };
+        self.inner.transmit(KIND_DATA, sequence, message);
+        self.inner.arm_timer(sequence, 0);
+        sequence
+    }
+
+    /// Replaces the callback receiving messages.
+    pub fn set_on_message(&self, on_message: Callback<Vec<u8>>) {
+        self.inner.state.borrow_mut().on_message = on_message;
+    }
+
+    /// Whether `sequence` is still waiting for an acknowledgement.
+    pub fn is_pending(&self, sequence: u64) -> bool {
+        self.inner.state.borrow().unacked.contains_key(&sequence)
+    }
+
+    /// Number of messages waiting for an acknowledgement.
+    pub fn in_flight(&self) -> usize {
+        self.inner.state.borrow().unacked.len()
+    }
+
+    /// The smoothed round-trip time, once a message has been acknowledged.
+    pub fn srtt(&self) -> Option<Duration> {
+        self.inner.state.borrow().rtt.srtt()
+    }
+
+    pub fn stats(&self) -> ArqStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl fmt::Debug for ArqChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("ArqChannel")
+            .field("id", &self.inner.channel.id())
+            .field("in_flight", &state.unacked.len())
+            .field("srtt", &state.rtt.srtt())
+            .finish()
+    }
+}
@@ -0,0 +1,109 @@
+//! Passing an auth token to the server.
+//!
+//! Browsers don't let WebTransport sessions carry custom headers, so a token has to travel in
+//! the URL, as a query parameter. Tokens usually expire, and one that was valid for the first
+//! session may no longer be by the time it is re-established: a [`TokenAuth`] fetches a fresh
+//! token before every connection, see
+//! [`ReconnectingSession::connect_with_auth`](crate::reconnect::ReconnectingSession::connect_with_auth).
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use std::{fmt, future::Future, rc::Rc};
+
+use crate::webtransport::{WebTransportBuilder, WebTransportError, WebTransportTask};
+
+/// Appends `param=token` to the query of `url`, percent-encoding the token.
+pub fn with_token(url: &str, param: &str, token: &str) -> String {
+    let (base, fragment) = match url.find('#') {
+        Some(at) => url.split_at(at),
+        None => (url, ""),
+    };
+    let separator = match base.find('?') {
+        Some(at) if at + 1 == base.len() || base.ends_with('&') => "",
+        Some(_) => "&",
+        None => "?",
+    };
+    format!(
+        "{base}{separator}{}={}{fragment}",
+        percent_encode(param),
+        percent_encode(token)
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+type GetToken = dyn Fn() -> LocalBoxFuture<'static, Result<String, WebTransportError>>;
+
+/// Fetches a token before connecting and passes it in the query parameter `param`.
+///
+/// Cloning a `TokenAuth` yields another handle to the same token source.
+#[derive(Clone)]
+pub struct TokenAuth {
+    param: String,
+    get_token: Rc<GetToken>,
+}
+
+impl TokenAuth {
+    /// Calls `get_token` for a token before every connection.
+    pub fn new<F>(param: impl Into<String>, get_token: impl Fn() -> F + 'static) -> Self
+    where
+        F: Future<Output = Result<String, WebTransportError>> + 'static,
+    {
+        Self {
+            param: param.into(),
+            get_token: Rc::new(move || get_token().boxed_local()),
+        }
+    }
+
+    /// Passes the same `token` every time.
+    pub fn fixed(param: impl Into<String>, token: impl Into<String>) -> Self {
+        let token = token.into();
+        Self::new(param, move || futures::future::ready(Ok(token.clone())))
+    }
+
+    pub fn param(&self) -> &str {
+        &self.param
+    }
+
+    /// Fetches a token and appends it to `url`.
+    pub fn authenticate(
+        &self,
+        url: &str,
+    ) -> LocalBoxFuture<'static, Result<String, WebTransportError>> {
+        let url = url.to_string();
+        let param = self.param.clone();
+        let token = (self.get_token)();
+        async move { Ok(with_token(&url, &param, &token.await?)) }.boxed_local()
+    }
+
+    /// Fetches a token, and connects with `builder` to its URL carrying the token.
+    pub fn connect(
+        &self,
+        mut builder: WebTransportBuilder,
+    ) -> LocalBoxFuture<'static, Result<WebTransportTask, WebTransportError>> {
+        let url = self.authenticate(&builder.url);
+        async move {
+            builder.url = url.await?;
+            builder.connect()
+        }
+        .boxed_local()
+    }
+}
+
+impl fmt::Debug for TokenAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenAuth")
+            .field("param", &self.param)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,352 @@
+//! Measuring a session's latency and throughput against an echo server.
+//!
+//! The server is expected to behave like the one in `examples/echo-server`: datagrams are sent
+//! straight back, and whatever is written on a bidirectional stream is echoed on the same stream
+//! until the client finishes it.
+//!
+//! [`Benchmark::datagram_latency`] sends numbered probes on a [`DatagramChannel`] at a fixed
+//! interval and reports the distribution of their round-trip times. Probes are
+//! `sequence number (varint) | padding`. [`Benchmark::stream_throughput`] echoes a payload over a
+//! bidirectional stream and reports how fast it went through.
+
+#[cfg(feature = "datagrams")]
+use futures::channel::oneshot;
+use futures::future::{FutureExt, LocalBoxFuture};
+#[cfg(feature = "datagrams")]
+use std::{
+    cell::{Cell, RefCell},
+    rc::Weak,
+};
+use std::{fmt, rc::Rc, time::Duration};
+#[cfg(feature = "datagrams")]
+use yew::callback::Callback;
+
+#[cfg(feature = "datagrams")]
+use crate::channel::DatagramChannel;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+#[cfg(feature = "datagrams")]
+use crate::varint;
+#[cfg(feature = "streams")]
+use crate::webtransport::WebTransportError;
+
+/// Tuning for [`Benchmark::datagram_latency`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyConfig {
+    /// Probes sent.
+    pub probes: usize,
+    /// Time between probes.
+    pub interval: Duration,
+    /// Time to wait for answers after the last probe; later answers count as lost.
+    pub timeout: Duration,
+    /// Datagram channel the probes are sent on.
+    pub channel: u64,
+    /// Size of a probe, sequence number included.
+    pub payload_size: usize,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            probes: 100,
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(1),
+            channel: 0,
+            payload_size: 64,
+        }
+    }
+}
+
+/// Round-trip times measured by [`Benchmark::datagram_latency`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyReport {
+    /// Probes sent.
+    pub sent: usize,
+    /// Round-trip time of every probe answered, shortest first.
+    pub samples: Vec<Duration>,
+}
+
+impl LatencyReport {
+    pub fn from_samples(sent: usize, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self { sent, samples }
+    }
+
+    /// Probes answered.
+    pub fn received(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Share of the probes that went unanswered, between 0 and 1.
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        1.0 - self.received() as f64 / self.sent as f64
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.first().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.last().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let total: Duration = self.samples.iter().sum();
+        Some(total / self.samples.len() as u32)
+    }
+
+    /// The round-trip time below which `percentile` percent of the answered probes fall, with
+    /// `percentile` between 0 and 100.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * self.samples.len() as f64).ceil();
+        let index = (rank as usize)
+            .saturating_sub(1)
+            .min(self.samples.len() - 1);
+        Some(self.samples[index])
+    }
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} datagrams answered ({:.1}% loss)",
+            self.received(),
+            self.sent,
+            self.loss() * 100.0
+        )?;
+        let (Some(min), Some(mean), Some(max)) = (self.min(), self.mean(), self.max()) else {
+            return Ok(());
+        };
+        let p = |percentile| self.percentile(percentile).unwrap_or_default();
+        write!(
+            f,
+            ", rtt min {:?} mean {:?} p50 {:?} p90 {:?} p99 {:?} max {:?}",
+            min,
+            mean,
+            p(50.0),
+            p(90.0),
+            p(99.0),
+            max
+        )
+    }
+}
+
+/// A payload echoed by [`Benchmark::stream_throughput`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThroughputReport {
+    /// Bytes written.
+    pub sent: usize,
+    /// Bytes echoed back.
+    pub received: usize,
+    /// Time from opening the stream until the echo was complete.
+    pub elapsed: Duration,
+}
+
+impl ThroughputReport {
+    /// Bits per second that made the round trip, counting each byte once.
+    pub fn bits_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.received as f64 * 8.0 / secs
+    }
+}
+
+impl fmt::Display for ThroughputReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes sent, {} echoed in {:?} ({:.2} Mbit/s)",
+            self.sent,
+            self.received,
+            self.elapsed,
+            self.bits_per_second() / 1_000_000.0
+        )
+    }
+}
+
+#[cfg(feature = "datagrams")]
+struct LatencyRun {
+    config: LatencyConfig,
+    scheduler: Rc<dyn Scheduler>,
+    channel: RefCell<Option<DatagramChannel>>,
+    /// Send time of every probe not answered yet, by sequence number.
+    pending: RefCell<Vec<Option<Duration>>>,
+    samples: RefCell<Vec<Duration>>,
+    sent: Cell<usize>,
+    done: Cell<Option<oneshot::Sender<()>>>,
+}
+
+#[cfg(feature = "datagrams")]
+impl LatencyRun {
+    fn probe(&self) {
+        let sequence = self.sent.get();
+        let mut data = Vec::with_capacity(self.config.payload_size.max(8));
+        varint::encode(sequence as u64, &mut data);
+        if data.len() < self.config.payload_size {
+            data.resize(self.config.payload_size, 0);
+        }
+        self.pending.borrow_mut().push(Some(self.scheduler.now()));
+        self.sent.set(sequence + 1);
+        if let Some(channel) = self.channel.borrow().as_ref() {
+            channel.send(&data);
+        }
+    }
+
+    fn schedule(self: &Rc<Self>) {
+        let run = Rc::downgrade(self);
+        if self.sent.get() < self.config.probes {
+            self.scheduler.schedule(
+                self.config.interval,
+                Box::new(move || {
+                    if let Some(run) = run.upgrade() {
+                        run.probe();
+                        run.schedule();
+                    }
+                }),
+            );
+        } else {
+            self.scheduler.schedule(
+                self.config.timeout,
+                Box::new(move || {
+                    if let Some(run) = run.upgrade() {
+                        run.finish();
+                    }
+                }),
+            );
+        }
+    }
+
+    fn receive(&self, data: Vec<u8>) {
+        let now = self.scheduler.now();
+        let Some((sequence, _)) = varint::decode(&data) else {
+            return;
+        };
+        let sent_at = self
+            .pending
+            .borrow_mut()
+            .get_mut(sequence as usize)
+            .and_then(Option::take);
+        let Some(sent_at) = sent_at else {
+            return;
+        };
+        let answered = {
+            let mut samples = self.samples.borrow_mut();
+            samples.push(now.saturating_sub(sent_at));
+            samples.len()
+        };
+        if answered == self.config.probes {
+            self.finish();
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Runs measurements over a transport.
+pub struct Benchmark<T> {
+    transport: Rc<T>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl<T: Transport + 'static> Benchmark<T> {
+    /// Measures `transport`, timed by the platform clock and timers.
+    pub fn new(transport: Rc<T>) -> Self {
+        Self::with_scheduler(transport, Rc::new(PlatformScheduler))
+    }
+
+    /// Measures `transport`, timed by `scheduler`.
+    pub fn with_scheduler(transport: Rc<T>, scheduler: Rc<dyn Scheduler>) -> Self {
+        Self {
+            transport,
+            scheduler,
+        }
+    }
+
+    /// Sends `config.probes` datagrams and resolves once every one was answered, or
+    /// `config.timeout` after the last one was sent.
+    #[cfg(feature = "datagrams")]
+    pub fn datagram_latency(
+        &self,
+        config: LatencyConfig,
+    ) -> LocalBoxFuture<'static, LatencyReport> {
+        let (done, finished) = oneshot::channel();
+        let channel_id = config.channel;
+        let run = Rc::new_cyclic(|run: &Weak<LatencyRun>| {
+            let receiver = run.clone();
+            let channel = self.transport.datagram_channel(
+                channel_id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(run) = receiver.upgrade() {
+                        run.receive(data);
+                    }
+                }),
+            );
+            LatencyRun {
+                config,
+                scheduler: self.scheduler.clone(),
+                channel: RefCell::new(Some(channel)),
+                pending: RefCell::new(Vec::new()),
+                samples: RefCell::new(Vec::new()),
+                sent: Cell::new(0),
+                done: Cell::new(Some(done)),
+            }
+        });
+        if run.config.probes == 0 {
+            run.finish();
+        } else {
+            run.probe();
+            run.schedule();
+        }
+        async move {
+            let _ = finished.await;
+            // Unregisters the channel, so late answers go nowhere.
+            run.channel.borrow_mut().take();
+            let samples = run.samples.take();
+            LatencyReport::from_samples(run.sent.get(), samples)
+        }
+        .boxed_local()
+    }
+
+    /// Echoes `bytes` bytes over a bidirectional stream.
+    #[cfg(feature = "streams")]
+    pub fn stream_throughput(
+        &self,
+        bytes: usize,
+    ) -> LocalBoxFuture<'static, Result<ThroughputReport, WebTransportError>> {
+        let payload: Vec<u8> = (0..bytes).map(|i| i as u8).collect();
+        let scheduler = self.scheduler.clone();
+        let started = scheduler.now();
+        let echo = self.transport.request(payload);
+        async move {
+            let echoed = echo.await?;
+            Ok(ThroughputReport {
+                sent: bytes,
+                received: echoed.len(),
+                elapsed: scheduler.now().saturating_sub(started),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+impl<T> fmt::Debug for Benchmark<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Benchmark").finish_non_exhaustive()
+    }
+}
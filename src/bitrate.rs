@@ -0,0 +1,239 @@
+//! Encoder bitrate adaptation from the session's statistics.
+//!
+//! A [`BitrateAdapter`] samples [`Transport::stats`] at a fixed interval and compares each
+//! sample with the previous one: the send rate over the interval, and the fraction of packets
+//! lost or datagrams dropped. High loss lowers the target bitrate, multiplicatively and never
+//! above what actually got through; sustained low loss raises it slowly. Loss rates between the
+//! two thresholds leave it alone, and a change needs several agreeing intervals, so the target
+//! doesn't oscillate around the available bandwidth.
+
+use std::{cell::RefCell, fmt, rc::Rc, time::Duration};
+use yew::callback::Callback;
+
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::{Transport, TransportStats};
+
+/// Tuning for a [`BitrateAdapter`]. Bitrates are in bits per second.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitrateConfig {
+    /// Time between two samples.
+    pub interval: Duration,
+    pub initial_bitrate: u64,
+    pub min_bitrate: u64,
+    pub max_bitrate: u64,
+    /// Loss rate at or above which the bitrate is lowered.
+    pub high_loss: f64,
+    /// Loss rate at or below which the bitrate is raised.
+    pub low_loss: f64,
+    pub increase_factor: f64,
+    pub decrease_factor: f64,
+    /// Consecutive low loss intervals needed to raise the bitrate.
+    pub increase_after: u32,
+    /// Consecutive high loss intervals needed to lower the bitrate.
+    pub decrease_after: u32,
+}
+
+impl Default for BitrateConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            initial_bitrate: 500_000,
+            min_bitrate: 50_000,
+            max_bitrate: 5_000_000,
+            high_loss: 0.1,
+            low_loss: 0.02,
+            increase_factor: 1.08,
+            decrease_factor: 0.85,
+            increase_after: 3,
+            decrease_after: 1,
+        }
+    }
+}
+
+/// What a [`BitrateAdapter`] reports after every interval.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BandwidthEstimate {
+    /// Bitrate the encoder should target.
+    pub bitrate: u64,
+    /// Bits sent per second over the last interval.
+    pub send_rate: u64,
+    /// Fraction of the packets sent over the last interval that were lost, or of datagrams that
+    /// were dropped, whichever is higher.
+    pub loss_rate: f64,
+    pub rtt: Option<Duration>,
+}
+
+struct AdapterState {
+    config: BitrateConfig,
+    bitrate: u64,
+    previous: Option<(Duration, TransportStats)>,
+    /// Consecutive intervals asking for a raise (positive) or a cut (negative).
+    streak: i32,
+    estimate: Option<BandwidthEstimate>,
+    on_estimate: Callback<BandwidthEstimate>,
+}
+
+impl AdapterState {
+    fn observe(&mut self, now: Duration, stats: TransportStats) -> Option<BandwidthEstimate> {
+        let previous = self.previous.replace((now, stats.clone()));
+        let (then, previous) = previous?;
+        // Counters going backwards mean a new session: start over from this sample.
+        if stats.packets_sent < previous.packets_sent || stats.bytes_sent < previous.bytes_sent {
+            return None;
+        }
+        let elapsed = now.saturating_sub(then).as_secs_f64();
+        let packets_sent = stats.packets_sent - previous.packets_sent;
+        if elapsed <= 0.0 || packets_sent == 0 {
+            return None;
+        }
+        let packets_lost = stats.packets_lost.saturating_sub(previous.packets_lost);
+        let datagrams_dropped = (stats.datagrams_lost_outgoing + stats.datagrams_expired_outgoing)
+            .saturating_sub(previous.datagrams_lost_outgoing + previous.datagrams_expired_outgoing);
+        let loss_rate = (packets_lost.max(datagrams_dropped) as f64 / packets_sent as f64).min(1.0);
+        let send_rate = ((stats.bytes_sent - previous.bytes_sent) as f64 * 8.0 / elapsed) as u64;
+
+        let config = &self.config;
+        if loss_rate >= config.high_loss {
+            self.streak = self.streak.min(0) - 1;
+        } else if loss_rate <= config.low_loss {
+            self.streak = self.streak.max(0) + 1;
+        } else {
+            self.streak = 0;
+        }
+        if -self.streak >= config.decrease_after.max(1) as i32 {
+            let delivered = send_rate as f64 * (1.0 - loss_rate);
+            let lowered = (self.bitrate as f64 * config.decrease_factor).min(delivered);
+            self.bitrate = (lowered as u64).max(config.min_bitrate);
+            self.streak = 0;
+        } else if self.streak >= config.increase_after.max(1) as i32 {
+            let raised = self.bitrate as f64 * config.increase_factor;
+            self.bitrate = (raised as u64)
+                .max(self.bitrate + 1)
+                .min(config.max_bitrate);
+            self.streak = 0;
+        }
+        let estimate = BandwidthEstimate {
+            bitrate: self.bitrate,
+            send_rate,
+            loss_rate,
+            rtt: stats.smoothed_rtt,
+        };
+        self.estimate = Some(estimate.clone());
+        Some(estimate)
+    }
+}
+
+struct Inner<T> {
+    transport: Rc<T>,
+    scheduler: Rc<dyn Scheduler>,
+    state: RefCell<AdapterState>,
+}
+
+impl<T> Inner<T> {
+    fn observe(&self, stats: TransportStats) {
+        let now = self.scheduler.now();
+        let reported = {
+            let mut state = self.state.borrow_mut();
+            state
+                .observe(now, stats)
+                .map(|estimate| (state.on_estimate.clone(), estimate))
+        };
+        if let Some((on_estimate, estimate)) = reported {
+            on_estimate.emit(estimate);
+        }
+    }
+}
+
+impl<T: Transport + 'static> Inner<T> {
+    fn schedule_sample(self: &Rc<Self>) {
+        let interval = self.state.borrow().config.interval;
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            interval,
+            Box::new(move || {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let stats = inner.transport.stats();
+                let sampler = Rc::downgrade(&inner);
+                yew::platform::spawn_local(async move {
+                    if let (Ok(stats), Some(inner)) = (stats.await, sampler.upgrade()) {
+                        inner.observe(stats);
+                    }
+                });
+                inner.schedule_sample();
+            }),
+        );
+    }
+}
+
+/// Periodically turns the session's statistics into a target bitrate.
+pub struct BitrateAdapter<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T: Transport + 'static> BitrateAdapter<T> {
+    /// Starts sampling `transport` on the platform timers. Every interval, the estimate is
+    /// passed to `on_estimate`.
+    pub fn new(
+        transport: Rc<T>,
+        config: BitrateConfig,
+        on_estimate: Callback<BandwidthEstimate>,
+    ) -> Self {
+        Self::with_scheduler(transport, config, on_estimate, Rc::new(PlatformScheduler))
+    }
+
+    /// Starts sampling `transport` on `scheduler`.
+    pub fn with_scheduler(
+        transport: Rc<T>,
+        config: BitrateConfig,
+        on_estimate: Callback<BandwidthEstimate>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let bitrate = config.initial_bitrate.clamp(
+            config.min_bitrate,
+            config.max_bitrate.max(config.min_bitrate),
+        );
+        let inner = Rc::new(Inner {
+            transport,
+            scheduler,
+            state: RefCell::new(AdapterState {
+                config,
+                bitrate,
+                previous: None,
+                streak: 0,
+                estimate: None,
+                on_estimate,
+            }),
+        });
+        inner.schedule_sample();
+        Self { inner }
+    }
+}
+
+impl<T> BitrateAdapter<T> {
+    /// Feeds a sample taken by the application, in addition to the periodic ones.
+    pub fn observe(&self, stats: TransportStats) {
+        self.inner.observe(stats);
+    }
+
+    /// The current target bitrate.
+    pub fn bitrate(&self) -> u64 {
+        self.inner.state.borrow().bitrate
+    }
+
+    /// The last estimate reported.
+    pub fn estimate(&self) -> Option<BandwidthEstimate> {
+        self.inner.state.borrow().estimate.clone()
+    }
+}
+
+impl<T> fmt::Debug for BitrateAdapter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("BitrateAdapter")
+            .field("bitrate", &state.bitrate)
+            .field("estimate", &state.estimate)
+            .finish()
+    }
+}
@@ -0,0 +1,423 @@
+//! Recording incoming traffic and replaying it later.
+//!
+//! A [`SessionCapture`] records every datagram and stream chunk received through the callbacks
+//! it wraps, with the time it arrived, into a [`Recording`]. Replaying the recording emits each
+//! payload into the callback it was originally delivered to, with the original spacing, so
+//! rendering logic can be debugged offline against real traffic. A recording can be
+//! [encoded](Recording::encode) to bytes to be saved and loaded into a capture in a later run.
+//!
+//! ```no_run
+//! # use yew::Callback;
+//! # use yew_webtransport::capture::SessionCapture;
+//! # use yew_webtransport::webtransport::WebTransportService;
+//! # fn connect(on_datagram: Callback<Vec<u8>>) {
+//! let capture = SessionCapture::new();
+//! let task = WebTransportService::connect(
+//!     "https://example.com:4433",
+//!     capture.datagrams(on_datagram),
+//!     Callback::noop(),
+//!     Callback::noop(),
+//!     Callback::noop(),
+//! )
+//! .unwrap();
+//! let task = capture.wrap(task);
+//! // ... later
+//! let saved = capture.recording().encode();
+//! # }
+//! ```
+//!
+//! Streams are told apart by the order in which their callbacks were wrapped, so a replay
+//! feeds the right callbacks as long as the application wraps them in the same order as during
+//! the capture. Responses to [`Transport::request`] are not captured.
+
+use futures::future::LocalBoxFuture;
+use std::{cell::RefCell, fmt, rc::Rc, time::Duration};
+use yew::callback::Callback;
+
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramSubscription};
+use crate::time::{PlatformScheduler, Scheduler};
+#[cfg(feature = "datagrams")]
+use crate::transport::DatagramPriority;
+#[cfg(feature = "streams")]
+use crate::transport::StreamWriter;
+use crate::transport::{QueuedSends, Transport, TransportStats};
+use crate::varint;
+use crate::webtransport::WebTransportError;
+
+/// Where a captured payload was delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptureSource {
+    /// The session's datagram callback.
+    Datagram,
+    /// A [`DatagramChannel`] with this id.
+    Channel(u64),
+    /// The nth stream callback wrapped by the capture, counting from zero.
+    Stream(u64),
+}
+
+impl CaptureSource {
+    fn encode(self, out: &mut Vec<u8>) {
+        match self {
+            CaptureSource::Datagram => out.push(0),
+            CaptureSource::Channel(id) => {
+                out.push(1);
+                varint::encode(id, out);
+            }
+            CaptureSource::Stream(index) => {
+                out.push(2);
+                varint::encode(index, out);
+            }
+        }
+    }
+}
+
+/// A payload received during a capture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedEvent {
+    /// Time since the capture started.
+    pub at: Duration,
+    pub source: CaptureSource,
+    pub data: Vec<u8>,
+}
+
+/// Everything received during a capture, in arrival order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub events: Vec<CapturedEvent>,
+}
+
+impl Recording {
+    /// Encodes the recording as `(microseconds varint | source | length varint | data)*`, where
+    /// the source is a kind byte followed by the channel id or stream index.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for event in &self.events {
+            varint::encode(event.at.as_micros() as u64, &mut out);
+            event.source.encode(&mut out);
+            varint::encode(event.data.len() as u64, &mut out);
+            out.extend_from_slice(&event.data);
+        }
+        out
+    }
+
+    /// Decodes a recording produced by [`Recording::encode`].
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        fn next(data: &[u8], at: &mut usize) -> Option<u64> {
+            let (value, len) = varint::decode(data.get(*at..)?)?;
+            *at += len;
+            Some(value)
+        }
+
+        let mut at = 0;
+        let mut events = Vec::new();
+        while at < data.len() {
+            let micros = next(data, &mut at)?;
+            let kind = *data.get(at)?;
+            at += 1;
+            let source = match kind {
+                0 => CaptureSource::Datagram,
+                1 => CaptureSource::Channel(next(data, &mut at)?),
+                2 => CaptureSource::Stream(next(data, &mut at)?),
+                _ => return None,
+            };
+            let len = usize::try_from(next(data, &mut at)?).ok()?;
+            let end = at.checked_add(len)?;
+            events.push(CapturedEvent {
+                at: Duration::from_micros(micros),
+                source,
+                data: data.get(at..end)?.to_vec(),
+            });
+            at = end;
+        }
+        Some(Self { events })
+    }
+}
+
+struct CaptureState {
+    recording: bool,
+    started_at: Duration,
+    events: Vec<CapturedEvent>,
+    next_stream: u64,
+    targets: Vec<(CaptureSource, Callback<Vec<u8>>)>,
+}
+
+impl CaptureState {
+    fn target(&self, source: CaptureSource) -> Option<Callback<Vec<u8>>> {
+        self.targets
+            .iter()
+            .rev()
+            .find(|(target, _)| *target == source)
+            .map(|(_, callback)| callback.clone())
+    }
+}
+
+/// Records the payloads delivered to the callbacks it wraps, and replays them.
+///
+/// Cloning a `SessionCapture` yields another handle to the same capture.
+#[derive(Clone)]
+pub struct SessionCapture {
+    state: Rc<RefCell<CaptureState>>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl SessionCapture {
+    /// Starts a capture timed by the platform clock.
+    pub fn new() -> Self {
+        Self::with_scheduler(Rc::new(PlatformScheduler))
+    }
+
+    /// Starts a capture timed by `scheduler`.
+    pub fn with_scheduler(scheduler: Rc<dyn Scheduler>) -> Self {
+        let started_at = scheduler.now();
+        Self {
+            state: Rc::new(RefCell::new(CaptureState {
+                recording: true,
+                started_at,
+                events: Vec::new(),
+                next_stream: 0,
+                targets: Vec::new(),
+            })),
+            scheduler,
+        }
+    }
+
+    /// Creates a capture holding `recording`, for replaying it. Nothing new is recorded.
+    pub fn from_recording(recording: Recording, scheduler: Rc<dyn Scheduler>) -> Self {
+        let capture = Self::with_scheduler(scheduler);
+        {
+            let mut state = capture.state.borrow_mut();
+            state.recording = false;
+            state.events = recording.events;
+        }
+        capture
+    }
+
+    /// Wraps the session's datagram callback.
+    pub fn datagrams(&self, callback: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        self.record(CaptureSource::Datagram, callback)
+    }
+
+    /// Wraps the callback receiving the chunks of one stream.
+    pub fn stream(&self, callback: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        let index = {
+            let mut state = self.state.borrow_mut();
+            state.next_stream += 1;
+            state.next_stream - 1
+        };
+        self.record(CaptureSource::Stream(index), callback)
+    }
+
+    /// Wraps an established session so that the streams and datagram channels opened through
+    /// it are captured.
+    pub fn wrap<T: Transport>(&self, transport: T) -> CapturingTransport<T> {
+        CapturingTransport {
+            inner: transport,
+            capture: self.clone(),
+        }
+    }
+
+    fn record(&self, source: CaptureSource, callback: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        self.state
+            .borrow_mut()
+            .targets
+            .push((source, callback.clone()));
+        let capture = self.clone();
+        Callback::from(move |data: Vec<u8>| {
+            {
+                let now = capture.scheduler.now();
+                let mut state = capture.state.borrow_mut();
+                if state.recording {
+                    let at = now.saturating_sub(state.started_at);
+                    state.events.push(CapturedEvent {
+                        at,
+                        source,
+                        data: data.clone(),
+                    });
+                }
+            }
+            callback.emit(data);
+        })
+    }
+
+    /// Stops recording; payloads keep being delivered.
+    pub fn stop(&self) {
+        self.state.borrow_mut().recording = false;
+    }
+
+    /// Whether payloads are being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.state.borrow().recording
+    }
+
+    /// Everything recorded so far.
+    pub fn recording(&self) -> Recording {
+        Recording {
+            events: self.state.borrow().events.clone(),
+        }
+    }
+
+    /// Emits every recorded payload into the callback it was delivered to, keeping the original
+    /// spacing from now on. Payloads whose callback was not wrapped are skipped. Replayed
+    /// payloads are not recorded again.
+    pub fn replay(&self) {
+        let state = self.state.borrow();
+        for event in &state.events {
+            let Some(callback) = state.target(event.source) else {
+                continue;
+            };
+            let data = event.data.clone();
+            self.scheduler
+                .schedule(event.at, Box::new(move || callback.emit(data)));
+        }
+    }
+
+    /// Like [`SessionCapture::replay`], but emits every payload right away, in order.
+    pub fn replay_immediately(&self) {
+        let events: Vec<_> = {
+            let state = self.state.borrow();
+            state
+                .events
+                .iter()
+                .filter_map(|event| Some((state.target(event.source)?, event.data.clone())))
+                .collect()
+        };
+        for (callback, data) in events {
+            callback.emit(data);
+        }
+    }
+}
+
+impl Default for SessionCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SessionCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("SessionCapture")
+            .field("recording", &state.recording)
+            .field("events", &state.events.len())
+            .finish()
+    }
+}
+
+/// A session whose incoming streams and channel datagrams go through a [`SessionCapture`].
+pub struct CapturingTransport<T> {
+    inner: T,
+    capture: SessionCapture,
+}
+
+impl<T> CapturingTransport<T> {
+    /// The wrapped session.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn capture(&self) -> &SessionCapture {
+        &self.capture
+    }
+}
+
+impl<T> fmt::Debug for CapturingTransport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapturingTransport")
+            .field("capture", &self.capture)
+            .finish()
+    }
+}
+
+impl<T: Transport> Transport for CapturingTransport<T> {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        self.inner.send_datagram(data);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        self.inner.send_datagram_with_deadline(data, deadline);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        self.inner.send_datagram_with_priority(data, priority);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        self.inner.send_unidirectional_stream(data);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        self.inner
+            .send_unidirectional_stream_with_order(data, send_order);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        self.inner.send_unidirectional_stream_finished(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        self.inner
+            .send_bidirectional_stream(data, self.capture.stream(callback));
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        self.inner.request(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        self.inner
+            .open_bidirectional_stream(self.capture.stream(on_data))
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let on_message = self.capture.record(CaptureSource::Channel(id), on_message);
+        self.inner.datagram_channel(id, on_message)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.inner.subscribe_datagrams(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        self.inner.stats()
+    }
+
+    fn queued(&self) -> QueuedSends {
+        self.inner.queued()
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        self.inner.queued_on_channel(id)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        self.inner.set_channel_priority(id, priority);
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        self.inner.close()
+    }
+}
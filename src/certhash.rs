@@ -0,0 +1,111 @@
+//! Certificate hashes, for self-signed servers.
+//!
+//! Browsers accept a self-signed certificate only if the session is created with its SHA-256
+//! hash in `serverCertificateHashes`, see
+//! [`WebTransportBuilder::server_certificate_hashes`](crate::webtransport::WebTransportBuilder::server_certificate_hashes).
+//! The `certhash` tool prints the hash of a PEM certificate:
+//! `cargo run -p certhash -- cert.pem`.
+
+use js_sys::Uint8Array;
+use std::{fmt, str::FromStr};
+use web_sys::WebTransportHash;
+
+/// The SHA-256 hash of a server certificate.
+///
+/// Parsed from hex, with or without `:` between bytes, or from base64, standard or URL-safe,
+/// and displayed in hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CertificateHash([u8; 32]);
+
+/// A string that is not a SHA-256 hash in hex or base64.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidCertificateHash(String);
+
+impl CertificateHash {
+    pub fn new(hash: [u8; 32]) -> Self {
+        Self(hash)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// `{ algorithm: "sha-256", value: hash }`, an entry of `serverCertificateHashes`.
+    pub fn to_js(&self) -> WebTransportHash {
+        let mut hash = WebTransportHash::new();
+        hash.algorithm("sha-256")
+            .value(&Uint8Array::from(&self.0[..]));
+        hash
+    }
+}
+
+impl From<[u8; 32]> for CertificateHash {
+    fn from(hash: [u8; 32]) -> Self {
+        Self(hash)
+    }
+}
+
+impl From<CertificateHash> for [u8; 32] {
+    fn from(hash: CertificateHash) -> Self {
+        hash.0
+    }
+}
+
+impl FromStr for CertificateHash {
+    type Err = InvalidCertificateHash;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let hex: String = text.chars().filter(|&c| c != ':').collect();
+        let hash = if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            (0..64)
+                .step_by(2)
+                .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).unwrap())
+                .collect()
+        } else {
+            decode_base64(text).ok_or_else(|| {
+                InvalidCertificateHash(format!("{text:?} is neither hex nor base64"))
+            })?
+        };
+        let length = hash.len();
+        let hash: [u8; 32] = hash.try_into().map_err(|_| {
+            InvalidCertificateHash(format!(
+                "a SHA-256 hash is 32 bytes long, {text:?} is {length} bytes long"
+            ))
+        })?;
+        Ok(Self(hash))
+    }
+}
+
+impl fmt::Display for CertificateHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
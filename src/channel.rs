@@ -0,0 +1,318 @@
+//! Independent logical channels sharing the datagram path.
+//!
+//! Every datagram sent on a [`DatagramChannel`] is prefixed with the channel id as a QUIC
+//! varint. Incoming datagrams whose prefix matches a registered channel are delivered to that
+//! channel's callback with the prefix stripped; everything else reaches the session's
+//! `on_datagram` callback untouched.
+//!
+//! With the `encryption` feature, a channel can be given a
+//! [`ChannelCipher`](crate::crypto::ChannelCipher) of its own with
+//! [`DatagramChannel::encrypt`], sealing what it sends and opening what it receives.
+//!
+//! Several parts of an application can also each get a copy of the datagrams reaching
+//! `on_datagram` by subscribing, optionally with a filter. A [`DatagramSubscription`] is
+//! cancelled when dropped.
+
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+use yew::callback::Callback;
+
+#[cfg(feature = "encryption")]
+use crate::crypto::ChannelCipher;
+#[cfg(feature = "encryption")]
+use crate::logging::DATAGRAMS;
+use crate::varint;
+
+/// A registered channel's callback and cipher, tagged with the token of the [`DatagramChannel`]
+/// owning it.
+#[derive(Clone)]
+struct Registration {
+    token: u64,
+    on_message: Callback<Vec<u8>>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<ChannelCipher>,
+}
+
+impl Registration {
+    /// Passes a datagram received on channel `id`, with the prefix stripped, to the callback.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn deliver(&self, id: u64, data: Vec<u8>) {
+        #[cfg(feature = "encryption")]
+        let data = match &self.cipher {
+            Some(cipher) => match cipher.open(id, &data) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!(target: DATAGRAMS, "dropped a datagram on channel {id}: {e}");
+                    return;
+                }
+            },
+            None => data,
+        };
+        self.on_message.emit(data);
+    }
+}
+
+/// Decides which datagrams a subscriber gets a copy of.
+pub type DatagramFilter = Rc<dyn Fn(&[u8]) -> bool>;
+
+/// A subscriber's filter and callback, tagged with the token of the [`DatagramSubscription`]
+/// owning it.
+type Subscriber = (u64, Option<DatagramFilter>, Callback<Vec<u8>>);
+
+#[derive(Default)]
+struct Registry {
+    next_token: Cell<u64>,
+    channels: RefCell<HashMap<u64, Registration>>,
+    subscribers: RefCell<Vec<Subscriber>>,
+}
+
+impl Registry {
+    fn next_token(&self) -> u64 {
+        let token = self.next_token.get();
+        self.next_token.set(token + 1);
+        token
+    }
+}
+
+/// Dispatches incoming datagrams to the [`DatagramChannel`]s registered on a session.
+#[derive(Clone, Default)]
+pub struct DatagramRouter {
+    registry: Rc<Registry>,
+}
+
+impl DatagramRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the session's datagram callback so that datagrams addressed to a registered
+    /// channel are routed there and everything else goes to `fallback`.
+    pub fn route(&self, fallback: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        let router = self.clone();
+        Callback::from(move |data: Vec<u8>| {
+            if let Err(data) = router.dispatch(data) {
+                fallback.emit(data);
+            }
+        })
+    }
+
+    /// Delivers `data` to the channel it is addressed to, or hands it back if no registered
+    /// channel matches, once every subscriber whose filter accepts it got a copy.
+    pub fn dispatch(&self, data: Vec<u8>) -> Result<(), Vec<u8>> {
+        let channel = varint::decode(&data).and_then(|(id, len)| {
+            let channels = self.registry.channels.borrow();
+            channels
+                .get(&id)
+                .map(|registration| (id, registration.clone(), len))
+        });
+        if let Some((id, registration, len)) = channel {
+            registration.deliver(id, data[len..].to_vec());
+            return Ok(());
+        }
+        let subscribers: Vec<Callback<Vec<u8>>> = self
+            .registry
+            .subscribers
+            .borrow()
+            .iter()
+            .filter(|(_, filter, _)| match filter {
+                Some(filter) => filter(&data),
+                None => true,
+            })
+            .map(|(_, _, callback)| callback.clone())
+            .collect();
+        for subscriber in subscribers {
+            subscriber.emit(data.clone());
+        }
+        Err(data)
+    }
+
+    /// Whether a channel is currently registered under `id`.
+    pub fn is_registered(&self, id: u64) -> bool {
+        self.registry.channels.borrow().contains_key(&id)
+    }
+
+    /// Registers `on_message` for channel `id` and returns the channel, sending through
+    /// `send_datagram`. A later registration of the same id replaces this one.
+    ///
+    /// # Panics
+    ///
+    /// If `id` doesn't fit in a QUIC varint.
+    pub fn channel(
+        &self,
+        id: u64,
+        on_message: Callback<Vec<u8>>,
+        send_datagram: Rc<dyn Fn(Vec<u8>)>,
+    ) -> DatagramChannel {
+        assert!(
+            id <= varint::MAX,
+            "channel id {id} does not fit in a QUIC varint"
+        );
+        let token = self.registry.next_token();
+        self.registry.channels.borrow_mut().insert(
+            id,
+            Registration {
+                token,
+                on_message,
+                #[cfg(feature = "encryption")]
+                cipher: None,
+            },
+        );
+        DatagramChannel {
+            id,
+            token,
+            router: self.clone(),
+            send_datagram,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// Passes a copy of every datagram that isn't addressed to a channel to `on_datagram`,
+    /// as long as `filter`, if given, accepts it, until the subscription is dropped.
+    pub fn subscribe(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        let token = self.registry.next_token();
+        self.registry
+            .subscribers
+            .borrow_mut()
+            .push((token, filter, on_datagram));
+        DatagramSubscription {
+            token,
+            router: self.clone(),
+        }
+    }
+
+    /// Number of live subscriptions.
+    pub fn subscribers(&self) -> usize {
+        self.registry.subscribers.borrow().len()
+    }
+}
+
+impl fmt::Debug for DatagramRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids: Vec<u64> = self.registry.channels.borrow().keys().copied().collect();
+        ids.sort_unstable();
+        f.debug_struct("DatagramRouter")
+            .field("channels", &ids)
+            .field("subscribers", &self.subscribers())
+            .finish()
+    }
+}
+
+/// A subscription to a session's datagrams, see [`DatagramRouter::subscribe`]. Cancelled when
+/// dropped.
+pub struct DatagramSubscription {
+    token: u64,
+    router: DatagramRouter,
+}
+
+impl Drop for DatagramSubscription {
+    fn drop(&mut self) {
+        self.router
+            .registry
+            .subscribers
+            .borrow_mut()
+            .retain(|(token, _, _)| *token != self.token);
+    }
+}
+
+impl fmt::Debug for DatagramSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatagramSubscription")
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+/// A logical channel over datagrams. The channel is unregistered when dropped.
+pub struct DatagramChannel {
+    id: u64,
+    token: u64,
+    router: DatagramRouter,
+    send_datagram: Rc<dyn Fn(Vec<u8>)>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<ChannelCipher>,
+}
+
+impl DatagramChannel {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sends `data` as a datagram on this channel.
+    pub fn send(&self, data: &[u8]) {
+        #[cfg(feature = "encryption")]
+        let sealed = self
+            .cipher
+            .as_ref()
+            .map(|cipher| cipher.seal(self.id, data));
+        #[cfg(feature = "encryption")]
+        let data = sealed.as_deref().unwrap_or(data);
+        let mut datagram = Vec::with_capacity(varint::encoded_len(self.id) + data.len());
+        varint::encode(self.id, &mut datagram);
+        datagram.extend_from_slice(data);
+        (self.send_datagram)(datagram);
+    }
+
+    /// Sends this channel's datagrams through the sender `wrap` makes of the current one, for
+    /// wrappers such as [`SimulatedTransport`](crate::sim::SimulatedTransport).
+    pub fn wrap_sender(
+        mut self,
+        wrap: impl FnOnce(Rc<dyn Fn(Vec<u8>)>) -> Rc<dyn Fn(Vec<u8>)>,
+    ) -> Self {
+        self.send_datagram = wrap(self.send_datagram.clone());
+        self
+    }
+
+    /// Seals everything sent on this channel with `cipher`, and opens everything received on
+    /// it. Datagrams that fail to open are logged and dropped. See [`crate::crypto`].
+    #[cfg(feature = "encryption")]
+    pub fn encrypt(mut self, cipher: ChannelCipher) -> Self {
+        if let Some(registration) = self.registration().as_mut() {
+            registration.cipher = Some(cipher.clone());
+        }
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Replaces the callback receiving this channel's datagrams.
+    pub fn set_on_message(&self, on_message: Callback<Vec<u8>>) {
+        if let Some(registration) = self.registration().as_mut() {
+            registration.on_message = on_message;
+        }
+    }
+
+    /// This channel's registration, unless a later one of the same id replaced it.
+    fn registration(&self) -> Option<RefMut<'_, Registration>> {
+        let channels = self.router.registry.channels.borrow_mut();
+        RefMut::filter_map(channels, |channels| {
+            channels
+                .get_mut(&self.id)
+                .filter(|registration| registration.token == self.token)
+        })
+        .ok()
+    }
+}
+
+impl Drop for DatagramChannel {
+    fn drop(&mut self) {
+        if self.registration().is_some() {
+            self.router.registry.channels.borrow_mut().remove(&self.id);
+        }
+    }
+}
+
+impl fmt::Debug for DatagramChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatagramChannel")
+            .field("id", &self.id)
+            .finish()
+    }
+}
@@ -0,0 +1,210 @@
+//! End-to-end encryption of logical channels, for designs where the WebTransport server is an
+//! untrusted relay.
+//!
+//! A [`ChannelCipher`] seals every message of the [`DatagramChannel`](crate::channel::DatagramChannel)
+//! or [`MuxChannel`](crate::mux::MuxChannel) it is given to with AES-256-GCM, under a symmetric
+//! key the peers agreed on out of band. A sealed message is laid out as
+//! `key id varint | nonce (12 bytes) | ciphertext | tag (16 bytes)`, and authenticates the
+//! channel id as well, so the relay can neither read it nor replay it on another channel.
+//!
+//! Keys are told apart by their id, which lets them be rotated without a pause:
+//! [`ChannelCipher::rotate`] seals with a new key while the previous one still opens the
+//! messages in flight, and the hook set with [`ChannelCipher::on_unknown_key`] is asked for
+//! the keys the peer switched to. Nonces are random, so a key should be rotated well before
+//! it sealed 2^32 messages.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use std::{cell::RefCell, fmt, rc::Rc};
+use thiserror::Error as ThisError;
+
+use crate::varint;
+
+const NONCE_LEN: usize = 12;
+
+/// A message could not be opened by a [`ChannelCipher`].
+#[derive(Clone, Debug, PartialEq, Eq, ThisError)]
+pub enum ChannelCryptoError {
+    #[error("sealed message is too short")]
+    /// The message is too short to hold a key id, a nonce and a tag.
+    Truncated,
+    #[error("message is sealed with unknown key {0}")]
+    /// The message is sealed with a key that is neither the current or previous one, nor
+    /// provided by the [`ChannelCipher::on_unknown_key`] hook.
+    UnknownKey(u64),
+    #[error("message failed authentication")]
+    /// The message was altered, sealed for another channel, or sealed with another key under
+    /// the same id.
+    Authentication,
+}
+
+/// A 256-bit AES-GCM key, and the id the peers know it by.
+#[derive(Clone)]
+pub struct ChannelKey {
+    id: u64,
+    cipher: Aes256Gcm,
+}
+
+impl ChannelKey {
+    /// A key from its raw bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `id` doesn't fit in a QUIC varint.
+    pub fn new(id: u64, key: [u8; 32]) -> Self {
+        assert!(
+            id <= varint::MAX,
+            "key id {id} does not fit in a QUIC varint"
+        );
+        Self {
+            id,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl fmt::Debug for ChannelKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelKey").field("id", &self.id).finish()
+    }
+}
+
+type UnknownKeyHook = Rc<dyn Fn(u64) -> Option<ChannelKey>>;
+
+struct Keys {
+    current: ChannelKey,
+    previous: Option<ChannelKey>,
+    on_unknown_key: Option<UnknownKeyHook>,
+}
+
+/// Seals and opens the messages of a channel. Clones share their keys, so a clone kept aside
+/// can rotate the key of the channel the cipher was given to.
+#[derive(Clone)]
+pub struct ChannelCipher {
+    keys: Rc<RefCell<Keys>>,
+}
+
+impl ChannelCipher {
+    pub fn new(key: ChannelKey) -> Self {
+        Self {
+            keys: Rc::new(RefCell::new(Keys {
+                current: key,
+                previous: None,
+                on_unknown_key: None,
+            })),
+        }
+    }
+
+    /// Seals with `key` from now on. The key replaced still opens messages, until the next
+    /// rotation, so those the peer sealed before switching aren't lost.
+    pub fn rotate(&self, key: ChannelKey) {
+        let mut keys = self.keys.borrow_mut();
+        let previous = std::mem::replace(&mut keys.current, key);
+        keys.previous = Some(previous);
+    }
+
+    /// Calls `hook` with the id of the key a message was sealed with when it is neither the
+    /// current nor the previous key, typically because the peer rotated first. The key
+    /// returned opens the message, and is kept as the previous key. Messages are dropped if
+    /// the hook returns `None`.
+    pub fn on_unknown_key(&self, hook: impl Fn(u64) -> Option<ChannelKey> + 'static) {
+        self.keys.borrow_mut().on_unknown_key = Some(Rc::new(hook));
+    }
+
+    /// Id of the key messages are sealed with.
+    pub fn key_id(&self) -> u64 {
+        self.keys.borrow().current.id
+    }
+
+    /// Seals `message` for `channel` with the current key.
+    ///
+    /// # Panics
+    ///
+    /// If the platform has no source of randomness for the nonce.
+    pub fn seal(&self, channel: u64, message: &[u8]) -> Vec<u8> {
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).expect("no source of randomness for the nonce");
+        let keys = self.keys.borrow();
+        let aad = channel_aad(channel);
+        let ciphertext = keys
+            .current
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: message,
+                    aad: &aad,
+                },
+            )
+            .expect("message too long to seal");
+        let mut sealed =
+            Vec::with_capacity(varint::encoded_len(keys.current.id) + NONCE_LEN + ciphertext.len());
+        varint::encode(keys.current.id, &mut sealed);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Opens a message sealed for `channel`.
+    pub fn open(&self, channel: u64, sealed: &[u8]) -> Result<Vec<u8>, ChannelCryptoError> {
+        let (key_id, len) = varint::decode(sealed).ok_or(ChannelCryptoError::Truncated)?;
+        let rest = &sealed[len..];
+        if rest.len() < NONCE_LEN {
+            return Err(ChannelCryptoError::Truncated);
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let key = self.key(key_id)?;
+        key.cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &channel_aad(channel),
+                },
+            )
+            .map_err(|_| ChannelCryptoError::Authentication)
+    }
+
+    fn key(&self, id: u64) -> Result<ChannelKey, ChannelCryptoError> {
+        let hook = {
+            let keys = self.keys.borrow();
+            if keys.current.id == id {
+                return Ok(keys.current.clone());
+            }
+            if let Some(previous) = keys.previous.as_ref().filter(|key| key.id == id) {
+                return Ok(previous.clone());
+            }
+            keys.on_unknown_key.clone()
+        };
+        // The hook is called with the keys released, so it may rotate them.
+        let key = hook
+            .and_then(|hook| hook(id))
+            .filter(|key| key.id == id)
+            .ok_or(ChannelCryptoError::UnknownKey(id))?;
+        self.keys.borrow_mut().previous = Some(key.clone());
+        Ok(key)
+    }
+}
+
+impl fmt::Debug for ChannelCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.keys.borrow();
+        f.debug_struct("ChannelCipher")
+            .field("key_id", &keys.current.id)
+            .field(
+                "previous_key_id",
+                &keys.previous.as_ref().map(ChannelKey::id),
+            )
+            .finish()
+    }
+}
+
+fn channel_aad(channel: u64) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(varint::encoded_len(channel));
+    varint::encode(channel, &mut aad);
+    aad
+}
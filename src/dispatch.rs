@@ -0,0 +1,224 @@
+//! Routing incoming messages to the parts of an application that asked for them.
+//!
+//! A root component receiving every message of a session on one callback ends up matching on
+//! each kind of message to forward it where it belongs. A [`Dispatcher`] does that instead: it
+//! is given a function telling the key of a message, a channel id, a topic or a message type,
+//! and passes every message to the callbacks registered for its key with [`Dispatcher::on`],
+//! and to those registered with a predicate accepting it with [`Dispatcher::on_matching`].
+//! A [`DispatchRegistration`] is cancelled when dropped; in function components,
+//! [`use_dispatch`](crate::hooks::use_dispatch) keeps one for as long as the component is
+//! mounted.
+//!
+//! ```ignore
+//! // Messages start with a byte telling their type.
+//! let dispatcher = Dispatcher::new(|message: &Vec<u8>| message.first().copied());
+//! let chat = dispatcher.on(CHAT, Callback::from(|message| { /* ... */ }));
+//! let task = WebTransportService::builder(url)
+//!     .on_datagram(dispatcher.route(Callback::from(|_| log::warn!("unknown message"))))
+//!     .connect()?;
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    rc::Rc,
+};
+use yew::callback::Callback;
+
+type KeyOf<K, M> = dyn Fn(&M) -> Option<K>;
+
+/// Decides which messages a callback registered with [`Dispatcher::on_matching`] gets.
+pub type DispatchPredicate<M> = Rc<dyn Fn(&M) -> bool>;
+
+/// A callback registered for a key, tagged with the token of the [`DispatchRegistration`]
+/// owning it.
+type Keyed<M> = Vec<(u64, Callback<M>)>;
+
+/// A callback registered with a predicate, tagged with the token of the
+/// [`DispatchRegistration`] owning it.
+type Matching<M> = (u64, DispatchPredicate<M>, Callback<M>);
+
+struct Registry<K, M> {
+    key: Box<KeyOf<K, M>>,
+    next_token: Cell<u64>,
+    keyed: RefCell<HashMap<K, Keyed<M>>>,
+    matching: RefCell<Vec<Matching<M>>>,
+}
+
+/// The registrations of a [`Dispatcher`], whatever its key and message types.
+trait Unregister {
+    fn unregister(&self, token: u64);
+}
+
+impl<K: Eq + Hash, M> Unregister for Registry<K, M> {
+    fn unregister(&self, token: u64) {
+        self.keyed.borrow_mut().retain(|_, callbacks| {
+            callbacks.retain(|(registered, _)| *registered != token);
+            !callbacks.is_empty()
+        });
+        self.matching
+            .borrow_mut()
+            .retain(|(registered, _, _)| *registered != token);
+    }
+}
+
+/// Passes incoming messages to the callbacks registered for them, see the
+/// [module documentation](self). Clones share their registrations.
+pub struct Dispatcher<K, M> {
+    registry: Rc<Registry<K, M>>,
+}
+
+impl<K: Eq + Hash + 'static, M: Clone + 'static> Dispatcher<K, M> {
+    /// A dispatcher telling the key of every message with `key`. Messages without a key only
+    /// reach the callbacks registered with a predicate.
+    pub fn new(key: impl Fn(&M) -> Option<K> + 'static) -> Self {
+        Self {
+            registry: Rc::new(Registry {
+                key: Box::new(key),
+                next_token: Cell::new(0),
+                keyed: RefCell::default(),
+                matching: RefCell::default(),
+            }),
+        }
+    }
+
+    /// Passes the messages with `key` to `on_message`, until the returned registration is
+    /// dropped.
+    pub fn on(&self, key: K, on_message: Callback<M>) -> DispatchRegistration {
+        let token = self.next_token();
+        self.registry
+            .keyed
+            .borrow_mut()
+            .entry(key)
+            .or_default()
+            .push((token, on_message));
+        self.registration(token)
+    }
+
+    /// Passes the messages `predicate` accepts to `on_message`, whatever their key, until the
+    /// returned registration is dropped.
+    pub fn on_matching(
+        &self,
+        predicate: impl Fn(&M) -> bool + 'static,
+        on_message: Callback<M>,
+    ) -> DispatchRegistration {
+        let token = self.next_token();
+        self.registry
+            .matching
+            .borrow_mut()
+            .push((token, Rc::new(predicate), on_message));
+        self.registration(token)
+    }
+
+    /// Passes `message` to the callbacks registered for its key, then to those whose
+    /// predicate accepts it, each in the order they were registered. Hands it back if no
+    /// callback got it.
+    pub fn dispatch(&self, message: M) -> Result<(), M> {
+        // Callbacks may register or unregister while being called.
+        let mut callbacks: Vec<Callback<M>> = match (self.registry.key)(&message) {
+            Some(key) => self
+                .registry
+                .keyed
+                .borrow()
+                .get(&key)
+                .map(|callbacks| {
+                    callbacks
+                        .iter()
+                        .map(|(_, callback)| callback.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let matching: Vec<_> = self.registry.matching.borrow().clone();
+        callbacks.extend(
+            matching
+                .into_iter()
+                .filter(|(_, predicate, _)| predicate(&message))
+                .map(|(_, _, callback)| callback),
+        );
+        let Some((last, others)) = callbacks.split_last() else {
+            return Err(message);
+        };
+        for callback in others {
+            callback.emit(message.clone());
+        }
+        last.emit(message);
+        Ok(())
+    }
+
+    /// A callback dispatching the messages it is passed, and passing those no callback got
+    /// to `fallback`, for the session's `on_datagram` for instance.
+    pub fn route(&self, fallback: Callback<M>) -> Callback<M> {
+        let dispatcher = self.clone();
+        Callback::from(move |message| {
+            if let Err(message) = dispatcher.dispatch(message) {
+                fallback.emit(message);
+            }
+        })
+    }
+
+    /// Number of live registrations.
+    pub fn registrations(&self) -> usize {
+        let keyed: usize = self.registry.keyed.borrow().values().map(Vec::len).sum();
+        keyed + self.registry.matching.borrow().len()
+    }
+
+    fn next_token(&self) -> u64 {
+        let token = self.registry.next_token.get();
+        self.registry.next_token.set(token + 1);
+        token
+    }
+
+    fn registration(&self, token: u64) -> DispatchRegistration {
+        DispatchRegistration {
+            token,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl<K, M> Clone for Dispatcher<K, M> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl<K, M> PartialEq for Dispatcher<K, M> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.registry, &other.registry)
+    }
+}
+
+impl<K, M> fmt::Debug for Dispatcher<K, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("keys", &self.registry.keyed.borrow().len())
+            .field("matching", &self.registry.matching.borrow().len())
+            .finish()
+    }
+}
+
+/// A callback registered with a [`Dispatcher`]. Cancelled when dropped.
+pub struct DispatchRegistration {
+    token: u64,
+    registry: Rc<dyn Unregister>,
+}
+
+impl Drop for DispatchRegistration {
+    fn drop(&mut self) {
+        self.registry.unregister(self.token);
+    }
+}
+
+impl fmt::Debug for DispatchRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DispatchRegistration")
+            .field("token", &self.token)
+            .finish()
+    }
+}
@@ -0,0 +1,103 @@
+//! The crate-level error type.
+//!
+//! Most of the API reports failures with the error type of the module at hand, e.g.
+//! [`WebTransportError`]. All of them convert into [`Error`], which sorts them by kind so that
+//! an application can handle every failure of the crate in one place and match on what went
+//! wrong.
+//!
+//! Errors are `Clone` and `PartialEq`, so they can be passed as component properties, and
+//! implement [`ToHtml`], rendering their message, so an error view can show them as they are:
+//! `html! { <p class="error">{ error.to_html() }</p> }`.
+
+use wasm_bindgen::JsValue;
+use yew::html::ToHtml;
+use yew::Html;
+
+use crate::certhash::InvalidCertificateHash;
+#[cfg(feature = "encryption")]
+use crate::crypto::ChannelCryptoError;
+use crate::framing::FramingError;
+#[cfg(feature = "formats")]
+use crate::webtransport::FormatError;
+use crate::webtransport::{CloseInfo, SessionError, WebTransportError};
+
+/// Any failure of this crate, by kind.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[cfg(feature = "formats")]
+    #[error(transparent)]
+    /// A value doesn't fit the format it is stored or sent with.
+    Format(#[from] FormatError),
+    #[error("{0}")]
+    /// A value could not be serialized or deserialized.
+    Serialization(String),
+    #[error("{0}")]
+    /// The browser threw an exception or rejected a promise.
+    Js(String),
+    #[error(transparent)]
+    /// The session could not be established.
+    Session(#[from] SessionError),
+    #[error("the session is closed")]
+    /// The session was closed, by either side, before the operation completed.
+    Closed,
+    #[error("timed out")]
+    /// The operation did not complete in time.
+    Timeout,
+    #[error("unsupported: {0}")]
+    /// The browser or the backend doesn't support the operation.
+    Unsupported(String),
+    #[error("remote error: {0}")]
+    /// The server reported an error while handling a request.
+    Remote(String),
+}
+
+impl From<WebTransportError> for Error {
+    fn from(error: WebTransportError) -> Self {
+        match error {
+            WebTransportError::CreationError(e) | WebTransportError::StreamError(e) => Error::Js(e),
+            WebTransportError::SerializationError(e) => Error::Serialization(e),
+            WebTransportError::Timeout => Error::Timeout,
+            WebTransportError::RemoteError(e) => Error::Remote(e),
+            WebTransportError::AlreadyClosed => Error::Closed,
+            WebTransportError::Session(e) => Error::Session(e),
+        }
+    }
+}
+
+impl From<FramingError> for Error {
+    fn from(error: FramingError) -> Self {
+        Error::Serialization(error.to_string())
+    }
+}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        Error::Js(format!("{value:?}"))
+    }
+}
+
+macro_rules! impl_to_html_via_display {
+    ($($(#[$attr:meta])* $error:ty),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl ToHtml for $error {
+                fn to_html(&self) -> Html {
+                    Html::from(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_to_html_via_display!(
+    Error,
+    WebTransportError,
+    SessionError,
+    CloseInfo,
+    FramingError,
+    InvalidCertificateHash,
+    #[cfg(feature = "formats")]
+    FormatError,
+    #[cfg(feature = "encryption")]
+    ChannelCryptoError,
+);
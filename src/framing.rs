@@ -0,0 +1,327 @@
+//! Ways of delimiting messages on a byte stream, for [`StreamOptions`](crate::options::StreamOptions)
+//! and for application protocols of their own.
+//!
+//! A stream carries bytes, not messages: the peer may read what was written in one call split
+//! in several chunks, or merged with the next write. A [`Framing`] delimits every message so a
+//! [`Deframer`] can put them back together on the other side. Besides the QUIC varint length
+//! prefix used elsewhere in this crate, fixed 32-bit prefixes and protobuf varints are
+//! supported, for servers that already speak length-delimited protobuf, as well as COBS, for
+//! gateways and embedded peers delimiting messages with zero bytes.
+//!
+//! With the `checksum` feature, a [`Checksum`] can be appended to every message before it is
+//! framed, for legacy systems on the other side that check the integrity of each message.
+
+use thiserror::Error as ThisError;
+
+use crate::varint;
+
+/// How messages are delimited on a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// Messages are not delimited: every chunk read is passed on as it is.
+    #[default]
+    None,
+    /// `length | payload`, the length as a QUIC varint.
+    Varint,
+    /// `length | payload`, the length as a big-endian 32-bit integer.
+    U32,
+    /// `length | payload`, the length as a protobuf varint, as written by
+    /// `writeDelimitedTo` and the like.
+    Protobuf,
+    /// Consistent Overhead Byte Stuffing: the payload is encoded without zero bytes, and
+    /// followed by one. Empty frames, as sent by peers resynchronizing, are skipped.
+    Cobs,
+}
+
+/// A stream's data doesn't follow its [`Framing`].
+#[derive(Clone, Debug, PartialEq, Eq, ThisError)]
+pub enum FramingError {
+    #[error("malformed length prefix")]
+    /// A length prefix could not be decoded.
+    MalformedLength,
+    #[error("malformed COBS frame")]
+    /// A COBS frame ends in the middle of a block, or has a zero code byte.
+    MalformedCobs,
+    #[cfg(feature = "checksum")]
+    #[error("message checksum mismatch")]
+    /// A message is shorter than its [`Checksum`], or doesn't match it.
+    ChecksumMismatch,
+}
+
+/// Longest protobuf varint encoding a `u64`.
+const MAX_PROTOBUF_VARINT_LEN: usize = 10;
+
+impl Framing {
+    /// Appends `message` to `out`, delimited.
+    ///
+    /// # Panics
+    ///
+    /// If `message` is too long for the length prefix, e.g. 4 GiB or more with
+    /// [`Framing::U32`].
+    pub fn encode(self, message: &[u8], out: &mut Vec<u8>) {
+        let len = message.len() as u64;
+        match self {
+            Framing::None => {}
+            Framing::Cobs => return encode_cobs(message, out),
+            Framing::Varint => varint::encode(len, out),
+            Framing::U32 => {
+                let len = u32::try_from(len).expect("message too long for a 32-bit prefix");
+                out.extend_from_slice(&len.to_be_bytes());
+            }
+            Framing::Protobuf => encode_protobuf_varint(len, out),
+        }
+        out.extend_from_slice(message);
+    }
+
+    /// Decodes the length prefix at the start of `buf`, returning the length of the message
+    /// and of the prefix, or `None` if `buf` is too short.
+    fn decode_length(self, buf: &[u8]) -> Result<Option<(u64, usize)>, FramingError> {
+        match self {
+            Framing::None | Framing::Cobs => Ok(Some((buf.len() as u64, 0))),
+            Framing::Varint => Ok(varint::decode(buf)),
+            Framing::U32 => Ok(buf
+                .get(..4)
+                .map(|prefix| (u32::from_be_bytes(prefix.try_into().unwrap()) as u64, 4))),
+            Framing::Protobuf => decode_protobuf_varint(buf),
+        }
+    }
+}
+
+fn encode_protobuf_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn decode_protobuf_varint(buf: &[u8]) -> Result<Option<(u64, usize)>, FramingError> {
+    let mut value = 0u64;
+    for (index, byte) in buf.iter().take(MAX_PROTOBUF_VARINT_LEN).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, index + 1)));
+        }
+    }
+    if buf.len() >= MAX_PROTOBUF_VARINT_LEN {
+        return Err(FramingError::MalformedLength);
+    }
+    Ok(None)
+}
+
+/// Longest run of non-zero bytes in a COBS block.
+const MAX_COBS_RUN: usize = 0xFE;
+
+fn encode_cobs(message: &[u8], out: &mut Vec<u8>) {
+    let mut code_index = out.len();
+    out.push(0);
+    let mut run = 0;
+    for &byte in message {
+        if byte != 0 {
+            out.push(byte);
+            run += 1;
+        }
+        if byte == 0 || run == MAX_COBS_RUN {
+            out[code_index] = run as u8 + 1;
+            code_index = out.len();
+            out.push(0);
+            run = 0;
+        }
+    }
+    out[code_index] = run as u8 + 1;
+    out.push(0);
+}
+
+/// Decodes a COBS frame, without its trailing zero byte.
+fn decode_cobs(frame: &[u8]) -> Result<Vec<u8>, FramingError> {
+    let mut message = Vec::with_capacity(frame.len());
+    let mut rest = frame;
+    while let Some((&code, block)) = rest.split_first() {
+        let run = usize::from(code)
+            .checked_sub(1)
+            .ok_or(FramingError::MalformedCobs)?;
+        let data = block.get(..run).ok_or(FramingError::MalformedCobs)?;
+        message.extend_from_slice(data);
+        rest = &block[run..];
+        if run < MAX_COBS_RUN && !rest.is_empty() {
+            message.push(0);
+        }
+    }
+    Ok(message)
+}
+
+/// A checksum appended to every message, after its payload.
+#[cfg(feature = "checksum")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-32 (IEEE), as 4 big-endian bytes.
+    Crc32,
+    /// 64-bit XXH3, as 8 big-endian bytes.
+    Xxh3,
+}
+
+#[cfg(feature = "checksum")]
+impl Checksum {
+    /// Appends the checksum of `message` to it.
+    pub fn append(self, message: &mut Vec<u8>) {
+        match self {
+            Checksum::Crc32 => {
+                let checksum = crc32fast::hash(message);
+                message.extend_from_slice(&checksum.to_be_bytes());
+            }
+            Checksum::Xxh3 => {
+                let checksum = xxhash_rust::xxh3::xxh3_64(message);
+                message.extend_from_slice(&checksum.to_be_bytes());
+            }
+        }
+    }
+
+    /// Checks the checksum at the end of `message`, and strips it.
+    pub fn verify(self, mut message: Vec<u8>) -> Result<Vec<u8>, FramingError> {
+        let size = match self {
+            Checksum::Crc32 => 4,
+            Checksum::Xxh3 => 8,
+        };
+        let payload_len = message
+            .len()
+            .checked_sub(size)
+            .ok_or(FramingError::ChecksumMismatch)?;
+        let (payload, checksum) = message.split_at(payload_len);
+        let matches = match self {
+            Checksum::Crc32 => checksum == crc32fast::hash(payload).to_be_bytes(),
+            Checksum::Xxh3 => checksum == xxhash_rust::xxh3::xxh3_64(payload).to_be_bytes(),
+        };
+        if !matches {
+            return Err(FramingError::ChecksumMismatch);
+        }
+        message.truncate(payload_len);
+        Ok(message)
+    }
+}
+
+/// A piece of a message, as returned by [`Deframer::next_part`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessagePart {
+    /// A message no longer than the threshold, whole.
+    Whole(Vec<u8>),
+    /// A message longer than the threshold starts. Its bytes follow as [`MessagePart::Body`].
+    Start { len: u64 },
+    /// The next bytes of the message started last, `last` on the piece that ends it.
+    Body { data: Vec<u8>, last: bool },
+}
+
+/// Puts the messages of a stream back together from the chunks it is read in.
+#[derive(Debug)]
+pub struct Deframer {
+    framing: Framing,
+    buffer: Vec<u8>,
+    /// Bytes of the message being passed in parts that are still to come.
+    remaining: u64,
+}
+
+impl Deframer {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            buffer: Vec::new(),
+            remaining: 0,
+        }
+    }
+
+    /// Appends a chunk read from the stream.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Takes the next complete message. Data that doesn't follow the framing can't be made
+    /// sense of, so what was buffered is dropped along with the error. COBS frames end at the
+    /// next zero byte whatever their content, so only the malformed frame is.
+    pub fn next_message(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        if self.framing == Framing::None {
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            return Ok(Some(std::mem::take(&mut self.buffer)));
+        }
+        if self.framing == Framing::Cobs {
+            return self.next_cobs_message();
+        }
+        let (len, prefix) = match self.framing.decode_length(&self.buffer) {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                self.buffer.clear();
+                return Err(e);
+            }
+        };
+        let Some(end) = usize::try_from(len)
+            .ok()
+            .and_then(|len| prefix.checked_add(len))
+        else {
+            self.buffer.clear();
+            return Err(FramingError::MalformedLength);
+        };
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+        let message = self.buffer[prefix..end].to_vec();
+        self.buffer.drain(..end);
+        Ok(Some(message))
+    }
+
+    fn next_cobs_message(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        loop {
+            let Some(end) = self.buffer.iter().position(|&byte| byte == 0) else {
+                return Ok(None);
+            };
+            let frame: Vec<u8> = self.buffer.drain(..=end).collect();
+            if end == 0 {
+                continue;
+            }
+            return decode_cobs(&frame[..end]).map(Some);
+        }
+    }
+
+    /// Like [`Deframer::next_message`], except that messages longer than `threshold` are
+    /// passed in parts as their bytes are received, rather than buffered whole. Only messages
+    /// with a length prefix can be: with [`Framing::None`] and [`Framing::Cobs`], this is
+    /// [`Deframer::next_message`].
+    pub fn next_part(&mut self, threshold: usize) -> Result<Option<MessagePart>, FramingError> {
+        if self.remaining > 0 {
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            let len = self
+                .buffer
+                .len()
+                .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+            let data: Vec<u8> = self.buffer.drain(..len).collect();
+            self.remaining -= len as u64;
+            return Ok(Some(MessagePart::Body {
+                data,
+                last: self.remaining == 0,
+            }));
+        }
+        if matches!(self.framing, Framing::None | Framing::Cobs) {
+            return self
+                .next_message()
+                .map(|message| message.map(MessagePart::Whole));
+        }
+        match self.framing.decode_length(&self.buffer) {
+            Ok(Some((len, prefix))) if len > threshold as u64 => {
+                self.buffer.drain(..prefix);
+                self.remaining = len;
+                Ok(Some(MessagePart::Start { len }))
+            }
+            Ok(None) => Ok(None),
+            _ => self
+                .next_message()
+                .map(|message| message.map(MessagePart::Whole)),
+        }
+    }
+
+    /// Number of bytes received that do not form a complete message yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
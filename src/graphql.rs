@@ -0,0 +1,423 @@
+//! GraphQL subscriptions over a session, following the graphql-ws protocol
+//! (`graphql-transport-ws`), so apps subscribing over a WebSocket can switch to WebTransport
+//! without changing their server's GraphQL layer.
+//!
+//! A [`GraphqlClient`] carries the protocol's JSON messages on a
+//! [`ReliableChannel`], one message each, instead of WebSocket frames. It starts with
+//! `connection_init`, and holds the operations subscribed to until the server answers
+//! `connection_ack`. Every operation then gets a `subscribe` with an id of its own, and its
+//! callback is passed what the server sends for that id: `next` results until `complete`, or an
+//! `error`. Dropping a [`GraphqlSubscription`] sends `complete`. Pings are answered with pongs.
+//!
+//! Queries and mutations go the same way, as operations with a single result, see
+//! [`GraphqlClient::execute`].
+
+use futures::channel::oneshot;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_json::{Map, Value};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::{Rc, Weak},
+};
+use yew::callback::Callback;
+
+use crate::logging::STREAMS;
+use crate::reliable::ReliableChannel;
+use crate::transport::Transport;
+use crate::webtransport::WebTransportError;
+
+/// A GraphQL operation, as sent in a `subscribe` message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphqlRequest {
+    pub query: String,
+    pub operation_name: Option<String>,
+    pub variables: Option<Map<String, Value>>,
+    pub extensions: Option<Map<String, Value>>,
+}
+
+impl GraphqlRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            operation_name: None,
+            variables: None,
+            extensions: None,
+        }
+    }
+
+    pub fn operation_name(mut self, operation_name: impl Into<String>) -> Self {
+        self.operation_name = Some(operation_name.into());
+        self
+    }
+
+    pub fn variables(mut self, variables: Map<String, Value>) -> Self {
+        self.variables = Some(variables);
+        self
+    }
+
+    pub fn extensions(mut self, extensions: Map<String, Value>) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        let mut request = Map::new();
+        request.insert("query".to_string(), Value::from(self.query.as_str()));
+        if let Some(operation_name) = &self.operation_name {
+            request.insert(
+                "operationName".to_string(),
+                Value::from(operation_name.as_str()),
+            );
+        }
+        if let Some(variables) = &self.variables {
+            request.insert("variables".to_string(), Value::Object(variables.clone()));
+        }
+        if let Some(extensions) = &self.extensions {
+            request.insert("extensions".to_string(), Value::Object(extensions.clone()));
+        }
+        Value::Object(request)
+    }
+
+    fn from_json(request: &Value) -> Option<Self> {
+        let object = |key| match request.get(key) {
+            None | Some(Value::Null) => Some(None),
+            Some(Value::Object(map)) => Some(Some(map.clone())),
+            Some(_) => None,
+        };
+        Some(Self {
+            query: request.get("query")?.as_str()?.to_string(),
+            operation_name: match request.get("operationName") {
+                None | Some(Value::Null) => None,
+                Some(name) => Some(name.as_str()?.to_string()),
+            },
+            variables: object("variables")?,
+            extensions: object("extensions")?,
+        })
+    }
+}
+
+/// A message of the graphql-ws protocol. Payloads left out, or `null`, are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphqlMessage {
+    ConnectionInit(Option<Map<String, Value>>),
+    ConnectionAck(Option<Map<String, Value>>),
+    Ping(Option<Map<String, Value>>),
+    Pong(Option<Map<String, Value>>),
+    Subscribe {
+        id: String,
+        payload: GraphqlRequest,
+    },
+    /// A result of operation `id`, with its `data` and `errors`.
+    Next {
+        id: String,
+        payload: Value,
+    },
+    /// Operation `id` failed before executing, with the GraphQL errors telling why.
+    Error {
+        id: String,
+        payload: Vec<Value>,
+    },
+    Complete {
+        id: String,
+    },
+}
+
+impl GraphqlMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let payload_of = |payload: &Option<Map<String, Value>>| payload.clone().map(Value::Object);
+        let (kind, id, payload) = match self {
+            GraphqlMessage::ConnectionInit(payload) => {
+                ("connection_init", None, payload_of(payload))
+            }
+            GraphqlMessage::ConnectionAck(payload) => ("connection_ack", None, payload_of(payload)),
+            GraphqlMessage::Ping(payload) => ("ping", None, payload_of(payload)),
+            GraphqlMessage::Pong(payload) => ("pong", None, payload_of(payload)),
+            GraphqlMessage::Subscribe { id, payload } => {
+                ("subscribe", Some(id), Some(payload.to_json()))
+            }
+            GraphqlMessage::Next { id, payload } => ("next", Some(id), Some(payload.clone())),
+            GraphqlMessage::Error { id, payload } => {
+                ("error", Some(id), Some(Value::Array(payload.clone())))
+            }
+            GraphqlMessage::Complete { id } => ("complete", Some(id), None),
+        };
+        let mut message = Map::new();
+        message.insert("type".to_string(), Value::from(kind));
+        if let Some(id) = id {
+            message.insert("id".to_string(), Value::from(id.as_str()));
+        }
+        if let Some(payload) = payload {
+            message.insert("payload".to_string(), payload);
+        }
+        Value::Object(message).to_string().into_bytes()
+    }
+
+    /// Decodes a message, or returns `None` if it is malformed or of an unknown type.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let message: Value = serde_json::from_slice(data).ok()?;
+        let payload = message.get("payload").filter(|payload| !payload.is_null());
+        let object = || match payload {
+            None => Some(None),
+            Some(Value::Object(map)) => Some(Some(map.clone())),
+            Some(_) => None,
+        };
+        let id = || Some(message.get("id")?.as_str()?.to_string());
+        match message.get("type")?.as_str()? {
+            "connection_init" => Some(GraphqlMessage::ConnectionInit(object()?)),
+            "connection_ack" => Some(GraphqlMessage::ConnectionAck(object()?)),
+            "ping" => Some(GraphqlMessage::Ping(object()?)),
+            "pong" => Some(GraphqlMessage::Pong(object()?)),
+            "subscribe" => Some(GraphqlMessage::Subscribe {
+                id: id()?,
+                payload: GraphqlRequest::from_json(payload?)?,
+            }),
+            "next" => Some(GraphqlMessage::Next {
+                id: id()?,
+                payload: payload?.clone(),
+            }),
+            "error" => Some(GraphqlMessage::Error {
+                id: id()?,
+                payload: payload?.as_array()?.clone(),
+            }),
+            "complete" => Some(GraphqlMessage::Complete { id: id()? }),
+            _ => None,
+        }
+    }
+}
+
+/// What the server sent for an operation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphqlEvent {
+    /// A result, with its `data` and `errors`.
+    Next(Value),
+    /// The operation failed before executing, with the GraphQL errors telling why. Nothing
+    /// follows.
+    Error(Vec<Value>),
+    /// The operation is over. Nothing follows.
+    Complete,
+}
+
+struct Inner {
+    channel: ReliableChannel,
+    acknowledged: Cell<bool>,
+    /// `subscribe` messages waiting for `connection_ack`.
+    waiting: RefCell<Vec<Vec<u8>>>,
+    next_id: Cell<u64>,
+    operations: RefCell<HashMap<String, Callback<GraphqlEvent>>>,
+}
+
+impl Inner {
+    fn receive(&self, data: Vec<u8>) {
+        let Some(message) = GraphqlMessage::decode(&data) else {
+            log::warn!(target: STREAMS, "dropped a malformed graphql-ws message");
+            return;
+        };
+        let (id, event) = match message {
+            GraphqlMessage::ConnectionAck(_) => {
+                self.acknowledged.set(true);
+                let waiting = std::mem::take(&mut *self.waiting.borrow_mut());
+                for message in waiting {
+                    self.channel.send(&message);
+                }
+                return;
+            }
+            GraphqlMessage::Ping(_) => {
+                self.channel.send(&GraphqlMessage::Pong(None).encode());
+                return;
+            }
+            GraphqlMessage::Next { id, payload } => (id, GraphqlEvent::Next(payload)),
+            GraphqlMessage::Error { id, payload } => (id, GraphqlEvent::Error(payload)),
+            GraphqlMessage::Complete { id } => (id, GraphqlEvent::Complete),
+            GraphqlMessage::ConnectionInit(_)
+            | GraphqlMessage::Pong(_)
+            | GraphqlMessage::Subscribe { .. } => return,
+        };
+        let callback = match &event {
+            GraphqlEvent::Next(_) => self.operations.borrow().get(&id).cloned(),
+            GraphqlEvent::Error(_) | GraphqlEvent::Complete => {
+                self.operations.borrow_mut().remove(&id)
+            }
+        };
+        if let Some(callback) = callback {
+            callback.emit(event);
+        }
+    }
+
+    fn send(&self, message: &GraphqlMessage) {
+        let data = message.encode();
+        if self.acknowledged.get() {
+            self.channel.send(&data);
+        } else {
+            self.waiting.borrow_mut().push(data);
+        }
+    }
+
+    fn complete(&self, id: &str) {
+        if self.operations.borrow_mut().remove(id).is_none() {
+            return;
+        }
+        let message = GraphqlMessage::Complete { id: id.to_string() };
+        if self.acknowledged.get() {
+            self.channel.send(&message.encode());
+        } else {
+            // The server never heard of the operation.
+            let subscribe = self.waiting.borrow().iter().position(|waiting| {
+                matches!(
+                    GraphqlMessage::decode(waiting),
+                    Some(GraphqlMessage::Subscribe { id: waiting, .. }) if waiting == id
+                )
+            });
+            if let Some(subscribe) = subscribe {
+                self.waiting.borrow_mut().remove(subscribe);
+            }
+        }
+    }
+}
+
+/// Runs GraphQL operations over a session, see the [module documentation](self). Clones share
+/// the connection.
+#[derive(Clone)]
+pub struct GraphqlClient {
+    inner: Rc<Inner>,
+}
+
+impl GraphqlClient {
+    /// Opens the connection on `transport`, sending `connection_init` with `payload`, the
+    /// parameters the server expects, such as an auth token.
+    pub fn connect<T: Transport + ?Sized>(
+        transport: &T,
+        payload: Option<Map<String, Value>>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let receiver = inner.clone();
+            Inner {
+                channel: ReliableChannel::open(
+                    transport,
+                    Callback::from(move |data: Vec<u8>| {
+                        if let Some(inner) = receiver.upgrade() {
+                            inner.receive(data);
+                        }
+                    }),
+                ),
+                acknowledged: Cell::new(false),
+                waiting: RefCell::default(),
+                next_id: Cell::new(0),
+                operations: RefCell::default(),
+            }
+        });
+        inner
+            .channel
+            .send(&GraphqlMessage::ConnectionInit(payload).encode());
+        Self { inner }
+    }
+
+    /// Subscribes to `request`, passing what the server sends for it to `on_event` until the
+    /// operation completes or fails, or the returned subscription is dropped.
+    pub fn subscribe(
+        &self,
+        request: GraphqlRequest,
+        on_event: Callback<GraphqlEvent>,
+    ) -> GraphqlSubscription {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        let id = id.to_string();
+        self.inner
+            .operations
+            .borrow_mut()
+            .insert(id.clone(), on_event);
+        self.inner.send(&GraphqlMessage::Subscribe {
+            id: id.clone(),
+            payload: request,
+        });
+        GraphqlSubscription {
+            client: self.clone(),
+            id,
+        }
+    }
+
+    /// Runs a query or mutation, resolving with its first result, `data` and `errors`.
+    /// Fails with [`WebTransportError::RemoteError`] if the operation fails before executing,
+    /// and with [`WebTransportError::AlreadyClosed`] if it completes without a result.
+    pub fn execute(
+        &self,
+        request: GraphqlRequest,
+    ) -> LocalBoxFuture<'static, Result<Value, WebTransportError>> {
+        let (result, on_result) = oneshot::channel();
+        let result = RefCell::new(Some(result));
+        let subscription = self.subscribe(
+            request,
+            Callback::from(move |event| {
+                let Some(result) = result.borrow_mut().take() else {
+                    return;
+                };
+                let _ = result.send(match event {
+                    GraphqlEvent::Next(payload) => Ok(payload),
+                    GraphqlEvent::Error(errors) => Err(WebTransportError::RemoteError(
+                        Value::Array(errors).to_string(),
+                    )),
+                    GraphqlEvent::Complete => Err(WebTransportError::AlreadyClosed),
+                });
+            }),
+        );
+        async move {
+            let result = on_result.await;
+            drop(subscription);
+            result.unwrap_or(Err(WebTransportError::AlreadyClosed))
+        }
+        .boxed_local()
+    }
+
+    /// Whether the server answered `connection_ack`.
+    pub fn is_acknowledged(&self) -> bool {
+        self.inner.acknowledged.get()
+    }
+
+    /// Number of operations neither completed nor failed.
+    pub fn active(&self) -> usize {
+        self.inner.operations.borrow().len()
+    }
+}
+
+impl PartialEq for GraphqlClient {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for GraphqlClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GraphqlClient")
+            .field("acknowledged", &self.is_acknowledged())
+            .field("active", &self.active())
+            .finish()
+    }
+}
+
+/// An operation of a [`GraphqlClient`]. Dropping it completes the operation, unless the server
+/// did.
+pub struct GraphqlSubscription {
+    client: GraphqlClient,
+    id: String,
+}
+
+impl GraphqlSubscription {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Drop for GraphqlSubscription {
+    fn drop(&mut self) {
+        self.client.inner.complete(&self.id);
+    }
+}
+
+impl fmt::Debug for GraphqlSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GraphqlSubscription")
+            .field("id", &self.id)
+            .finish()
+    }
+}
@@ -0,0 +1,33 @@
+//! Plain closures accepted wherever the connection layer takes a [`Callback`], so that it can be
+//! used from apps that are not built with yew components, such as plain wasm-bindgen apps and
+//! workers.
+
+use yew::callback::Callback;
+
+/// Something to pass values of type `T` to: a [`Callback`], or any `Fn(T)` closure.
+///
+/// ```ignore
+/// let task = WebTransportService::connect(
+///     url,
+///     |datagram: Vec<u8>| console::log_1(&format!("{datagram:?}").into()),
+///     |_stream: WebTransportReceiveStream| {},
+///     |_stream: WebTransportBidirectionalStream| {},
+///     |status: WebTransportStatus| console::log_1(&format!("{status:?}").into()),
+/// )?;
+/// ```
+pub trait Handler<T>: 'static {
+    /// The [`Callback`] values are passed through.
+    fn into_callback(self) -> Callback<T>;
+}
+
+impl<T: 'static> Handler<T> for Callback<T> {
+    fn into_callback(self) -> Callback<T> {
+        self
+    }
+}
+
+impl<T: 'static, F: Fn(T) + 'static> Handler<T> for F {
+    fn into_callback(self) -> Callback<T> {
+        Callback::from(self)
+    }
+}
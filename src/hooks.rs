@@ -0,0 +1,1335 @@
+//! Function component support.
+//!
+//! A [`WebTransportProvider`] connects to its `url` and shares the session with every component
+//! under it. Those components reach it with [`use_webtransport`], and receive datagrams with
+//! [`use_datagrams`]:
+//!
+//! ```ignore
+//! #[function_component]
+//! fn Chat() -> Html {
+//!     let session = use_webtransport();
+//!     use_datagrams(Callback::from(|datagram: Vec<u8>| { /* ... */ }));
+//!     let send = Callback::from(move |_| session.send_datagram(b"hello".to_vec()));
+//!     html! { <button onclick={send}>{ "Send" }</button> }
+//! }
+//!
+//! html! {
+//!     <WebTransportProvider url="https://example.com:4433">
+//!         <Chat />
+//!     </WebTransportProvider>
+//! }
+//! ```
+//!
+//! The session is closed when the provider is unmounted, and replaced when its `url` changes.
+//! A [`ConnectConfig`] passed as its `config` sets the rest of how it connects, reconnecting
+//! included.
+//! A [`RoutedWebTransportProvider`] picks its endpoint from the current route instead.
+//!
+//! [`use_webtransport_rpc`] calls a server method over the same session, keeping the state of the
+//! latest call for rendering:
+//!
+//! ```ignore
+//! let add = use_webtransport_rpc::<(i64, i64), i64>("add");
+//! let onclick = { let add = add.clone(); Callback::from(move |_| add.call(&(1, 2))) };
+//! html! {
+//!     match add.state() {
+//!         RpcState::Loading => html! { "..." },
+//!         RpcState::Done(sum) => html! { sum },
+//!         RpcState::Failed(error) => html! { error.to_string() },
+//!         RpcState::Idle => html! { <button {onclick}>{ "Add" }</button> },
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "streams")]
+use futures::future::{self, FutureExt, LocalBoxFuture};
+#[cfg(all(feature = "streams", feature = "formats"))]
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "streams")]
+use std::rc::Weak;
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    hash::Hash,
+    rc::Rc,
+    time::Duration,
+};
+#[cfg(all(feature = "streams", feature = "formats"))]
+use std::{
+    collections::{HashMap, VecDeque},
+    marker::PhantomData,
+};
+use yew::platform::{spawn_local, time::sleep};
+#[cfg(all(feature = "streams", feature = "formats"))]
+use yew::suspense::{Suspension, SuspensionResult};
+use yew::{
+    function_component, hook, html, use_context, use_effect_with, use_memo, use_mut_ref, use_state,
+    use_state_eq, AttrValue, Callback, Children, ContextProvider, Html, Properties, UseStateHandle,
+};
+#[cfg(all(feature = "streams", feature = "formats"))]
+use yew::{use_reducer_eq, Reducible, UseReducerHandle};
+
+use crate::certhash::CertificateHash;
+use crate::dispatch::Dispatcher;
+#[cfg(all(feature = "streams", feature = "formats"))]
+use crate::logging::STREAMS;
+#[cfg(feature = "streams")]
+use crate::pubsub::PubSub;
+use crate::reconnect::{ReconnectPolicy, ReconnectingSession, Restored};
+#[cfg(all(feature = "streams", feature = "formats"))]
+use crate::rpc::RpcClient;
+use crate::scope::TaskScope;
+use crate::sessions::SessionState;
+#[cfg(any(feature = "datagrams", all(feature = "streams", feature = "formats")))]
+use crate::throttle::{Throttle, Throttler};
+use crate::transport::{Transport, TransportStats};
+#[cfg(feature = "streams")]
+use crate::webtransport::WebTransportError;
+use crate::webtransport::{
+    CloseInfo, CongestionControl, ReadMode, SendChunking, SendFailurePolicy, SessionError,
+    StreamAcceptance, WebTransportBuilder, WebTransportService, WebTransportStatus,
+    WebTransportTask,
+};
+
+/// Callbacks passed every datagram the session receives.
+#[derive(Default)]
+struct Subscribers {
+    #[cfg(feature = "datagrams")]
+    next_id: Cell<u64>,
+    callbacks: RefCell<Vec<(u64, Callback<Vec<u8>>)>>,
+}
+
+impl Subscribers {
+    #[cfg(feature = "datagrams")]
+    fn subscribe(&self, callback: Callback<Vec<u8>>) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.callbacks.borrow_mut().push((id, callback));
+        id
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn unsubscribe(&self, id: u64) {
+        self.callbacks
+            .borrow_mut()
+            .retain(|(subscriber, _)| *subscriber != id);
+    }
+
+    fn emit(&self, datagram: Vec<u8>) {
+        // Subscribers may unsubscribe while being called.
+        let callbacks: Vec<_> = self
+            .callbacks
+            .borrow()
+            .iter()
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        if let Some((last, others)) = callbacks.split_last() {
+            for callback in others {
+                callback.emit(datagram.clone());
+            }
+            last.emit(datagram);
+        }
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+enum CachedRequest {
+    /// The call is in flight, and the components rendering it are suspended until it answers.
+    Pending(Suspension),
+    Done(Result<Vec<u8>, WebTransportError>),
+}
+
+/// Responses of the calls made by [`use_wt_request`], by method and request.
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[derive(Default)]
+struct RequestCache {
+    entries: RefCell<HashMap<(AttrValue, Vec<u8>), CachedRequest>>,
+}
+
+/// The pub/sub channel of a provider's session, along with the session it was opened on, once
+/// something used it.
+#[cfg(feature = "streams")]
+type SessionPubSub = RefCell<Option<(Weak<WebTransportTask>, PubSub)>>;
+
+/// The session of the closest [`WebTransportProvider`], as returned by [`use_webtransport`].
+///
+/// Sends made while the session is not connected are dropped.
+#[derive(Clone)]
+pub struct UseWebTransportHandle {
+    state: SessionState,
+    session: Option<Rc<WebTransportTask>>,
+    subscribers: Rc<Subscribers>,
+    #[cfg(all(feature = "streams", feature = "formats"))]
+    requests: Rc<RequestCache>,
+    #[cfg(feature = "streams")]
+    pubsub: Rc<SessionPubSub>,
+}
+
+impl UseWebTransportHandle {
+    pub fn state(&self) -> &SessionState {
+        &self.state
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == SessionState::Open
+    }
+
+    /// The session, unless connecting to it failed right away, or it was lost and the provider
+    /// is reconnecting.
+    pub fn session(&self) -> Option<Rc<WebTransportTask>> {
+        self.session.clone()
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub fn send_datagram(&self, data: Vec<u8>) {
+        if let Some(session) = &self.session {
+            session.send_datagram(data);
+        }
+    }
+
+    #[cfg(feature = "streams")]
+    pub fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        if let Some(session) = &self.session {
+            session.send_unidirectional_stream(data);
+        }
+    }
+
+    /// Sends `data` on a bidirectional stream and resolves with the response, see
+    /// [`Transport::request`].
+    #[cfg(feature = "streams")]
+    pub fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        match &self.session {
+            Some(session) => session.request(data),
+            None => future::ready(Err(WebTransportError::AlreadyClosed)).boxed_local(),
+        }
+    }
+
+    /// The session's pub/sub channel, opened the first time it is asked for, unless connecting
+    /// to the session failed right away. See [`crate::pubsub`].
+    #[cfg(feature = "streams")]
+    pub fn pubsub(&self) -> Option<PubSub> {
+        let session = self.session.as_ref()?;
+        let mut pubsub = self.pubsub.borrow_mut();
+        match &*pubsub {
+            Some((opened_on, pubsub)) if opened_on.as_ptr() == Rc::as_ptr(session) => {
+                Some(pubsub.clone())
+            }
+            _ => {
+                let opened = PubSub::open(&**session);
+                *pubsub = Some((Rc::downgrade(session), opened.clone()));
+                Some(opened)
+            }
+        }
+    }
+
+    /// Forgets the responses [`use_wt_request`] cached for `method`, so that the components
+    /// rendered next call it again. Calls in flight are made again as well.
+    #[cfg(all(feature = "streams", feature = "formats"))]
+    pub fn invalidate_requests(&self, method: &str) {
+        self.requests
+            .entries
+            .borrow_mut()
+            .retain(|(cached, _), _| cached.as_str() != method);
+    }
+}
+
+impl PartialEq for UseWebTransportHandle {
+    fn eq(&self, other: &Self) -> bool {
+        let same_session = match (&self.session, &other.session) {
+            (Some(session), Some(other)) => Rc::ptr_eq(session, other),
+            (None, None) => true,
+            _ => false,
+        };
+        self.state == other.state
+            && same_session
+            && Rc::ptr_eq(&self.subscribers, &other.subscribers)
+    }
+}
+
+impl fmt::Debug for UseWebTransportHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UseWebTransportHandle")
+            .field("state", &self.state)
+            .field("connected", &self.session.is_some())
+            .finish()
+    }
+}
+
+/// How a provider connects, as a single property. Providers compare it with the previous
+/// one when rendered again, and reconnect only when it changed.
+///
+/// ```ignore
+/// let config = ConnectConfig::new("https://example.com:4433")
+///     .label("game")
+///     .reconnect(ReconnectPolicy::default());
+/// html! { <WebTransportProvider {config}>{ children }</WebTransportProvider> }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectConfig {
+    /// URL of the server to connect to.
+    pub url: AttrValue,
+    pub options: ConnectOptions,
+    /// Connects again whenever the session is lost, following the policy, see
+    /// [`crate::reconnect`]. The session is only connected once without one.
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Names the session, see [`WebTransportBuilder::label`].
+    pub label: Option<AttrValue>,
+}
+
+impl ConnectConfig {
+    pub fn new(url: impl Into<AttrValue>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<AttrValue>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    pub fn options(mut self, options: ConnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<AttrValue>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// A builder connecting as configured, without callbacks.
+    pub fn builder(&self) -> WebTransportBuilder {
+        let options = &self.options;
+        let mut builder = WebTransportService::builder(self.url.to_string())
+            .require_unreliable(options.require_unreliable)
+            .server_certificate_hashes(options.server_certificate_hashes.iter().copied())
+            .on_send_failure(options.send_failure)
+            .send_chunk_size(options.chunking.chunk_size)
+            .yield_interval(options.chunking.yield_interval)
+            .read_mode(options.read_mode)
+            .stream_acceptance(options.stream_acceptance);
+        if let Some(congestion_control) = options.congestion_control {
+            builder = builder.congestion_control(congestion_control);
+        }
+        if let Some(max_age) = options.datagram_max_age {
+            builder = builder.datagram_max_age(max_age);
+        }
+        if let Some(label) = &self.label {
+            builder = builder.label(label.to_string());
+        }
+        builder
+    }
+}
+
+/// The options of a [`ConnectConfig`], those of [`WebTransportBuilder`] that can be compared.
+/// The defaults are the builder's.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectOptions {
+    /// See [`WebTransportBuilder::require_unreliable`].
+    pub require_unreliable: bool,
+    /// See [`WebTransportBuilder::congestion_control`].
+    pub congestion_control: Option<CongestionControl>,
+    /// See [`WebTransportBuilder::server_certificate_hashes`].
+    pub server_certificate_hashes: Vec<CertificateHash>,
+    /// See [`WebTransportBuilder::on_send_failure`].
+    pub send_failure: SendFailurePolicy,
+    /// See [`WebTransportBuilder::datagram_max_age`].
+    pub datagram_max_age: Option<Duration>,
+    pub chunking: SendChunking,
+    /// See [`WebTransportBuilder::read_mode`].
+    pub read_mode: ReadMode,
+    /// See [`WebTransportBuilder::stream_acceptance`].
+    pub stream_acceptance: StreamAcceptance,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct WebTransportProviderProps {
+    /// URL of the server to connect to, taking precedence over the `config`'s if set.
+    #[prop_or_default]
+    pub url: AttrValue,
+    /// How to connect.
+    #[prop_or_default]
+    pub config: ConnectConfig,
+    /// Passed every status notification of the session.
+    #[prop_or_default]
+    pub on_status: Callback<WebTransportStatus>,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Connects to `url`, or as `config` says, and shares the session with the components under
+/// it, see the [module documentation](self).
+#[function_component]
+pub fn WebTransportProvider(props: &WebTransportProviderProps) -> Html {
+    let config = match props.url.is_empty() {
+        true => props.config.clone(),
+        false => props.config.clone().url(props.url.clone()),
+    };
+    let handle = use_provided_session(Some(config), props.on_status.clone());
+    html! {
+        <ContextProvider<UseWebTransportHandle> context={handle}>
+            { props.children.clone() }
+        </ContextProvider<UseWebTransportHandle>>
+    }
+}
+
+/// Which endpoint a [`RoutedWebTransportProvider`] connects to on each path.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouteEndpoints {
+    routes: Vec<(AttrValue, AttrValue)>,
+}
+
+impl RouteEndpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects to `url` on the paths matching `pattern`: the path itself, or with a trailing
+    /// `/*` the path and every path under it, as in `/live/*`. `*` alone matches every path.
+    /// Routes are tried in the order they were added.
+    pub fn route(mut self, pattern: impl Into<AttrValue>, url: impl Into<AttrValue>) -> Self {
+        self.routes.push((pattern.into(), url.into()));
+        self
+    }
+
+    /// The endpoint of the first route matching `path`, if any.
+    pub fn endpoint(&self, path: &str) -> Option<&AttrValue> {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| route_matches(pattern, path))
+            .map(|(_, url)| url)
+    }
+}
+
+fn route_matches(pattern: &str, path: &str) -> bool {
+    let path = path
+        .strip_suffix('/')
+        .filter(|path| !path.is_empty())
+        .unwrap_or(path);
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => {
+            path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        }
+        None => path == pattern,
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RoutedWebTransportProviderProps {
+    /// The current path, as given by `use_location()` with yew-router.
+    pub path: AttrValue,
+    pub routes: RouteEndpoints,
+    /// How to connect, to the endpoint of the route rather than the `config`'s URL.
+    #[prop_or_default]
+    pub config: ConnectConfig,
+    /// How long the session outlives a navigation away from its routes, or to another
+    /// endpoint's.
+    #[prop_or(Duration::from_secs(2))]
+    pub grace: Duration,
+    /// Passed every status notification of the session.
+    #[prop_or_default]
+    pub on_status: Callback<WebTransportStatus>,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Like [`WebTransportProvider`], connecting to the endpoint [`RouteEndpoints`] gives for the
+/// current `path`, and staying disconnected on paths without one. The session is closed, or
+/// replaced, once `grace` has elapsed since the navigation, so that navigating back in the
+/// meantime keeps it rather than reconnecting. Connecting from a disconnected path is
+/// immediate.
+///
+/// ```ignore
+/// let path = use_location().unwrap().path().to_string();
+/// let routes = RouteEndpoints::new().route("/live/*", "https://example.com:4433");
+/// html! {
+///     <RoutedWebTransportProvider {path} {routes}>
+///         <Switch<Route> render={switch} />
+///     </RoutedWebTransportProvider>
+/// }
+/// ```
+#[function_component]
+pub fn RoutedWebTransportProvider(props: &RoutedWebTransportProviderProps) -> Html {
+    let wanted = props.routes.endpoint(&props.path).cloned();
+    let url = use_state_eq(|| wanted.clone());
+    // Incremented with every navigation, so that a pending switch is cancelled by the next.
+    let navigation = use_mut_ref(|| 0u64);
+    {
+        let url = url.clone();
+        use_effect_with((wanted, props.grace), move |(wanted, grace)| {
+            let current = {
+                let mut navigation = navigation.borrow_mut();
+                *navigation += 1;
+                *navigation
+            };
+            if url.is_none() {
+                url.set(wanted.clone());
+            } else if *url != *wanted {
+                let wanted = wanted.clone();
+                let grace = *grace;
+                let navigation = navigation.clone();
+                spawn_local(async move {
+                    sleep(grace).await;
+                    if *navigation.borrow() == current {
+                        url.set(wanted);
+                    }
+                });
+            }
+            move || *navigation.borrow_mut() += 1
+        });
+    }
+    let config = (*url).clone().map(|url| props.config.clone().url(url));
+    let handle = use_provided_session(config, props.on_status.clone());
+    html! {
+        <ContextProvider<UseWebTransportHandle> context={handle}>
+            { props.children.clone() }
+        </ContextProvider<UseWebTransportHandle>>
+    }
+}
+
+/// The session of a provider, connected as `config` says if there is one, and closed
+/// otherwise.
+#[hook]
+fn use_provided_session(
+    config: Option<ConnectConfig>,
+    on_status: Callback<WebTransportStatus>,
+) -> UseWebTransportHandle {
+    let state = use_state_eq(|| SessionState::Connecting);
+    let session = use_state(|| None::<Rc<WebTransportTask>>);
+    let subscribers = use_memo((), |_| Subscribers::default());
+    #[cfg(all(feature = "streams", feature = "formats"))]
+    let requests = use_memo((), |_| RequestCache::default());
+    #[cfg(feature = "streams")]
+    let pubsub = use_memo((), |_| RefCell::new(None));
+    // Incremented with every session, so that a replaced session can't report to the next.
+    let generation = use_mut_ref(|| 0u64);
+    let latest_on_status = use_mut_ref(Callback::noop);
+    *latest_on_status.borrow_mut() = on_status;
+    {
+        let state = state.clone();
+        let session = session.clone();
+        let subscribers = subscribers.clone();
+        use_effect_with(config, move |config| {
+            let current = {
+                let mut generation = generation.borrow_mut();
+                *generation += 1;
+                *generation
+            };
+            let notification = {
+                let state = state.clone();
+                Callback::from(move |status: WebTransportStatus| {
+                    if *generation.borrow() != current {
+                        return;
+                    }
+                    if let Some(next) = SessionState::from_status(&status) {
+                        state.set(next);
+                    }
+                    let on_status = latest_on_status.borrow().clone();
+                    on_status.emit(status);
+                })
+            };
+            let connection = match config {
+                Some(config) => connect(config, &state, &session, notification, subscribers),
+                None => {
+                    state.set(SessionState::Closed(CloseInfo::default()));
+                    session.set(None);
+                    None
+                }
+            };
+            move || {
+                if let Some(connection) = connection {
+                    connection.close();
+                }
+            }
+        });
+    }
+    UseWebTransportHandle {
+        state: (*state).clone(),
+        session: (*session).clone(),
+        subscribers,
+        #[cfg(all(feature = "streams", feature = "formats"))]
+        requests,
+        #[cfg(feature = "streams")]
+        pubsub,
+    }
+}
+
+/// What a provider connected.
+enum Connection {
+    Session(Rc<WebTransportTask>),
+    /// Kept along with the setup passing every new session to the provider.
+    Reconnecting(
+        ReconnectingSession<WebTransportTask>,
+        Restored<WebTransportTask, ()>,
+    ),
+}
+
+impl Connection {
+    fn close(self) {
+        let _ = match self {
+            Connection::Session(task) => task.close(),
+            Connection::Reconnecting(reconnecting, restored) => {
+                drop(restored);
+                reconnecting.close()
+            }
+        };
+    }
+}
+
+/// Connects a provider's session as `config` says, setting `state` to where it stands, and
+/// `session` to the session, or to every new one when reconnecting.
+fn connect(
+    config: &ConnectConfig,
+    state: &UseStateHandle<SessionState>,
+    session: &UseStateHandle<Option<Rc<WebTransportTask>>>,
+    notification: Callback<WebTransportStatus>,
+    subscribers: Rc<Subscribers>,
+) -> Option<Connection> {
+    state.set(SessionState::Connecting);
+    let on_datagram = Callback::from(move |datagram| subscribers.emit(datagram));
+    let builder = config.builder().on_datagram(on_datagram);
+    if let Some(policy) = &config.reconnect {
+        session.set(None);
+        let notification = {
+            let session = session.clone();
+            Callback::from(move |status: WebTransportStatus| {
+                if matches!(
+                    status,
+                    WebTransportStatus::Closed(_) | WebTransportStatus::Error(_)
+                ) {
+                    session.set(None);
+                }
+                notification.emit(status);
+            })
+        };
+        let reconnecting =
+            ReconnectingSession::connect(builder.on_status(notification), policy.clone());
+        let session = session.clone();
+        let restored = reconnecting.restore(move |task| session.set(Some(task.clone())));
+        return Some(Connection::Reconnecting(reconnecting, restored));
+    }
+    match builder.on_status(notification).connect() {
+        Ok(task) => {
+            let task = Rc::new(task);
+            session.set(Some(task.clone()));
+            Some(Connection::Session(task))
+        }
+        Err(error) => {
+            state.set(SessionState::Failed(SessionError::Other {
+                name: String::new(),
+                message: error.to_string(),
+            }));
+            session.set(None);
+            None
+        }
+    }
+}
+
+/// The session of the closest [`WebTransportProvider`]. The component is rendered again
+/// whenever the session's state changes.
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[hook]
+pub fn use_webtransport() -> UseWebTransportHandle {
+    use_context::<UseWebTransportHandle>()
+        .expect("use_webtransport must be called under a WebTransportProvider")
+}
+
+/// Passes every datagram received by the session of the closest [`WebTransportProvider`] to
+/// `on_datagram`, for as long as the component is mounted. Datagrams received while the
+/// component renders go to the latest `on_datagram`.
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[cfg(feature = "datagrams")]
+#[hook]
+pub fn use_datagrams(on_datagram: Callback<Vec<u8>>) {
+    let subscribers = use_webtransport().subscribers;
+    let latest = use_mut_ref(Callback::noop);
+    *latest.borrow_mut() = on_datagram;
+    use_effect_with((), move |_| {
+        let id = subscribers.subscribe(Callback::from(move |datagram| {
+            let on_datagram = latest.borrow().clone();
+            on_datagram.emit(datagram);
+        }));
+        move || subscribers.unsubscribe(id)
+    });
+}
+
+/// Like [`use_datagrams`], passing the datagrams received to `on_datagrams` at most once per
+/// `throttle.interval`, for components rendering from streams of datagrams far faster than
+/// the screen refreshes. Those received within an interval are passed together, or only the
+/// latest of them, as `throttle.coalesce` says, see [`crate::throttle`].
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[cfg(feature = "datagrams")]
+#[hook]
+pub fn use_datagrams_throttled(throttle: Throttle, on_datagrams: Callback<Vec<Vec<u8>>>) {
+    let subscribers = use_webtransport().subscribers;
+    let latest = use_mut_ref(Callback::noop);
+    *latest.borrow_mut() = on_datagrams;
+    use_effect_with(throttle, move |throttle| {
+        let throttler = Throttler::new(
+            *throttle,
+            Callback::from(move |datagrams| {
+                let on_datagrams = latest.borrow().clone();
+                on_datagrams.emit(datagrams);
+            }),
+        );
+        let id = subscribers.subscribe(Callback::from(move |datagram| throttler.push(datagram)));
+        move || subscribers.unsubscribe(id)
+    });
+}
+
+/// Passes the messages `dispatcher` gets with `key` to `on_message`, for as long as the
+/// component is mounted, see [`crate::dispatch`]. Messages dispatched while the component
+/// renders go to the latest `on_message`.
+///
+/// ```ignore
+/// #[derive(Properties, PartialEq)]
+/// struct ChatProps { dispatcher: Dispatcher<u8, Vec<u8>> }
+///
+/// #[function_component]
+/// fn Chat(props: &ChatProps) -> Html {
+///     let last = use_state(|| None);
+///     let on_message = { let last = last.clone(); Callback::from(move |m| last.set(Some(m))) };
+///     use_dispatch(&props.dispatcher, CHAT, on_message);
+///     html! { <p>{ format!("{:?}", *last) }</p> }
+/// }
+/// ```
+#[hook]
+pub fn use_dispatch<K, M>(dispatcher: &Dispatcher<K, M>, key: K, on_message: Callback<M>)
+where
+    K: Clone + Eq + Hash + 'static,
+    M: Clone + 'static,
+{
+    let latest = use_mut_ref(Callback::noop);
+    *latest.borrow_mut() = on_message;
+    use_effect_with((dispatcher.clone(), key), move |(dispatcher, key)| {
+        let registration = dispatcher.on(
+            key.clone(),
+            Callback::from(move |message| {
+                let on_message = latest.borrow().clone();
+                on_message.emit(message);
+            }),
+        );
+        move || drop(registration)
+    });
+}
+
+/// Like [`use_dispatch`], passing the messages `predicate` accepts whatever their key. The
+/// latest `predicate` decides.
+#[hook]
+pub fn use_dispatch_matching<K, M, P>(
+    dispatcher: &Dispatcher<K, M>,
+    predicate: P,
+    on_message: Callback<M>,
+) where
+    K: Eq + Hash + 'static,
+    M: Clone + 'static,
+    P: Fn(&M) -> bool + 'static,
+{
+    let latest = use_mut_ref(|| None::<(Rc<dyn Fn(&M) -> bool>, Callback<M>)>);
+    *latest.borrow_mut() = Some((Rc::new(predicate), on_message));
+    use_effect_with(dispatcher.clone(), move |dispatcher| {
+        let on_message = {
+            let latest = latest.clone();
+            Callback::from(move |message| {
+                let on_message = latest
+                    .borrow()
+                    .as_ref()
+                    .map(|(_, on_message)| on_message.clone());
+                if let Some(on_message) = on_message {
+                    on_message.emit(message);
+                }
+            })
+        };
+        let registration = dispatcher.on_matching(
+            move |message| {
+                let predicate = latest
+                    .borrow()
+                    .as_ref()
+                    .map(|(predicate, _)| predicate.clone());
+                predicate.is_some_and(|predicate| predicate(message))
+            },
+            on_message,
+        );
+        move || drop(registration)
+    });
+}
+
+/// A [`TaskScope`] for the component, cancelling its tasks when the component is unmounted.
+/// Clones of the returned `Rc` kept elsewhere, in a callback for instance, keep it running.
+#[hook]
+pub fn use_task_scope() -> Rc<TaskScope> {
+    use_memo((), |_| TaskScope::new())
+}
+
+/// The statistics of the session of the closest [`WebTransportProvider`], polled every
+/// `interval` while the component is mounted, or `None` until the first poll answered. The
+/// component is rendered again whenever they change. Polling starts over when the session is
+/// replaced or its state changes, and stops when the component is unmounted.
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[hook]
+pub fn use_webtransport_stats(interval: Duration) -> Option<TransportStats> {
+    let handle = use_webtransport();
+    let stats = use_state_eq(|| None);
+    {
+        let stats = stats.clone();
+        use_effect_with((interval, handle), move |(interval, handle)| {
+            let polling = Rc::new(Cell::new(true));
+            if let Some(session) = &handle.session {
+                let session = Rc::downgrade(session);
+                let polling = polling.clone();
+                let interval = *interval;
+                spawn_local(async move {
+                    while let Some(session) = session.upgrade().filter(|_| polling.get()) {
+                        let sample = session.stats();
+                        drop(session);
+                        let sample = sample.await;
+                        if !polling.get() {
+                            break;
+                        }
+                        if let Ok(sample) = sample {
+                            stats.set(Some(sample));
+                        }
+                        sleep(interval).await;
+                    }
+                });
+            }
+            move || polling.set(false)
+        });
+    }
+    (*stats).clone()
+}
+
+/// Where the latest call of a [`UseRpcHandle`] stands.
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpcState<T> {
+    /// No call was made yet.
+    Idle,
+    Loading,
+    Done(T),
+    Failed(WebTransportError),
+}
+
+/// Calls a server method over the session of the closest [`WebTransportProvider`], as returned
+/// by [`use_webtransport_rpc`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+pub struct UseRpcHandle<Req, Resp> {
+    method: AttrValue,
+    client: Option<RpcClient<WebTransportTask>>,
+    state: UseStateHandle<RpcState<Resp>>,
+    /// Number of the latest call, the only one whose outcome is kept.
+    latest: Rc<Cell<u64>>,
+    request: PhantomData<fn(&Req)>,
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<Req, Resp> UseRpcHandle<Req, Resp>
+where
+    Req: Serialize,
+    Resp: DeserializeOwned + 'static,
+{
+    /// Calls the method with `request`. The state is [`RpcState::Loading`] until the response,
+    /// unless another call is made meanwhile, which replaces this one's outcome.
+    pub fn call(&self, request: &Req) {
+        let call = self.latest.get() + 1;
+        self.latest.set(call);
+        let Some(client) = &self.client else {
+            self.state
+                .set(RpcState::Failed(WebTransportError::AlreadyClosed));
+            return;
+        };
+        let response = client.call::<Req, Resp>(&self.method, request);
+        self.state.set(RpcState::Loading);
+        let state = self.state.clone();
+        let latest = self.latest.clone();
+        spawn_local(async move {
+            let response = response.await;
+            if latest.get() != call {
+                return;
+            }
+            state.set(match response {
+                Ok(response) => RpcState::Done(response),
+                Err(error) => RpcState::Failed(error),
+            });
+        });
+    }
+
+    /// Forgets the outcome of the latest call, and ignores it if it is still in flight.
+    pub fn reset(&self) {
+        self.latest.set(self.latest.get() + 1);
+        self.state.set(RpcState::Idle);
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<Req, Resp> UseRpcHandle<Req, Resp> {
+    pub fn state(&self) -> &RpcState<Resp> {
+        &self.state
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(*self.state, RpcState::Loading)
+    }
+
+    /// The response to the latest call, once it succeeded.
+    pub fn data(&self) -> Option<&Resp> {
+        match &*self.state {
+            RpcState::Done(response) => Some(response),
+            _ => None,
+        }
+    }
+
+    /// Why the latest call failed, if it did.
+    pub fn error(&self) -> Option<&WebTransportError> {
+        match &*self.state {
+            RpcState::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The client calls are made with, for calls to other methods or without state, unless
+    /// connecting to the session failed right away.
+    pub fn client(&self) -> Option<&RpcClient<WebTransportTask>> {
+        self.client.as_ref()
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<Req, Resp> Clone for UseRpcHandle<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            method: self.method.clone(),
+            client: self.client.clone(),
+            state: self.state.clone(),
+            latest: self.latest.clone(),
+            request: PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<Req, Resp: PartialEq> PartialEq for UseRpcHandle<Req, Resp> {
+    fn eq(&self, other: &Self) -> bool {
+        let same_client = match (&self.client, &other.client) {
+            (Some(client), Some(other)) => Rc::ptr_eq(client.transport(), other.transport()),
+            (None, None) => true,
+            _ => false,
+        };
+        self.method == other.method && same_client && *self.state == *other.state
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<Req, Resp: fmt::Debug> fmt::Debug for UseRpcHandle<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UseRpcHandle")
+            .field("method", &self.method)
+            .field("state", &*self.state)
+            .finish()
+    }
+}
+
+/// A client calling `method` over the session of the closest [`WebTransportProvider`], with
+/// JSON encoded requests and responses as [`RpcClient::call`] does. The component is rendered
+/// again whenever the state of the latest call changes.
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[hook]
+pub fn use_webtransport_rpc<Req, Resp>(method: impl Into<AttrValue>) -> UseRpcHandle<Req, Resp>
+where
+    Req: 'static,
+    Resp: 'static,
+{
+    let session = use_webtransport().session;
+    let state = use_state(|| RpcState::Idle);
+    let latest = use_memo((), |_| Cell::new(0));
+    // Kept across renders so call ids keep increasing on the same session.
+    let client = use_mut_ref(|| None::<RpcClient<WebTransportTask>>);
+    let client = {
+        let mut client = client.borrow_mut();
+        let same_session = match (&*client, &session) {
+            (Some(client), Some(session)) => Rc::ptr_eq(client.transport(), session),
+            _ => false,
+        };
+        if !same_session {
+            *client = session.map(RpcClient::new);
+        }
+        client.clone()
+    };
+    UseRpcHandle {
+        method: method.into(),
+        client,
+        state,
+        latest,
+        request: PhantomData,
+    }
+}
+
+/// Calls `method` with `request` over the session of the closest [`WebTransportProvider`],
+/// suspending the component until the response, for rendering under a
+/// [`Suspense`](yew::suspense::Suspense). Requests and responses are JSON encoded, as
+/// [`RpcClient::call`] does.
+///
+/// Responses, errors included, are cached by method and request for as long as the provider
+/// lives, so components making the same call share it, and render again without calling, until
+/// [`UseWebTransportHandle::invalidate_requests`]. The response is decoded at every render.
+///
+/// ```ignore
+/// #[function_component]
+/// fn Profile(props: &ProfileProps) -> HtmlResult {
+///     let profile = use_wt_request::<_, Profile>("profile", &props.user_id)?;
+///     Ok(match profile {
+///         Ok(profile) => html! { profile.name },
+///         Err(error) => html! { error.to_string() },
+///     })
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[hook]
+pub fn use_wt_request<Req, Resp>(
+    method: impl Into<AttrValue>,
+    request: &Req,
+) -> SuspensionResult<Result<Resp, WebTransportError>>
+where
+    Req: Serialize,
+    Resp: DeserializeOwned,
+{
+    let handle = use_webtransport();
+    let payload = match serde_json::to_vec(request) {
+        Ok(payload) => payload,
+        Err(e) => return Ok(Err(WebTransportError::SerializationError(e.to_string()))),
+    };
+    let key = (method.into(), payload);
+    match handle.requests.entries.borrow().get(&key) {
+        Some(CachedRequest::Done(response)) => {
+            return Ok(response.clone().and_then(|response| {
+                serde_json::from_slice(&response)
+                    .map_err(|e| WebTransportError::SerializationError(e.to_string()))
+            }));
+        }
+        Some(CachedRequest::Pending(suspension)) => return Err(suspension.clone()),
+        None => {}
+    }
+    let Some(session) = handle.session else {
+        return Ok(Err(WebTransportError::AlreadyClosed));
+    };
+    let response = RpcClient::new(session).call_raw(&key.0, &key.1);
+    let (suspension, resume) = Suspension::new();
+    let requests = handle.requests.clone();
+    requests
+        .entries
+        .borrow_mut()
+        .insert(key.clone(), CachedRequest::Pending(suspension.clone()));
+    {
+        let suspension = suspension.clone();
+        spawn_local(async move {
+            let response = response.await;
+            {
+                let mut entries = requests.entries.borrow_mut();
+                // Unless the call was invalidated meanwhile.
+                if let Some(CachedRequest::Pending(pending)) = entries.get(&key) {
+                    if *pending == suspension {
+                        entries.insert(key, CachedRequest::Done(response));
+                    }
+                }
+            }
+            resume.resume();
+        });
+    }
+    Err(suspension)
+}
+
+/// Number of values kept by [`use_subscription`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+pub const DEFAULT_TOPIC_HISTORY: usize = 32;
+
+/// How [`use_subscription_with_options`] keeps the values received on a topic.
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubscriptionOptions {
+    /// Number of values kept, set when the component is first rendered.
+    pub history: usize,
+    /// How often the values received are passed on to the component, rendering it again.
+    /// Every value is, as it is received, without one.
+    pub throttle: Option<Throttle>,
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        Self {
+            history: DEFAULT_TOPIC_HISTORY,
+            throttle: None,
+        }
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl SubscriptionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+}
+
+/// The last values received on a topic, oldest first.
+#[cfg(all(feature = "streams", feature = "formats"))]
+struct TopicHistory<T> {
+    topic: AttrValue,
+    capacity: usize,
+    values: VecDeque<Rc<T>>,
+    /// Incremented with every value, for renders to tell the histories apart cheaply.
+    received: u64,
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+enum TopicAction<T> {
+    /// The component subscribed to another topic: what was received on the previous one goes.
+    Subscribe(AttrValue),
+    /// Values received, oldest first.
+    Receive(Vec<T>),
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T> Reducible for TopicHistory<T> {
+    type Action = TopicAction<T>;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            TopicAction::Subscribe(topic) if topic == self.topic => self,
+            TopicAction::Subscribe(topic) => Rc::new(Self {
+                topic,
+                capacity: self.capacity,
+                values: VecDeque::new(),
+                received: 0,
+            }),
+            TopicAction::Receive(received) if received.is_empty() => self,
+            TopicAction::Receive(received) => {
+                let count = received.len() as u64;
+                let mut values = self.values.clone();
+                values.extend(received.into_iter().map(Rc::new));
+                if values.len() > self.capacity {
+                    values.drain(..values.len() - self.capacity);
+                }
+                Rc::new(Self {
+                    topic: self.topic.clone(),
+                    capacity: self.capacity,
+                    values,
+                    received: self.received + count,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T> PartialEq for TopicHistory<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.topic == other.topic && self.received == other.received
+    }
+}
+
+/// The values received on a topic, as returned by [`use_subscription`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+pub struct UseSubscriptionHandle<T> {
+    history: UseReducerHandle<TopicHistory<T>>,
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T> UseSubscriptionHandle<T> {
+    pub fn topic(&self) -> &AttrValue {
+        &self.history.topic
+    }
+
+    /// The last value received, if any.
+    pub fn latest(&self) -> Option<&T> {
+        self.history.values.back().map(|value| &**value)
+    }
+
+    /// The last values received, oldest first.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &T> + '_ {
+        self.history.values.iter().map(|value| &**value)
+    }
+
+    /// Number of values received on the topic since subscribing, including those no longer
+    /// in the history, but not those skipped by a [`Coalesce::Latest`](crate::throttle::Coalesce)
+    /// throttle.
+    pub fn received(&self) -> u64 {
+        self.history.received
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T> Clone for UseSubscriptionHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            history: self.history.clone(),
+        }
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T> PartialEq for UseSubscriptionHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        *self.history == *other.history
+    }
+}
+
+#[cfg(all(feature = "streams", feature = "formats"))]
+impl<T: fmt::Debug> fmt::Debug for UseSubscriptionHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UseSubscriptionHandle")
+            .field("topic", &self.history.topic)
+            .field("latest", &self.latest())
+            .field("received", &self.history.received)
+            .finish()
+    }
+}
+
+/// Subscribes to `topic` on the pub/sub channel of the session of the closest
+/// [`WebTransportProvider`], see [`crate::pubsub`], for as long as the component is mounted.
+/// Payloads are decoded from JSON; those that fail to are logged and dropped. The last
+/// [`DEFAULT_TOPIC_HISTORY`] values are kept, and the component is rendered again with every
+/// value received.
+///
+/// ```ignore
+/// let prices = use_subscription::<Quote>("prices/BTC");
+/// html! { <p>{ prices.latest().map(|quote| quote.price) }</p> }
+/// ```
+///
+/// # Panics
+///
+/// Outside of a [`WebTransportProvider`].
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[hook]
+pub fn use_subscription<T>(topic: impl Into<AttrValue>) -> UseSubscriptionHandle<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    use_subscription_with_history(topic, DEFAULT_TOPIC_HISTORY)
+}
+
+/// Like [`use_subscription`], keeping the last `history` values. The length of the history is
+/// set when the component is first rendered.
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[hook]
+pub fn use_subscription_with_history<T>(
+    topic: impl Into<AttrValue>,
+    history: usize,
+) -> UseSubscriptionHandle<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    use_subscription_with_options(topic, SubscriptionOptions::new().history(history))
+}
+
+/// Like [`use_subscription`], keeping and passing on the values received as `options` says.
+/// With a throttle, the component is rendered again at most once per interval however fast
+/// values come, with every value received in between added to the history, or only the latest
+/// of them:
+///
+/// ```ignore
+/// let ticks = use_subscription_with_options::<Tick>(
+///     "ticks",
+///     SubscriptionOptions::new().throttle(Throttle::hz(10)),
+/// );
+/// ```
+#[cfg(all(feature = "streams", feature = "formats"))]
+#[hook]
+pub fn use_subscription_with_options<T>(
+    topic: impl Into<AttrValue>,
+    options: SubscriptionOptions,
+) -> UseSubscriptionHandle<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    let topic = topic.into();
+    let pubsub = use_webtransport().pubsub();
+    let state = {
+        let topic = topic.clone();
+        use_reducer_eq(move || TopicHistory {
+            topic,
+            capacity: options.history.max(1),
+            values: VecDeque::new(),
+            received: 0,
+        })
+    };
+    let dispatcher = state.dispatcher();
+    use_effect_with(
+        (topic, pubsub, options.throttle),
+        move |(topic, pubsub, throttle)| {
+            dispatcher.dispatch(TopicAction::Subscribe(topic.clone()));
+            let receive = Callback::from(move |values| {
+                dispatcher.dispatch(TopicAction::Receive(values));
+            });
+            let receive = match throttle {
+                Some(throttle) => {
+                    let throttler = Throttler::new(*throttle, receive);
+                    Callback::from(move |value| throttler.push(value))
+                }
+                None => receive.reform(|value| vec![value]),
+            };
+            let subscription = pubsub.as_ref().map(|pubsub| {
+                let topic = topic.clone();
+                pubsub.subscribe(
+                    topic.to_string(),
+                    Callback::from(move |payload: Vec<u8>| {
+                        match serde_json::from_slice::<T>(&payload) {
+                            Ok(value) => receive.emit(value),
+                            Err(e) => log::warn!(
+                                target: STREAMS,
+                                "dropped a message of topic {topic} failing to decode: {e}"
+                            ),
+                        }
+                    }),
+                )
+            });
+            move || drop(subscription)
+        },
+    );
+    UseSubscriptionHandle { history: state }
+}
@@ -0,0 +1,278 @@
+//! Client-to-server input events, batched per tick.
+//!
+//! An [`InputChannel`] collects the events pushed during a tick into an [`InputBatch`] stamped
+//! with a sequence number and the time it was closed. Every tick it sends one datagram carrying
+//! all batches the server has not acknowledged yet, up to [`InputConfig::window`] of them, so a
+//! lost datagram is covered by the next one without waiting for a retransmission timer.
+//!
+//! Datagrams on the channel are:
+//!
+//! - `0 | batch count varint | batches`, each batch being
+//!   `sequence varint | timestamp ms varint | event count varint | (length varint | event)*`
+//! - `1 | sequence varint` from the server, acknowledging every batch up to `sequence`.
+//!
+//! [`decode_batches`] and [`encode_ack`] implement the server side of the format.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::channel::DatagramChannel;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+use crate::varint;
+
+const KIND_BATCHES: u8 = 0;
+const KIND_ACK: u8 = 1;
+
+/// The events of one tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputBatch {
+    pub sequence: u64,
+    /// When the tick was closed, in milliseconds of the sender's clock.
+    pub timestamp_ms: u64,
+    pub events: Vec<Vec<u8>>,
+}
+
+impl InputBatch {
+    fn encode(&self, out: &mut Vec<u8>) {
+        varint::encode(self.sequence, out);
+        varint::encode(self.timestamp_ms, out);
+        varint::encode(self.events.len() as u64, out);
+        for event in &self.events {
+            varint::encode(event.len() as u64, out);
+            out.extend_from_slice(event);
+        }
+    }
+}
+
+/// Decodes the batches carried by a datagram sent by an [`InputChannel`].
+pub fn decode_batches(data: &[u8]) -> Option<Vec<InputBatch>> {
+    fn next(data: &[u8], at: &mut usize) -> Option<u64> {
+        let (value, len) = varint::decode(data.get(*at..)?)?;
+        *at += len;
+        Some(value)
+    }
+
+    let (&KIND_BATCHES, data) = data.split_first()? else {
+        return None;
+    };
+    let mut at = 0;
+    let count = next(data, &mut at)?;
+    let mut batches = Vec::new();
+    for _ in 0..count {
+        let sequence = next(data, &mut at)?;
+        let timestamp_ms = next(data, &mut at)?;
+        let event_count = next(data, &mut at)?;
+        let mut events = Vec::new();
+        for _ in 0..event_count {
+            let len = usize::try_from(next(data, &mut at)?).ok()?;
+            let end = at.checked_add(len)?;
+            events.push(data.get(at..end)?.to_vec());
+            at = end;
+        }
+        batches.push(InputBatch {
+            sequence,
+            timestamp_ms,
+            events,
+        });
+    }
+    Some(batches)
+}
+
+/// Encodes the acknowledgement of every batch up to `sequence`.
+pub fn encode_ack(sequence: u64) -> Vec<u8> {
+    let mut data = vec![KIND_ACK];
+    varint::encode(sequence, &mut data);
+    data
+}
+
+/// Tuning for an [`InputChannel`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputConfig {
+    /// Time between ticks.
+    pub tick_interval: Duration,
+    /// Unacknowledged batches kept and resent at most; the oldest is dropped beyond that.
+    pub window: usize,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            tick_interval: Duration::from_millis(16),
+            window: 32,
+        }
+    }
+}
+
+/// Counters kept by an [`InputChannel`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputStats {
+    pub events_pushed: u64,
+    pub batches_sent: u64,
+    pub batches_acked: u64,
+    /// Batches pushed out of the window before they were acknowledged.
+    pub batches_dropped: u64,
+    pub datagrams_sent: u64,
+}
+
+struct InputState {
+    config: InputConfig,
+    pending: Vec<Vec<u8>>,
+    next_sequence: u64,
+    unacked: VecDeque<InputBatch>,
+    stats: InputStats,
+}
+
+struct Inner {
+    state: RefCell<InputState>,
+    channel: DatagramChannel,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl Inner {
+    fn tick(&self) {
+        let data = {
+            let now_ms = self.scheduler.now().as_millis() as u64;
+            let mut state = self.state.borrow_mut();
+            if !state.pending.is_empty() {
+                let batch = InputBatch {
+                    sequence: state.next_sequence,
+                    timestamp_ms: now_ms,
+                    events: std::mem::take(&mut state.pending),
+                };
+                state.next_sequence += 1;
+                state.stats.batches_sent += 1;
+                state.unacked.push_back(batch);
+                while state.unacked.len() > state.config.window.max(1) {
+                    state.unacked.pop_front();
+                    state.stats.batches_dropped += 1;
+                }
+            }
+            if state.unacked.is_empty() {
+                return;
+            }
+            state.stats.datagrams_sent += 1;
+            let mut data = vec![KIND_BATCHES];
+            varint::encode(state.unacked.len() as u64, &mut data);
+            for batch in &state.unacked {
+                batch.encode(&mut data);
+            }
+            data
+        };
+        self.channel.send(&data);
+    }
+
+    fn schedule_tick(self: &Rc<Self>) {
+        let interval = self.state.borrow().config.tick_interval;
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            interval,
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.tick();
+                    inner.schedule_tick();
+                }
+            }),
+        );
+    }
+
+    fn receive(&self, data: Vec<u8>) {
+        let Some((&KIND_ACK, rest)) = data.split_first() else {
+            return;
+        };
+        let Some((acked, _)) = varint::decode(rest) else {
+            return;
+        };
+        let mut state = self.state.borrow_mut();
+        while state
+            .unacked
+            .front()
+            .is_some_and(|batch| batch.sequence <= acked)
+        {
+            state.unacked.pop_front();
+            state.stats.batches_acked += 1;
+        }
+    }
+}
+
+/// Batches input events per tick and sends them redundantly until acknowledged.
+pub struct InputChannel {
+    inner: Rc<Inner>,
+}
+
+impl InputChannel {
+    /// Registers datagram channel `id` on `transport` and starts ticking on the platform timers.
+    pub fn new<T: Transport + ?Sized>(transport: &T, id: u64, config: InputConfig) -> Self {
+        Self::with_scheduler(transport, id, config, Rc::new(PlatformScheduler))
+    }
+
+    /// Registers datagram channel `id` on `transport` and starts ticking on `scheduler`.
+    pub fn with_scheduler<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        config: InputConfig,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let receiver = inner.clone();
+            let channel = transport.datagram_channel(
+                id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            Inner {
+                state: RefCell::new(InputState {
+                    config,
+                    pending: Vec::new(),
+                    next_sequence: 0,
+                    unacked: VecDeque::new(),
+                    stats: InputStats::default(),
+                }),
+                channel,
+                scheduler,
+            }
+        });
+        inner.schedule_tick();
+        Self { inner }
+    }
+
+    /// Adds `event` to the current tick's batch.
+    pub fn push(&self, event: Vec<u8>) {
+        let mut state = self.inner.state.borrow_mut();
+        state.pending.push(event);
+        state.stats.events_pushed += 1;
+    }
+
+    /// Closes the current tick now instead of waiting for the timer.
+    pub fn flush(&self) {
+        self.inner.tick();
+    }
+
+    /// Number of batches sent but not acknowledged yet.
+    pub fn unacked(&self) -> usize {
+        self.inner.state.borrow().unacked.len()
+    }
+
+    pub fn stats(&self) -> InputStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl fmt::Debug for InputChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("InputChannel")
+            .field("next_sequence", &state.next_sequence)
+            .field("unacked", &state.unacked.len())
+            .finish()
+    }
+}
@@ -0,0 +1,329 @@
+//! Smoothing the arrival of datagram-delivered media and game packets.
+//!
+//! Datagrams arrive with variable delay, out of order or not at all. A [`JitterBuffer`] holds
+//! each packet until its playout time, `first arrival + (timestamp - first timestamp) + delay`,
+//! and then hands packets to the consumer in sequence order at the pace they were produced.
+//!
+//! In [`JitterMode::Adaptive`] the delay follows the measured interarrival jitter, estimated as
+//! in [RFC 3550](https://www.rfc-editor.org/rfc/rfc3550#appendix-A.8), trading latency for
+//! fewer late packets when the network gets worse. Packets whose turn has already passed are
+//! handled according to the [`LatePolicy`].
+
+use std::{
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap},
+    fmt,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::time::{PlatformScheduler, Scheduler};
+
+/// How the playout delay is chosen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JitterMode {
+    /// Always [`JitterConfig::target_delay`].
+    Fixed,
+    /// Four times the measured jitter, starting from [`JitterConfig::target_delay`] and kept
+    /// within the bounds. Should `min_delay` exceed `max_delay`, `max_delay` wins.
+    Adaptive {
+        min_delay: Duration,
+        max_delay: Duration,
+    },
+}
+
+/// What happens to a packet that arrives after later packets were played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LatePolicy {
+    /// Drop it.
+    Drop,
+    /// Play it immediately, out of order.
+    Deliver,
+}
+
+/// Tuning for a [`JitterBuffer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct JitterConfig {
+    /// Delay between a packet's nominal arrival and its playout.
+    pub target_delay: Duration,
+    pub mode: JitterMode,
+    pub late_policy: LatePolicy,
+    /// Packets held at most; the oldest is dropped beyond that.
+    pub capacity: usize,
+}
+
+impl Default for JitterConfig {
+    fn default() -> Self {
+        Self {
+            target_delay: Duration::from_millis(60),
+            mode: JitterMode::Fixed,
+            late_policy: LatePolicy::Drop,
+            capacity: 256,
+        }
+    }
+}
+
+/// Counters kept by a [`JitterBuffer`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JitterStats {
+    pub packets_played: u64,
+    /// Packets never received, judging from gaps in the sequence numbers played.
+    pub packets_lost: u64,
+    pub packets_late: u64,
+    pub packets_duplicate: u64,
+    pub packets_overflowed: u64,
+    /// Current interarrival jitter estimate.
+    pub jitter: Duration,
+    /// Current playout delay.
+    pub delay: Duration,
+}
+
+struct Packet<T> {
+    timestamp: Duration,
+    value: T,
+}
+
+struct JitterState<T> {
+    config: JitterConfig,
+    packets: BTreeMap<u64, Packet<T>>,
+    /// Local time corresponding to media timestamp zero.
+    base: Option<Duration>,
+    last_played: Option<u64>,
+    /// Arrival time and timestamp of the previous packet, for the jitter estimate.
+    previous: Option<(Duration, Duration)>,
+    jitter: f64,
+    delay: Duration,
+    armed_at: Option<Duration>,
+    stats: JitterStats,
+    on_playout: Callback<T>,
+}
+
+impl<T> JitterState<T> {
+    fn update_jitter(&mut self, arrival: Duration, timestamp: Duration) {
+        if let Some((previous_arrival, previous_timestamp)) = self.previous {
+            let transit = arrival.as_secs_f64() - timestamp.as_secs_f64();
+            let previous_transit =
+                previous_arrival.as_secs_f64() - previous_timestamp.as_secs_f64();
+            let d = (transit - previous_transit).abs();
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.previous = Some((arrival, timestamp));
+        if let JitterMode::Adaptive {
+            min_delay,
+            max_delay,
+        } = self.config.mode
+        {
+            // Not `Duration::clamp`, which panics on bounds set the wrong way round. Wild
+            // timestamps can push the estimate past what a `Duration` holds.
+            self.delay = Duration::try_from_secs_f64(self.jitter * 4.0)
+                .unwrap_or(max_delay)
+                .max(min_delay)
+                .min(max_delay);
+        }
+        self.stats.jitter = Duration::try_from_secs_f64(self.jitter).unwrap_or(Duration::MAX);
+        self.stats.delay = self.delay;
+    }
+
+    fn playout_time(&self, timestamp: Duration) -> Duration {
+        self.base
+            .unwrap_or_default()
+            .saturating_add(timestamp)
+            .saturating_add(self.delay)
+    }
+
+    fn next_playout_time(&self) -> Option<Duration> {
+        let (_, packet) = self.packets.first_key_value()?;
+        Some(self.playout_time(packet.timestamp))
+    }
+
+    fn played(&mut self, sequence: u64) {
+        if let Some(last) = self.last_played {
+            self.stats.packets_lost += sequence.saturating_sub(last + 1);
+        }
+        self.last_played = Some(sequence);
+        self.stats.packets_played += 1;
+    }
+}
+
+struct Inner<T> {
+    state: RefCell<JitterState<T>>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl<T: 'static> Inner<T> {
+    fn push(self: &Rc<Self>, sequence: u64, timestamp: Duration, value: T) {
+        let now = self.scheduler.now();
+        let late = {
+            let mut state = self.state.borrow_mut();
+            state.update_jitter(now, timestamp);
+            if state.base.is_none() {
+                state.base = Some(now.saturating_sub(timestamp));
+            }
+            if state.last_played.is_some_and(|last| sequence <= last) {
+                state.stats.packets_late += 1;
+                match state.config.late_policy {
+                    LatePolicy::Drop => return,
+                    LatePolicy::Deliver => Some((state.on_playout.clone(), value)),
+                }
+            } else {
+                match state.packets.entry(sequence) {
+                    Entry::Occupied(_) => {
+                        state.stats.packets_duplicate += 1;
+                        return;
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(Packet { timestamp, value });
+                    }
+                }
+                while state.packets.len() > state.config.capacity.max(1) {
+                    state.packets.pop_first();
+                    state.stats.packets_overflowed += 1;
+                }
+                None
+            }
+        };
+        if let Some((on_playout, value)) = late {
+            on_playout.emit(value);
+            return;
+        }
+        self.drain();
+    }
+
+    /// Plays every packet that is due, then waits for the next one.
+    fn drain(self: &Rc<Self>) {
+        loop {
+            let now = self.scheduler.now();
+            let due = {
+                let mut state = self.state.borrow_mut();
+                match state.next_playout_time() {
+                    Some(at) if at <= now => {
+                        let (sequence, packet) = state.packets.pop_first().unwrap();
+                        state.played(sequence);
+                        Some((state.on_playout.clone(), packet.value))
+                    }
+                    _ => None,
+                }
+            };
+            match due {
+                Some((on_playout, value)) => on_playout.emit(value),
+                None => break,
+            }
+        }
+        self.arm_timer();
+    }
+
+    fn arm_timer(self: &Rc<Self>) {
+        let now = self.scheduler.now();
+        let at = {
+            let mut state = self.state.borrow_mut();
+            let Some(at) = state.next_playout_time() else {
+                return;
+            };
+            if state.armed_at.is_some_and(|armed| armed <= at) {
+                return;
+            }
+            state.armed_at = Some(at);
+            at
+        };
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            at.saturating_sub(now),
+            Box::new(move || {
+                if let Some(inner) = Weak::upgrade(&inner) {
+                    {
+                        let mut state = inner.state.borrow_mut();
+                        if state.armed_at == Some(at) {
+                            state.armed_at = None;
+                        }
+                    }
+                    inner.drain();
+                }
+            }),
+        );
+    }
+}
+
+/// Holds packets until their playout time and plays them in sequence order.
+pub struct JitterBuffer<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T: 'static> JitterBuffer<T> {
+    /// Creates a buffer playing packets to `on_playout`, timed by the platform timers.
+    pub fn new(config: JitterConfig, on_playout: Callback<T>) -> Self {
+        Self::with_scheduler(config, on_playout, Rc::new(PlatformScheduler))
+    }
+
+    /// Creates a buffer playing packets to `on_playout`, timed by `scheduler`.
+    pub fn with_scheduler(
+        config: JitterConfig,
+        on_playout: Callback<T>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let delay = config.target_delay;
+        // Seed the estimate so that the adaptive delay starts at the target.
+        let jitter = match config.mode {
+            JitterMode::Fixed => 0.0,
+            JitterMode::Adaptive { .. } => delay.as_secs_f64() / 4.0,
+        };
+        Self {
+            inner: Rc::new(Inner {
+                state: RefCell::new(JitterState {
+                    config,
+                    packets: BTreeMap::new(),
+                    base: None,
+                    last_played: None,
+                    previous: None,
+                    jitter,
+                    delay,
+                    armed_at: None,
+                    stats: JitterStats {
+                        jitter: Duration::from_secs_f64(jitter),
+                        delay,
+                        ..JitterStats::default()
+                    },
+                    on_playout,
+                }),
+                scheduler,
+            }),
+        }
+    }
+
+    /// Adds a received packet. `timestamp` is the packet's media time, as set by the sender.
+    pub fn push(&self, sequence: u64, timestamp: Duration, value: T) {
+        self.inner.push(sequence, timestamp, value);
+    }
+
+    /// Drops every buffered packet and starts over, e.g. when the sender restarts.
+    pub fn reset(&self) {
+        let mut state = self.inner.state.borrow_mut();
+        state.packets.clear();
+        state.base = None;
+        state.last_played = None;
+        state.previous = None;
+    }
+
+    /// Number of packets waiting for their playout time.
+    pub fn len(&self) -> usize {
+        self.inner.state.borrow().packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl<T> fmt::Debug for JitterBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("JitterBuffer")
+            .field("config", &state.config)
+            .field("buffered", &state.packets.len())
+            .finish()
+    }
+}
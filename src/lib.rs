@@ -1 +1,67 @@
+#[cfg(feature = "datagrams")]
+pub mod arq;
+pub mod auth;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+pub mod bench;
+pub mod bitrate;
+pub mod capture;
+pub mod certhash;
+pub mod channel;
+#[cfg(feature = "encryption")]
+pub mod crypto;
+pub mod dispatch;
+pub mod error;
+pub mod framing;
+#[cfg(all(feature = "streams", feature = "formats"))]
+pub mod graphql;
+pub mod handler;
+pub mod hooks;
+#[cfg(feature = "datagrams")]
+pub mod input;
+pub mod jitter;
+pub mod limit;
+pub mod logging;
+pub mod loopback;
+#[cfg(all(feature = "datagrams", feature = "streams"))]
+pub mod media;
+pub mod mock;
+#[cfg(feature = "streams")]
+pub mod mux;
+#[cfg(feature = "datagrams")]
+pub mod nack;
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub mod native;
+#[cfg(feature = "streams")]
+pub mod options;
+#[cfg(feature = "datagrams")]
+pub mod pacer;
+#[cfg(feature = "datagrams")]
+pub mod pool;
+#[cfg(feature = "streams")]
+pub mod pubsub;
+pub mod reconnect;
+#[cfg(feature = "streams")]
+pub mod reliable;
+#[cfg(feature = "streams")]
+pub mod rpc;
+pub mod scope;
+#[cfg(feature = "datagrams")]
+pub mod sequenced;
+pub mod sessions;
+pub mod sim;
+#[cfg(feature = "datagrams")]
+pub mod snapshot;
+pub mod throttle;
+pub mod time;
+#[cfg(feature = "datagrams")]
+pub mod timesync;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod transport;
+#[cfg(feature = "streams")]
+pub mod upload;
+pub mod varint;
 pub mod webtransport;
+pub mod worker;
+
+pub use error::Error;
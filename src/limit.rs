@@ -0,0 +1,246 @@
+//! Capping the received data an application has yet to process.
+//!
+//! Incoming datagrams and stream chunks are handed to callbacks as soon as they are read, so an
+//! application that can't keep up, say a component whose messages queue up behind a slow render,
+//! accumulates them until it runs out of memory. A [`ReceiveLimit`] counts the bytes delivered
+//! to the application that it has not [released](ReceiveLimit::release) yet, and once they
+//! reach `max_bytes`, applies an [`OverflowPolicy`].
+//!
+//! Set one on a connection with
+//! [`WebTransportBuilder::receive_limit`](crate::webtransport::WebTransportBuilder::receive_limit).
+//!
+//! A [`ReadGate`] stops a receive loop outright instead, say while the component showing the
+//! data is hidden, and lets it go on where it left off.
+
+use futures::channel::oneshot;
+#[cfg(any(feature = "datagrams", feature = "streams", feature = "native"))]
+use std::future::Future;
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// What happens to data received while a [`ReceiveLimit`] is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Datagrams are dropped. Streams can't lose data, so their reads are paused as with
+    /// [`OverflowPolicy::Backpressure`].
+    #[default]
+    DropDatagrams,
+    /// Reads are paused until enough is released. Paused stream reads make the server wait
+    /// through flow control; datagrams left unread are dropped by the browser once its queue
+    /// is full.
+    Backpressure,
+    /// The datagram loop or stream hitting the limit stops, and the failure is reported as
+    /// [`WebTransportStatus::StreamError`](crate::webtransport::WebTransportStatus::StreamError).
+    Error,
+}
+
+/// Counters kept by a [`ReceiveLimit`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LimitStats {
+    /// Datagrams dropped because the limit was full.
+    pub dropped_datagrams: u64,
+    /// Bytes of the datagrams dropped.
+    pub dropped_bytes: u64,
+    /// Times reading paused until data was released.
+    pub pauses: u64,
+    /// Largest number of bytes held at once.
+    pub peak: usize,
+}
+
+struct LimitState {
+    max_bytes: usize,
+    policy: OverflowPolicy,
+    buffered: usize,
+    waiting: Vec<oneshot::Sender<()>>,
+    stats: LimitStats,
+}
+
+/// A cap on the bytes held by the application, shared by the readers of a connection.
+///
+/// Cloning a `ReceiveLimit` yields another handle to the same counter.
+#[derive(Clone)]
+pub struct ReceiveLimit {
+    state: Rc<RefCell<LimitState>>,
+}
+
+impl ReceiveLimit {
+    pub fn new(max_bytes: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(LimitState {
+                max_bytes,
+                policy,
+                buffered: 0,
+                waiting: Vec::new(),
+                stats: LimitStats::default(),
+            })),
+        }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.state.borrow().max_bytes
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.state.borrow().policy
+    }
+
+    /// Bytes delivered and not released yet.
+    pub fn buffered(&self) -> usize {
+        self.state.borrow().buffered
+    }
+
+    pub fn is_full(&self) -> bool {
+        let state = self.state.borrow();
+        state.buffered >= state.max_bytes
+    }
+
+    /// Tells the limit that the application is done with `bytes` bytes of what it received,
+    /// resuming paused reads once there is room again.
+    pub fn release(&self, bytes: usize) {
+        let waiting = {
+            let mut state = self.state.borrow_mut();
+            state.buffered = state.buffered.saturating_sub(bytes);
+            if state.buffered >= state.max_bytes {
+                return;
+            }
+            std::mem::take(&mut state.waiting)
+        };
+        for waiter in waiting {
+            let _ = waiter.send(());
+        }
+    }
+
+    pub fn stats(&self) -> LimitStats {
+        self.state.borrow().stats.clone()
+    }
+
+    /// Counts a datagram of `len` bytes about to be delivered. Returns `false`, and counts the
+    /// datagram as dropped, if it doesn't fit; a datagram always fits an empty limit.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    pub(crate) fn admit(&self, len: usize) -> bool {
+        let mut state = self.state.borrow_mut();
+        if state.buffered > 0 && state.buffered + len > state.max_bytes {
+            state.stats.dropped_datagrams += 1;
+            state.stats.dropped_bytes += len as u64;
+            return false;
+        }
+        state.buffered += len;
+        state.stats.peak = state.stats.peak.max(state.buffered);
+        true
+    }
+
+    /// Counts `len` bytes of a stream about to be delivered. Streams can't drop data, so the
+    /// bytes are counted even if they overflow the limit, except with [`OverflowPolicy::Error`],
+    /// where `false` is returned instead.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    pub(crate) fn charge(&self, len: usize) -> bool {
+        if self.policy() == OverflowPolicy::Error {
+            return self.admit(len);
+        }
+        let mut state = self.state.borrow_mut();
+        state.buffered += len;
+        state.stats.peak = state.stats.peak.max(state.buffered);
+        true
+    }
+
+    /// Resolves once the limit has room, right away if it has.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    pub(crate) fn room(&self) -> impl Future<Output = ()> + 'static {
+        let wait = {
+            let mut state = self.state.borrow_mut();
+            if state.buffered < state.max_bytes {
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                state.waiting.push(sender);
+                state.stats.pauses += 1;
+                Some(receiver)
+            }
+        };
+        async move {
+            if let Some(wait) = wait {
+                let _ = wait.await;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ReceiveLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("ReceiveLimit")
+            .field("max_bytes", &state.max_bytes)
+            .field("policy", &state.policy)
+            .field("buffered", &state.buffered)
+            .field("stats", &state.stats)
+            .finish()
+    }
+}
+
+/// Pauses and resumes a receive loop. While paused, the loop stops reading, after the read in
+/// progress, so the browser's backpressure applies upstream: the server waits through flow
+/// control on a stream, and datagrams left unread are dropped by the browser once its queue is
+/// full.
+///
+/// Cloning a `ReadGate` yields another handle to the same switch.
+#[derive(Clone, Default)]
+pub struct ReadGate {
+    state: Rc<RefCell<GateState>>,
+}
+
+#[derive(Default)]
+struct GateState {
+    paused: bool,
+    waiting: Vec<oneshot::Sender<()>>,
+}
+
+impl ReadGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.state.borrow_mut().paused = true;
+    }
+
+    /// Lets the loop read again. Resuming a gate that isn't paused does nothing.
+    pub fn resume(&self) {
+        let waiting = {
+            let mut state = self.state.borrow_mut();
+            state.paused = false;
+            std::mem::take(&mut state.waiting)
+        };
+        for waiter in waiting {
+            let _ = waiter.send(());
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    /// Resolves once the gate is open, right away if it is.
+    #[cfg(any(feature = "datagrams", feature = "streams", feature = "native"))]
+    pub(crate) fn opened(&self) -> impl Future<Output = ()> + 'static {
+        let wait = {
+            let mut state = self.state.borrow_mut();
+            state.paused.then(|| {
+                let (sender, receiver) = oneshot::channel();
+                state.waiting.push(sender);
+                receiver
+            })
+        };
+        async move {
+            if let Some(wait) = wait {
+                let _ = wait.await;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ReadGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadGate")
+            .field("paused", &self.is_paused())
+            .finish()
+    }
+}
@@ -0,0 +1,19 @@
+//! Targets of the crate's log records.
+//!
+//! Everything the crate logs goes through the [`log`](https://docs.rs/log) facade, so nothing
+//! is printed until the application installs a logger, such as `console_log` or
+//! `wasm-logger`. Records are filed under these targets, letting a logger keep session events
+//! while silencing the per-datagram and per-chunk records:
+//!
+//! | Target | Levels |
+//! |--------|--------|
+//! | [`SESSION`] | `info` when a session is dialed, opens or closes, `warn` when it fails |
+//! | [`DATAGRAMS`] | `trace` for every datagram, `warn` and `error` when reading or sending fails |
+//! | [`STREAMS`] | `debug` for every stream, `trace` for every chunk, `warn` and `error` when a stream fails |
+
+/// Session lifecycle: dialing, opening, closing and failing.
+pub const SESSION: &str = "yew_webtransport::session";
+/// Datagrams sent and received.
+pub const DATAGRAMS: &str = "yew_webtransport::datagrams";
+/// Streams opened, accepted and read.
+pub const STREAMS: &str = "yew_webtransport::streams";
@@ -0,0 +1,227 @@
+//! A session that echoes everything back locally, for trying the API, and running the demo,
+//! without a WebTransport server.
+//!
+//! A [`LoopbackTransport`] behaves like a session connected to the echo server in
+//! `examples/echo-server`: datagrams come back as datagrams, bidirectional streams are echoed on
+//! the same stream, and unidirectional streams come back whole to the `on_unidirectional_stream`
+//! callback, since there is no browser stream to hand over. Echoes are delivered from the
+//! scheduler rather than from within the send, as they would be over a network.
+
+#[cfg(feature = "streams")]
+use futures::channel::oneshot;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+    time::Duration,
+};
+use yew::callback::Callback;
+
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramRouter, DatagramSubscription};
+use crate::time::{PlatformScheduler, Scheduler};
+#[cfg(feature = "streams")]
+use crate::transport::StreamWriter;
+use crate::transport::{Transport, TransportStats};
+use crate::webtransport::{CloseInfo, WebTransportError, WebTransportStatus};
+
+struct Inner {
+    scheduler: Rc<dyn Scheduler>,
+    #[cfg_attr(not(feature = "datagrams"), allow(dead_code))]
+    on_datagram: Callback<Vec<u8>>,
+    #[cfg_attr(not(feature = "streams"), allow(dead_code))]
+    on_unidirectional_stream: Callback<Vec<u8>>,
+    notification: Callback<WebTransportStatus>,
+    #[cfg(feature = "datagrams")]
+    datagram_router: DatagramRouter,
+    closed: Cell<bool>,
+    stats: RefCell<TransportStats>,
+}
+
+impl Inner {
+    fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// Runs `deliver` with the echo of `data` once the scheduler gets to it, unless the session
+    /// is closed by then.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    fn echo(self: &Rc<Self>, data: Vec<u8>, deliver: impl FnOnce(Vec<u8>) + 'static) {
+        if self.is_closed() {
+            return;
+        }
+        {
+            let mut stats = self.stats.borrow_mut();
+            stats.bytes_sent += data.len() as u64;
+            stats.packets_sent += 1;
+        }
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            Duration::ZERO,
+            Box::new(move || {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                if inner.is_closed() {
+                    return;
+                }
+                inner.stats.borrow_mut().bytes_received += data.len() as u64;
+                deliver(data);
+            }),
+        );
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn echo_datagram(self: &Rc<Self>, data: Vec<u8>) {
+        let on_datagram = self.datagram_router.route(self.on_datagram.clone());
+        self.echo(data, move |data| on_datagram.emit(data));
+    }
+}
+
+/// A session echoing everything it is sent. See the [module documentation](self).
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    inner: Rc<Inner>,
+}
+
+impl LoopbackTransport {
+    /// Opens a loopback session timed by the platform timers. `notification` is passed
+    /// [`WebTransportStatus::Opened`] right after, like a session reaching a server instantly.
+    pub fn connect(
+        on_datagram: Callback<Vec<u8>>,
+        on_unidirectional_stream: Callback<Vec<u8>>,
+        notification: Callback<WebTransportStatus>,
+    ) -> Self {
+        Self::with_scheduler(
+            on_datagram,
+            on_unidirectional_stream,
+            notification,
+            Rc::new(PlatformScheduler),
+        )
+    }
+
+    /// Like [`LoopbackTransport::connect`], with echoes and notifications delivered by
+    /// `scheduler`.
+    pub fn with_scheduler(
+        on_datagram: Callback<Vec<u8>>,
+        on_unidirectional_stream: Callback<Vec<u8>>,
+        notification: Callback<WebTransportStatus>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new(Inner {
+            scheduler,
+            on_datagram,
+            on_unidirectional_stream,
+            notification,
+            #[cfg(feature = "datagrams")]
+            datagram_router: DatagramRouter::new(),
+            closed: Cell::new(false),
+            stats: RefCell::default(),
+        });
+        let opened = Rc::downgrade(&inner);
+        inner.scheduler.schedule(
+            Duration::ZERO,
+            Box::new(move || {
+                if let Some(inner) = opened.upgrade() {
+                    if !inner.is_closed() {
+                        inner.notification.emit(WebTransportStatus::Opened);
+                    }
+                }
+            }),
+        );
+        Self { inner }
+    }
+
+    /// Whether the session was closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl fmt::Debug for LoopbackTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoopbackTransport")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+impl Transport for LoopbackTransport {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        self.inner.echo_datagram(data);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        let on_unidirectional_stream = self.inner.on_unidirectional_stream.clone();
+        self.inner
+            .echo(data, move |data| on_unidirectional_stream.emit(data));
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        self.inner.echo(data, move |data| callback.emit(data));
+    }
+
+    /// Resolves with `data` itself, or fails with [`WebTransportError::AlreadyClosed`] if the
+    /// session is closed before the echo.
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        let (response, on_response) = oneshot::channel();
+        self.inner.echo(data, move |data| {
+            let _ = response.send(data);
+        });
+        on_response
+            .map(|response| response.map_err(|_| WebTransportError::AlreadyClosed))
+            .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        let inner = self.inner.clone();
+        StreamWriter::new(
+            move |data| {
+                let on_data = on_data.clone();
+                inner.echo(data, move |data| on_data.emit(data));
+            },
+            || {},
+        )
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let inner = self.inner.clone();
+        let send = Rc::new(move |data| inner.echo_datagram(data));
+        self.inner.datagram_router.channel(id, on_message, send)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.inner.datagram_router.subscribe(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        future::ready(Ok(self.inner.stats.borrow().clone())).boxed_local()
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        if self.inner.closed.replace(true) {
+            return Err(WebTransportError::AlreadyClosed);
+        }
+        let notification = self.inner.notification.clone();
+        self.inner.scheduler.schedule(
+            Duration::ZERO,
+            Box::new(move || notification.emit(WebTransportStatus::Closed(CloseInfo::default()))),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+//! Prioritized sending of media frames.
+//!
+//! A [`FrameScheduler`] queues outgoing frames by [`FrameClass`] and sends them within a
+//! bandwidth budget, strictly highest class first, so audio keeps flowing while video and bulk
+//! data wait when bandwidth dips. Every frame carries a deadline; frames still queued past it
+//! are dropped rather than sent late.
+//!
+//! Small audio frames go out as datagrams. Everything else, and audio frames too large for a
+//! datagram, goes on its own unidirectional stream whose send order follows the class, so the
+//! session's congestion controller keeps honouring the priorities after frames left the queue.
+
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc, time::Duration};
+
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+
+/// The kind of a frame, from most to least urgent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameClass {
+    Audio,
+    /// A video frame that can be decoded on its own.
+    VideoKey,
+    /// A video frame that depends on earlier ones.
+    VideoDelta,
+    Data,
+}
+
+impl FrameClass {
+    /// Every class, from most to least urgent.
+    pub const ALL: [FrameClass; 4] = [
+        FrameClass::Audio,
+        FrameClass::VideoKey,
+        FrameClass::VideoDelta,
+        FrameClass::Data,
+    ];
+
+    /// The stream send order used for frames of this class; higher is sent first.
+    pub fn send_order(self) -> i64 {
+        match self {
+            FrameClass::Audio => 3,
+            FrameClass::VideoKey => 2,
+            FrameClass::VideoDelta => 1,
+            FrameClass::Data => 0,
+        }
+    }
+
+    /// Whether frames of this class are sent as datagrams when they fit in one.
+    pub fn prefers_datagram(self) -> bool {
+        matches!(self, FrameClass::Audio)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            FrameClass::Audio => 0,
+            FrameClass::VideoKey => 1,
+            FrameClass::VideoDelta => 2,
+            FrameClass::Data => 3,
+        }
+    }
+}
+
+/// Tuning for a [`FrameScheduler`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSchedulerConfig {
+    /// Available bandwidth in bytes per second, or `None` to send frames as soon as they are
+    /// queued.
+    pub bandwidth: Option<u64>,
+    /// Bytes that can be sent in a burst after the scheduler was idle.
+    pub burst: usize,
+    /// Largest frame sent as a datagram.
+    pub max_datagram_size: usize,
+}
+
+impl Default for FrameSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            bandwidth: None,
+            burst: 64 * 1024,
+            max_datagram_size: 1200,
+        }
+    }
+}
+
+/// Counters for one [`FrameClass`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassStats {
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub frames_expired: u64,
+}
+
+/// Counters kept by a [`FrameScheduler`], per class.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameSchedulerStats {
+    classes: [ClassStats; 4],
+}
+
+impl FrameSchedulerStats {
+    pub fn class(&self, class: FrameClass) -> &ClassStats {
+        &self.classes[class.index()]
+    }
+}
+
+struct QueuedFrame {
+    data: Vec<u8>,
+    deadline: Duration,
+}
+
+struct SchedulerState {
+    config: FrameSchedulerConfig,
+    queues: [VecDeque<QueuedFrame>; 4],
+    tokens: f64,
+    last_refill: Duration,
+    timer_armed: bool,
+    stats: FrameSchedulerStats,
+}
+
+impl SchedulerState {
+    fn refill(&mut self, now: Duration) {
+        if let Some(bandwidth) = self.config.bandwidth {
+            let elapsed = now.saturating_sub(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * bandwidth as f64).min(self.config.burst as f64);
+        }
+        self.last_refill = now;
+    }
+
+    fn drop_expired(&mut self, now: Duration) {
+        for class in FrameClass::ALL {
+            let queue = &mut self.queues[class.index()];
+            let before = queue.len();
+            queue.retain(|frame| frame.deadline >= now);
+            let expired = (before - queue.len()) as u64;
+            self.stats.classes[class.index()].frames_expired += expired;
+        }
+    }
+
+    /// Takes the next frame to send, or how long to wait for the budget to allow it.
+    fn next_frame(&mut self) -> Result<(FrameClass, Vec<u8>), Option<Duration>> {
+        let class = FrameClass::ALL
+            .into_iter()
+            .find(|class| !self.queues[class.index()].is_empty())
+            .ok_or(None)?;
+        let queue = &mut self.queues[class.index()];
+        let len = queue.front().map_or(0, |frame| frame.data.len()) as f64;
+        if let Some(bandwidth) = self.config.bandwidth {
+            // A frame larger than the burst is let through once the bucket is full.
+            let needed = len.min(self.config.burst as f64);
+            if self.tokens < needed {
+                let wait = (needed - self.tokens) / bandwidth.max(1) as f64;
+                return Err(Some(
+                    Duration::from_secs_f64(wait).max(Duration::from_millis(1)),
+                ));
+            }
+            self.tokens -= len;
+        }
+        let frame = queue.pop_front().ok_or(None)?;
+        let stats = &mut self.stats.classes[class.index()];
+        stats.frames_sent += 1;
+        stats.bytes_sent += frame.data.len() as u64;
+        Ok((class, frame.data))
+    }
+}
+
+struct Inner<T> {
+    transport: Rc<T>,
+    scheduler: Rc<dyn Scheduler>,
+    state: RefCell<SchedulerState>,
+}
+
+impl<T: Transport + 'static> Inner<T> {
+    fn pump(self: &Rc<Self>) {
+        loop {
+            let next = {
+                let now = self.scheduler.now();
+                let mut state = self.state.borrow_mut();
+                state.refill(now);
+                state.drop_expired(now);
+                state.next_frame()
+            };
+            match next {
+                Ok((class, data)) => self.send(class, data),
+                Err(Some(wait)) => {
+                    self.arm_timer(wait);
+                    return;
+                }
+                Err(None) => return,
+            }
+        }
+    }
+
+    fn send(&self, class: FrameClass, data: Vec<u8>) {
+        let max_datagram_size = self.state.borrow().config.max_datagram_size;
+        if class.prefers_datagram() && data.len() <= max_datagram_size {
+            self.transport.send_datagram(data);
+        } else {
+            self.transport
+                .send_unidirectional_stream_with_order(data, class.send_order());
+        }
+    }
+
+    fn arm_timer(self: &Rc<Self>, wait: Duration) {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.timer_armed {
+                return;
+            }
+            state.timer_armed = true;
+        }
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            wait,
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.state.borrow_mut().timer_armed = false;
+                    inner.pump();
+                }
+            }),
+        );
+    }
+}
+
+/// Sends frames in priority order within a bandwidth budget, dropping those past their deadline.
+pub struct FrameScheduler<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T: Transport + 'static> FrameScheduler<T> {
+    /// Creates a scheduler sending on `transport`, timed by the platform timers.
+    pub fn new(transport: Rc<T>, config: FrameSchedulerConfig) -> Self {
+        Self::with_scheduler(transport, config, Rc::new(PlatformScheduler))
+    }
+
+    /// Creates a scheduler sending on `transport`, timed by `scheduler`.
+    pub fn with_scheduler(
+        transport: Rc<T>,
+        config: FrameSchedulerConfig,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let now = scheduler.now();
+        let tokens = config.burst as f64;
+        Self {
+            inner: Rc::new(Inner {
+                transport,
+                scheduler,
+                state: RefCell::new(SchedulerState {
+                    config,
+                    queues: Default::default(),
+                    tokens,
+                    last_refill: now,
+                    timer_armed: false,
+                    stats: FrameSchedulerStats::default(),
+                }),
+            }),
+        }
+    }
+
+    /// Queues `data`, to be dropped if it could not be sent within `deadline` from now.
+    pub fn enqueue(&self, class: FrameClass, data: Vec<u8>, deadline: Duration) {
+        {
+            let deadline = self.inner.scheduler.now() + deadline;
+            let mut state = self.inner.state.borrow_mut();
+            state.queues[class.index()].push_back(QueuedFrame { data, deadline });
+        }
+        self.inner.pump();
+    }
+
+    /// Updates the bandwidth budget, e.g. from a congestion estimate.
+    pub fn set_bandwidth(&self, bandwidth: Option<u64>) {
+        {
+            let now = self.inner.scheduler.now();
+            let mut state = self.inner.state.borrow_mut();
+            state.refill(now);
+            state.config.bandwidth = bandwidth;
+        }
+        self.inner.pump();
+    }
+
+    /// Number of frames of `class` waiting to be sent.
+    pub fn queued(&self, class: FrameClass) -> usize {
+        self.inner.state.borrow().queues[class.index()].len()
+    }
+
+    pub fn stats(&self) -> FrameSchedulerStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl<T> fmt::Debug for FrameScheduler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("FrameScheduler")
+            .field("config", &state.config)
+            .field("stats", &state.stats)
+            .finish()
+    }
+}
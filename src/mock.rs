@@ -0,0 +1,375 @@
+//! An in-memory stand-in for [`WebTransportService`](crate::webtransport::WebTransportService)
+//! that lets tests drive a component without a WebTransport server.
+//!
+//! A [`MockWebTransport`] plays the part of the server: tests use it to inject incoming
+//! datagrams and streams, fire status notifications and inspect everything the component sent
+//! through the [`MockWebTransportTask`] returned by [`MockWebTransport::connect`].
+
+use futures::channel::oneshot;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc};
+use web_sys::{WebTransportBidirectionalStream, WebTransportReceiveStream};
+use yew::callback::Callback;
+
+use crate::channel::DatagramRouter;
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramSubscription};
+use crate::handler::Handler;
+#[cfg(feature = "streams")]
+use crate::transport::StreamWriter;
+use crate::transport::{Transport, TransportStats};
+use crate::webtransport::{CloseInfo, SessionError, WebTransportError, WebTransportStatus};
+
+/// A message sent by the application through a [`MockWebTransportTask`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SentMessage {
+    /// Sent with [`Transport::send_datagram`].
+    Datagram(Vec<u8>),
+    /// Sent with [`Transport::send_unidirectional_stream`] or
+    /// [`Transport::send_unidirectional_stream_with_order`].
+    UnidirectionalStream(Vec<u8>),
+    /// Sent with [`Transport::send_bidirectional_stream`] or [`Transport::request`], or
+    /// written on a stream opened with [`Transport::open_bidirectional_stream`], one message
+    /// per write.
+    BidirectionalStream(Vec<u8>),
+}
+
+struct Handlers {
+    on_datagram: Callback<Vec<u8>>,
+    on_unidirectional_stream: Callback<WebTransportReceiveStream>,
+    on_bidirectional_stream: Callback<WebTransportBidirectionalStream>,
+    notification: Callback<WebTransportStatus>,
+}
+
+struct PendingStream {
+    callback: Callback<Vec<u8>>,
+    finished: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    url: Option<String>,
+    handlers: Option<Handlers>,
+    connect_error: Option<String>,
+    sent: Vec<SentMessage>,
+    pending_bidirectional: VecDeque<PendingStream>,
+    closed: bool,
+    datagram_router: DatagramRouter,
+    stats: TransportStats,
+}
+
+/// The server side of a mocked WebTransport session.
+///
+/// Cloning a `MockWebTransport` yields another handle to the same session.
+#[derive(Clone, Default)]
+pub struct MockWebTransport {
+    state: Rc<RefCell<MockState>>,
+}
+
+impl MockWebTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mirrors [`WebTransportService::connect`](crate::webtransport::WebTransportService::connect).
+    ///
+    /// The session stays pending until [`MockWebTransport::open`] is called.
+    pub fn connect(
+        &self,
+        url: &str,
+        on_datagram: impl Handler<Vec<u8>>,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+        notification: impl Handler<WebTransportStatus>,
+    ) -> Result<MockWebTransportTask, WebTransportError> {
+        let mut state = self.state.borrow_mut();
+        if let Some(error) = state.connect_error.take() {
+            return Err(WebTransportError::CreationError(error));
+        }
+        state.url = Some(url.to_string());
+        state.handlers = Some(Handlers {
+            on_datagram: on_datagram.into_callback(),
+            on_unidirectional_stream: on_unidirectional_stream.into_callback(),
+            on_bidirectional_stream: on_bidirectional_stream.into_callback(),
+            notification: notification.into_callback(),
+        });
+        state.sent.clear();
+        state.pending_bidirectional.clear();
+        state.closed = false;
+        Ok(MockWebTransportTask { mock: self.clone() })
+    }
+
+    /// Makes the next call to [`MockWebTransport::connect`] fail with `error`.
+    pub fn fail_next_connect(&self, error: &str) {
+        self.state.borrow_mut().connect_error = Some(error.to_string());
+    }
+
+    /// The url passed to the last successful [`MockWebTransport::connect`].
+    pub fn url(&self) -> Option<String> {
+        self.state.borrow().url.clone()
+    }
+
+    /// Completes the handshake, notifying [`WebTransportStatus::Opened`].
+    pub fn open(&self) {
+        self.notify(WebTransportStatus::Opened);
+    }
+
+    /// Closes the session from the server side, notifying [`WebTransportStatus::Closed`].
+    pub fn close_with(&self, info: CloseInfo) {
+        self.shut_down();
+        self.notify(WebTransportStatus::Closed(info));
+    }
+
+    /// Fails the session, notifying [`WebTransportStatus::Error`].
+    pub fn fail(&self, error: SessionError) {
+        self.shut_down();
+        self.notify(WebTransportStatus::Error(error));
+    }
+
+    /// Sets the counters reported by [`Transport::stats`].
+    pub fn set_stats(&self, stats: TransportStats) {
+        self.state.borrow_mut().stats = stats;
+    }
+
+    /// Delivers an incoming datagram to the application, routing it to a
+    /// [`DatagramChannel`] if it is addressed to one.
+    pub fn receive_datagram(&self, data: Vec<u8>) {
+        let router = self.state.borrow().datagram_router.clone();
+        let callback = self.handler(|handlers| handlers.on_datagram.clone());
+        router
+            .route(callback.unwrap_or_else(Callback::noop))
+            .emit(data);
+    }
+
+    /// Delivers an incoming unidirectional stream to the application.
+    pub fn receive_unidirectional_stream(&self, stream: WebTransportReceiveStream) {
+        let callback = self.handler(|handlers| handlers.on_unidirectional_stream.clone());
+        if let Some(callback) = callback {
+            callback.emit(stream);
+        }
+    }
+
+    /// Delivers an incoming bidirectional stream to the application.
+    pub fn receive_bidirectional_stream(&self, stream: WebTransportBidirectionalStream) {
+        let callback = self.handler(|handlers| handlers.on_bidirectional_stream.clone());
+        if let Some(callback) = callback {
+            callback.emit(stream);
+        }
+    }
+
+    /// Writes `data` back on the oldest bidirectional stream opened by the application
+    /// that has not been finished yet. Returns `false` if there is no such stream.
+    pub fn respond_bidirectional(&self, data: Vec<u8>) -> bool {
+        let callback = self
+            .state
+            .borrow()
+            .pending_bidirectional
+            .front()
+            .map(|stream| stream.callback.clone());
+        match callback {
+            Some(callback) => {
+                callback.emit(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finishes the oldest bidirectional stream opened by the application, resolving the
+    /// matching [`Transport::request`] if there is one.
+    pub fn finish_bidirectional(&self) -> bool {
+        let stream = self.state.borrow_mut().pending_bidirectional.pop_front();
+        match stream {
+            Some(stream) => {
+                if let Some(finished) = stream.finished {
+                    let _ = finished.send(());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Everything the application has sent so far, in order.
+    pub fn sent(&self) -> Vec<SentMessage> {
+        self.state.borrow().sent.clone()
+    }
+
+    /// Like [`MockWebTransport::sent`] but clears the record.
+    pub fn take_sent(&self) -> Vec<SentMessage> {
+        std::mem::take(&mut self.state.borrow_mut().sent)
+    }
+
+    /// Whether the session was closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.state.borrow().closed
+    }
+
+    /// Marks the session closed and resets every open stream.
+    fn shut_down(&self) {
+        let mut state = self.state.borrow_mut();
+        state.closed = true;
+        state.pending_bidirectional.clear();
+    }
+
+    fn handler<T>(&self, select: impl FnOnce(&Handlers) -> T) -> Option<T> {
+        let state = self.state.borrow();
+        if state.closed {
+            return None;
+        }
+        state.handlers.as_ref().map(select)
+    }
+
+    fn notify(&self, status: WebTransportStatus) {
+        let notification = self
+            .state
+            .borrow()
+            .handlers
+            .as_ref()
+            .map(|handlers| handlers.notification.clone());
+        if let Some(notification) = notification {
+            notification.emit(status);
+        }
+    }
+
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    fn record(&self, message: SentMessage) {
+        let mut state = self.state.borrow_mut();
+        if !state.closed {
+            state.sent.push(message);
+        }
+    }
+}
+
+impl fmt::Debug for MockWebTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MockWebTransport")
+    }
+}
+
+/// The application side of a mocked WebTransport session.
+pub struct MockWebTransportTask {
+    mock: MockWebTransport,
+}
+
+impl MockWebTransportTask {
+    /// The mock server this task is connected to.
+    pub fn mock(&self) -> &MockWebTransport {
+        &self.mock
+    }
+}
+
+impl fmt::Debug for MockWebTransportTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MockWebTransportTask")
+    }
+}
+
+impl Transport for MockWebTransportTask {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        self.mock.record(SentMessage::Datagram(data));
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        self.mock.record(SentMessage::UnidirectionalStream(data));
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        if self.mock.is_closed() {
+            return;
+        }
+        self.mock.record(SentMessage::BidirectionalStream(data));
+        self.mock
+            .state
+            .borrow_mut()
+            .pending_bidirectional
+            .push_back(PendingStream {
+                callback,
+                finished: None,
+            });
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        let response = Rc::new(RefCell::new(Vec::new()));
+        let (finished, on_finished) = oneshot::channel();
+        if !self.mock.is_closed() {
+            self.mock.record(SentMessage::BidirectionalStream(data));
+            let callback = {
+                let response = response.clone();
+                Callback::from(move |chunk: Vec<u8>| response.borrow_mut().extend(chunk))
+            };
+            self.mock
+                .state
+                .borrow_mut()
+                .pending_bidirectional
+                .push_back(PendingStream {
+                    callback,
+                    finished: Some(finished),
+                });
+        }
+        async move {
+            on_finished
+                .await
+                .map_err(|_| WebTransportError::StreamError("stream was reset".to_string()))?;
+            let response = response.take();
+            Ok(response)
+        }
+        .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        if !self.mock.is_closed() {
+            self.mock
+                .state
+                .borrow_mut()
+                .pending_bidirectional
+                .push_back(PendingStream {
+                    callback: on_data,
+                    finished: None,
+                });
+        }
+        let mock = self.mock.clone();
+        StreamWriter::new(
+            move |data| mock.record(SentMessage::BidirectionalStream(data)),
+            || {},
+        )
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let mock = self.mock.clone();
+        let send = Rc::new(move |data| mock.record(SentMessage::Datagram(data)));
+        let router = self.mock.state.borrow().datagram_router.clone();
+        router.channel(id, on_message, send)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        let router = self.mock.state.borrow().datagram_router.clone();
+        router.subscribe(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        future::ready(Ok(self.mock.state.borrow().stats.clone())).boxed_local()
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        if self.mock.is_closed() {
+            return Err(WebTransportError::AlreadyClosed);
+        }
+        self.mock.close_with(CloseInfo::default());
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Many logical channels over a single long-lived bidirectional stream.
+//!
+//! Opening a stream costs a round trip and servers may cap how many can be open at once, which
+//! hurts bursts of small messages. A [`StreamMux`] opens one stream up front and carries every
+//! [`MuxChannel`] on it as frames of `channel id varint | length varint | payload`. Frames keep
+//! their order within the stream, so a channel's messages arrive in the order they were sent.
+//!
+//! With the `encryption` feature, a channel can be given a
+//! [`ChannelCipher`](crate::crypto::ChannelCipher) of its own with [`MuxChannel::encrypt`],
+//! sealing the payload of every frame it sends and opening those it receives.
+
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+use yew::callback::Callback;
+
+#[cfg(feature = "encryption")]
+use crate::crypto::ChannelCipher;
+#[cfg(feature = "encryption")]
+use crate::logging::STREAMS;
+use crate::transport::{StreamWriter, Transport};
+use crate::varint;
+
+/// Appends a frame carrying `payload` for channel `id` to `out`.
+pub fn encode_frame(id: u64, payload: &[u8], out: &mut Vec<u8>) {
+    varint::encode(id, out);
+    varint::encode(payload.len() as u64, out);
+    out.extend_from_slice(payload);
+}
+
+/// Reassembles frames from the chunks of a stream, which may split or merge them arbitrarily.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a chunk read from the stream.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Takes the next complete frame, as a channel id and its payload.
+    pub fn next_frame(&mut self) -> Option<(u64, Vec<u8>)> {
+        let (id, id_len) = varint::decode(&self.buffer)?;
+        let (len, len_len) = varint::decode(&self.buffer[id_len..])?;
+        let start = id_len + len_len;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        if self.buffer.len() < end {
+            return None;
+        }
+        let payload = self.buffer[start..end].to_vec();
+        self.buffer.drain(..end);
+        Some((id, payload))
+    }
+
+    /// Number of bytes received that do not form a complete frame yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// A registered channel's callback and cipher, tagged with the token of the [`MuxChannel`]
+/// owning it.
+#[derive(Clone)]
+struct Registration {
+    token: u64,
+    on_message: Callback<Vec<u8>>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<ChannelCipher>,
+}
+
+impl Registration {
+    /// Passes the payload of a frame received on channel `id` to the callback.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn deliver(&self, id: u64, payload: Vec<u8>) {
+        #[cfg(feature = "encryption")]
+        let payload = match &self.cipher {
+            Some(cipher) => match cipher.open(id, &payload) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    log::warn!(target: STREAMS, "dropped a frame on mux channel {id}: {e}");
+                    return;
+                }
+            },
+            None => payload,
+        };
+        self.on_message.emit(payload);
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    next_token: Cell<u64>,
+    channels: RefCell<HashMap<u64, Registration>>,
+    decoder: RefCell<FrameDecoder>,
+}
+
+impl Registry {
+    fn receive(&self, chunk: Vec<u8>) {
+        self.decoder.borrow_mut().push(&chunk);
+        loop {
+            let frame = self.decoder.borrow_mut().next_frame();
+            let Some((id, payload)) = frame else {
+                break;
+            };
+            let registration = self.channels.borrow().get(&id).cloned();
+            // Frames for channels that are not registered on this side are dropped.
+            if let Some(registration) = registration {
+                registration.deliver(id, payload);
+            }
+        }
+    }
+}
+
+/// Carries [`MuxChannel`]s over one bidirectional stream.
+///
+/// The stream is finished once the mux and every channel opened on it are dropped.
+#[derive(Clone)]
+pub struct StreamMux {
+    registry: Rc<Registry>,
+    writer: Rc<StreamWriter>,
+}
+
+impl StreamMux {
+    /// Opens the underlying stream on `transport`.
+    pub fn open<T: Transport + ?Sized>(transport: &T) -> Self {
+        let registry = Rc::new(Registry::default());
+        let on_data = {
+            let registry = registry.clone();
+            Callback::from(move |chunk: Vec<u8>| registry.receive(chunk))
+        };
+        let writer = Rc::new(transport.open_bidirectional_stream(on_data));
+        Self { registry, writer }
+    }
+
+    /// Registers `on_message` for channel `id` and returns the channel. A later registration
+    /// of the same id replaces this one.
+    ///
+    /// # Panics
+    ///
+    /// If `id` doesn't fit in a QUIC varint.
+    pub fn channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> MuxChannel {
+        assert!(
+            id <= varint::MAX,
+            "channel id {id} does not fit in a QUIC varint"
+        );
+        let token = self.registry.next_token.get();
+        self.registry.next_token.set(token + 1);
+        self.registry.channels.borrow_mut().insert(
+            id,
+            Registration {
+                token,
+                on_message,
+                #[cfg(feature = "encryption")]
+                cipher: None,
+            },
+        );
+        MuxChannel {
+            id,
+            token,
+            mux: self.clone(),
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// Whether a channel is currently registered under `id`.
+    pub fn is_registered(&self, id: u64) -> bool {
+        self.registry.channels.borrow().contains_key(&id)
+    }
+}
+
+impl fmt::Debug for StreamMux {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids: Vec<u64> = self.registry.channels.borrow().keys().copied().collect();
+        ids.sort_unstable();
+        f.debug_struct("StreamMux").field("channels", &ids).finish()
+    }
+}
+
+/// A logical channel of a [`StreamMux`]. The channel is unregistered when dropped.
+pub struct MuxChannel {
+    id: u64,
+    token: u64,
+    mux: StreamMux,
+    #[cfg(feature = "encryption")]
+    cipher: Option<ChannelCipher>,
+}
+
+impl MuxChannel {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sends `data` as one message on this channel.
+    pub fn send(&self, data: &[u8]) {
+        #[cfg(feature = "encryption")]
+        let sealed = self
+            .cipher
+            .as_ref()
+            .map(|cipher| cipher.seal(self.id, data));
+        #[cfg(feature = "encryption")]
+        let data = sealed.as_deref().unwrap_or(data);
+        let mut frame = Vec::with_capacity(data.len() + 16);
+        encode_frame(self.id, data, &mut frame);
+        self.mux.writer.write(frame);
+    }
+
+    /// Seals every message sent on this channel with `cipher`, and opens every message
+    /// received on it. Messages that fail to open are logged and dropped. See
+    /// [`crate::crypto`].
+    #[cfg(feature = "encryption")]
+    pub fn encrypt(mut self, cipher: ChannelCipher) -> Self {
+        if let Some(registration) = self.registration().as_mut() {
+            registration.cipher = Some(cipher.clone());
+        }
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Replaces the callback receiving this channel's messages.
+    pub fn set_on_message(&self, on_message: Callback<Vec<u8>>) {
+        if let Some(registration) = self.registration().as_mut() {
+            registration.on_message = on_message;
+        }
+    }
+
+    /// This channel's registration, unless a later one of the same id replaced it.
+    fn registration(&self) -> Option<RefMut<'_, Registration>> {
+        let channels = self.mux.registry.channels.borrow_mut();
+        RefMut::filter_map(channels, |channels| {
+            channels
+                .get_mut(&self.id)
+                .filter(|registration| registration.token == self.token)
+        })
+        .ok()
+    }
+}
+
+impl Drop for MuxChannel {
+    fn drop(&mut self) {
+        if self.registration().is_some() {
+            self.mux.registry.channels.borrow_mut().remove(&self.id);
+        }
+    }
+}
+
+impl fmt::Debug for MuxChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MuxChannel").field("id", &self.id).finish()
+    }
+}
@@ -0,0 +1,521 @@
+//! Selective retransmission of media frames sent as datagrams.
+//!
+//! A [`NackSender`] numbers every frame it sends and keeps the most recent ones in a
+//! retransmission cache, bounded in size and age. A [`NackReceiver`] delivers frames as they
+//! arrive and watches the sequence numbers for gaps: after a short grace period for reordering,
+//! it asks for the missing frames with a NACK, and repeats the request a few times before giving
+//! them up. Unlike [`ArqChannel`](crate::arq::ArqChannel), nothing is acknowledged, so the
+//! common case of no loss costs no traffic in the reverse direction, and a frame that could not
+//! be recovered in time is simply skipped.
+//!
+//! Frames travel on the data channel as `sequence varint | payload`. NACKs travel on a separate
+//! control channel as `count varint | sequence varint*`.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::channel::DatagramChannel;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+use crate::varint;
+
+/// Sequence numbers carried by one NACK at most, so that it fits in a datagram.
+const MAX_NACK_ENTRIES: usize = 64;
+
+fn encode_nack(sequences: &[u64]) -> Vec<u8> {
+    let mut data = Vec::new();
+    varint::encode(sequences.len() as u64, &mut data);
+    for &sequence in sequences {
+        varint::encode(sequence, &mut data);
+    }
+    data
+}
+
+fn decode_nack(data: &[u8]) -> Option<Vec<u64>> {
+    let (count, mut at) = varint::decode(data)?;
+    let mut sequences = Vec::new();
+    for _ in 0..count {
+        let (sequence, len) = varint::decode(data.get(at..)?)?;
+        at += len;
+        sequences.push(sequence);
+    }
+    Some(sequences)
+}
+
+/// Bounds of a [`NackSender`]'s retransmission cache.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetransmitConfig {
+    /// Frames kept at most.
+    pub max_frames: usize,
+    /// Age after which a frame is no longer retransmitted.
+    pub max_age: Duration,
+}
+
+impl Default for RetransmitConfig {
+    fn default() -> Self {
+        Self {
+            max_frames: 512,
+            max_age: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Counters kept by a [`NackSender`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NackSenderStats {
+    pub frames_sent: u64,
+    pub nacks_received: u64,
+    pub retransmissions: u64,
+    /// Frames requested after they left the cache.
+    pub not_cached: u64,
+}
+
+struct CachedFrame {
+    sent_at: Duration,
+    datagram: Vec<u8>,
+}
+
+struct SenderState {
+    config: RetransmitConfig,
+    next_sequence: u64,
+    /// Frames from `first_cached` on, in sequence order.
+    cache: VecDeque<CachedFrame>,
+    first_cached: u64,
+    stats: NackSenderStats,
+}
+
+impl SenderState {
+    fn evict(&mut self, now: Duration) {
+        let max_age = self.config.max_age;
+        while self.cache.len() > self.config.max_frames
+            || self
+                .cache
+                .front()
+                .is_some_and(|frame| now.saturating_sub(frame.sent_at) > max_age)
+        {
+            self.cache.pop_front();
+            self.first_cached += 1;
+        }
+    }
+}
+
+struct SenderInner {
+    state: RefCell<SenderState>,
+    data: DatagramChannel,
+    // Kept registered so that NACKs are routed to this sender.
+    _control: DatagramChannel,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl SenderInner {
+    fn receive_nack(&self, data: Vec<u8>) {
+        let Some(sequences) = decode_nack(&data) else {
+            return;
+        };
+        let retransmit = {
+            let mut state = self.state.borrow_mut();
+            state.evict(self.scheduler.now());
+            state.stats.nacks_received += 1;
+            let mut retransmit = Vec::new();
+            for sequence in sequences {
+                let cached = sequence
+                    .checked_sub(state.first_cached)
+                    .and_then(|index| state.cache.get(index as usize));
+                match cached {
+                    Some(frame) => retransmit.push(frame.datagram.clone()),
+                    None => state.stats.not_cached += 1,
+                }
+            }
+            state.stats.retransmissions += retransmit.len() as u64;
+            retransmit
+        };
+        for datagram in retransmit {
+            self.data.send(&datagram);
+        }
+    }
+}
+
+/// Sends numbered frames and retransmits the ones the receiver reports missing.
+pub struct NackSender {
+    inner: Rc<SenderInner>,
+}
+
+impl NackSender {
+    /// Sends frames on datagram channel `data_id` of `transport`, and listens for NACKs on
+    /// `control_id`.
+    pub fn new<T: Transport + ?Sized>(
+        transport: &T,
+        data_id: u64,
+        control_id: u64,
+        config: RetransmitConfig,
+    ) -> Self {
+        Self::with_scheduler(
+            transport,
+            data_id,
+            control_id,
+            config,
+            Rc::new(PlatformScheduler),
+        )
+    }
+
+    /// Like [`NackSender::new`], with the cache ages measured by `scheduler`.
+    pub fn with_scheduler<T: Transport + ?Sized>(
+        transport: &T,
+        data_id: u64,
+        control_id: u64,
+        config: RetransmitConfig,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<SenderInner>| {
+            let receiver = inner.clone();
+            let control = transport.datagram_channel(
+                control_id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive_nack(data);
+                    }
+                }),
+            );
+            SenderInner {
+                state: RefCell::new(SenderState {
+                    config,
+                    next_sequence: 0,
+                    cache: VecDeque::new(),
+                    first_cached: 0,
+                    stats: NackSenderStats::default(),
+                }),
+                data: transport.datagram_channel(data_id, Callback::noop()),
+                _control: control,
+                scheduler,
+            }
+        });
+        Self { inner }
+    }
+
+    /// Sends `frame` with the next sequence number, which is returned.
+    pub fn send(&self, frame: &[u8]) -> u64 {
+        let (sequence, datagram) = {
+            let now = self.inner.scheduler.now();
+            let mut state = self.inner.state.borrow_mut();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.stats.frames_sent += 1;
+            let mut datagram = Vec::with_capacity(frame.len() + 4);
+            varint::encode(sequence, &mut datagram);
+            datagram.extend_from_slice(frame);
+            if state.cache.is_empty() {
+                state.first_cached = sequence;
+            }
+            state.cache.push_back(CachedFrame {
+                sent_at: now,
+                datagram: datagram.clone(),
+            });
+            state.evict(now);
+            (sequence, datagram)
+        };
+        self.inner.data.send(&datagram);
+        sequence
+    }
+
+    /// Number of frames that can still be retransmitted.
+    pub fn cached(&self) -> usize {
+        let mut state = self.inner.state.borrow_mut();
+        state.evict(self.inner.scheduler.now());
+        state.cache.len()
+    }
+
+    pub fn stats(&self) -> NackSenderStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl fmt::Debug for NackSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("NackSender")
+            .field("next_sequence", &state.next_sequence)
+            .field("cached", &state.cache.len())
+            .finish()
+    }
+}
+
+/// Tuning for a [`NackReceiver`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NackConfig {
+    /// Time a gap is given to fill by reordering before the first NACK.
+    pub reorder_delay: Duration,
+    /// Time between two NACKs for the same frame.
+    pub retry_interval: Duration,
+    /// NACKs sent for a frame before it is given up.
+    pub max_nacks: u32,
+    /// Missing frames tracked at most; older gaps are given up beyond that.
+    pub max_missing: usize,
+}
+
+impl Default for NackConfig {
+    fn default() -> Self {
+        Self {
+            reorder_delay: Duration::from_millis(10),
+            retry_interval: Duration::from_millis(50),
+            max_nacks: 3,
+            max_missing: 256,
+        }
+    }
+}
+
+/// Counters kept by a [`NackReceiver`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NackReceiverStats {
+    pub frames_received: u64,
+    pub duplicates_received: u64,
+    pub nacks_sent: u64,
+    /// Missing frames that arrived after all.
+    pub frames_recovered: u64,
+    /// Missing frames given up on.
+    pub frames_lost: u64,
+}
+
+struct Missing {
+    next_nack: Duration,
+    nacks_sent: u32,
+}
+
+struct ReceiverState {
+    config: NackConfig,
+    highest: Option<u64>,
+    missing: BTreeMap<u64, Missing>,
+    armed_at: Option<Duration>,
+    stats: NackReceiverStats,
+    on_frame: Callback<(u64, Vec<u8>)>,
+}
+
+impl ReceiverState {
+    /// Records `sequence`, returning `false` if it is a duplicate or was already given up on.
+    fn record(&mut self, sequence: u64, now: Duration) -> bool {
+        match self.highest {
+            Some(highest) if sequence <= highest => {
+                if self.missing.remove(&sequence).is_some() {
+                    self.stats.frames_recovered += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            highest => {
+                if let Some(highest) = highest {
+                    let next_nack = now + self.config.reorder_delay;
+                    // Only the newest `max_missing` frames of the gap could be kept, so the rest
+                    // is given up on at once rather than one by one.
+                    let start =
+                        (highest + 1).max(sequence.saturating_sub(self.config.max_missing as u64));
+                    self.stats.frames_lost += start - (highest + 1);
+                    for gap in start..sequence {
+                        self.missing.insert(
+                            gap,
+                            Missing {
+                                next_nack,
+                                nacks_sent: 0,
+                            },
+                        );
+                        if self.missing.len() > self.config.max_missing {
+                            self.missing.pop_first();
+                            self.stats.frames_lost += 1;
+                        }
+                    }
+                }
+                self.highest = Some(sequence);
+                true
+            }
+        }
+    }
+
+    /// Collects the frames due for a NACK and gives up on those asked for too many times.
+    fn due(&mut self, now: Duration) -> Vec<u64> {
+        let NackConfig {
+            retry_interval,
+            max_nacks,
+            ..
+        } = self.config;
+        let mut lost = 0;
+        let mut due = Vec::new();
+        self.missing.retain(|&sequence, missing| {
+            if missing.next_nack > now {
+                return true;
+            }
+            if missing.nacks_sent >= max_nacks {
+                lost += 1;
+                return false;
+            }
+            missing.nacks_sent += 1;
+            missing.next_nack = now + retry_interval;
+            due.push(sequence);
+            true
+        });
+        self.stats.frames_lost += lost;
+        due
+    }
+}
+
+struct ReceiverInner {
+    state: RefCell<ReceiverState>,
+    // Kept registered so that frames are routed to this receiver.
+    _data: DatagramChannel,
+    control: DatagramChannel,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl ReceiverInner {
+    fn receive(self: &Rc<Self>, data: Vec<u8>) {
+        let Some((sequence, len)) = varint::decode(&data) else {
+            return;
+        };
+        let on_frame = {
+            let now = self.scheduler.now();
+            let mut state = self.state.borrow_mut();
+            if !state.record(sequence, now) {
+                state.stats.duplicates_received += 1;
+                return;
+            }
+            state.stats.frames_received += 1;
+            state.on_frame.clone()
+        };
+        on_frame.emit((sequence, data[len..].to_vec()));
+        self.arm_timer();
+    }
+
+    fn send_nacks(self: &Rc<Self>) {
+        let due = {
+            let mut state = self.state.borrow_mut();
+            let due = state.due(self.scheduler.now());
+            state.stats.nacks_sent += due.chunks(MAX_NACK_ENTRIES).len() as u64;
+            due
+        };
+        for sequences in due.chunks(MAX_NACK_ENTRIES) {
+            self.control.send(&encode_nack(sequences));
+        }
+        self.arm_timer();
+    }
+
+    fn arm_timer(self: &Rc<Self>) {
+        let now = self.scheduler.now();
+        let at = {
+            let mut state = self.state.borrow_mut();
+            let Some(at) = state
+                .missing
+                .values()
+                .map(|missing| missing.next_nack)
+                .min()
+            else {
+                return;
+            };
+            if state.armed_at.is_some_and(|armed| armed <= at) {
+                return;
+            }
+            state.armed_at = Some(at);
+            at
+        };
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            at.saturating_sub(now),
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    {
+                        let mut state = inner.state.borrow_mut();
+                        if state.armed_at == Some(at) {
+                            state.armed_at = None;
+                        }
+                    }
+                    inner.send_nacks();
+                }
+            }),
+        );
+    }
+}
+
+/// Receives numbered frames and asks for the missing ones.
+pub struct NackReceiver {
+    inner: Rc<ReceiverInner>,
+}
+
+impl NackReceiver {
+    /// Receives frames on datagram channel `data_id` of `transport` and sends NACKs on
+    /// `control_id`. Every frame is passed to `on_frame` with its sequence number as soon as it
+    /// arrives, so retransmitted frames come out of order.
+    pub fn new<T: Transport + ?Sized>(
+        transport: &T,
+        data_id: u64,
+        control_id: u64,
+        config: NackConfig,
+        on_frame: Callback<(u64, Vec<u8>)>,
+    ) -> Self {
+        Self::with_scheduler(
+            transport,
+            data_id,
+            control_id,
+            config,
+            on_frame,
+            Rc::new(PlatformScheduler),
+        )
+    }
+
+    /// Like [`NackReceiver::new`], with NACKs timed by `scheduler`.
+    pub fn with_scheduler<T: Transport + ?Sized>(
+        transport: &T,
+        data_id: u64,
+        control_id: u64,
+        config: NackConfig,
+        on_frame: Callback<(u64, Vec<u8>)>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<ReceiverInner>| {
+            let receiver = inner.clone();
+            let data = transport.datagram_channel(
+                data_id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            ReceiverInner {
+                state: RefCell::new(ReceiverState {
+                    config,
+                    highest: None,
+                    missing: BTreeMap::new(),
+                    armed_at: None,
+                    stats: NackReceiverStats::default(),
+                    on_frame,
+                }),
+                _data: data,
+                control: transport.datagram_channel(control_id, Callback::noop()),
+                scheduler,
+            }
+        });
+        Self { inner }
+    }
+
+    /// Number of frames currently missing and being asked for.
+    pub fn missing(&self) -> usize {
+        self.inner.state.borrow().missing.len()
+    }
+
+    pub fn stats(&self) -> NackReceiverStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl fmt::Debug for NackReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("NackReceiver")
+            .field("highest", &state.highest)
+            .field("missing", &state.missing.len())
+            .finish()
+    }
+}
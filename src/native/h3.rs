@@ -0,0 +1,263 @@
+//! The sliver of HTTP/3 and QPACK needed to establish a WebTransport session
+//! ([draft-ietf-webtrans-http3](https://datatracker.ietf.org/doc/draft-ietf-webtrans-http3/)).
+//!
+//! We advertise a QPACK dynamic table of size zero, so the server may only use the static
+//! table and literals, which keeps both the encoder and the decoder tiny.
+
+use quinn::RecvStream;
+
+use crate::varint;
+
+pub const STREAM_TYPE_CONTROL: u64 = 0x00;
+pub const STREAM_TYPE_WEBTRANSPORT: u64 = 0x54;
+pub const SIGNAL_WEBTRANSPORT_STREAM: u64 = 0x41;
+
+const FRAME_HEADERS: u64 = 0x01;
+const FRAME_SETTINGS: u64 = 0x04;
+
+const SETTINGS_ENABLE_CONNECT_PROTOCOL: u64 = 0x08;
+const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+const SETTINGS_H3_DATAGRAM_DRAFT04: u64 = 0xff_d277;
+const SETTINGS_ENABLE_WEBTRANSPORT: u64 = 0x2b60_3742;
+const SETTINGS_WEBTRANSPORT_MAX_SESSIONS: u64 = 0xc671_706a;
+
+/// The control stream preamble: stream type followed by our SETTINGS frame.
+pub fn control_stream_preamble() -> Vec<u8> {
+    let mut settings = Vec::new();
+    for (id, value) in [
+        (SETTINGS_ENABLE_CONNECT_PROTOCOL, 1),
+        (SETTINGS_H3_DATAGRAM, 1),
+        (SETTINGS_H3_DATAGRAM_DRAFT04, 1),
+        (SETTINGS_ENABLE_WEBTRANSPORT, 1),
+        (SETTINGS_WEBTRANSPORT_MAX_SESSIONS, 1),
+    ] {
+        varint::encode(id, &mut settings);
+        varint::encode(value, &mut settings);
+    }
+    let mut out = Vec::new();
+    varint::encode(STREAM_TYPE_CONTROL, &mut out);
+    frame(FRAME_SETTINGS, &settings, &mut out);
+    out
+}
+
+/// The HEADERS frame of an extended CONNECT request opening a WebTransport session.
+pub fn connect_request(authority: &str, path: &str) -> Vec<u8> {
+    // Required insert count and delta base, both zero without a dynamic table.
+    let mut fields = vec![0x00, 0x00];
+    // Static table entries 15 (`:method: CONNECT`) and 23 (`:scheme: https`).
+    fields.push(0xc0 | 15);
+    fields.push(0xc0 | 23);
+    // Static table names 0 (`:authority`) and 1 (`:path`) with literal values.
+    name_reference(0, authority, &mut fields);
+    name_reference(1, path, &mut fields);
+    literal(":protocol", "webtransport", &mut fields);
+    literal("sec-webtransport-http3-draft02", "1", &mut fields);
+
+    let mut out = Vec::new();
+    frame(FRAME_HEADERS, &fields, &mut out);
+    out
+}
+
+fn frame(kind: u64, payload: &[u8], out: &mut Vec<u8>) {
+    varint::encode(kind, out);
+    varint::encode(payload.len() as u64, out);
+    out.extend_from_slice(payload);
+}
+
+fn name_reference(static_index: u64, value: &str, out: &mut Vec<u8>) {
+    // 01NT: not sensitive, static table.
+    prefixed_int(static_index, 4, 0x50, out);
+    prefixed_int(value.len() as u64, 7, 0x00, out);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn literal(name: &str, value: &str, out: &mut Vec<u8>) {
+    // 001NH: not sensitive, no huffman.
+    prefixed_int(name.len() as u64, 3, 0x20, out);
+    out.extend_from_slice(name.as_bytes());
+    prefixed_int(value.len() as u64, 7, 0x00, out);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn prefixed_int(mut value: u64, prefix_bits: u32, flags: u8, out: &mut Vec<u8>) {
+    let max = (1u64 << prefix_bits) - 1;
+    if value < max {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_prefixed_int(buf: &[u8], pos: &mut usize, prefix_bits: u32) -> Option<u64> {
+    let max = (1u64 << prefix_bits) - 1;
+    let mut value = u64::from(*buf.get(*pos)?) & max;
+    *pos += 1;
+    if value < max {
+        return Some(value);
+    }
+    let mut shift = 0;
+    loop {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        value = value.checked_add(u64::from(byte & 0x7f).checked_shl(shift)?)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+fn read_string(buf: &[u8], pos: &mut usize, prefix_bits: u32) -> Option<Vec<u8>> {
+    let huffman = buf.get(*pos)? & (1 << prefix_bits) != 0;
+    let len = read_prefixed_int(buf, pos, prefix_bits)? as usize;
+    let bytes = buf.get(*pos..*pos + len)?;
+    *pos += len;
+    if huffman {
+        huffman_digits(bytes)
+    } else {
+        Some(bytes.to_vec())
+    }
+}
+
+/// Decodes a huffman encoded string made only of ASCII digits, which is all a `:status`
+/// value can contain. Anything else is rejected.
+fn huffman_digits(bytes: &[u8]) -> Option<Vec<u8>> {
+    let total = bytes.len() * 8;
+    let bit = |i: usize| (bytes[i / 8] >> (7 - i % 8)) & 1;
+    let take = |from: usize, n: usize| (from..from + n).fold(0u8, |acc, i| (acc << 1) | bit(i));
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while total - pos >= 5 {
+        let code = take(pos, 5);
+        if code <= 2 {
+            out.push(b'0' + code);
+            pos += 5;
+            continue;
+        }
+        if total - pos >= 6 {
+            let code = take(pos, 6);
+            if (0x19..=0x1f).contains(&code) {
+                out.push(b'3' + code - 0x19);
+                pos += 6;
+                continue;
+            }
+        }
+        break;
+    }
+    // Whatever is left must be padding: fewer than eight bits, all ones.
+    let padding = total - pos;
+    if padding >= 8 || (pos..total).any(|i| bit(i) == 0) {
+        return None;
+    }
+    Some(out)
+}
+
+fn static_status(index: u64) -> Option<u16> {
+    Some(match index {
+        24 => 103,
+        25 => 200,
+        26 => 304,
+        27 => 404,
+        28 => 503,
+        63 => 100,
+        64 => 204,
+        65 => 206,
+        66 => 302,
+        67 => 400,
+        68 => 403,
+        69 => 421,
+        70 => 425,
+        71 => 500,
+        _ => return None,
+    })
+}
+
+/// Extracts `:status` from a QPACK encoded field section.
+fn parse_status(fields: &[u8]) -> Option<u16> {
+    let mut pos = 0;
+    let _required_insert_count = read_prefixed_int(fields, &mut pos, 8)?;
+    let _delta_base = read_prefixed_int(fields, &mut pos, 7)?;
+    while pos < fields.len() {
+        let first = fields[pos];
+        if first & 0x80 != 0 {
+            // Indexed field line; only the static table can be referenced.
+            if first & 0x40 == 0 {
+                return None;
+            }
+            let index = read_prefixed_int(fields, &mut pos, 6)?;
+            if let Some(status) = static_status(index) {
+                return Some(status);
+            }
+        } else if first & 0x40 != 0 {
+            // Literal with name reference.
+            if first & 0x10 == 0 {
+                return None;
+            }
+            let index = read_prefixed_int(fields, &mut pos, 4)?;
+            let value = read_string(fields, &mut pos, 7)?;
+            if static_status(index).is_some() {
+                return std::str::from_utf8(&value).ok()?.parse().ok();
+            }
+        } else if first & 0x20 != 0 {
+            // Literal with literal name.
+            let name = read_string(fields, &mut pos, 3)?;
+            let value = read_string(fields, &mut pos, 7)?;
+            if name == b":status" {
+                return std::str::from_utf8(&value).ok()?.parse().ok();
+            }
+        } else {
+            // Post-base references need a dynamic table, which we never allowed.
+            return None;
+        }
+    }
+    None
+}
+
+/// Reads a QUIC varint from a stream. `Ok(None)` means the stream ended cleanly first.
+pub async fn read_varint(stream: &mut RecvStream) -> Result<Option<u64>, String> {
+    let mut first = [0u8; 1];
+    match stream.read(&mut first).await.map_err(|e| e.to_string())? {
+        Some(1) => {}
+        _ => return Ok(None),
+    }
+    let mut buf = [0u8; 8];
+    let len = varint::len_from_first_byte(first[0]);
+    buf[0] = first[0];
+    stream
+        .read_exact(&mut buf[1..len])
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(varint::decode(&buf[..len]).map(|(value, _)| value))
+}
+
+/// Reads frames from the CONNECT stream until the response HEADERS and returns its status.
+pub async fn read_response_status(stream: &mut RecvStream) -> Result<u16, String> {
+    loop {
+        let kind = read_varint(stream)
+            .await?
+            .ok_or("stream closed before the response")?;
+        let len = read_varint(stream)
+            .await?
+            .ok_or("stream closed before the response")?;
+        let mut payload = vec![0u8; len as usize];
+        stream
+            .read_exact(&mut payload)
+            .await
+            .map_err(|e| e.to_string())?;
+        if kind == FRAME_HEADERS {
+            return parse_status(&payload).ok_or_else(|| "malformed response headers".to_string());
+        }
+    }
+}
+
+/// Drains a stream we have no use for, such as the server's control or QPACK streams.
+pub async fn drain(mut stream: RecvStream) {
+    let mut buf = [0u8; 1024];
+    while let Ok(Some(_)) = stream.read(&mut buf).await {}
+}
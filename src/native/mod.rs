@@ -0,0 +1,924 @@
+//! A service to connect to a WebTransport server from non-wasm targets, such as a CLI or a
+//! desktop app, with the same shape as [`crate::webtransport`].
+//!
+//! Sessions are established over HTTP/3 with [quinn](https://docs.rs/quinn) and need to be
+//! driven from a Yew platform runtime (or a tokio `LocalSet`), since callbacks are emitted from
+//! local tasks. Incoming streams are handed out as quinn streams positioned at the start of
+//! their payload.
+
+mod h3;
+mod tls;
+
+use futures::channel::mpsc;
+use futures::future::{FutureExt, LocalBoxFuture, Shared};
+use futures::StreamExt;
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig, Connection, ConnectionError, Endpoint, RecvStream, SendStream};
+use std::cell::{Cell, RefCell};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use std::{fmt, rc::Rc, sync::Arc};
+use yew::callback::Callback;
+use yew::platform::spawn_local;
+
+use crate::channel::DatagramRouter;
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramSubscription};
+use crate::handler::Handler;
+use crate::limit::ReadGate;
+#[cfg(feature = "datagrams")]
+use crate::logging::DATAGRAMS;
+use crate::logging::{SESSION, STREAMS};
+use crate::transport::{StreamWriter, Transport, TransportStats};
+use crate::varint;
+pub use crate::webtransport::{CloseInfo, SessionError, WebTransportError, WebTransportStatus};
+
+/// An incoming unidirectional stream, positioned after the WebTransport stream header.
+pub type WebTransportReceiveStream = RecvStream;
+
+/// A bidirectional stream, positioned after the WebTransport stream header.
+pub struct WebTransportBidirectionalStream {
+    pub send: SendStream,
+    pub recv: RecvStream,
+}
+
+impl fmt::Debug for WebTransportBidirectionalStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebTransportBidirectionalStream")
+    }
+}
+
+/// Options for native sessions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NativeOptions {
+    /// SHA-256 hashes of acceptable server certificates. When set, the server certificate is
+    /// checked against them instead of the web PKI, like the browser's `serverCertificateHashes`.
+    pub server_certificate_hashes: Vec<[u8; 32]>,
+    /// The deadline of the datagrams sent without one of their own, like
+    /// [`WebTransportBuilder::datagram_max_age`](crate::webtransport::WebTransportBuilder::datagram_max_age).
+    pub datagram_max_age: Option<Duration>,
+}
+
+#[derive(Clone)]
+struct Established {
+    connection: Connection,
+    session_id: u64,
+    // The endpoint, the control stream and the CONNECT stream must stay open for as long as the
+    // session lives; closing either stream ends it.
+    _keep_alive: Rc<(Endpoint, SendStream, SendStream, RecvStream)>,
+}
+
+impl Established {
+    fn stream_header(&self, kind: u64) -> Vec<u8> {
+        let mut header = Vec::new();
+        varint::encode(kind, &mut header);
+        varint::encode(self.session_id, &mut header);
+        header
+    }
+}
+
+/// A native WebTransport session, the counterpart of `web_sys::WebTransport`.
+pub struct WebTransport {
+    ready: Shared<LocalBoxFuture<'static, Result<Established, String>>>,
+    closed: Cell<bool>,
+    last_error: RefCell<Option<String>>,
+}
+
+impl WebTransport {
+    /// Waits until the session is established.
+    pub async fn ready(&self) -> Result<(), String> {
+        self.established().await.map(|_| ())
+    }
+
+    /// Closes the session.
+    pub fn close(&self) {
+        if self.closed.replace(true) {
+            return;
+        }
+        if let Some(Ok(established)) = self.ready.peek() {
+            established.connection.close(0u32.into(), b"");
+        }
+    }
+
+    /// The last error encountered by the session, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.borrow().clone()
+    }
+
+    async fn established(&self) -> Result<Established, String> {
+        if self.closed.get() {
+            return Err("session is closed".to_string());
+        }
+        self.ready.clone().await
+    }
+
+    fn fail(&self, error: String) {
+        *self.last_error.borrow_mut() = Some(error);
+        self.close();
+    }
+}
+
+impl fmt::Debug for WebTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebTransport")
+    }
+}
+
+/// A handle to control the WebTransport connection.
+#[must_use = "the connection will be closed when the task is dropped"]
+pub struct WebTransportTask {
+    pub transport: Rc<WebTransport>,
+    #[cfg(feature = "streams")]
+    notification: Callback<WebTransportStatus>,
+    #[cfg(feature = "datagrams")]
+    datagram_router: DatagramRouter,
+    expired_datagrams: Rc<Cell<u64>>,
+    datagram_max_age: Option<Duration>,
+    #[cfg(feature = "datagrams")]
+    datagram_gate: ReadGate,
+}
+
+impl WebTransportTask {
+    /// Number of datagrams sent with a deadline that were dropped because it passed before
+    /// they could be sent.
+    pub fn expired_datagrams(&self) -> u64 {
+        self.expired_datagrams.get()
+    }
+
+    /// Stops reading incoming datagrams, after the read in progress, until
+    /// [`WebTransportTask::resume_datagrams`]. Quinn drops the oldest of those that arrive
+    /// meanwhile once its receive buffer is full.
+    #[cfg(feature = "datagrams")]
+    pub fn pause_datagrams(&self) {
+        log::debug!(target: DATAGRAMS, "pausing incoming datagrams");
+        self.datagram_gate.pause();
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub fn resume_datagrams(&self) {
+        log::debug!(target: DATAGRAMS, "resuming incoming datagrams");
+        self.datagram_gate.resume();
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub fn datagrams_paused(&self) -> bool {
+        self.datagram_gate.is_paused()
+    }
+
+    /// The deadline of the datagrams sent without one of their own, see
+    /// [`NativeOptions::datagram_max_age`].
+    pub fn datagram_max_age(&self) -> Option<Duration> {
+        self.datagram_max_age
+    }
+
+    /// The deadline of a datagram sent with `deadline`, or with none.
+    #[cfg(feature = "datagrams")]
+    fn datagram_deadline(
+        &self,
+        deadline: Option<Duration>,
+    ) -> Option<(Duration, Callback<Vec<u8>>)> {
+        let deadline = deadline.or(self.datagram_max_age)?;
+        let expired = self.expired_datagrams.clone();
+        Some((
+            deadline,
+            Callback::from(move |_| expired.set(expired.get() + 1)),
+        ))
+    }
+
+    /// Resolves once the session is established, or with the reason it could not be. Fails
+    /// with [`WebTransportError::AlreadyClosed`] if the task was closed first.
+    pub fn ready(
+        &self,
+    ) -> impl std::future::Future<Output = Result<(), WebTransportError>> + 'static {
+        let transport = self.transport.clone();
+        async move {
+            transport.ready().await.map_err(|e| {
+                if transport.closed.get() {
+                    WebTransportError::AlreadyClosed
+                } else {
+                    WebTransportError::Session(SessionError::Network(e))
+                }
+            })
+        }
+    }
+
+    /// Resolves once the session is closed, by either side, or lost, with how it ended. A
+    /// session that failed to open resolves with the error as the reason.
+    pub fn closed(&self) -> impl std::future::Future<Output = CloseInfo> + 'static {
+        let ready = self.transport.ready.clone();
+        async move {
+            match ready.await {
+                Ok(established) => close_info(established.connection.closed().await),
+                Err(reason) => CloseInfo {
+                    close_code: None,
+                    reason,
+                },
+            }
+        }
+    }
+
+    /// Closes the session. Closing a session that is already closed, by either side, does
+    /// nothing and returns [`WebTransportError::AlreadyClosed`].
+    pub fn close(&self) -> Result<(), WebTransportError> {
+        if self.transport.closed.get() {
+            return Err(WebTransportError::AlreadyClosed);
+        }
+        self.transport.close();
+        Ok(())
+    }
+}
+
+impl Drop for WebTransportTask {
+    fn drop(&mut self) {
+        self.transport.close();
+    }
+}
+
+impl fmt::Debug for WebTransportTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebTransportTask")
+    }
+}
+
+/// A WebTransport service for native targets.
+#[derive(Default, Debug)]
+pub struct WebTransportService {}
+
+impl WebTransportService {
+    /// Connects to a server through a WebTransport connection. The handlers, yew
+    /// [`Callback`]s or plain closures, are passed incoming datagrams and streams, and updates
+    /// about the WebTransport's status.
+    pub fn connect(
+        url: &str,
+        on_datagram: impl Handler<Vec<u8>>,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+        notification: impl Handler<WebTransportStatus>,
+    ) -> Result<WebTransportTask, WebTransportError> {
+        Self::connect_with_options(
+            url,
+            NativeOptions::default(),
+            on_datagram,
+            on_unidirectional_stream,
+            on_bidirectional_stream,
+            notification,
+        )
+    }
+
+    /// Like [`WebTransportService::connect`], with [`NativeOptions`].
+    pub fn connect_with_options(
+        url: &str,
+        options: NativeOptions,
+        on_datagram: impl Handler<Vec<u8>>,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+        notification: impl Handler<WebTransportStatus>,
+    ) -> Result<WebTransportTask, WebTransportError> {
+        let on_unidirectional_stream = on_unidirectional_stream.into_callback();
+        let on_bidirectional_stream = on_bidirectional_stream.into_callback();
+        let notification = notification.into_callback();
+        let url = url::Url::parse(url).map_err(|e| {
+            WebTransportError::CreationError(format!("Failed to create WebTransport: {e}"))
+        })?;
+        if url.scheme() != "https" {
+            return Err(WebTransportError::CreationError(
+                "Failed to create WebTransport: the url scheme must be https".to_string(),
+            ));
+        }
+        let datagram_max_age = options.datagram_max_age;
+        let datagram_gate = ReadGate::new();
+        let gate = datagram_gate.clone();
+        let transport = Rc::new(WebTransport {
+            ready: handshake(url.clone(), options).boxed_local().shared(),
+            closed: Cell::new(false),
+            last_error: RefCell::new(None),
+        });
+
+        let datagram_router = DatagramRouter::new();
+        let on_datagram = datagram_router.route(on_datagram.into_callback());
+        let session = transport.clone();
+        let notify = notification.clone();
+        log::info!(target: SESSION, "connecting to {url}");
+        spawn_local(async move {
+            let established = match session.ready.clone().await {
+                Ok(established) => established,
+                Err(e) => {
+                    log::warn!(target: SESSION, "session to {url} failed: {e}");
+                    *session.last_error.borrow_mut() = Some(e.clone());
+                    notify.emit(WebTransportStatus::Error(SessionError::Network(e)));
+                    return;
+                }
+            };
+            if session.closed.get() {
+                established.connection.close(0u32.into(), b"");
+                notify.emit(WebTransportStatus::Closed(CloseInfo::default()));
+                return;
+            }
+            log::info!(target: SESSION, "session to {url} opened");
+            notify.emit(WebTransportStatus::Opened);
+            spawn_local(listen_datagrams(
+                established.clone(),
+                on_datagram,
+                gate,
+                notify.clone(),
+            ));
+            spawn_local(listen_unidirectional_streams(
+                established.clone(),
+                on_unidirectional_stream,
+                notify.clone(),
+            ));
+            spawn_local(listen_bidirectional_streams(
+                established.clone(),
+                on_bidirectional_stream,
+                notify.clone(),
+            ));
+            let reason = established.connection.closed().await;
+            log::info!(target: SESSION, "session to {url} closed: {reason}");
+            if !session.closed.replace(true) {
+                *session.last_error.borrow_mut() = Some(reason.to_string());
+            }
+            notify.emit(WebTransportStatus::Closed(close_info(reason)));
+        });
+
+        Ok(WebTransportTask {
+            transport,
+            #[cfg(feature = "streams")]
+            notification,
+            #[cfg(feature = "datagrams")]
+            datagram_router,
+            expired_datagrams: Rc::default(),
+            datagram_max_age,
+            #[cfg(feature = "datagrams")]
+            datagram_gate,
+        })
+    }
+}
+
+async fn handshake(url: url::Url, options: NativeOptions) -> Result<Established, String> {
+    let host = url.host_str().ok_or("the url has no host")?.to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+    let authority = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.clone(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+
+    let address = tokio::net::lookup_host((server_name, port))
+        .await
+        .map_err(|e| format!("failed to resolve {host}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{host} did not resolve to any address"))?;
+    let bind: SocketAddr = if address.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+
+    let crypto = tls::client_config(&options.server_certificate_hashes)?;
+    let crypto = QuicClientConfig::try_from(crypto).map_err(|e| e.to_string())?;
+    let mut endpoint = Endpoint::client(bind).map_err(|e| e.to_string())?;
+    endpoint.set_default_client_config(ClientConfig::new(Arc::new(crypto)));
+    let connection = endpoint
+        .connect(address, server_name)
+        .map_err(|e| e.to_string())?
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut control = connection.open_uni().await.map_err(|e| e.to_string())?;
+    control
+        .write_all(&h3::control_stream_preamble())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (mut send, mut recv) = connection.open_bi().await.map_err(|e| e.to_string())?;
+    send.write_all(&h3::connect_request(&authority, &path))
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = h3::read_response_status(&mut recv).await?;
+    if !(200..300).contains(&status) {
+        return Err(format!("server rejected the session with status {status}"));
+    }
+
+    Ok(Established {
+        connection,
+        session_id: u64::from(send.id()),
+        _keep_alive: Rc::new((endpoint, control, send, recv)),
+    })
+}
+
+/// Describes how the connection ended, like the browser's `WebTransportCloseInfo`.
+fn close_info(error: ConnectionError) -> CloseInfo {
+    match error {
+        ConnectionError::LocallyClosed => CloseInfo::default(),
+        ConnectionError::ApplicationClosed(close) => CloseInfo {
+            close_code: u32::try_from(close.error_code.into_inner()).ok(),
+            reason: String::from_utf8_lossy(&close.reason).into_owned(),
+        },
+        error => CloseInfo {
+            close_code: None,
+            reason: error.to_string(),
+        },
+    }
+}
+
+/// Reports the failure of a single stream, which leaves the session open.
+fn report_stream_error(notification: Option<&Callback<WebTransportStatus>>, error: String) {
+    log::warn!(target: STREAMS, "{error}");
+    if let Some(notification) = notification {
+        notification.emit(WebTransportStatus::StreamError(error));
+    }
+}
+
+/// Reports why a receive loop stopped, unless it is because the session was closed, which is
+/// notified separately.
+fn report_listen_error(
+    notification: &Callback<WebTransportStatus>,
+    what: &str,
+    error: ConnectionError,
+) {
+    match error {
+        ConnectionError::LocallyClosed
+        | ConnectionError::ApplicationClosed(_)
+        | ConnectionError::ConnectionClosed(_) => {}
+        error => notification.emit(WebTransportStatus::StreamError(format!(
+            "Failed to read incoming {what} {error}"
+        ))),
+    }
+}
+
+async fn listen_datagrams(
+    established: Established,
+    callback: Callback<Vec<u8>>,
+    gate: ReadGate,
+    notification: Callback<WebTransportStatus>,
+) {
+    let quarter_stream_id = established.session_id / 4;
+    loop {
+        gate.opened().await;
+        match established.connection.read_datagram().await {
+            Ok(datagram) => {
+                if let Some((id, len)) = varint::decode(&datagram) {
+                    if id == quarter_stream_id {
+                        callback.emit(datagram[len..].to_vec());
+                    }
+                }
+            }
+            Err(e) => {
+                report_listen_error(&notification, "datagrams", e);
+                break;
+            }
+        }
+    }
+}
+
+async fn listen_unidirectional_streams(
+    established: Established,
+    callback: Callback<WebTransportReceiveStream>,
+    notification: Callback<WebTransportStatus>,
+) {
+    loop {
+        let mut stream = match established.connection.accept_uni().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                report_listen_error(&notification, "unidirectional streams", e);
+                break;
+            }
+        };
+        let session_id = established.session_id;
+        let callback = callback.clone();
+        let notification = notification.clone();
+        spawn_local(async move {
+            let result = async {
+                let stream_type = h3::read_varint(&mut stream).await?;
+                if stream_type == Some(h3::STREAM_TYPE_WEBTRANSPORT) {
+                    if h3::read_varint(&mut stream).await? == Some(session_id) {
+                        callback.emit(stream);
+                    }
+                } else if stream_type.is_some() {
+                    // Control and QPACK streams must stay open but carry nothing we need.
+                    h3::drain(stream).await;
+                }
+                Ok::<_, String>(())
+            }
+            .await;
+            if let Err(e) = result {
+                report_stream_error(
+                    Some(&notification),
+                    format!("Failed to read incoming stream header {e}"),
+                );
+            }
+        });
+    }
+}
+
+async fn listen_bidirectional_streams(
+    established: Established,
+    callback: Callback<WebTransportBidirectionalStream>,
+    notification: Callback<WebTransportStatus>,
+) {
+    loop {
+        let (send, mut recv) = match established.connection.accept_bi().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                report_listen_error(&notification, "bidirectional streams", e);
+                break;
+            }
+        };
+        let session_id = established.session_id;
+        let callback = callback.clone();
+        let notification = notification.clone();
+        spawn_local(async move {
+            let result = async {
+                if h3::read_varint(&mut recv).await? == Some(h3::SIGNAL_WEBTRANSPORT_STREAM)
+                    && h3::read_varint(&mut recv).await? == Some(session_id)
+                {
+                    callback.emit(WebTransportBidirectionalStream { send, recv });
+                }
+                Ok::<_, String>(())
+            }
+            .await;
+            if let Err(e) = result {
+                report_stream_error(
+                    Some(&notification),
+                    format!("Failed to read incoming stream header {e}"),
+                );
+            }
+        });
+    }
+}
+
+impl WebTransportTask {
+    /// Sends data to a WebTransport connection.
+    pub fn send_datagram(transport: Rc<WebTransport>, data: Vec<u8>) {
+        Self::datagram(transport, data, None);
+    }
+
+    /// Like [`WebTransportTask::send_datagram`], but gives up on `data` if the session was not
+    /// ready to send it within `deadline`, and passes it to `on_expired` instead.
+    pub fn send_datagram_with_deadline(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        deadline: Duration,
+        on_expired: Callback<Vec<u8>>,
+    ) {
+        Self::datagram(transport, data, Some((deadline, on_expired)));
+    }
+
+    fn datagram(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        deadline: Option<(Duration, Callback<Vec<u8>>)>,
+    ) {
+        let queued_at = Instant::now();
+        spawn_local(async move {
+            let result: Result<(), String> = async {
+                let established = transport.established().await?;
+                if let Some((deadline, on_expired)) = deadline {
+                    if queued_at.elapsed() > deadline {
+                        on_expired.emit(data);
+                        return Ok(());
+                    }
+                }
+                let mut datagram = Vec::with_capacity(data.len() + 8);
+                varint::encode(established.session_id / 4, &mut datagram);
+                datagram.extend_from_slice(&data);
+                established
+                    .connection
+                    .send_datagram(datagram.into())
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+            if let Err(e) = result {
+                transport.fail(e);
+            }
+        });
+    }
+
+    pub fn send_unidirectional_stream(transport: Rc<WebTransport>, data: Vec<u8>) {
+        Self::unidirectional_stream(transport, data, None);
+    }
+
+    /// Like [`WebTransportTask::send_unidirectional_stream`], but competes for bandwidth with
+    /// the session's other streams according to `send_order`: streams with a higher send order
+    /// are sent first.
+    pub fn send_unidirectional_stream_with_order(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        send_order: i64,
+    ) {
+        Self::unidirectional_stream(transport, data, Some(send_order));
+    }
+
+    fn unidirectional_stream(transport: Rc<WebTransport>, data: Vec<u8>, send_order: Option<i64>) {
+        spawn_local(async move {
+            let result = Self::write_unidirectional_stream(&transport, &data, send_order).await;
+            if let Err(e) = result {
+                transport.fail(e);
+            }
+        });
+    }
+
+    /// Sends `data` on a new unidirectional stream and resolves once the stream is finished.
+    /// Unlike [`WebTransportTask::send_unidirectional_stream`], a failure only affects this
+    /// stream and is returned.
+    pub async fn send_unidirectional_stream_finished(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+    ) -> Result<(), WebTransportError> {
+        Self::write_unidirectional_stream(&transport, &data, None)
+            .await
+            .map_err(WebTransportError::StreamError)
+    }
+
+    async fn write_unidirectional_stream(
+        transport: &WebTransport,
+        data: &[u8],
+        send_order: Option<i64>,
+    ) -> Result<(), String> {
+        let established = transport.established().await?;
+        let mut stream = established
+            .connection
+            .open_uni()
+            .await
+            .map_err(|e| format!("failed to create stream {e}"))?;
+        if let Some(send_order) = send_order {
+            let priority = send_order.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+            stream.set_priority(priority).map_err(|e| e.to_string())?;
+        }
+        let header = established.stream_header(h3::STREAM_TYPE_WEBTRANSPORT);
+        stream
+            .write_all(&header)
+            .await
+            .map_err(|e| format!("Error writing to stream: {e}"))?;
+        stream
+            .write_all(data)
+            .await
+            .map_err(|e| format!("Error writing to stream: {e}"))?;
+        stream
+            .finish()
+            .map_err(|e| format!("Error closing stream {e}"))?;
+        // Resolves once the peer has acknowledged everything.
+        stream
+            .stopped()
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Error closing stream {e}"))
+    }
+
+    /// Sends `data` on a new bidirectional stream and passes every chunk received on it to
+    /// `callback`. A failure only affects this stream.
+    pub fn send_bidirectional_stream(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        callback: Callback<Vec<u8>>,
+    ) {
+        Self::send_bidirectional_stream_reporting(transport, data, callback, None);
+    }
+
+    /// Like [`WebTransportTask::send_bidirectional_stream`], reporting a failure to
+    /// `notification` as [`WebTransportStatus::StreamError`] when given one.
+    fn send_bidirectional_stream_reporting(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        callback: Callback<Vec<u8>>,
+        notification: Option<Callback<WebTransportStatus>>,
+    ) {
+        spawn_local(async move {
+            let result = Self::bidirectional_stream(&transport, data, callback).await;
+            if let Err(e) = result {
+                report_stream_error(notification.as_ref(), e);
+            }
+        });
+    }
+
+    /// Sends `data` on a new bidirectional stream and resolves with everything the server
+    /// wrote back once it finishes the stream.
+    pub async fn request_bidirectional_stream(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, WebTransportError> {
+        let response = Rc::new(RefCell::new(Vec::new()));
+        let collect = {
+            let response = response.clone();
+            Callback::from(move |chunk: Vec<u8>| response.borrow_mut().extend(chunk))
+        };
+        Self::bidirectional_stream(&transport, data, collect)
+            .await
+            .map_err(WebTransportError::StreamError)?;
+        let response = response.take();
+        Ok(response)
+    }
+
+    /// Reads the connection's counters. quinn doesn't track datagrams separately, so only the
+    /// packet level counts are filled in.
+    pub async fn stats(transport: Rc<WebTransport>) -> Result<TransportStats, WebTransportError> {
+        let established = transport
+            .established()
+            .await
+            .map_err(WebTransportError::StreamError)?;
+        let stats = established.connection.stats();
+        Ok(TransportStats {
+            bytes_sent: stats.udp_tx.bytes,
+            bytes_received: stats.udp_rx.bytes,
+            packets_sent: stats.path.sent_packets,
+            packets_lost: stats.path.lost_packets,
+            smoothed_rtt: Some(stats.path.rtt),
+            ..TransportStats::default()
+        })
+    }
+
+    /// Opens a bidirectional stream that stays open until the returned writer is dropped.
+    /// Every chunk received on it is passed to `callback`. A failure only affects this stream.
+    pub fn open_bidirectional_stream(
+        transport: Rc<WebTransport>,
+        callback: Callback<Vec<u8>>,
+    ) -> StreamWriter {
+        Self::open_bidirectional_stream_reporting(transport, callback, None)
+    }
+
+    /// Like [`WebTransportTask::open_bidirectional_stream`], reporting a failure to
+    /// `notification` as [`WebTransportStatus::StreamError`] when given one.
+    fn open_bidirectional_stream_reporting(
+        transport: Rc<WebTransport>,
+        callback: Callback<Vec<u8>>,
+        notification: Option<Callback<WebTransportStatus>>,
+    ) -> StreamWriter {
+        let (sender, mut receiver) = mpsc::unbounded::<Vec<u8>>();
+        spawn_local(async move {
+            let reader_notification = notification.clone();
+            let result: Result<(), String> = async {
+                let established = transport.established().await?;
+                let (mut send, mut recv) = established
+                    .connection
+                    .open_bi()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                spawn_local(async move {
+                    loop {
+                        match recv.read_chunk(usize::MAX, true).await {
+                            Ok(Some(chunk)) => callback.emit(chunk.bytes.to_vec()),
+                            Ok(None) => break,
+                            Err(e) => {
+                                let _ = recv.stop(0u32.into());
+                                report_stream_error(
+                                    reader_notification.as_ref(),
+                                    format!("Failed to read incoming stream {e}"),
+                                );
+                                break;
+                            }
+                        }
+                    }
+                });
+                let header = established.stream_header(h3::SIGNAL_WEBTRANSPORT_STREAM);
+                send.write_all(&header).await.map_err(|e| e.to_string())?;
+                while let Some(data) = receiver.next().await {
+                    send.write_all(&data).await.map_err(|e| e.to_string())?;
+                }
+                send.finish().map_err(|e| e.to_string())
+            }
+            .await;
+            if let Err(e) = result {
+                report_stream_error(notification.as_ref(), e);
+            }
+        });
+        let writer = sender.clone();
+        StreamWriter::new(
+            move |data| {
+                let _ = writer.unbounded_send(data);
+            },
+            move || sender.close_channel(),
+        )
+    }
+
+    async fn bidirectional_stream(
+        transport: &WebTransport,
+        data: Vec<u8>,
+        callback: Callback<Vec<u8>>,
+    ) -> Result<(), String> {
+        let established = transport.established().await?;
+        let (mut send, mut recv) = established
+            .connection
+            .open_bi()
+            .await
+            .map_err(|e| e.to_string())?;
+        let header = established.stream_header(h3::SIGNAL_WEBTRANSPORT_STREAM);
+        send.write_all(&header).await.map_err(|e| e.to_string())?;
+        send.write_all(&data).await.map_err(|e| e.to_string())?;
+        send.finish().map_err(|e| e.to_string())?;
+        loop {
+            match recv.read_chunk(usize::MAX, true).await {
+                Ok(Some(chunk)) => callback.emit(chunk.bytes.to_vec()),
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    let _ = recv.stop(0u32.into());
+                    return Err(format!("Failed to read incoming stream {e}"));
+                }
+            }
+        }
+    }
+}
+
+impl Transport for WebTransportTask {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        WebTransportTask::datagram(self.transport.clone(), data, self.datagram_deadline(None));
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        WebTransportTask::datagram(
+            self.transport.clone(),
+            data,
+            self.datagram_deadline(Some(deadline)),
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        WebTransportTask::send_unidirectional_stream(self.transport.clone(), data);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        WebTransportTask::send_unidirectional_stream_with_order(
+            self.transport.clone(),
+            data,
+            send_order,
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        WebTransportTask::send_unidirectional_stream_finished(self.transport.clone(), data)
+            .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        WebTransportTask::send_bidirectional_stream_reporting(
+            self.transport.clone(),
+            data,
+            callback,
+            Some(self.notification.clone()),
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        WebTransportTask::request_bidirectional_stream(self.transport.clone(), data).boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        WebTransportTask::open_bidirectional_stream_reporting(
+            self.transport.clone(),
+            on_data,
+            Some(self.notification.clone()),
+        )
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let transport = self.transport.clone();
+        let deadline = self.datagram_deadline(None);
+        let send = Rc::new(move |data| {
+            WebTransportTask::datagram(transport.clone(), data, deadline.clone());
+        });
+        self.datagram_router.channel(id, on_message, send)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.datagram_router.subscribe(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        let expired = self.expired_datagrams.clone();
+        WebTransportTask::stats(self.transport.clone())
+            .map(move |stats| {
+                stats.map(|stats| TransportStats {
+                    datagrams_expired_outgoing: expired.get(),
+                    ..stats
+                })
+            })
+            .boxed_local()
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        WebTransportTask::close(self)
+    }
+}
@@ -0,0 +1,340 @@
+//! Options for a single stream, gathered in a [`StreamOptions`] builder rather than in ever more
+//! parameters of the methods opening streams.
+//!
+//! The `*_with_options` methods of [`Transport`](crate::transport::Transport) take one. Data
+//! written is compressed, given a checksum, then framed as the [`Framing`] asked for, and data
+//! received on the stream is split back into messages, checked and decompressed the same way
+//! before being passed on, along with the stream's label.
+//!
+//! Messages too large to hold in memory, as the wasm heap often is, can be streamed into a sink
+//! of the application's instead, see [`LargeMessageSink`].
+
+use futures::channel::mpsc;
+use futures::io::{AsyncWrite, AsyncWriteExt};
+use futures::StreamExt;
+use std::{cell::RefCell, fmt, pin::Pin, rc::Rc};
+use yew::callback::Callback;
+use yew::platform::spawn_local;
+
+#[cfg(feature = "checksum")]
+use crate::framing::Checksum;
+use crate::framing::{Deframer, Framing, MessagePart};
+use crate::logging::STREAMS;
+use crate::transport::StreamWriter;
+use crate::webtransport::WebTransportStatus;
+
+/// How the messages written on a stream are compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Messages are sent as they are.
+    #[default]
+    None,
+    /// Messages are compressed with deflate, at a level from 0 to 10.
+    #[cfg(feature = "compression")]
+    Deflate { level: u8 },
+}
+
+impl Compression {
+    /// Deflate at level 6, a good tradeoff between speed and size.
+    #[cfg(feature = "compression")]
+    pub fn deflate() -> Self {
+        Compression::Deflate { level: 6 }
+    }
+
+    fn compress(self, data: Vec<u8>) -> Vec<u8> {
+        match self {
+            Compression::None => data,
+            #[cfg(feature = "compression")]
+            Compression::Deflate { level } => miniz_oxide::deflate::compress_to_vec(&data, level),
+        }
+    }
+
+    fn decompress(self, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::None => Ok(data),
+            #[cfg(feature = "compression")]
+            Compression::Deflate { .. } => miniz_oxide::inflate::decompress_to_vec(&data)
+                .map_err(|e| format!("failed to inflate a message: {:?}", e.status)),
+        }
+    }
+}
+
+/// A message too large to be passed whole, see [`LargeMessageSink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LargeMessage {
+    /// The label of the stream it is received on.
+    pub label: Option<Rc<str>>,
+    /// Its length in bytes.
+    pub len: u64,
+}
+
+/// Where messages longer than `threshold` bytes are written as they are received, instead of
+/// being passed whole along with the others. `open` is called as such a message starts, and
+/// returns the sink its bytes are written to: a file, an IndexedDB writer, and the like. The
+/// sink is closed once the message is written entirely, and left as it is if the stream ends
+/// first or a write fails, which is reported like other errors on the stream.
+///
+/// Only messages with a length prefix, and that are neither compressed nor checksummed, can be
+/// written as they are received; others are passed whole whatever their length.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LargeMessageSink {
+    pub threshold: usize,
+    pub open: Callback<LargeMessage, Pin<Box<dyn AsyncWrite>>>,
+}
+
+/// Options for one stream. The defaults open the stream like the methods without options do.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StreamOptions {
+    /// Streams with a higher send order are sent first. Backends without prioritization ignore
+    /// it.
+    pub send_order: Option<i64>,
+    /// How every write is framed as a message, so the peer gets it whole however the stream
+    /// chunks it, and how data received is split back into the messages the peer framed.
+    pub framing: Framing,
+    /// How messages are compressed. Compressed messages are always framed, with
+    /// [`Framing::Varint`] unless another framing is set, so the peer can tell where each one
+    /// ends.
+    pub compression: Compression,
+    /// The checksum appended to every message, and checked on every message received.
+    /// Like compressed ones, messages with a checksum are always framed.
+    #[cfg(feature = "checksum")]
+    pub checksum: Option<Checksum>,
+    /// Passed along with the data received on the stream, and prefixes the errors logged
+    /// about it instead of the session's label.
+    pub label: Option<Rc<str>>,
+    /// Where large messages received are written rather than passed whole.
+    pub large_messages: Option<LargeMessageSink>,
+}
+
+impl StreamOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send_order(mut self, send_order: i64) -> Self {
+        self.send_order = Some(send_order);
+        self
+    }
+
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[cfg(feature = "checksum")]
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(Rc::from(label.into()));
+        self
+    }
+
+    pub fn large_messages(
+        mut self,
+        threshold: usize,
+        open: Callback<LargeMessage, Pin<Box<dyn AsyncWrite>>>,
+    ) -> Self {
+        self.large_messages = Some(LargeMessageSink { threshold, open });
+        self
+    }
+
+    /// The framing used on the stream: the one set, or [`Framing::Varint`] if messages are
+    /// compressed or checksummed and none was.
+    pub fn effective_framing(&self) -> Framing {
+        match self.framing {
+            Framing::None if self.transforms_messages() => Framing::Varint,
+            framing => framing,
+        }
+    }
+
+    fn transforms_messages(&self) -> bool {
+        #[cfg(feature = "checksum")]
+        if self.checksum.is_some() {
+            return true;
+        }
+        self.compression != Compression::None
+    }
+
+    /// Turns a write into the bytes sent on the stream.
+    pub fn encode(&self, data: Vec<u8>) -> Vec<u8> {
+        #[allow(unused_mut)]
+        let mut data = self.compression.compress(data);
+        #[cfg(feature = "checksum")]
+        if let Some(checksum) = self.checksum {
+            checksum.append(&mut data);
+        }
+        let framing = self.effective_framing();
+        if framing == Framing::None {
+            return data;
+        }
+        let mut framed = Vec::with_capacity(data.len() + 10);
+        framing.encode(&data, &mut framed);
+        framed
+    }
+
+    /// Wraps `writer` so that everything written through it is encoded first.
+    pub fn writer(&self, writer: StreamWriter) -> StreamWriter {
+        let options = self.clone();
+        StreamWriter::new(move |data| writer.write(options.encode(data)), || {})
+    }
+
+    /// Wraps `on_data` into a callback taking the chunks read from the stream. Messages that
+    /// fail their checksum or to decompress, and data that doesn't follow the framing, are
+    /// logged and dropped.
+    pub fn receiver(&self, on_data: Callback<LabeledData>) -> Callback<Vec<u8>> {
+        self.receiver_reporting(on_data, None)
+    }
+
+    /// Like [`StreamOptions::receiver`], reporting the messages dropped to `notification` as
+    /// [`WebTransportStatus::StreamError`] rather than logging them, if there is one.
+    pub(crate) fn receiver_reporting(
+        &self,
+        on_data: Callback<LabeledData>,
+        notification: Option<Callback<WebTransportStatus>>,
+    ) -> Callback<Vec<u8>> {
+        let label = self.label.clone();
+        let framing = self.effective_framing();
+        if framing == Framing::None {
+            return on_data.reform(move |data| LabeledData {
+                label: label.clone(),
+                data,
+            });
+        }
+        let compression = self.compression;
+        #[cfg(feature = "checksum")]
+        let checksum = self.checksum;
+        let deframer = RefCell::new(Deframer::new(framing));
+        let report: Report = Rc::new(move |label, error| match &notification {
+            Some(notification) => notification.emit(WebTransportStatus::StreamError(match label {
+                Some(label) => format!("[{label}] {error}"),
+                None => error.to_string(),
+            })),
+            None => warn(label, error),
+        });
+        let large_messages = self
+            .large_messages
+            .clone()
+            .filter(|_| !self.transforms_messages());
+        let large_message = RefCell::new(None::<mpsc::UnboundedSender<Vec<u8>>>);
+        Callback::from(move |chunk: Vec<u8>| {
+            deframer.borrow_mut().push(&chunk);
+            loop {
+                let part = match &large_messages {
+                    Some(sink) => deframer.borrow_mut().next_part(sink.threshold),
+                    None => deframer
+                        .borrow_mut()
+                        .next_message()
+                        .map(|message| message.map(MessagePart::Whole)),
+                };
+                let message = match part {
+                    Ok(Some(MessagePart::Whole(message))) => message,
+                    Ok(Some(MessagePart::Start { len })) => {
+                        let Some(sink) = &large_messages else {
+                            continue;
+                        };
+                        let message = LargeMessage {
+                            label: label.clone(),
+                            len,
+                        };
+                        *large_message.borrow_mut() =
+                            Some(write_large_message(sink, message, report.clone()));
+                        continue;
+                    }
+                    Ok(Some(MessagePart::Body { data, last })) => {
+                        let mut large_message = large_message.borrow_mut();
+                        if let Some(parts) = large_message.as_ref() {
+                            let _ = parts.unbounded_send(data);
+                        }
+                        if last {
+                            *large_message = None;
+                        }
+                        continue;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        report(label.as_deref(), &e);
+                        continue;
+                    }
+                };
+                #[cfg(feature = "checksum")]
+                let message = match checksum {
+                    Some(checksum) => match checksum.verify(message) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            report(label.as_deref(), &e);
+                            continue;
+                        }
+                    },
+                    None => message,
+                };
+                match compression.decompress(message) {
+                    Ok(data) => on_data.emit(LabeledData {
+                        label: label.clone(),
+                        data,
+                    }),
+                    Err(e) => report(label.as_deref(), &e),
+                }
+            }
+        })
+    }
+}
+
+type Report = Rc<dyn Fn(Option<&str>, &dyn fmt::Display)>;
+
+/// Opens the sink of `message`, and writes the parts sent on the returned channel to it in the
+/// background, closing it once they add up to the whole message.
+fn write_large_message(
+    sink: &LargeMessageSink,
+    message: LargeMessage,
+    report: Report,
+) -> mpsc::UnboundedSender<Vec<u8>> {
+    let mut writer = sink.open.emit(message.clone());
+    let (parts, mut received) = mpsc::unbounded::<Vec<u8>>();
+    spawn_local(async move {
+        let label = message.label.as_deref();
+        let mut written = 0;
+        while let Some(part) = received.next().await {
+            if let Err(e) = writer.write_all(&part).await {
+                report(label, &format!("failed to write a large message: {e}"));
+                return;
+            }
+            written += part.len() as u64;
+        }
+        if written < message.len {
+            report(
+                label,
+                &format!(
+                    "stream ended after {written} of the {} bytes of a large message",
+                    message.len
+                ),
+            );
+            return;
+        }
+        if let Err(e) = writer.close().await {
+            report(label, &format!("failed to close a large message: {e}"));
+        }
+    });
+    parts
+}
+
+fn warn(label: Option<&str>, error: &dyn fmt::Display) {
+    match label {
+        Some(label) => log::warn!(target: STREAMS, "[{label}] {error}"),
+        None => log::warn!(target: STREAMS, "{error}"),
+    }
+}
+
+/// Data received on a stream opened with [`StreamOptions`], along with the stream's label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabeledData {
+    pub label: Option<Rc<str>>,
+    pub data: Vec<u8>,
+}
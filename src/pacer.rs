@@ -0,0 +1,217 @@
+//! Releasing small payloads at a steady rate, for voice.
+//!
+//! Encoders tend to hand over packets in bursts. A [`Pacer`] queues them and releases one per
+//! slot of a fixed grid, `origin + k * interval`, so packets leave evenly spaced however they
+//! were produced, and the spacing doesn't drift the way chained timers do. The timer only runs
+//! while there is something queued.
+
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc, time::Duration};
+use yew::callback::Callback;
+
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+
+/// Tuning for a [`Pacer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacerConfig {
+    /// Time between two releases.
+    pub interval: Duration,
+    /// Payloads queued at most; the oldest is dropped beyond that.
+    pub max_queue: usize,
+}
+
+impl Default for PacerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(20),
+            max_queue: 50,
+        }
+    }
+}
+
+/// Counters kept by a [`Pacer`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacerStats {
+    pub released: u64,
+    pub dropped: u64,
+    /// Deepest the queue has been.
+    pub max_queue_depth: usize,
+}
+
+struct PacerState {
+    config: PacerConfig,
+    origin: Duration,
+    queue: VecDeque<Vec<u8>>,
+    last_slot: Option<u128>,
+    timer_armed: bool,
+    stats: PacerStats,
+    on_release: Callback<Vec<u8>>,
+}
+
+impl PacerState {
+    fn interval_nanos(&self) -> u128 {
+        self.config.interval.as_nanos().max(1)
+    }
+
+    /// The first slot at or after `now` that comes after the last release.
+    fn next_slot(&self, now: Duration) -> u128 {
+        let interval = self.interval_nanos();
+        let elapsed = now.saturating_sub(self.origin).as_nanos();
+        let slot = elapsed.div_ceil(interval);
+        match self.last_slot {
+            Some(last) => slot.max(last + 1),
+            None => slot,
+        }
+    }
+
+    fn slot_time(&self, slot: u128) -> Duration {
+        let nanos = slot * self.interval_nanos();
+        self.origin
+            + Duration::new(
+                (nanos / 1_000_000_000) as u64,
+                (nanos % 1_000_000_000) as u32,
+            )
+    }
+}
+
+struct Inner {
+    state: RefCell<PacerState>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl Inner {
+    /// Releases the head of the queue if its slot has come, then waits for the next slot.
+    fn run(self: &Rc<Self>) {
+        let now = self.scheduler.now();
+        let (release, wait) = {
+            let mut state = self.state.borrow_mut();
+            if state.queue.is_empty() {
+                return;
+            }
+            let slot = state.next_slot(now);
+            let at = state.slot_time(slot);
+            if at > now {
+                (None, Some(at - now))
+            } else {
+                state.last_slot = Some(slot);
+                state.stats.released += 1;
+                let payload = state.queue.pop_front();
+                let wait = (!state.queue.is_empty()).then(|| {
+                    let next = state.slot_time(slot + 1);
+                    next.saturating_sub(now)
+                });
+                (
+                    payload.map(|payload| (state.on_release.clone(), payload)),
+                    wait,
+                )
+            }
+        };
+        if let Some((on_release, payload)) = release {
+            on_release.emit(payload);
+        }
+        if let Some(wait) = wait {
+            self.arm_timer(wait);
+        }
+    }
+
+    fn arm_timer(self: &Rc<Self>, wait: Duration) {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.timer_armed {
+                return;
+            }
+            state.timer_armed = true;
+        }
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            wait,
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.state.borrow_mut().timer_armed = false;
+                    inner.run();
+                }
+            }),
+        );
+    }
+}
+
+/// Releases queued payloads one per interval.
+pub struct Pacer {
+    inner: Rc<Inner>,
+}
+
+impl Pacer {
+    /// Creates a pacer releasing payloads to `on_release`, timed by the platform timers.
+    pub fn new(config: PacerConfig, on_release: Callback<Vec<u8>>) -> Self {
+        Self::with_scheduler(config, on_release, Rc::new(PlatformScheduler))
+    }
+
+    /// Creates a pacer releasing payloads to `on_release`, timed by `scheduler`.
+    pub fn with_scheduler(
+        config: PacerConfig,
+        on_release: Callback<Vec<u8>>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let origin = scheduler.now();
+        Self {
+            inner: Rc::new(Inner {
+                state: RefCell::new(PacerState {
+                    config,
+                    origin,
+                    queue: VecDeque::new(),
+                    last_slot: None,
+                    timer_armed: false,
+                    stats: PacerStats::default(),
+                    on_release,
+                }),
+                scheduler,
+            }),
+        }
+    }
+
+    /// Creates a pacer sending the payloads as datagrams on `transport`.
+    pub fn for_datagrams<T: Transport + 'static>(transport: Rc<T>, config: PacerConfig) -> Self {
+        Self::new(
+            config,
+            Callback::from(move |payload| transport.send_datagram(payload)),
+        )
+    }
+
+    /// Queues `payload` for the next free slot.
+    pub fn push(&self, payload: Vec<u8>) {
+        {
+            let mut state = self.inner.state.borrow_mut();
+            state.queue.push_back(payload);
+            while state.queue.len() > state.config.max_queue.max(1) {
+                state.queue.pop_front();
+                state.stats.dropped += 1;
+            }
+            state.stats.max_queue_depth = state.stats.max_queue_depth.max(state.queue.len());
+        }
+        self.inner.run();
+    }
+
+    /// Drops every queued payload.
+    pub fn clear(&self) {
+        self.inner.state.borrow_mut().queue.clear();
+    }
+
+    /// Number of payloads waiting for their slot.
+    pub fn queued(&self) -> usize {
+        self.inner.state.borrow().queue.len()
+    }
+
+    pub fn stats(&self) -> PacerStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl fmt::Debug for Pacer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("Pacer")
+            .field("config", &state.config)
+            .field("queued", &state.queue.len())
+            .finish()
+    }
+}
@@ -0,0 +1,166 @@
+//! Reusing receive buffers across datagrams.
+//!
+//! Copying every incoming datagram into a fresh `Vec<u8>` keeps the allocator busy when
+//! thousands arrive per second. A [`BufferPool`] keeps the buffers of datagrams the application
+//! is done with, and hands them out again for the next ones: a [`PooledBuffer`] goes back to its
+//! pool when dropped. Connect with [`WebTransportService::connect_pooled`] to receive datagrams
+//! this way.
+//!
+//! [`WebTransportService::connect_pooled`]: crate::webtransport::WebTransportService::connect_pooled
+
+use std::{
+    cell::RefCell,
+    fmt, mem,
+    ops::{Deref, DerefMut},
+    rc::{Rc, Weak},
+};
+
+/// Counters kept by a [`BufferPool`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Buffers handed out that had to be allocated.
+    pub allocated: u64,
+    /// Buffers handed out that were reused.
+    pub reused: u64,
+    /// Buffers dropped back into a full pool, and freed.
+    pub discarded: u64,
+}
+
+struct PoolState {
+    buffers: Vec<Vec<u8>>,
+    max_buffers: usize,
+    stats: PoolStats,
+}
+
+/// A pool of reusable byte buffers.
+///
+/// Cloning a `BufferPool` yields another handle to the same pool.
+#[derive(Clone)]
+pub struct BufferPool {
+    state: Rc<RefCell<PoolState>>,
+}
+
+impl BufferPool {
+    /// Creates a pool keeping at most `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(PoolState {
+                buffers: Vec::new(),
+                max_buffers,
+                stats: PoolStats::default(),
+            })),
+        }
+    }
+
+    /// Hands out a buffer of `len` zeroed bytes, reusing an idle one when there is one.
+    pub fn take(&self, len: usize) -> PooledBuffer {
+        let data = {
+            let mut state = self.state.borrow_mut();
+            match state.buffers.pop() {
+                Some(data) => {
+                    state.stats.reused += 1;
+                    data
+                }
+                None => {
+                    state.stats.allocated += 1;
+                    Vec::new()
+                }
+            }
+        };
+        let mut buffer = self.attach(data);
+        buffer.clear();
+        buffer.resize(len, 0);
+        buffer
+    }
+
+    /// Wraps `data` so that it goes back to the pool when dropped.
+    pub fn attach(&self, data: Vec<u8>) -> PooledBuffer {
+        PooledBuffer {
+            data,
+            pool: Rc::downgrade(&self.state),
+        }
+    }
+
+    /// Number of idle buffers.
+    pub fn available(&self) -> usize {
+        self.state.borrow().buffers.len()
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.state.borrow().stats.clone()
+    }
+}
+
+impl Default for BufferPool {
+    /// A pool keeping up to 64 idle buffers.
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("BufferPool")
+            .field("available", &state.buffers.len())
+            .field("max_buffers", &state.max_buffers)
+            .field("stats", &state.stats)
+            .finish()
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], returned to it when dropped. Dereferences to the
+/// `Vec<u8>` it holds.
+pub struct PooledBuffer {
+    data: Vec<u8>,
+    pool: Weak<RefCell<PoolState>>,
+}
+
+impl PooledBuffer {
+    /// Takes the bytes out of the pool's keeping; the buffer won't go back to the pool.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.pool = Weak::new();
+        mem::take(&mut self.data)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.data
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(pool) = self.pool.upgrade() else {
+            return;
+        };
+        let mut state = pool.borrow_mut();
+        if state.buffers.len() < state.max_buffers && self.data.capacity() > 0 {
+            let data = mem::take(&mut self.data);
+            state.buffers.push(data);
+        } else {
+            state.stats.discarded += 1;
+        }
+    }
+}
+
+impl fmt::Debug for PooledBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledBuffer").field(&self.data).finish()
+    }
+}
@@ -0,0 +1,229 @@
+//! Topic based publish/subscribe over a [`ReliableChannel`].
+//!
+//! A [`PubSub`] carries every topic on one reliable channel, with messages laid out as
+//!
+//! ```text
+//! kind (u8) | topic length (varint) | topic (utf-8) | payload
+//! ```
+//!
+//! where kind `0` subscribes to the topic, `1` unsubscribes from it, and `2` publishes the
+//! payload on it. The server passes what is published on a topic to the sessions subscribed to
+//! it. The server only hears of a topic when its first local subscription starts and its last
+//! one ends, however many components subscribe in between. Subscriptions and unsubscriptions
+//! received are ignored, so a session connected to an echo server gets its own publications
+//! back.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::{Rc, Weak},
+};
+use yew::callback::Callback;
+
+use crate::reliable::ReliableChannel;
+use crate::transport::Transport;
+use crate::varint;
+
+const KIND_SUBSCRIBE: u8 = 0;
+const KIND_UNSUBSCRIBE: u8 = 1;
+const KIND_PUBLISH: u8 = 2;
+
+/// A message of the pub/sub protocol, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TopicMessage {
+    Subscribe(String),
+    Unsubscribe(String),
+    Publish { topic: String, payload: Vec<u8> },
+}
+
+impl TopicMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, topic, payload): (u8, &str, &[u8]) = match self {
+            TopicMessage::Subscribe(topic) => (KIND_SUBSCRIBE, topic, &[]),
+            TopicMessage::Unsubscribe(topic) => (KIND_UNSUBSCRIBE, topic, &[]),
+            TopicMessage::Publish { topic, payload } => (KIND_PUBLISH, topic, payload),
+        };
+        let mut out = Vec::with_capacity(topic.len() + payload.len() + 9);
+        out.push(kind);
+        varint::encode(topic.len() as u64, &mut out);
+        out.extend_from_slice(topic.as_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Decodes a message, or returns `None` if it is malformed or of an unknown kind.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (&kind, rest) = data.split_first()?;
+        let (len, prefix) = varint::decode(rest)?;
+        let rest = &rest[prefix..];
+        let len = usize::try_from(len).ok().filter(|&len| len <= rest.len())?;
+        let (topic, payload) = rest.split_at(len);
+        let topic = std::str::from_utf8(topic).ok()?.to_string();
+        match kind {
+            KIND_SUBSCRIBE if payload.is_empty() => Some(TopicMessage::Subscribe(topic)),
+            KIND_UNSUBSCRIBE if payload.is_empty() => Some(TopicMessage::Unsubscribe(topic)),
+            KIND_PUBLISH => Some(TopicMessage::Publish {
+                topic,
+                payload: payload.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The callbacks subscribed to a topic, with their subscription's token.
+type Subscribers = Vec<(u64, Callback<Vec<u8>>)>;
+
+struct Inner {
+    channel: ReliableChannel,
+    next_token: Cell<u64>,
+    topics: RefCell<HashMap<String, Subscribers>>,
+}
+
+impl Inner {
+    fn receive(&self, data: Vec<u8>) {
+        let Some(TopicMessage::Publish { topic, payload }) = TopicMessage::decode(&data) else {
+            return;
+        };
+        // Subscribers may unsubscribe while being called.
+        let subscribers: Vec<_> = match self.topics.borrow().get(&topic) {
+            Some(subscribers) => subscribers
+                .iter()
+                .map(|(_, callback)| callback.clone())
+                .collect(),
+            None => return,
+        };
+        if let Some((last, others)) = subscribers.split_last() {
+            for callback in others {
+                callback.emit(payload.clone());
+            }
+            last.emit(payload);
+        }
+    }
+
+    fn unsubscribe(&self, topic: &str, token: u64) {
+        let mut topics = self.topics.borrow_mut();
+        let Some(subscribers) = topics.get_mut(topic) else {
+            return;
+        };
+        subscribers.retain(|(subscriber, _)| *subscriber != token);
+        if subscribers.is_empty() {
+            topics.remove(topic);
+            drop(topics);
+            let message = TopicMessage::Unsubscribe(topic.to_string());
+            self.channel.send(&message.encode());
+        }
+    }
+}
+
+/// Publishes and subscribes to topics over one reliable channel. Clones share the channel and
+/// its subscriptions.
+#[derive(Clone)]
+pub struct PubSub {
+    inner: Rc<Inner>,
+}
+
+impl PubSub {
+    /// Opens the channel on `transport`.
+    pub fn open<T: Transport + ?Sized>(transport: &T) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let receiver = inner.clone();
+            Inner {
+                channel: ReliableChannel::open(
+                    transport,
+                    Callback::from(move |data: Vec<u8>| {
+                        if let Some(inner) = receiver.upgrade() {
+                            inner.receive(data);
+                        }
+                    }),
+                ),
+                next_token: Cell::new(0),
+                topics: RefCell::default(),
+            }
+        });
+        Self { inner }
+    }
+
+    /// Passes every payload published on `topic` to `on_message`, until the returned
+    /// subscription is dropped.
+    pub fn subscribe(
+        &self,
+        topic: impl Into<String>,
+        on_message: Callback<Vec<u8>>,
+    ) -> TopicSubscription {
+        let topic = topic.into();
+        let token = self.inner.next_token.get();
+        self.inner.next_token.set(token + 1);
+        let first = {
+            let mut topics = self.inner.topics.borrow_mut();
+            let subscribers = topics.entry(topic.clone()).or_default();
+            subscribers.push((token, on_message));
+            subscribers.len() == 1
+        };
+        if first {
+            let message = TopicMessage::Subscribe(topic.clone());
+            self.inner.channel.send(&message.encode());
+        }
+        TopicSubscription {
+            pubsub: self.clone(),
+            topic,
+            token,
+        }
+    }
+
+    /// Publishes `payload` on `topic`.
+    pub fn publish(&self, topic: &str, payload: &[u8]) {
+        let message = TopicMessage::Publish {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+        };
+        self.inner.channel.send(&message.encode());
+    }
+
+    /// Whether anything subscribed to `topic` through this channel.
+    pub fn is_subscribed(&self, topic: &str) -> bool {
+        self.inner.topics.borrow().contains_key(topic)
+    }
+}
+
+impl PartialEq for PubSub {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for PubSub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut topics: Vec<String> = self.inner.topics.borrow().keys().cloned().collect();
+        topics.sort_unstable();
+        f.debug_struct("PubSub").field("topics", &topics).finish()
+    }
+}
+
+/// A subscription to a topic of a [`PubSub`]. Dropping it unsubscribes.
+pub struct TopicSubscription {
+    pubsub: PubSub,
+    topic: String,
+    token: u64,
+}
+
+impl TopicSubscription {
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+}
+
+impl Drop for TopicSubscription {
+    fn drop(&mut self) {
+        self.pubsub.inner.unsubscribe(&self.topic, self.token);
+    }
+}
+
+impl fmt::Debug for TopicSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TopicSubscription")
+            .field("topic", &self.topic)
+            .finish()
+    }
+}
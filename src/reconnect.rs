@@ -0,0 +1,408 @@
+//! Re-establishing a session once it is lost.
+//!
+//! A [`ReconnectingSession`] connects, and whenever the session closes or fails to be
+//! established, connects again after a delay that doubles with every failed attempt, up to
+//! [`ReconnectPolicy::max_delay`]. Closing it through [`ReconnectingSession::close`] stops
+//! reconnecting.
+//!
+//! Sessions are created by a connector function, so that the same logic drives browser
+//! sessions, through [`ReconnectingSession::connect`], and mocked ones in tests.
+//!
+//! Long-lived state set up on a session, such as a [`StreamMux`](crate::mux::StreamMux),
+//! datagram channels or subscriptions, is gone with it. Registering its setup with
+//! [`ReconnectingSession::restore`] runs it again on every new session, so that the
+//! application doesn't need reconnect-specific code.
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::auth::TokenAuth;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+use crate::webtransport::{
+    WebTransportBuilder, WebTransportError, WebTransportStatus, WebTransportTask,
+};
+
+/// When to try connecting again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt after the session was lost.
+    pub initial_delay: Duration,
+    /// Longest delay between two attempts.
+    pub max_delay: Duration,
+    /// Attempts made in a row before giving up, or `None` to keep trying.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before attempt number `attempt`, counted from 1 since the session was lost.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.initial_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// Where a [`ReconnectingSession`] stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReconnectState {
+    Connecting,
+    Open,
+    /// Waiting to try again.
+    Waiting,
+    /// Stopped after [`ReconnectPolicy::max_attempts`] failed attempts.
+    GaveUp,
+    /// Closed through [`ReconnectingSession::close`].
+    Closed,
+}
+
+/// A setup registered with [`ReconnectingSession::restore`].
+trait Restore<T> {
+    /// Runs the setup on a new session.
+    fn open(&self, session: &Rc<T>);
+    /// Drops what the setup returned for the lost session.
+    fn lost(&self);
+}
+
+type Setup<T, H> = dyn Fn(&Rc<T>) -> H;
+
+struct Restorer<T, H> {
+    open: Box<Setup<T, H>>,
+    handle: RefCell<Option<Rc<H>>>,
+}
+
+impl<T, H> Restore<T> for Restorer<T, H> {
+    fn open(&self, session: &Rc<T>) {
+        let handle = (self.open)(session);
+        *self.handle.borrow_mut() = Some(Rc::new(handle));
+    }
+
+    fn lost(&self) {
+        let handle = self.handle.borrow_mut().take();
+        drop(handle);
+    }
+}
+
+/// A setup run again on every session of a [`ReconnectingSession`], returned by
+/// [`ReconnectingSession::restore`]. Dropping it stops running the setup, and drops what it
+/// returned for the current session.
+pub struct Restored<T, H> {
+    restorer: Rc<Restorer<T, H>>,
+}
+
+impl<T, H> Restored<T, H> {
+    /// What the setup returned for the current session, unless the session was lost and not
+    /// re-established yet.
+    pub fn get(&self) -> Option<Rc<H>> {
+        self.restorer.handle.borrow().clone()
+    }
+}
+
+impl<T, H> fmt::Debug for Restored<T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Restored")
+            .field("open", &self.restorer.handle.borrow().is_some())
+            .finish()
+    }
+}
+
+type Connector<T> =
+    dyn Fn(Callback<WebTransportStatus>) -> LocalBoxFuture<'static, Result<T, WebTransportError>>;
+
+struct Inner<T> {
+    connect: Box<Connector<T>>,
+    policy: ReconnectPolicy,
+    scheduler: Rc<dyn Scheduler>,
+    notification: Callback<WebTransportStatus>,
+    session: RefCell<Option<Rc<T>>>,
+    state: Cell<ReconnectState>,
+    /// Attempts failed since the session was last open.
+    attempts: Cell<u32>,
+    opened: Cell<bool>,
+    reconnects: Cell<u64>,
+    /// Incremented with every attempt, so that a lost session can't affect the next one.
+    generation: Cell<u64>,
+    restorers: RefCell<Vec<Weak<dyn Restore<T>>>>,
+}
+
+impl<T: Transport + 'static> Inner<T> {
+    fn dial(self: &Rc<Self>) {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+        self.state.set(ReconnectState::Connecting);
+        let inner = Rc::downgrade(self);
+        let notification = Callback::from(move |status| {
+            if let Some(inner) = inner.upgrade() {
+                inner.status(generation, status);
+            }
+        });
+        let mut connecting = (self.connect)(notification);
+        match (&mut connecting).now_or_never() {
+            Some(result) => self.connected(generation, result),
+            None => {
+                let inner = Rc::downgrade(self);
+                yew::platform::spawn_local(async move {
+                    let result = connecting.await;
+                    if let Some(inner) = inner.upgrade() {
+                        inner.connected(generation, result);
+                    }
+                });
+            }
+        }
+    }
+
+    fn connected(self: &Rc<Self>, generation: u64, result: Result<T, WebTransportError>) {
+        // The session may have reported it opened before `connect` returned.
+        let current = generation == self.generation.get()
+            && matches!(
+                self.state.get(),
+                ReconnectState::Connecting | ReconnectState::Open
+            );
+        match result {
+            Ok(session) if current => {
+                *self.session.borrow_mut() = Some(Rc::new(session));
+                if self.state.get() == ReconnectState::Open {
+                    self.restore();
+                }
+            }
+            Ok(session) => {
+                let _ = session.close();
+            }
+            Err(_) if current => self.retry(),
+            Err(_) => {}
+        }
+    }
+
+    fn status(self: &Rc<Self>, generation: u64, status: WebTransportStatus) {
+        if generation != self.generation.get() {
+            return;
+        }
+        match &status {
+            WebTransportStatus::Opened => {
+                self.state.set(ReconnectState::Open);
+                self.attempts.set(0);
+                if self.opened.replace(true) {
+                    self.reconnects.set(self.reconnects.get() + 1);
+                }
+                self.restore();
+            }
+            WebTransportStatus::Closed(_) | WebTransportStatus::Error(_) => {
+                self.lose();
+                let lost = self.session.borrow_mut().take();
+                if self.state.get() != ReconnectState::Closed {
+                    self.retry();
+                }
+                drop(lost);
+            }
+            WebTransportStatus::StreamError(_) => {}
+        }
+        self.notification.emit(status);
+    }
+
+    /// Runs every registered setup on the current session, if it is open.
+    fn restore(&self) {
+        let Some(session) = self.session.borrow().clone() else {
+            return;
+        };
+        for restorer in self.restorers() {
+            restorer.open(&session);
+        }
+    }
+
+    fn lose(&self) {
+        for restorer in self.restorers() {
+            restorer.lost();
+        }
+    }
+
+    /// The registered setups still alive, dropping the others.
+    fn restorers(&self) -> Vec<Rc<dyn Restore<T>>> {
+        let mut restorers = self.restorers.borrow_mut();
+        restorers.retain(|restorer| restorer.strong_count() > 0);
+        restorers.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn retry(self: &Rc<Self>) {
+        let attempt = self.attempts.get() + 1;
+        self.attempts.set(attempt);
+        if self
+            .policy
+            .max_attempts
+            .is_some_and(|max_attempts| attempt > max_attempts)
+        {
+            self.state.set(ReconnectState::GaveUp);
+            return;
+        }
+        self.state.set(ReconnectState::Waiting);
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            self.policy.delay(attempt),
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    if inner.state.get() == ReconnectState::Waiting {
+                        inner.dial();
+                    }
+                }
+            }),
+        );
+    }
+}
+
+/// A session re-established whenever it is lost.
+///
+/// Status notifications of every session are passed on, so that the application sees the
+/// session close and open again.
+pub struct ReconnectingSession<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T: Transport + 'static> ReconnectingSession<T> {
+    /// Connects with `connect`, and again following `policy`, timed by the platform timers.
+    /// `connect` is passed the status callback the session must report to.
+    pub fn with_connector<F>(
+        policy: ReconnectPolicy,
+        notification: Callback<WebTransportStatus>,
+        connect: impl Fn(Callback<WebTransportStatus>) -> F + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Result<T, WebTransportError>> + 'static,
+    {
+        Self::with_scheduler(policy, notification, Rc::new(PlatformScheduler), connect)
+    }
+
+    /// Like [`ReconnectingSession::with_connector`], timed by `scheduler`. A connector whose
+    /// future is ready right away connects synchronously.
+    pub fn with_scheduler<F>(
+        policy: ReconnectPolicy,
+        notification: Callback<WebTransportStatus>,
+        scheduler: Rc<dyn Scheduler>,
+        connect: impl Fn(Callback<WebTransportStatus>) -> F + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Result<T, WebTransportError>> + 'static,
+    {
+        let inner = Rc::new(Inner {
+            connect: Box::new(move |notification| connect(notification).boxed_local()),
+            policy,
+            scheduler,
+            notification,
+            session: RefCell::new(None),
+            state: Cell::new(ReconnectState::Connecting),
+            attempts: Cell::new(0),
+            opened: Cell::new(false),
+            reconnects: Cell::new(0),
+            generation: Cell::new(0),
+            restorers: RefCell::new(Vec::new()),
+        });
+        inner.dial();
+        Self { inner }
+    }
+
+    /// The current session, unless it was lost and not re-established yet.
+    pub fn session(&self) -> Option<Rc<T>> {
+        self.inner.session.borrow().clone()
+    }
+
+    pub fn state(&self) -> ReconnectState {
+        self.inner.state.get()
+    }
+
+    /// Attempts failed since the session was last open.
+    pub fn attempts(&self) -> u32 {
+        self.inner.attempts.get()
+    }
+
+    /// Times the session was re-established after it was first opened.
+    pub fn reconnects(&self) -> u64 {
+        self.inner.reconnects.get()
+    }
+
+    /// Runs `open` on the session once it is open, and again on every session re-established
+    /// after it. What `open` returns, a mux or a channel for instance, is kept until the
+    /// session is lost, and available through [`Restored::get`] meanwhile. The setup runs for
+    /// as long as the returned [`Restored`] is kept.
+    ///
+    /// `open` is where registration handshakes go, such as subscribing again to the topics
+    /// the application follows.
+    pub fn restore<H: 'static>(&self, open: impl Fn(&Rc<T>) -> H + 'static) -> Restored<T, H> {
+        let restorer = Rc::new(Restorer {
+            open: Box::new(open),
+            handle: RefCell::new(None),
+        });
+        let registered: Rc<dyn Restore<T>> = restorer.clone();
+        self.inner
+            .restorers
+            .borrow_mut()
+            .push(Rc::downgrade(&registered));
+        if self.inner.state.get() == ReconnectState::Open {
+            if let Some(session) = self.session() {
+                restorer.open(&session);
+            }
+        }
+        Restored { restorer }
+    }
+
+    /// Closes the session and stops reconnecting.
+    pub fn close(&self) -> Result<(), WebTransportError> {
+        if self.inner.state.replace(ReconnectState::Closed) == ReconnectState::Closed {
+            return Err(WebTransportError::AlreadyClosed);
+        }
+        self.inner.lose();
+        let session = self.inner.session.borrow_mut().take();
+        match session {
+            Some(session) => session.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ReconnectingSession<WebTransportTask> {
+    /// Connects with `builder`, and again with a copy of it whenever the session is lost. The
+    /// builder's status callback is passed the notifications of every session.
+    pub fn connect(builder: WebTransportBuilder, policy: ReconnectPolicy) -> Self {
+        let notification = builder.notification.clone();
+        Self::with_connector(policy, notification, move |notification| {
+            futures::future::ready(builder.clone().on_status(notification).connect())
+        })
+    }
+
+    /// Like [`ReconnectingSession::connect`], fetching a fresh token from `auth` before every
+    /// attempt.
+    pub fn connect_with_auth(
+        builder: WebTransportBuilder,
+        auth: TokenAuth,
+        policy: ReconnectPolicy,
+    ) -> Self {
+        let notification = builder.notification.clone();
+        Self::with_connector(policy, notification, move |notification| {
+            auth.connect(builder.clone().on_status(notification))
+        })
+    }
+}
+
+impl<T> fmt::Debug for ReconnectingSession<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectingSession")
+            .field("state", &self.inner.state.get())
+            .field("attempts", &self.inner.attempts.get())
+            .field("reconnects", &self.inner.reconnects.get())
+            .finish()
+    }
+}
@@ -0,0 +1,292 @@
+//! A socket of messages: reliable, ordered and exactly once.
+//!
+//! A [`ReliableChannel`] keeps one bidirectional stream open and frames every message as
+//! `length varint | payload`. The stream takes care of retransmission and ordering, so each
+//! message sent is delivered to the peer once, whole, and in the order it was sent, however
+//! the stream chunks it on the way.
+//!
+//! QUIC flow control stops a sender only once the browser's buffers are full, which a slow
+//! consumer may never see coming. A channel opened with [`FlowControl`] also grants its peer
+//! credit at the application level: the sender writes messages only while it has credit, and
+//! the receiver grants more as the application gets through what it received. Both sides must
+//! use flow control, since it changes what goes on the stream: every message then starts with
+//! a type byte, `0` for data and `1` for a window update carrying a varint of bytes granted.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt,
+    rc::{Rc, Weak},
+};
+use yew::callback::Callback;
+
+use crate::transport::{StreamWriter, Transport};
+use crate::varint;
+
+/// Appends `message` to `out`, prefixed with its length.
+pub fn encode_message(message: &[u8], out: &mut Vec<u8>) {
+    varint::encode(message.len() as u64, out);
+    out.extend_from_slice(message);
+}
+
+/// Splits the chunks of a stream back into length-prefixed messages.
+#[derive(Debug, Default)]
+pub struct MessageDecoder {
+    buffer: Vec<u8>,
+}
+
+impl MessageDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a chunk read from the stream.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Takes the next complete message.
+    pub fn next_message(&mut self) -> Option<Vec<u8>> {
+        let (len, prefix) = varint::decode(&self.buffer)?;
+        let end = prefix.checked_add(usize::try_from(len).ok()?)?;
+        if self.buffer.len() < end {
+            return None;
+        }
+        let message = self.buffer[prefix..end].to_vec();
+        self.buffer.drain(..end);
+        Some(message)
+    }
+}
+
+const DATA: u8 = 0;
+const WINDOW_UPDATE: u8 = 1;
+
+/// Credit-based flow control of a [`ReliableChannel`], see the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowControl {
+    /// Bytes of messages the peer may send ahead of what the application got through.
+    pub window: u64,
+    /// Whether a message counts as got through once passed to `on_message`. Otherwise the
+    /// application tells the channel with [`ReliableChannel::release`].
+    pub auto_release: bool,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self {
+            window: 256 * 1024,
+            auto_release: true,
+        }
+    }
+}
+
+struct Receiver {
+    decoder: RefCell<MessageDecoder>,
+    on_message: RefCell<Callback<Vec<u8>>>,
+    flow_control: Option<FlowControl>,
+    /// Bytes released by the application and not granted back to the peer yet.
+    released: Cell<u64>,
+    sender: RefCell<Weak<Sender>>,
+}
+
+impl Receiver {
+    fn receive(&self, chunk: Vec<u8>) {
+        self.decoder.borrow_mut().push(&chunk);
+        loop {
+            let message = self.decoder.borrow_mut().next_message();
+            let Some(mut message) = message else {
+                break;
+            };
+            if self.flow_control.is_some() {
+                match message.first() {
+                    Some(&DATA) => {
+                        message.remove(0);
+                    }
+                    Some(&WINDOW_UPDATE) => {
+                        if let Some((credit, _)) = varint::decode(&message[1..]) {
+                            if let Some(sender) = self.sender.borrow().upgrade() {
+                                sender.grant(credit);
+                            }
+                        }
+                        continue;
+                    }
+                    // Messages of unknown types are skipped, for later extensions.
+                    _ => continue,
+                }
+            }
+            let len = message.len() as u64;
+            let on_message = self.on_message.borrow().clone();
+            on_message.emit(message);
+            if self.flow_control.is_some_and(|flow| flow.auto_release) {
+                self.release(len);
+            }
+        }
+    }
+
+    /// Grants released bytes back to the peer, once they make up half the window, so that
+    /// window updates don't take as many messages as the data.
+    fn release(&self, bytes: u64) {
+        let Some(flow_control) = self.flow_control else {
+            return;
+        };
+        let released = self.released.get() + bytes;
+        if released < flow_control.window / 2 {
+            self.released.set(released);
+            return;
+        }
+        self.released.set(0);
+        if let Some(sender) = self.sender.borrow().upgrade() {
+            sender.write_window_update(released);
+        }
+    }
+}
+
+struct Sender {
+    writer: StreamWriter,
+    /// Bytes the peer granted that are not spent yet, `None` without flow control. A message
+    /// goes out while the credit is positive, so one larger than the window doesn't wait
+    /// forever, and may take it below zero.
+    credit: Option<Cell<i64>>,
+    /// Messages waiting for credit, framed, along with their length.
+    blocked: RefCell<VecDeque<(Vec<u8>, u64)>>,
+}
+
+impl Sender {
+    fn send(&self, message: &[u8]) {
+        let mut data = Vec::with_capacity(message.len() + 9);
+        let Some(credit) = &self.credit else {
+            encode_message(message, &mut data);
+            self.writer.write(data);
+            return;
+        };
+        varint::encode(message.len() as u64 + 1, &mut data);
+        data.push(DATA);
+        data.extend_from_slice(message);
+        let len = message.len() as u64;
+        let mut blocked = self.blocked.borrow_mut();
+        if blocked.is_empty() && credit.get() > 0 {
+            credit.set(credit.get().saturating_sub(len as i64));
+            self.writer.write(data);
+        } else {
+            blocked.push_back((data, len));
+        }
+    }
+
+    fn grant(&self, bytes: u64) {
+        let Some(credit) = &self.credit else {
+            return;
+        };
+        credit.set(credit.get().saturating_add(bytes as i64));
+        let mut blocked = self.blocked.borrow_mut();
+        while credit.get() > 0 {
+            let Some((data, len)) = blocked.pop_front() else {
+                break;
+            };
+            credit.set(credit.get().saturating_sub(len as i64));
+            self.writer.write(data);
+        }
+    }
+
+    fn write_window_update(&self, bytes: u64) {
+        let mut update = vec![WINDOW_UPDATE];
+        varint::encode(bytes.min(varint::MAX), &mut update);
+        let mut data = Vec::with_capacity(update.len() + 1);
+        encode_message(&update, &mut data);
+        self.writer.write(data);
+    }
+}
+
+/// Reliable, ordered messages over a dedicated bidirectional stream.
+///
+/// The stream is finished when the channel is dropped or [`ReliableChannel::close`]d.
+pub struct ReliableChannel {
+    receiver: Rc<Receiver>,
+    sender: Rc<Sender>,
+}
+
+impl ReliableChannel {
+    /// Opens the channel's stream on `transport`. Every message the peer sends is passed to
+    /// `on_message`.
+    pub fn open<T: Transport + ?Sized>(transport: &T, on_message: Callback<Vec<u8>>) -> Self {
+        Self::open_with(transport, None, on_message)
+    }
+
+    /// Like [`ReliableChannel::open`], with credit-based flow control. The peer is granted
+    /// `flow_control.window` bytes right away, and sends nothing before it grants some back.
+    pub fn open_with_flow_control<T: Transport + ?Sized>(
+        transport: &T,
+        flow_control: FlowControl,
+        on_message: Callback<Vec<u8>>,
+    ) -> Self {
+        let channel = Self::open_with(transport, Some(flow_control), on_message);
+        channel.sender.write_window_update(flow_control.window);
+        channel
+    }
+
+    fn open_with<T: Transport + ?Sized>(
+        transport: &T,
+        flow_control: Option<FlowControl>,
+        on_message: Callback<Vec<u8>>,
+    ) -> Self {
+        let receiver = Rc::new(Receiver {
+            decoder: RefCell::default(),
+            on_message: RefCell::new(on_message),
+            flow_control,
+            released: Cell::new(0),
+            sender: RefCell::new(Weak::new()),
+        });
+        let on_data = {
+            let receiver = receiver.clone();
+            Callback::from(move |chunk: Vec<u8>| receiver.receive(chunk))
+        };
+        let sender = Rc::new(Sender {
+            writer: transport.open_bidirectional_stream(on_data),
+            credit: flow_control.map(|_| Cell::new(0)),
+            blocked: RefCell::default(),
+        });
+        *receiver.sender.borrow_mut() = Rc::downgrade(&sender);
+        Self { receiver, sender }
+    }
+
+    /// Sends `message`. It is delivered after every message sent before it. With flow
+    /// control, it waits in the channel until the peer granted the credit.
+    pub fn send(&self, message: &[u8]) {
+        self.sender.send(message);
+    }
+
+    /// Tells the channel that the application got through `bytes` bytes of the messages it
+    /// received, so the peer can send that much more. Only needed with flow control, when
+    /// [`FlowControl::auto_release`] is off.
+    pub fn release(&self, bytes: u64) {
+        self.receiver.release(bytes);
+    }
+
+    /// Bytes the peer granted that are not spent yet. Negative after a message larger than
+    /// what was left, and `None` without flow control.
+    pub fn send_credit(&self) -> Option<i64> {
+        self.sender.credit.as_ref().map(Cell::get)
+    }
+
+    /// Number of messages waiting for credit.
+    pub fn blocked(&self) -> usize {
+        self.sender.blocked.borrow().len()
+    }
+
+    /// Replaces the callback receiving messages.
+    pub fn set_on_message(&self, on_message: Callback<Vec<u8>>) {
+        *self.receiver.on_message.borrow_mut() = on_message;
+    }
+
+    /// Finishes the stream once every message sent so far has been written.
+    pub fn close(self) {}
+}
+
+impl fmt::Debug for ReliableChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReliableChannel")
+            .field("flow_control", &self.receiver.flow_control)
+            .field("send_credit", &self.send_credit())
+            .field("blocked", &self.blocked())
+            .finish()
+    }
+}
@@ -0,0 +1,224 @@
+//! Request/response calls over bidirectional streams.
+//!
+//! Each call opens its own bidirectional stream, so calls never block each other. The request
+//! is framed as
+//!
+//! ```text
+//! call id (varint) | method length (varint) | method (utf-8) | payload
+//! ```
+//!
+//! and the server answers on the same stream with
+//!
+//! ```text
+//! call id (varint) | status (u8, 0 = ok, 1 = error) | payload or utf-8 error message
+//! ```
+//!
+//! before finishing it. Varints are QUIC varints, see [`crate::varint`].
+//!
+//! With the `prost` feature, [`RpcClient::call_prost`] calls with protobuf messages instead of
+//! JSON, and the `yew-webtransport-prost` crate in `tools/prost-rpc` generates a typed client
+//! for every service of a `.proto` file, calling each method as `/package.Service/Method`.
+
+use futures::future::{FutureExt, LocalBoxFuture};
+#[cfg(feature = "formats")]
+use serde::{de::DeserializeOwned, Serialize};
+use std::{cell::Cell, fmt, rc::Rc, time::Duration};
+
+use crate::time::timeout;
+use crate::transport::Transport;
+use crate::varint;
+use crate::webtransport::WebTransportError;
+
+/// The response to a call, or why it failed.
+pub type RpcCall<T> = LocalBoxFuture<'static, Result<T, WebTransportError>>;
+
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+
+/// Encodes a request envelope.
+pub fn encode_request(id: u64, method: &str, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + method.len() + 16);
+    varint::encode(id, &mut out);
+    varint::encode(method.len() as u64, &mut out);
+    out.extend_from_slice(method.as_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Encodes a response envelope, as a server would.
+pub fn encode_response(id: u64, result: Result<&[u8], &str>) -> Vec<u8> {
+    let mut out = Vec::new();
+    varint::encode(id, &mut out);
+    match result {
+        Ok(payload) => {
+            out.push(STATUS_OK);
+            out.extend_from_slice(payload);
+        }
+        Err(message) => {
+            out.push(STATUS_ERROR);
+            out.extend_from_slice(message.as_bytes());
+        }
+    }
+    out
+}
+
+/// Decodes a response envelope, checking that it answers call `id`.
+pub fn decode_response(id: u64, response: &[u8]) -> Result<Vec<u8>, WebTransportError> {
+    let malformed = || WebTransportError::StreamError("malformed rpc response".to_string());
+    let (response_id, len) = varint::decode(response).ok_or_else(malformed)?;
+    if response_id != id {
+        return Err(WebTransportError::StreamError(format!(
+            "rpc response for call {response_id} received on call {id}"
+        )));
+    }
+    let status = *response.get(len).ok_or_else(malformed)?;
+    let payload = &response[len + 1..];
+    match status {
+        STATUS_OK => Ok(payload.to_vec()),
+        STATUS_ERROR => Err(WebTransportError::RemoteError(
+            String::from_utf8_lossy(payload).into_owned(),
+        )),
+        _ => Err(malformed()),
+    }
+}
+
+/// Makes calls to a server over a session.
+pub struct RpcClient<T> {
+    transport: Rc<T>,
+    next_id: Rc<Cell<u64>>,
+    timeout: Option<Duration>,
+}
+
+impl<T> Clone for RpcClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            next_id: self.next_id.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<T> fmt::Debug for RpcClient<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcClient")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<T: Transport + 'static> RpcClient<T> {
+    pub fn new(transport: Rc<T>) -> Self {
+        Self {
+            transport,
+            next_id: Rc::new(Cell::new(0)),
+            timeout: None,
+        }
+    }
+
+    /// Sets the timeout applied to calls that don't specify their own.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn transport(&self) -> &Rc<T> {
+        &self.transport
+    }
+
+    /// Calls `method` with a raw payload, resolving with the raw response payload.
+    pub fn call_raw(&self, method: &str, payload: &[u8]) -> RpcCall<Vec<u8>> {
+        self.call_raw_with_timeout(method, payload, self.timeout)
+    }
+
+    /// Like [`RpcClient::call_raw`], overriding the client's timeout.
+    pub fn call_raw_with_timeout(
+        &self,
+        method: &str,
+        payload: &[u8],
+        call_timeout: Option<Duration>,
+    ) -> RpcCall<Vec<u8>> {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1) & varint::MAX);
+        let response = self.transport.request(encode_request(id, method, payload));
+        async move {
+            let response = match call_timeout {
+                Some(duration) => timeout(duration, response)
+                    .await
+                    .ok_or(WebTransportError::Timeout)??,
+                None => response.await?,
+            };
+            decode_response(id, &response)
+        }
+        .boxed_local()
+    }
+
+    /// Calls `method` with a JSON encoded `request`, resolving with the decoded response.
+    #[cfg(feature = "formats")]
+    pub fn call<Req, Resp>(&self, method: &str, request: &Req) -> RpcCall<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned + 'static,
+    {
+        self.call_with_timeout(method, request, self.timeout)
+    }
+
+    /// Like [`RpcClient::call`], overriding the client's timeout.
+    #[cfg(feature = "formats")]
+    pub fn call_with_timeout<Req, Resp>(
+        &self,
+        method: &str,
+        request: &Req,
+        call_timeout: Option<Duration>,
+    ) -> RpcCall<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned + 'static,
+    {
+        let payload = match serde_json::to_vec(request) {
+            Ok(payload) => payload,
+            Err(e) => {
+                let error = WebTransportError::SerializationError(e.to_string());
+                return futures::future::ready(Err(error)).boxed_local();
+            }
+        };
+        let response = self.call_raw_with_timeout(method, &payload, call_timeout);
+        async move {
+            let response = response.await?;
+            serde_json::from_slice(&response)
+                .map_err(|e| WebTransportError::SerializationError(e.to_string()))
+        }
+        .boxed_local()
+    }
+
+    /// Calls `method` with a protobuf encoded `request`, resolving with the decoded response.
+    #[cfg(feature = "prost")]
+    pub fn call_prost<Req, Resp>(&self, method: &str, request: &Req) -> RpcCall<Resp>
+    where
+        Req: prost::Message,
+        Resp: prost::Message + Default + 'static,
+    {
+        self.call_prost_with_timeout(method, request, self.timeout)
+    }
+
+    /// Like [`RpcClient::call_prost`], overriding the client's timeout.
+    #[cfg(feature = "prost")]
+    pub fn call_prost_with_timeout<Req, Resp>(
+        &self,
+        method: &str,
+        request: &Req,
+        call_timeout: Option<Duration>,
+    ) -> RpcCall<Resp>
+    where
+        Req: prost::Message,
+        Resp: prost::Message + Default + 'static,
+    {
+        let response = self.call_raw_with_timeout(method, &request.encode_to_vec(), call_timeout);
+        async move {
+            let response = response.await?;
+            Resp::decode(response.as_slice())
+                .map_err(|e| WebTransportError::SerializationError(e.to_string()))
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,110 @@
+//! Tying the tasks a component spawns to its lifetime.
+//!
+//! A component reading from a session typically spawns a loop, from `update()` or an effect,
+//! that runs until the stream ends, long after the component is gone, along with the
+//! callbacks and buffers it holds. A [`TaskScope`] kept in the component spawns those tasks
+//! instead, and aborts them when it is dropped with the component: every task is dropped where
+//! it awaits, freeing what it holds. Subscriptions, channels and other guards given to
+//! [`TaskScope::keep`] are dropped at the same time.
+//!
+//! ```ignore
+//! struct Feed {
+//!     tasks: TaskScope,
+//! }
+//!
+//! impl Component for Feed {
+//!     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+//!         let reading = session.read_incoming(stream, ctx.link().callback(Msg::Data));
+//!         self.tasks.spawn(async move {
+//!             let _ = reading.await;
+//!         });
+//!         false
+//!     }
+//! }
+//! ```
+//!
+//! Function components get one with [`use_task_scope`](crate::hooks::use_task_scope).
+
+use futures::future::{abortable, AbortHandle};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    rc::Rc,
+};
+use yew::platform::spawn_local;
+
+#[derive(Default)]
+struct Inner {
+    next_token: Cell<u64>,
+    tasks: RefCell<HashMap<u64, AbortHandle>>,
+    kept: RefCell<Vec<Box<dyn Any>>>,
+}
+
+/// Spawns tasks that are aborted when it is dropped, see the [module documentation](self).
+#[derive(Default)]
+pub struct TaskScope {
+    inner: Rc<Inner>,
+}
+
+impl TaskScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `task` in the background until it completes, or until the scope is dropped or
+    /// cancelled.
+    pub fn spawn(&self, task: impl Future<Output = ()> + 'static) {
+        let token = self.inner.next_token.get();
+        self.inner.next_token.set(token + 1);
+        let (task, handle) = abortable(task);
+        self.inner.tasks.borrow_mut().insert(token, handle);
+        let inner = Rc::downgrade(&self.inner);
+        spawn_local(async move {
+            let _ = task.await;
+            if let Some(inner) = inner.upgrade() {
+                inner.tasks.borrow_mut().remove(&token);
+            }
+        });
+    }
+
+    /// Keeps `value` until the scope is dropped or cancelled, for guards ending something when
+    /// dropped, like a [`DatagramSubscription`](crate::channel::DatagramSubscription).
+    pub fn keep(&self, value: impl Any) {
+        self.inner.kept.borrow_mut().push(Box::new(value));
+    }
+
+    /// Aborts every task still running and drops every value kept, as dropping the scope
+    /// does. The scope can be used again afterwards, when the component switches to another
+    /// session for instance.
+    pub fn cancel(&self) {
+        let tasks = std::mem::take(&mut *self.inner.tasks.borrow_mut());
+        for handle in tasks.into_values() {
+            handle.abort();
+        }
+        let kept = std::mem::take(&mut *self.inner.kept.borrow_mut());
+        drop(kept);
+    }
+
+    /// Number of tasks still running.
+    pub fn running(&self) -> usize {
+        self.inner.tasks.borrow().len()
+    }
+}
+
+impl Drop for TaskScope {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl fmt::Debug for TaskScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskScope")
+            .field("running", &self.running())
+            .field("kept", &self.inner.kept.borrow().len())
+            .finish()
+    }
+}
@@ -0,0 +1,108 @@
+//! Unreliable but ordered messages, for state where only the latest value matters.
+//!
+//! A [`SequencedChannel`] sends each message as a datagram on a [`DatagramChannel`], prefixed
+//! with a sequence number varint. Messages may still be lost, but one that arrives after a
+//! newer message has been delivered is stale and dropped, so the application never goes back
+//! in time. Position updates and live cursors are the typical use.
+
+use std::{cell::Cell, fmt, rc::Rc};
+use yew::callback::Callback;
+
+use crate::channel::DatagramChannel;
+use crate::transport::Transport;
+use crate::varint;
+
+#[derive(Default)]
+struct Sequence {
+    next_outgoing: Cell<u64>,
+    last_delivered: Cell<Option<u64>>,
+    stale: Cell<u64>,
+}
+
+impl Sequence {
+    fn accept(&self, data: Vec<u8>, on_message: &Callback<Vec<u8>>) {
+        let Some((sequence, len)) = varint::decode(&data) else {
+            return;
+        };
+        if self
+            .last_delivered
+            .get()
+            .is_some_and(|last| sequence <= last)
+        {
+            self.stale.set(self.stale.get() + 1);
+            return;
+        }
+        self.last_delivered.set(Some(sequence));
+        on_message.emit(data[len..].to_vec());
+    }
+}
+
+/// Datagrams that are delivered in order or not at all.
+pub struct SequencedChannel {
+    sequence: Rc<Sequence>,
+    channel: DatagramChannel,
+}
+
+impl SequencedChannel {
+    /// Registers datagram channel `id` on `transport`. Messages that are newer than every
+    /// message delivered so far are passed to `on_message`.
+    pub fn new<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        on_message: Callback<Vec<u8>>,
+    ) -> Self {
+        let sequence = Rc::new(Sequence::default());
+        let channel = transport.datagram_channel(id, Self::receiver(&sequence, on_message));
+        Self { sequence, channel }
+    }
+
+    fn receiver(sequence: &Rc<Sequence>, on_message: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        let sequence = sequence.clone();
+        Callback::from(move |data: Vec<u8>| sequence.accept(data, &on_message))
+    }
+
+    pub fn id(&self) -> u64 {
+        self.channel.id()
+    }
+
+    /// Sends `message` with the next sequence number.
+    pub fn send(&self, message: &[u8]) {
+        let sequence = self.sequence.next_outgoing.get();
+        self.sequence.next_outgoing.set(sequence + 1);
+        let mut data = Vec::with_capacity(message.len() + 8);
+        varint::encode(sequence, &mut data);
+        data.extend_from_slice(message);
+        self.channel.send(&data);
+    }
+
+    /// Replaces the callback receiving messages.
+    pub fn set_on_message(&self, on_message: Callback<Vec<u8>>) {
+        self.channel
+            .set_on_message(Self::receiver(&self.sequence, on_message));
+    }
+
+    /// Forgets the newest sequence number received, for when the peer starts counting again,
+    /// e.g. after reconnecting.
+    pub fn reset(&self) {
+        self.sequence.last_delivered.set(None);
+    }
+
+    /// Sequence number of the newest message delivered.
+    pub fn last_delivered(&self) -> Option<u64> {
+        self.sequence.last_delivered.get()
+    }
+
+    /// Number of messages dropped because a newer one had already been delivered.
+    pub fn stale_dropped(&self) -> u64 {
+        self.sequence.stale.get()
+    }
+}
+
+impl fmt::Debug for SequencedChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SequencedChannel")
+            .field("id", &self.channel.id())
+            .field("last_delivered", &self.sequence.last_delivered.get())
+            .finish()
+    }
+}
@@ -0,0 +1,220 @@
+//! Keeping several named sessions together.
+//!
+//! Applications talking to more than one endpoint, say a signaling server and a media server,
+//! keep one session per endpoint. A [`WebTransportPool`] holds them by name, tracks the status
+//! of each one, and closes them all at once.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    rc::Rc,
+};
+use yew::callback::Callback;
+
+use crate::transport::Transport;
+use crate::webtransport::{
+    CloseInfo, SessionError, WebTransportBuilder, WebTransportError, WebTransportStatus,
+    WebTransportTask,
+};
+
+/// Where a pooled session stands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionState {
+    /// Connecting, or inserted without status updates.
+    Connecting,
+    Open,
+    Closed(CloseInfo),
+    /// The session could not be established.
+    Failed(SessionError),
+}
+
+impl SessionState {
+    /// The state a session is in after notifying `status`, or `None` if `status` doesn't
+    /// change it.
+    pub fn from_status(status: &WebTransportStatus) -> Option<Self> {
+        match status {
+            WebTransportStatus::Opened => Some(Self::Open),
+            WebTransportStatus::Closed(info) => Some(Self::Closed(info.clone())),
+            WebTransportStatus::Error(error) => Some(Self::Failed(error.clone())),
+            WebTransportStatus::StreamError(_) => None,
+        }
+    }
+}
+
+/// Sessions keyed by name.
+///
+/// Sessions are held as `Rc`s, so that a handle can be passed to the parts of the application
+/// using it while the pool keeps ownership. Dropping the pool drops its handles.
+pub struct WebTransportPool<T = WebTransportTask> {
+    sessions: BTreeMap<String, Pooled<T>>,
+    /// State of every session, by generation, updated by the callbacks returned from
+    /// [`WebTransportPool::track`]. A session replaced under the same name keeps reporting to
+    /// its own generation, so it can't overwrite the state of its replacement.
+    states: Rc<RefCell<HashMap<u64, SessionState>>>,
+    /// Generations handed out by [`WebTransportPool::track`] to sessions not inserted yet.
+    reserved: HashMap<String, u64>,
+    next_generation: u64,
+}
+
+struct Pooled<T> {
+    session: Rc<T>,
+    generation: u64,
+}
+
+impl<T> Default for WebTransportPool<T> {
+    fn default() -> Self {
+        Self {
+            sessions: BTreeMap::new(),
+            states: Rc::default(),
+            reserved: HashMap::new(),
+            next_generation: 0,
+        }
+    }
+}
+
+impl<T: Transport> WebTransportPool<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `session` under `name`, returning the session it replaces, if any, which is left
+    /// open. Unless its status callback was wrapped with [`WebTransportPool::track`] beforehand,
+    /// its state stays [`SessionState::Connecting`].
+    pub fn insert(&mut self, name: impl Into<String>, session: T) -> Option<Rc<T>> {
+        let name = name.into();
+        let generation = match self.reserved.remove(&name) {
+            Some(generation) => generation,
+            None => self.reserve(),
+        };
+        let previous = self.sessions.insert(
+            name,
+            Pooled {
+                session: Rc::new(session),
+                generation,
+            },
+        )?;
+        self.states.borrow_mut().remove(&previous.generation);
+        Some(previous.session)
+    }
+
+    /// Wraps the status callback of a session about to be added under `name`, so that the pool
+    /// follows its state. The session must be the next one inserted under that name.
+    pub fn track(
+        &mut self,
+        name: impl Into<String>,
+        notification: Callback<WebTransportStatus>,
+    ) -> Callback<WebTransportStatus> {
+        let generation = self.reserve();
+        if let Some(abandoned) = self.reserved.insert(name.into(), generation) {
+            self.states.borrow_mut().remove(&abandoned);
+        }
+        let states = self.states.clone();
+        Callback::from(move |status: WebTransportStatus| {
+            let state = SessionState::from_status(&status);
+            if let (Some(state), Some(tracked)) = (state, states.borrow_mut().get_mut(&generation))
+            {
+                *tracked = state;
+            }
+            notification.emit(status);
+        })
+    }
+
+    fn reserve(&mut self) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.states
+            .borrow_mut()
+            .insert(generation, SessionState::Connecting);
+        generation
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<T>> {
+        self.sessions.get(name).map(|pooled| pooled.session.clone())
+    }
+
+    pub fn state(&self, name: &str) -> Option<SessionState> {
+        let pooled = self.sessions.get(name)?;
+        self.states.borrow().get(&pooled.generation).cloned()
+    }
+
+    /// Whether the session named `name` is open.
+    pub fn is_open(&self, name: &str) -> bool {
+        self.state(name) == Some(SessionState::Open)
+    }
+
+    /// Removes the session named `name` from the pool, without closing it.
+    pub fn remove(&mut self, name: &str) -> Option<Rc<T>> {
+        let pooled = self.sessions.remove(name)?;
+        self.states.borrow_mut().remove(&pooled.generation);
+        Some(pooled.session)
+    }
+
+    /// Closes the session named `name` and removes it from the pool.
+    pub fn close(&mut self, name: &str) -> Result<(), WebTransportError> {
+        match self.remove(name) {
+            Some(session) => session.close(),
+            None => Err(WebTransportError::AlreadyClosed),
+        }
+    }
+
+    /// Closes every session and empties the pool. Sessions that were already closed are
+    /// skipped.
+    pub fn close_all(&mut self) {
+        for (_, pooled) in std::mem::take(&mut self.sessions) {
+            self.states.borrow_mut().remove(&pooled.generation);
+            let _ = pooled.session.close();
+        }
+    }
+
+    /// Names of the sessions, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sessions.keys().map(String::as_str)
+    }
+
+    /// The sessions with their names, in order of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Rc<T>)> {
+        self.sessions
+            .iter()
+            .map(|(name, pooled)| (name.as_str(), &pooled.session))
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+impl WebTransportPool<WebTransportTask> {
+    /// Connects with `builder` and adds the session under `name`, replacing and closing the
+    /// session previously added under that name.
+    pub fn connect(
+        &mut self,
+        name: impl Into<String>,
+        builder: WebTransportBuilder,
+    ) -> Result<Rc<WebTransportTask>, WebTransportError> {
+        let name = name.into();
+        let notification = self.track(name.clone(), builder.notification.clone());
+        let task = builder.on_status(notification).connect()?;
+        if let Some(previous) = self.insert(name.clone(), task) {
+            let _ = previous.close();
+        }
+        Ok(self.sessions[&name].session.clone())
+    }
+}
+
+impl<T> fmt::Debug for WebTransportPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let states = self.states.borrow();
+        f.debug_map()
+            .entries(
+                self.sessions
+                    .iter()
+                    .map(|(name, pooled)| (name, states.get(&pooled.generation))),
+            )
+            .finish()
+    }
+}
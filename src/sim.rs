@@ -0,0 +1,492 @@
+//! Bad-network simulation for any [`Transport`].
+//!
+//! A [`NetworkSimulator`] sits between the application and a session and delays, reorders and
+//! drops traffic according to [`NetworkConditions`]. All randomness comes from a seeded
+//! generator, so a given seed and traffic pattern always produce the same outcome; pair it with
+//! a [`ManualScheduler`](crate::time::ManualScheduler) to make timing deterministic too.
+//!
+//! ```no_run
+//! # use yew::Callback;
+//! # use yew_webtransport::sim::{NetworkConditions, NetworkSimulator};
+//! # use yew_webtransport::webtransport::WebTransportService;
+//! # fn connect(on_datagram: Callback<Vec<u8>>) {
+//! let sim = NetworkSimulator::new(NetworkConditions::lossy(0.1));
+//! let task = WebTransportService::connect(
+//!     "https://example.com:4433",
+//!     sim.incoming_datagrams(on_datagram),
+//!     sim.incoming(Callback::noop()),
+//!     sim.incoming(Callback::noop()),
+//!     Callback::noop(),
+//! )
+//! .unwrap();
+//! let task = sim.wrap(task);
+//! # }
+//! ```
+
+#[cfg(feature = "streams")]
+use futures::channel::oneshot;
+#[cfg(feature = "streams")]
+use futures::future::FutureExt;
+use futures::future::LocalBoxFuture;
+use std::{cell::RefCell, fmt, rc::Rc, time::Duration};
+use yew::callback::Callback;
+
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramSubscription};
+use crate::time::{PlatformScheduler, Scheduler};
+#[cfg(feature = "datagrams")]
+use crate::transport::DatagramPriority;
+#[cfg(feature = "streams")]
+use crate::transport::StreamWriter;
+use crate::transport::{QueuedSends, Transport, TransportStats};
+use crate::webtransport::WebTransportError;
+
+/// The impairments applied by a [`NetworkSimulator`], in each direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConditions {
+    /// Fixed one-way delay added to everything.
+    pub latency: Duration,
+    /// Upper bound of a uniformly distributed extra delay added to everything.
+    pub jitter: Duration,
+    /// Probability, between 0 and 1, that a datagram is dropped.
+    pub loss: f64,
+    /// Probability, between 0 and 1, that a datagram is held back by `reorder_delay`.
+    pub reorder: f64,
+    /// Extra delay applied to datagrams selected for reordering.
+    pub reorder_delay: Duration,
+    /// Seed of the random generator deciding loss, jitter and reordering.
+    pub seed: u64,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.0,
+            reorder: 0.0,
+            reorder_delay: Duration::ZERO,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl NetworkConditions {
+    /// Conditions with a fixed one-way `latency` and nothing else.
+    pub fn with_latency(latency: Duration) -> Self {
+        Self {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    /// Conditions dropping datagrams with probability `loss` and nothing else.
+    pub fn lossy(loss: f64) -> Self {
+        Self {
+            loss,
+            ..Self::default()
+        }
+    }
+}
+
+/// Counters kept by a [`NetworkSimulator`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimulationStats {
+    pub datagrams_sent: u64,
+    pub datagrams_received: u64,
+    pub datagrams_dropped_outgoing: u64,
+    pub datagrams_dropped_incoming: u64,
+    pub datagrams_reordered: u64,
+}
+
+/// A small, seedable xorshift generator, so simulations don't depend on a system RNG.
+#[derive(Clone, Debug)]
+struct SimRng(u64);
+
+impl SimRng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero.
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A float uniformly distributed in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+enum Fate {
+    Drop,
+    Deliver(Duration),
+}
+
+struct SimulatorState {
+    conditions: NetworkConditions,
+    rng: SimRng,
+    stats: SimulationStats,
+}
+
+impl SimulatorState {
+    fn delay(&mut self) -> Duration {
+        let jitter = self.conditions.jitter.mul_f64(self.rng.next_f64());
+        self.conditions.latency + jitter
+    }
+
+    fn datagram_fate(&mut self) -> Fate {
+        if self.rng.next_f64() < self.conditions.loss {
+            return Fate::Drop;
+        }
+        let mut delay = self.delay();
+        if self.rng.next_f64() < self.conditions.reorder {
+            self.stats.datagrams_reordered += 1;
+            delay += self.conditions.reorder_delay;
+        }
+        Fate::Deliver(delay)
+    }
+}
+
+/// Injects latency, jitter, reordering and datagram loss between an application and a session.
+///
+/// Outgoing traffic is impaired by wrapping the session with [`NetworkSimulator::wrap`];
+/// incoming traffic by wrapping the callbacks handed to `connect` with
+/// [`NetworkSimulator::incoming_datagrams`] and [`NetworkSimulator::incoming`]. Streams are
+/// delayed but never dropped, and chunks of a single stream keep their order.
+#[derive(Clone)]
+pub struct NetworkSimulator {
+    state: Rc<RefCell<SimulatorState>>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl NetworkSimulator {
+    /// Creates a simulator driven by the platform timers.
+    pub fn new(conditions: NetworkConditions) -> Self {
+        Self::with_scheduler(conditions, Rc::new(PlatformScheduler))
+    }
+
+    /// Creates a simulator driven by `scheduler`.
+    pub fn with_scheduler(conditions: NetworkConditions, scheduler: Rc<dyn Scheduler>) -> Self {
+        let rng = SimRng::new(conditions.seed);
+        Self {
+            state: Rc::new(RefCell::new(SimulatorState {
+                conditions,
+                rng,
+                stats: SimulationStats::default(),
+            })),
+            scheduler,
+        }
+    }
+
+    /// Replaces the conditions applied to traffic from now on. The random generator is
+    /// reseeded if the seed changed.
+    pub fn set_conditions(&self, conditions: NetworkConditions) {
+        let mut state = self.state.borrow_mut();
+        if state.conditions.seed != conditions.seed {
+            state.rng = SimRng::new(conditions.seed);
+        }
+        state.conditions = conditions;
+    }
+
+    pub fn conditions(&self) -> NetworkConditions {
+        self.state.borrow().conditions.clone()
+    }
+
+    pub fn stats(&self) -> SimulationStats {
+        self.state.borrow().stats.clone()
+    }
+
+    /// Wraps an established session so that everything sent through it is impaired.
+    pub fn wrap<T: Transport + 'static>(&self, transport: T) -> SimulatedTransport<T> {
+        SimulatedTransport {
+            inner: Rc::new(transport),
+            simulator: self.clone(),
+        }
+    }
+
+    /// Wraps a datagram callback so that incoming datagrams are delayed, reordered and dropped.
+    pub fn incoming_datagrams(&self, callback: Callback<Vec<u8>>) -> Callback<Vec<u8>> {
+        let simulator = self.clone();
+        Callback::from(move |data: Vec<u8>| {
+            let fate = {
+                let mut state = simulator.state.borrow_mut();
+                state.stats.datagrams_received += 1;
+                let fate = state.datagram_fate();
+                if let Fate::Drop = fate {
+                    state.stats.datagrams_dropped_incoming += 1;
+                }
+                fate
+            };
+            if let Fate::Deliver(delay) = fate {
+                let callback = callback.clone();
+                simulator
+                    .scheduler
+                    .schedule(delay, Box::new(move || callback.emit(data)));
+            }
+        })
+    }
+
+    /// Wraps a callback so that whatever it receives is delayed but otherwise delivered in order.
+    ///
+    /// Use it for the incoming stream callbacks and for bidirectional stream responses.
+    pub fn incoming<T: 'static>(&self, callback: Callback<T>) -> Callback<T> {
+        let simulator = self.clone();
+        let last_delivery = Rc::new(RefCell::new(Duration::ZERO));
+        Callback::from(move |value: T| {
+            let delay = simulator.ordered_delay(&last_delivery);
+            let callback = callback.clone();
+            simulator
+                .scheduler
+                .schedule(delay, Box::new(move || callback.emit(value)));
+        })
+    }
+
+    /// A delay that never schedules delivery before the previous one on the same path.
+    fn ordered_delay(&self, last_delivery: &RefCell<Duration>) -> Duration {
+        let now = self.scheduler.now();
+        let delay = self.state.borrow_mut().delay();
+        let mut last_delivery = last_delivery.borrow_mut();
+        let deliver_at = (now + delay).max(*last_delivery);
+        *last_delivery = deliver_at;
+        deliver_at - now
+    }
+
+    /// Applies the outgoing datagram impairments, then hands `data` to `send`.
+    #[cfg(feature = "datagrams")]
+    fn impaired_datagram(&self, data: Vec<u8>, send: impl FnOnce(Vec<u8>) + 'static) {
+        let fate = {
+            let mut state = self.state.borrow_mut();
+            state.stats.datagrams_sent += 1;
+            let fate = state.datagram_fate();
+            if let Fate::Drop = fate {
+                state.stats.datagrams_dropped_outgoing += 1;
+            }
+            fate
+        };
+        if let Fate::Deliver(delay) = fate {
+            self.after(delay, move || send(data));
+        }
+    }
+
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    fn after(&self, delay: Duration, task: impl FnOnce() + 'static) {
+        self.scheduler.schedule(delay, Box::new(task));
+    }
+}
+
+impl fmt::Debug for NetworkSimulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkSimulator")
+            .field("conditions", &self.state.borrow().conditions)
+            .finish()
+    }
+}
+
+/// A session whose outgoing traffic goes through a [`NetworkSimulator`].
+pub struct SimulatedTransport<T> {
+    inner: Rc<T>,
+    simulator: NetworkSimulator,
+}
+
+impl<T> SimulatedTransport<T> {
+    /// The wrapped session.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn simulator(&self) -> &NetworkSimulator {
+        &self.simulator
+    }
+}
+
+impl<T> fmt::Debug for SimulatedTransport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedTransport")
+            .field("simulator", &self.simulator)
+            .finish()
+    }
+}
+
+#[cfg(feature = "datagrams")]
+impl<T: Transport + 'static> SimulatedTransport<T> {
+    /// Applies the outgoing datagram impairments, then hands `data` to `send`.
+    fn impaired_datagram(&self, data: Vec<u8>, send: impl FnOnce(&T, Vec<u8>) + 'static) {
+        let inner = self.inner.clone();
+        self.simulator
+            .impaired_datagram(data, move |data| send(&inner, data));
+    }
+}
+
+impl<T: Transport + 'static> Transport for SimulatedTransport<T> {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        self.impaired_datagram(data, |inner, data| inner.send_datagram(data));
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        self.impaired_datagram(data, move |inner, data| {
+            inner.send_datagram_with_deadline(data, deadline);
+        });
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        self.impaired_datagram(data, move |inner, data| {
+            inner.send_datagram_with_priority(data, priority);
+        });
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        let delay = self.simulator.state.borrow_mut().delay();
+        let inner = self.inner.clone();
+        self.simulator
+            .after(delay, move || inner.send_unidirectional_stream(data));
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        let delay = self.simulator.state.borrow_mut().delay();
+        let inner = self.inner.clone();
+        self.simulator.after(delay, move || {
+            inner.send_unidirectional_stream_with_order(data, send_order);
+        });
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        let (sender, receiver) = oneshot::channel();
+        let delay = self.simulator.state.borrow_mut().delay();
+        let inner = self.inner.clone();
+        self.simulator.after(delay, move || {
+            let _ = sender.send(inner.send_unidirectional_stream_finished(data));
+        });
+        async move {
+            let finished = receiver
+                .await
+                .map_err(|_| WebTransportError::StreamError("stream was dropped".to_string()))?;
+            finished.await
+        }
+        .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        let delay = self.simulator.state.borrow_mut().delay();
+        let inner = self.inner.clone();
+        let callback = self.simulator.incoming(callback);
+        self.simulator.after(delay, move || {
+            inner.send_bidirectional_stream(data, callback);
+        });
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        let (sender, receiver) = oneshot::channel();
+        let request_delay = self.simulator.state.borrow_mut().delay();
+        let inner = self.inner.clone();
+        let simulator = self.simulator.clone();
+        self.simulator.after(request_delay, move || {
+            let response = inner.request(data);
+            yew::platform::spawn_local(async move {
+                let response = response.await;
+                let response_delay = simulator.state.borrow_mut().delay();
+                simulator.after(response_delay, move || {
+                    let _ = sender.send(response);
+                });
+            });
+        });
+        async move {
+            receiver
+                .await
+                .map_err(|_| WebTransportError::StreamError("request was dropped".to_string()))?
+        }
+        .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        let inner = Rc::new(
+            self.inner
+                .open_bidirectional_stream(self.simulator.incoming(on_data)),
+        );
+        let simulator = self.simulator.clone();
+        let last_delivery = Rc::new(RefCell::new(Duration::ZERO));
+        let writer = inner.clone();
+        let finish_at = last_delivery.clone();
+        let finisher = self.simulator.clone();
+        StreamWriter::new(
+            move |data| {
+                let delay = simulator.ordered_delay(&last_delivery);
+                let writer = writer.clone();
+                simulator.after(delay, move || writer.write(data));
+            },
+            move || {
+                // Keep the stream open until every delayed write went through.
+                let delay = finisher.ordered_delay(&finish_at);
+                finisher.after(delay, move || drop(inner));
+            },
+        )
+    }
+
+    /// Channel datagrams are impaired on the way out only: incoming ones are routed by the
+    /// wrapped session before they reach the simulator. Once through, they are sent by the
+    /// wrapped session's channel, and so counted and prioritized as its channel datagrams.
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let simulator = self.simulator.clone();
+        self.inner
+            .datagram_channel(id, on_message)
+            .wrap_sender(move |send| {
+                Rc::new(move |data: Vec<u8>| {
+                    let send = send.clone();
+                    simulator.impaired_datagram(data, move |data| send(data));
+                })
+            })
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.inner.subscribe_datagrams(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        self.inner.stats()
+    }
+
+    fn queued(&self) -> QueuedSends {
+        self.inner.queued()
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        self.inner.queued_on_channel(id)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        self.inner.set_channel_priority(id, priority);
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        self.inner.close()
+    }
+}
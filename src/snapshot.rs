@@ -0,0 +1,320 @@
+//! Game state replication with snapshots and deltas over datagrams.
+//!
+//! Every tick, a [`SnapshotSender`] sends the current state either in full or as a delta
+//! against the newest state the [`SnapshotReceiver`] acknowledged, which both ends still have.
+//! Deltas are small but only decodable if their base arrived, so a full snapshot is sent
+//! periodically, and as soon as nothing has been acknowledged for a while: sustained loss
+//! otherwise leaves the receiver unable to apply anything.
+//!
+//! Datagrams on the channel are:
+//!
+//! - `0 | tick varint | full state`
+//! - `1 | tick varint | base tick varint | delta`
+//! - `2 | tick varint`, acknowledging a tick, from the receiver.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    rc::{Rc, Weak},
+};
+use yew::callback::Callback;
+
+use crate::channel::DatagramChannel;
+use crate::transport::Transport;
+use crate::varint;
+
+const KIND_FULL: u8 = 0;
+const KIND_DELTA: u8 = 1;
+const KIND_ACK: u8 = 2;
+
+/// A replicated state, with its full and delta encodings.
+pub trait SnapshotState: Sized {
+    /// Encodes the whole state.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Encodes what changed since `base`.
+    fn diff(&self, base: &Self) -> Vec<u8>;
+
+    /// Decodes a state encoded with [`SnapshotState::encode`].
+    fn decode(data: &[u8]) -> Option<Self>;
+
+    /// Applies a delta produced by [`SnapshotState::diff`] with `self` as the base.
+    fn apply(&self, delta: &[u8]) -> Option<Self>;
+}
+
+/// Tuning for a [`SnapshotSender`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotConfig {
+    /// Ticks between two periodic full snapshots.
+    pub full_interval: u64,
+    /// Ticks without a new acknowledgement after which full snapshots are sent until one
+    /// arrives.
+    pub max_unacked_ticks: u64,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            full_interval: 60,
+            max_unacked_ticks: 10,
+        }
+    }
+}
+
+/// Counters kept by a [`SnapshotSender`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    pub full_sent: u64,
+    pub deltas_sent: u64,
+    /// Full snapshots sent because of sustained loss rather than the periodic interval.
+    pub loss_fallbacks: u64,
+    pub acks_received: u64,
+}
+
+fn encode_header(kind: u8, tick: u64) -> Vec<u8> {
+    let mut data = vec![kind];
+    varint::encode(tick, &mut data);
+    data
+}
+
+fn decode_header(data: &[u8]) -> Option<(u8, u64, &[u8])> {
+    let (&kind, rest) = data.split_first()?;
+    let (tick, len) = varint::decode(rest)?;
+    Some((kind, tick, &rest[len..]))
+}
+
+struct SenderState<S> {
+    config: SnapshotConfig,
+    tick: u64,
+    last_full: Option<u64>,
+    acked: Option<u64>,
+    /// States that can still serve as a delta base.
+    history: BTreeMap<u64, S>,
+    stats: SnapshotStats,
+}
+
+struct SenderInner<S> {
+    state: RefCell<SenderState<S>>,
+    channel: DatagramChannel,
+}
+
+impl<S> SenderInner<S> {
+    fn receive(&self, data: Vec<u8>) {
+        let Some((KIND_ACK, tick, _)) = decode_header(&data) else {
+            return;
+        };
+        let mut state = self.state.borrow_mut();
+        state.stats.acks_received += 1;
+        if state.acked.is_some_and(|acked| tick <= acked) || !state.history.contains_key(&tick) {
+            return;
+        }
+        state.acked = Some(tick);
+        state.history = state.history.split_off(&tick);
+    }
+}
+
+/// The authoritative side, sending its state every tick.
+pub struct SnapshotSender<S> {
+    inner: Rc<SenderInner<S>>,
+}
+
+impl<S: SnapshotState + 'static> SnapshotSender<S> {
+    /// Registers datagram channel `id` on `transport`.
+    pub fn new<T: Transport + ?Sized>(transport: &T, id: u64, config: SnapshotConfig) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<SenderInner<S>>| {
+            let receiver = inner.clone();
+            let channel = transport.datagram_channel(
+                id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            SenderInner {
+                state: RefCell::new(SenderState {
+                    config,
+                    tick: 0,
+                    last_full: None,
+                    acked: None,
+                    history: BTreeMap::new(),
+                    stats: SnapshotStats::default(),
+                }),
+                channel,
+            }
+        });
+        Self { inner }
+    }
+
+    /// Sends `state` as the next tick, in full or as a delta. Returns the tick.
+    pub fn send(&self, snapshot: S) -> u64 {
+        let data = {
+            let mut state = self.inner.state.borrow_mut();
+            let state = &mut *state;
+            state.tick += 1;
+            let tick = state.tick;
+            let periodic = match state.last_full {
+                Some(last) => tick - last >= state.config.full_interval,
+                None => true,
+            };
+            let base = state
+                .acked
+                .filter(|acked| tick - acked <= state.config.max_unacked_ticks)
+                .and_then(|acked| state.history.get(&acked).map(|base| (acked, base)));
+            let data = match base {
+                Some((base_tick, base)) if !periodic => {
+                    state.stats.deltas_sent += 1;
+                    let mut data = encode_header(KIND_DELTA, tick);
+                    varint::encode(base_tick, &mut data);
+                    data.extend(snapshot.diff(base));
+                    data
+                }
+                _ => {
+                    if !periodic {
+                        state.stats.loss_fallbacks += 1;
+                    }
+                    state.stats.full_sent += 1;
+                    state.last_full = Some(tick);
+                    let mut data = encode_header(KIND_FULL, tick);
+                    data.extend(snapshot.encode());
+                    data
+                }
+            };
+            state.history.insert(tick, snapshot);
+            // States this old are never used as a base again.
+            let oldest_base = tick.saturating_sub(state.config.max_unacked_ticks);
+            state.history = state.history.split_off(&oldest_base);
+            data
+        };
+        self.inner.channel.send(&data);
+        self.inner.state.borrow().tick
+    }
+
+    /// The newest tick acknowledged by the receiver.
+    pub fn acked(&self) -> Option<u64> {
+        self.inner.state.borrow().acked
+    }
+
+    pub fn stats(&self) -> SnapshotStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl<S> fmt::Debug for SnapshotSender<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("SnapshotSender")
+            .field("tick", &state.tick)
+            .field("acked", &state.acked)
+            .finish()
+    }
+}
+
+/// How many received states a [`SnapshotReceiver`] keeps as potential delta bases.
+const RECEIVER_HISTORY: usize = 64;
+
+struct ReceiverState<S> {
+    latest: Option<u64>,
+    history: BTreeMap<u64, Rc<S>>,
+    undecodable: u64,
+    on_state: Callback<Rc<S>>,
+}
+
+struct ReceiverInner<S> {
+    state: RefCell<ReceiverState<S>>,
+    channel: DatagramChannel,
+}
+
+impl<S: SnapshotState> ReceiverInner<S> {
+    fn receive(&self, data: Vec<u8>) {
+        let Some((kind, tick, body)) = decode_header(&data) else {
+            return;
+        };
+        let decoded = {
+            let mut state = self.state.borrow_mut();
+            if state.latest.is_some_and(|latest| tick <= latest) {
+                return;
+            }
+            let decoded = match kind {
+                KIND_FULL => S::decode(body),
+                KIND_DELTA => varint::decode(body).and_then(|(base_tick, len)| {
+                    state
+                        .history
+                        .get(&base_tick)
+                        .and_then(|base| base.apply(&body[len..]))
+                }),
+                _ => return,
+            };
+            let Some(decoded) = decoded.map(Rc::new) else {
+                state.undecodable += 1;
+                return;
+            };
+            state.latest = Some(tick);
+            state.history.insert(tick, decoded.clone());
+            while state.history.len() > RECEIVER_HISTORY {
+                state.history.pop_first();
+            }
+            (state.on_state.clone(), decoded)
+        };
+        self.channel.send(&encode_header(KIND_ACK, tick));
+        let (on_state, decoded) = decoded;
+        on_state.emit(decoded);
+    }
+}
+
+/// The replicating side, acknowledging every state it could decode.
+pub struct SnapshotReceiver<S> {
+    inner: Rc<ReceiverInner<S>>,
+}
+
+impl<S: SnapshotState + 'static> SnapshotReceiver<S> {
+    /// Registers datagram channel `id` on `transport`. Every state newer than the last one is
+    /// passed to `on_state`.
+    pub fn new<T: Transport + ?Sized>(transport: &T, id: u64, on_state: Callback<Rc<S>>) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<ReceiverInner<S>>| {
+            let receiver = inner.clone();
+            let channel = transport.datagram_channel(
+                id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            ReceiverInner {
+                state: RefCell::new(ReceiverState {
+                    latest: None,
+                    history: BTreeMap::new(),
+                    undecodable: 0,
+                    on_state,
+                }),
+                channel,
+            }
+        });
+        Self { inner }
+    }
+
+    /// The newest state received, with its tick.
+    pub fn latest(&self) -> Option<(u64, Rc<S>)> {
+        let state = self.inner.state.borrow();
+        let tick = state.latest?;
+        state
+            .history
+            .get(&tick)
+            .map(|latest| (tick, latest.clone()))
+    }
+
+    /// Number of deltas dropped because their base was unknown or they failed to decode.
+    pub fn undecodable(&self) -> u64 {
+        self.inner.state.borrow().undecodable
+    }
+}
+
+impl<S> fmt::Debug for SnapshotReceiver<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotReceiver")
+            .field("latest", &self.inner.state.borrow().latest)
+            .finish()
+    }
+}
@@ -0,0 +1,195 @@
+//! Coalescing values that arrive faster than they can be rendered.
+//!
+//! A component rendering every message of a stream sending thousands a second spends its frames
+//! rendering states nobody sees. A [`Throttler`] passes values on at most once per
+//! [`Throttle::interval`]: the first value after a quiet interval goes through right away, and
+//! those arriving within the interval are held until it ends, then passed on together, or only
+//! the latest of them, as [`Coalesce`] says. The timer only runs while values are held, and
+//! nothing held is passed on once the throttler is dropped.
+
+use std::{cell::RefCell, fmt, rc::Rc, time::Duration};
+use yew::callback::Callback;
+
+use crate::time::{PlatformScheduler, Scheduler};
+
+/// What a [`Throttler`] passes on of the values received within an interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coalesce {
+    /// Only the latest value, for values that replace each other, like the state of an object.
+    #[default]
+    Latest,
+    /// Every value, in the order received, for values that add up, like log lines.
+    Batch,
+}
+
+/// How often a [`Throttler`] passes values on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Throttle {
+    /// Time between two deliveries, at least.
+    pub interval: Duration,
+    pub coalesce: Coalesce,
+}
+
+impl Throttle {
+    /// At most `rate` deliveries a second, of the latest value.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn hz(rate: u32) -> Self {
+        Self::latest(Duration::from_secs(1) / rate)
+    }
+
+    /// The latest value, at most once per `interval`.
+    pub fn latest(interval: Duration) -> Self {
+        Self {
+            interval,
+            coalesce: Coalesce::Latest,
+        }
+    }
+
+    /// Every value, in batches at most once per `interval`.
+    pub fn batch(interval: Duration) -> Self {
+        Self {
+            interval,
+            coalesce: Coalesce::Batch,
+        }
+    }
+
+    /// The same rate, coalescing as `coalesce` says.
+    pub fn coalesce(mut self, coalesce: Coalesce) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+}
+
+/// Counters kept by a [`Throttler`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    pub received: u64,
+    pub deliveries: u64,
+    /// Values replaced by a later one with [`Coalesce::Latest`].
+    pub skipped: u64,
+}
+
+struct ThrottleState<T> {
+    throttle: Throttle,
+    held: Vec<T>,
+    last_delivery: Option<Duration>,
+    timer_armed: bool,
+    stats: ThrottleStats,
+    on_values: Callback<Vec<T>>,
+}
+
+struct Inner<T> {
+    state: RefCell<ThrottleState<T>>,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl<T: 'static> Inner<T> {
+    /// Passes on what is held if the interval since the last delivery has passed, or waits
+    /// for it to.
+    fn run(self: &Rc<Self>) {
+        let now = self.scheduler.now();
+        let delivery = {
+            let mut state = self.state.borrow_mut();
+            if state.held.is_empty() || state.timer_armed {
+                return;
+            }
+            let due = state
+                .last_delivery
+                .map_or(now, |last| last + state.throttle.interval);
+            if due > now {
+                state.timer_armed = true;
+                Err(due - now)
+            } else {
+                state.last_delivery = Some(now);
+                state.stats.deliveries += 1;
+                Ok((state.on_values.clone(), std::mem::take(&mut state.held)))
+            }
+        };
+        match delivery {
+            Ok((on_values, values)) => on_values.emit(values),
+            Err(wait) => {
+                let inner = Rc::downgrade(self);
+                self.scheduler.schedule(
+                    wait,
+                    Box::new(move || {
+                        if let Some(inner) = inner.upgrade() {
+                            inner.state.borrow_mut().timer_armed = false;
+                            inner.run();
+                        }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+/// Passes the values pushed to it on at most once per interval, see the
+/// [module documentation](self).
+pub struct Throttler<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T: 'static> Throttler<T> {
+    /// Creates a throttler passing values to `on_values`, timed by the platform timers.
+    pub fn new(throttle: Throttle, on_values: Callback<Vec<T>>) -> Self {
+        Self::with_scheduler(throttle, on_values, Rc::new(PlatformScheduler))
+    }
+
+    /// Creates a throttler passing values to `on_values`, timed by `scheduler`.
+    pub fn with_scheduler(
+        throttle: Throttle,
+        on_values: Callback<Vec<T>>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                state: RefCell::new(ThrottleState {
+                    throttle,
+                    held: Vec::new(),
+                    last_delivery: None,
+                    timer_armed: false,
+                    stats: ThrottleStats::default(),
+                    on_values,
+                }),
+                scheduler,
+            }),
+        }
+    }
+
+    /// Passes `value` on now if the interval since the last delivery has passed, and holds it
+    /// until it has otherwise.
+    pub fn push(&self, value: T) {
+        {
+            let mut state = self.inner.state.borrow_mut();
+            state.stats.received += 1;
+            if state.throttle.coalesce == Coalesce::Latest && !state.held.is_empty() {
+                state.held.clear();
+                state.stats.skipped += 1;
+            }
+            state.held.push(value);
+        }
+        self.inner.run();
+    }
+
+    /// Number of values held until the interval ends.
+    pub fn held(&self) -> usize {
+        self.inner.state.borrow().held.len()
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        self.inner.state.borrow().stats.clone()
+    }
+}
+
+impl<T> fmt::Debug for Throttler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.borrow();
+        f.debug_struct("Throttler")
+            .field("throttle", &state.throttle)
+            .field("held", &state.held.len())
+            .finish()
+    }
+}
@@ -0,0 +1,136 @@
+//! Clocks and timers used by the helpers layered on top of a session.
+//!
+//! Everything time dependent in this crate goes through a [`Scheduler`], so tests can swap the
+//! browser's timers for a [`ManualScheduler`] and step through time deterministically.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc, time::Duration};
+use yew::platform::{spawn_local, time::sleep};
+
+/// Milliseconds since the unix epoch, as reported by the platform clock.
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64() * 1000.0)
+            .unwrap_or_default()
+    }
+}
+
+/// A source of time and delayed execution.
+pub trait Scheduler {
+    /// Time elapsed since a fixed, arbitrary origin.
+    fn now(&self) -> Duration;
+
+    /// Runs `task` once `delay` has elapsed.
+    fn schedule(&self, delay: Duration, task: Box<dyn FnOnce()>);
+}
+
+/// A [`Scheduler`] backed by the platform clock and timers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlatformScheduler;
+
+impl Scheduler for PlatformScheduler {
+    fn now(&self) -> Duration {
+        Duration::from_secs_f64(now_ms() / 1000.0)
+    }
+
+    fn schedule(&self, delay: Duration, task: Box<dyn FnOnce()>) {
+        spawn_local(async move {
+            sleep(delay).await;
+            task();
+        });
+    }
+}
+
+#[derive(Default)]
+struct ManualState {
+    now: Duration,
+    next_id: u64,
+    queue: BTreeMap<(Duration, u64), Box<dyn FnOnce()>>,
+}
+
+/// A [`Scheduler`] whose clock only moves when told to.
+///
+/// Tasks due at the same instant run in the order they were scheduled. Cloning a
+/// `ManualScheduler` yields another handle to the same clock.
+#[derive(Clone, Default)]
+pub struct ManualScheduler {
+    state: Rc<RefCell<ManualState>>,
+}
+
+impl ManualScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `by`, running every task that becomes due on the way,
+    /// including tasks scheduled by those tasks.
+    pub fn advance(&self, by: Duration) {
+        let target = self.state.borrow().now.saturating_add(by);
+        while let Some(task) = self.pop_due(target) {
+            task();
+        }
+        self.state.borrow_mut().now = target;
+    }
+
+    /// Runs tasks until none are left, moving the clock to each task's deadline.
+    pub fn run_until_idle(&self) {
+        while let Some(task) = self.pop_due(Duration::MAX) {
+            task();
+        }
+    }
+
+    /// Number of tasks waiting to run.
+    pub fn pending(&self) -> usize {
+        self.state.borrow().queue.len()
+    }
+
+    fn pop_due(&self, until: Duration) -> Option<Box<dyn FnOnce()>> {
+        let mut state = self.state.borrow_mut();
+        let key = *state.queue.keys().next()?;
+        if key.0 > until {
+            return None;
+        }
+        state.now = state.now.max(key.0);
+        state.queue.remove(&key)
+    }
+}
+
+impl Scheduler for ManualScheduler {
+    fn now(&self) -> Duration {
+        self.state.borrow().now
+    }
+
+    fn schedule(&self, delay: Duration, task: Box<dyn FnOnce()>) {
+        let mut state = self.state.borrow_mut();
+        let key = (state.now.saturating_add(delay), state.next_id);
+        state.next_id += 1;
+        state.queue.insert(key, task);
+    }
+}
+
+impl fmt::Debug for ManualScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("ManualScheduler")
+            .field("now", &state.now)
+            .field("pending", &state.queue.len())
+            .finish()
+    }
+}
+
+/// Resolves with the output of `future`, or `None` if it did not complete within `duration`.
+pub async fn timeout<F: std::future::Future>(duration: Duration, future: F) -> Option<F::Output> {
+    use futures::future::{select, Either};
+    let future = std::pin::pin!(future);
+    let deadline = std::pin::pin!(sleep(duration));
+    match select(future, deadline).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
@@ -0,0 +1,344 @@
+//! Estimating the server's clock over datagrams, NTP style.
+//!
+//! A [`TimeSync`] periodically sends a probe carrying its local send time `t0` on a
+//! [`DatagramChannel`]. The server answers with `t0`, the time `t1` it received the probe and the
+//! time `t2` it sent the answer; the client notes the arrival time `t3`. Each exchange yields
+//!
+//! - the round-trip delay `(t3 - t0) - (t2 - t1)`, and
+//! - the clock offset `((t1 - t0) + (t2 - t3)) / 2`.
+//!
+//! The offset of the exchange with the smallest delay among the last few is kept, since a short
+//! round trip bounds the error from asymmetric paths best.
+//!
+//! Probes are `0u8 | t0`, answers are `1u8 | t0 | t1 | t2`, each time a big-endian `f64` of
+//! milliseconds. Server times are expected to be milliseconds since the unix epoch.
+//!
+//! Probes double as the session's keepalive, so the round-trip delays of the last exchanges are
+//! also kept in an [`RttHistogram`], summed up as [`RttStats`] for applications pacing what they
+//! send on real measurements.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use yew::callback::Callback;
+
+use crate::arq::RttEstimator;
+use crate::channel::DatagramChannel;
+use crate::time::{PlatformScheduler, Scheduler};
+use crate::transport::Transport;
+
+const KIND_PROBE: u8 = 0;
+const KIND_ANSWER: u8 = 1;
+
+/// Tuning for a [`TimeSync`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeSyncConfig {
+    /// Time between probes.
+    pub interval: Duration,
+    /// Number of recent exchanges the best sample is picked from.
+    pub window: usize,
+    /// Number of recent round-trip delays the [`RttStats`] are computed from.
+    pub rtt_history: usize,
+}
+
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            window: 8,
+            rtt_history: 64,
+        }
+    }
+}
+
+impl Default for RttHistogram {
+    /// A histogram of as many delays as a [`TimeSync`] keeps by default.
+    fn default() -> Self {
+        Self::new(TimeSyncConfig::default().rtt_history)
+    }
+}
+
+/// The outcome of one probe exchange.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockSample {
+    /// Milliseconds to add to the local clock to get the server's.
+    pub offset_ms: f64,
+    /// Round-trip time of the exchange, in milliseconds, excluding the server's processing.
+    pub round_trip_ms: f64,
+}
+
+/// Round-trip delays summed up by an [`RttHistogram`], in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RttStats {
+    /// Number of delays the stats are computed from.
+    pub samples: usize,
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    /// Smoothed round-trip time, as in RFC 6298.
+    pub smoothed_ms: f64,
+    /// How much delays vary around the smoothed one, the RFC 6298 `rttvar`.
+    pub jitter_ms: f64,
+}
+
+/// The round-trip delays of the last exchanges, along with a smoothed estimate over all of them.
+#[derive(Clone, Debug)]
+pub struct RttHistogram {
+    capacity: usize,
+    samples: VecDeque<f64>,
+    estimator: RttEstimator,
+}
+
+impl RttHistogram {
+    /// A histogram of the last `capacity` delays.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: VecDeque::new(),
+            estimator: RttEstimator::new(),
+        }
+    }
+
+    /// Records a round-trip delay, forgetting the oldest one if the histogram is full. Delays too
+    /// large to be a [`Duration`], infinite ones included, are ignored.
+    pub fn record(&mut self, round_trip_ms: f64) {
+        let round_trip_ms = round_trip_ms.max(0.0);
+        let Ok(round_trip) = Duration::try_from_secs_f64(round_trip_ms / 1000.0) else {
+            return;
+        };
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(round_trip_ms);
+        self.estimator.sample(round_trip);
+    }
+
+    /// The delay `percentile` percent of the recorded ones are at or under, by nearest rank.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        percentile_of(&self.sorted(), percentile)
+    }
+
+    /// The stats of the recorded delays, once there is one.
+    pub fn stats(&self) -> Option<RttStats> {
+        let sorted = self.sorted();
+        let smoothed = self.estimator.srtt()?;
+        Some(RttStats {
+            samples: sorted.len(),
+            min_ms: *sorted.first()?,
+            p50_ms: percentile_of(&sorted, 50.0)?,
+            p90_ms: percentile_of(&sorted, 90.0)?,
+            p99_ms: percentile_of(&sorted, 99.0)?,
+            max_ms: *sorted.last()?,
+            smoothed_ms: smoothed.as_secs_f64() * 1000.0,
+            jitter_ms: self.estimator.rttvar().as_secs_f64() * 1000.0,
+        })
+    }
+
+    fn sorted(&self) -> Vec<f64> {
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        sorted
+    }
+}
+
+fn percentile_of(sorted: &[f64], percentile: f64) -> Option<f64> {
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+struct SyncState {
+    config: TimeSyncConfig,
+    samples: VecDeque<ClockSample>,
+    best: Option<ClockSample>,
+    on_offset: Callback<ClockSample>,
+    rtt: RttHistogram,
+    on_rtt_stats: Option<Callback<RttStats>>,
+}
+
+struct Inner {
+    state: RefCell<SyncState>,
+    channel: DatagramChannel,
+    scheduler: Rc<dyn Scheduler>,
+}
+
+impl Inner {
+    fn local_ms(&self) -> f64 {
+        self.scheduler.now().as_secs_f64() * 1000.0
+    }
+
+    fn probe(&self) {
+        let mut data = Vec::with_capacity(9);
+        data.push(KIND_PROBE);
+        data.extend_from_slice(&self.local_ms().to_be_bytes());
+        self.channel.send(&data);
+    }
+
+    fn schedule_probe(self: &Rc<Self>) {
+        let interval = self.state.borrow().config.interval;
+        let inner = Rc::downgrade(self);
+        self.scheduler.schedule(
+            interval,
+            Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.probe();
+                    inner.schedule_probe();
+                }
+            }),
+        );
+    }
+
+    fn receive(&self, data: Vec<u8>) {
+        let t3 = self.local_ms();
+        let Some((&KIND_ANSWER, rest)) = data.split_first() else {
+            return;
+        };
+        let times: Vec<f64> = rest
+            .chunks_exact(8)
+            .map(|bytes| f64::from_be_bytes(bytes.try_into().unwrap()))
+            .collect();
+        let [t0, t1, t2] = times[..] else {
+            return;
+        };
+        let sample = ClockSample {
+            offset_ms: ((t1 - t0) + (t2 - t3)) / 2.0,
+            round_trip_ms: ((t3 - t0) - (t2 - t1)).max(0.0),
+        };
+        // Garbage or extreme times from the server would poison the estimate.
+        if !sample.offset_ms.is_finite() || !sample.round_trip_ms.is_finite() {
+            return;
+        }
+        let rtt_stats = {
+            let mut state = self.state.borrow_mut();
+            state.rtt.record(sample.round_trip_ms);
+            state.on_rtt_stats.clone().zip(state.rtt.stats())
+        };
+        if let Some((on_rtt_stats, stats)) = rtt_stats {
+            on_rtt_stats.emit(stats);
+        }
+        let (best, on_offset) = {
+            let mut state = self.state.borrow_mut();
+            state.samples.push_back(sample);
+            while state.samples.len() > state.config.window.max(1) {
+                state.samples.pop_front();
+            }
+            let best = state
+                .samples
+                .iter()
+                .copied()
+                .min_by(|a, b| a.round_trip_ms.total_cmp(&b.round_trip_ms));
+            if best == state.best {
+                return;
+            }
+            state.best = best;
+            (best, state.on_offset.clone())
+        };
+        if let Some(best) = best {
+            on_offset.emit(best);
+        }
+    }
+}
+
+/// Keeps an estimate of the server's clock.
+pub struct TimeSync {
+    inner: Rc<Inner>,
+}
+
+impl TimeSync {
+    /// Starts probing on datagram channel `id` of `transport`, timed by the platform timers.
+    /// `on_offset` is called whenever the estimate changes.
+    pub fn new<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        config: TimeSyncConfig,
+        on_offset: Callback<ClockSample>,
+    ) -> Self {
+        Self::with_scheduler(transport, id, config, on_offset, Rc::new(PlatformScheduler))
+    }
+
+    /// Starts probing on datagram channel `id` of `transport`, timed by `scheduler`.
+    pub fn with_scheduler<T: Transport + ?Sized>(
+        transport: &T,
+        id: u64,
+        config: TimeSyncConfig,
+        on_offset: Callback<ClockSample>,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Self {
+        let inner = Rc::new_cyclic(|inner: &Weak<Inner>| {
+            let receiver = inner.clone();
+            let channel = transport.datagram_channel(
+                id,
+                Callback::from(move |data: Vec<u8>| {
+                    if let Some(inner) = receiver.upgrade() {
+                        inner.receive(data);
+                    }
+                }),
+            );
+            Inner {
+                state: RefCell::new(SyncState {
+                    rtt: RttHistogram::new(config.rtt_history),
+                    on_rtt_stats: None,
+                    config,
+                    samples: VecDeque::new(),
+                    best: None,
+                    on_offset,
+                }),
+                channel,
+                scheduler,
+            }
+        });
+        inner.probe();
+        inner.schedule_probe();
+        Self { inner }
+    }
+
+    /// Sends a probe now, on top of the periodic ones.
+    pub fn sync_now(&self) {
+        self.inner.probe();
+    }
+
+    /// The current estimate, once an answer has been received.
+    pub fn estimate(&self) -> Option<ClockSample> {
+        self.inner.state.borrow().best
+    }
+
+    /// Milliseconds to add to the local clock to get the server's.
+    pub fn offset_ms(&self) -> Option<f64> {
+        self.estimate().map(|sample| sample.offset_ms)
+    }
+
+    /// The server's clock right now, in milliseconds, once an answer has been received.
+    pub fn estimated_server_time(&self) -> Option<f64> {
+        self.offset_ms()
+            .map(|offset| self.inner.local_ms() + offset)
+    }
+
+    /// Replaces the callback notified of estimate changes.
+    pub fn set_on_offset(&self, on_offset: Callback<ClockSample>) {
+        self.inner.state.borrow_mut().on_offset = on_offset;
+    }
+
+    /// Stats of the round-trip delays of the last exchanges, once an answer has been received.
+    pub fn rtt_stats(&self) -> Option<RttStats> {
+        self.inner.state.borrow().rtt.stats()
+    }
+
+    /// Calls `on_rtt_stats` with the updated [`RttStats`] after every exchange.
+    pub fn set_on_rtt_stats(&self, on_rtt_stats: Callback<RttStats>) {
+        self.inner.state.borrow_mut().on_rtt_stats = Some(on_rtt_stats);
+    }
+}
+
+impl fmt::Debug for TimeSync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeSync")
+            .field("estimate", &self.inner.state.borrow().best)
+            .field("rtt_stats", &self.rtt_stats())
+            .finish()
+    }
+}
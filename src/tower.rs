@@ -0,0 +1,175 @@
+//! [`tower::Service`](tower_service::Service) adapters for request/response calls, so tower
+//! middleware such as retries, timeouts and rate limits can wrap WebTransport calls.
+//!
+//! Every call opens a bidirectional stream, writes the request, finishes the stream and
+//! resolves with everything the server writes back before finishing its side.
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt,
+    marker::PhantomData,
+    rc::Rc,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+use crate::rpc::RpcClient;
+use crate::transport::Transport;
+use crate::webtransport::WebTransportError;
+
+/// A `Service<Vec<u8>, Response = Vec<u8>>` over a session.
+pub struct BytesService<T> {
+    transport: Rc<T>,
+}
+
+impl<T> BytesService<T> {
+    pub fn new(transport: Rc<T>) -> Self {
+        Self { transport }
+    }
+}
+
+impl<T> Clone for BytesService<T> {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for BytesService<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BytesService")
+    }
+}
+
+impl<T: Transport> Service<Vec<u8>> for BytesService<T> {
+    type Response = Vec<u8>;
+    type Error = WebTransportError;
+    type Future = LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Vec<u8>) -> Self::Future {
+        self.transport.request(request)
+    }
+}
+
+/// A typed `Service<Req, Response = Resp>` over a session, exchanging JSON documents.
+pub struct JsonService<T, Req, Resp> {
+    inner: BytesService<T>,
+    _marker: PhantomData<fn(Req) -> Resp>,
+}
+
+impl<T, Req, Resp> JsonService<T, Req, Resp> {
+    pub fn new(transport: Rc<T>) -> Self {
+        Self {
+            inner: BytesService::new(transport),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Req, Resp> Clone for JsonService<T, Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Req, Resp> fmt::Debug for JsonService<T, Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JsonService")
+    }
+}
+
+impl<T, Req, Resp> Service<Req> for JsonService<T, Req, Resp>
+where
+    T: Transport,
+    Req: Serialize,
+    Resp: DeserializeOwned + 'static,
+{
+    type Response = Resp;
+    type Error = WebTransportError;
+    type Future = LocalBoxFuture<'static, Result<Resp, WebTransportError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        let request = match serde_json::to_vec(&request) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = WebTransportError::SerializationError(e.to_string());
+                return futures::future::ready(Err(error)).boxed_local();
+            }
+        };
+        let response = self.inner.call(request);
+        async move {
+            let response = response.await?;
+            serde_json::from_slice(&response)
+                .map_err(|e| WebTransportError::SerializationError(e.to_string()))
+        }
+        .boxed_local()
+    }
+}
+
+/// A typed `Service<Req, Response = Resp>` calling one method of an
+/// [`RpcClient`](crate::rpc::RpcClient).
+pub struct RpcService<T, Req, Resp> {
+    client: RpcClient<T>,
+    method: Rc<str>,
+    _marker: PhantomData<fn(Req) -> Resp>,
+}
+
+impl<T, Req, Resp> RpcService<T, Req, Resp> {
+    pub fn new(client: RpcClient<T>, method: &str) -> Self {
+        Self {
+            client,
+            method: method.into(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Req, Resp> Clone for RpcService<T, Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            method: self.method.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Req, Resp> fmt::Debug for RpcService<T, Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcService")
+            .field("method", &self.method)
+            .finish()
+    }
+}
+
+impl<T, Req, Resp> Service<Req> for RpcService<T, Req, Resp>
+where
+    T: Transport + 'static,
+    Req: Serialize,
+    Resp: DeserializeOwned + 'static,
+{
+    type Response = Resp;
+    type Error = WebTransportError;
+    type Future = LocalBoxFuture<'static, Result<Resp, WebTransportError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        self.client.call(&self.method, &request)
+    }
+}
@@ -0,0 +1,665 @@
+//! A backend-agnostic view of a connected WebTransport session.
+
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use std::{fmt, rc::Rc, time::Duration};
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use yew::callback::Callback;
+
+#[cfg(feature = "datagrams")]
+use crate::channel::{DatagramChannel, DatagramFilter, DatagramSubscription};
+#[cfg(feature = "streams")]
+use crate::options::{LabeledData, StreamOptions};
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use crate::webtransport::SendOptions;
+use crate::webtransport::{WebTransportError, WebTransportTask};
+
+/// The operations available on a connected WebTransport session.
+///
+/// [`WebTransportTask`] implements this for real browser sessions and
+/// [`MockWebTransportTask`](crate::mock::MockWebTransportTask) implements it for tests,
+/// so components that hold a `Box<dyn Transport>` can be exercised without a server.
+pub trait Transport {
+    /// Sends a datagram.
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>);
+
+    /// Like [`Transport::send_datagram`], but `data` is dropped if it is still waiting to be
+    /// sent once `deadline` has elapsed, so stale real-time data doesn't waste bandwidth.
+    /// `deadline` overrides the session's default max age, if it has one. Backends without a
+    /// send queue send it right away.
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        let _ = deadline;
+        self.send_datagram(data);
+    }
+
+    /// Like [`Transport::send_datagram`], but `data` waits in the `priority` lane: while
+    /// backpressure holds datagrams back, the high priority ones are written first, in the
+    /// order they were sent, however many normal ones are waiting. Backends without a send
+    /// queue send it right away.
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        let _ = priority;
+        self.send_datagram(data);
+    }
+
+    /// Opens a unidirectional stream, writes `data` to it and closes it.
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>);
+
+    /// Like [`Transport::send_unidirectional_stream`], but the stream competes for bandwidth
+    /// with the session's other streams according to `send_order`: streams with a higher send
+    /// order are sent first. Backends without prioritization ignore `send_order`.
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        let _ = send_order;
+        self.send_unidirectional_stream(data);
+    }
+
+    /// Like [`Transport::send_unidirectional_stream`], but resolves once the stream is
+    /// finished, or with the error that prevented it, which only affects this stream. Backends
+    /// that can't tell resolve right away.
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        self.send_unidirectional_stream(data);
+        future::ready(Ok(())).boxed_local()
+    }
+
+    /// Opens a bidirectional stream, writes `data` to it and passes every chunk
+    /// received on it to `callback`.
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>);
+
+    /// Opens a bidirectional stream, writes `data` to it and resolves with everything
+    /// received on it once the peer finishes the stream.
+    #[cfg(feature = "streams")]
+    fn request(&self, data: Vec<u8>)
+        -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>>;
+
+    /// Opens a long-lived bidirectional stream. Chunks written through the returned
+    /// [`StreamWriter`] are sent in order, and every chunk received is passed to `on_data`.
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter;
+
+    /// Like [`Transport::send_unidirectional_stream`], with the stream opened as `options`
+    /// say, see [`crate::options`].
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_options(&self, data: Vec<u8>, options: &StreamOptions) {
+        let data = options.encode(data);
+        match options.send_order {
+            Some(send_order) => self.send_unidirectional_stream_with_order(data, send_order),
+            None => self.send_unidirectional_stream(data),
+        }
+    }
+
+    /// Like [`Transport::send_bidirectional_stream`], with the stream opened as `options`
+    /// say. Backends that can't prioritize bidirectional streams ignore the send order.
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream_with_options(
+        &self,
+        data: Vec<u8>,
+        options: &StreamOptions,
+        callback: Callback<LabeledData>,
+    ) {
+        self.send_bidirectional_stream(options.encode(data), options.receiver(callback));
+    }
+
+    /// Like [`Transport::open_bidirectional_stream`], with the stream opened as `options`
+    /// say. Backends that can't prioritize bidirectional streams ignore the send order.
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream_with_options(
+        &self,
+        options: &StreamOptions,
+        on_data: Callback<LabeledData>,
+    ) -> StreamWriter {
+        options.writer(self.open_bidirectional_stream(options.receiver(on_data)))
+    }
+
+    /// Registers a logical datagram channel, see [`crate::channel`]. Datagrams addressed to
+    /// `id` are passed to `on_message` instead of the session's `on_datagram` callback.
+    ///
+    /// # Panics
+    ///
+    /// If `id` doesn't fit in a QUIC varint.
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel;
+
+    /// Passes a copy of every datagram reaching the session's `on_datagram` callback to
+    /// `on_datagram` as well, if `filter` accepts it, until the returned subscription is
+    /// dropped. See [`crate::channel`].
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription;
+
+    /// Reads the session's counters. Backends that don't keep statistics report zeros.
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        future::ready(Ok(TransportStats::default())).boxed_local()
+    }
+
+    /// Sends handed to the session that are not written yet, e.g. because of backpressure, to
+    /// show that something is being sent, or shed load when they pile up. Backends that don't
+    /// queue sends report zeros.
+    fn queued(&self) -> QueuedSends {
+        QueuedSends::default()
+    }
+
+    /// Like [`Transport::queued`], for the datagrams sent on the [`DatagramChannel`] `id`.
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        let _ = id;
+        QueuedSends::default()
+    }
+
+    /// Sends the datagrams of the [`DatagramChannel`] `id` through the `priority` lane, see
+    /// [`Transport::send_datagram_with_priority`]. Channels are sent at normal priority
+    /// otherwise.
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        let _ = (id, priority);
+    }
+
+    /// Closes the session. Closing a session that is already closed, by either side, does
+    /// nothing and returns [`WebTransportError::AlreadyClosed`].
+    fn close(&self) -> Result<(), WebTransportError>;
+}
+
+/// A session's counters, as returned by [`Transport::stats`]. Counts are totals since the
+/// session was established.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransportStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_lost: u64,
+    pub smoothed_rtt: Option<Duration>,
+    /// Outgoing datagrams sent but lost in the network.
+    pub datagrams_lost_outgoing: u64,
+    /// Outgoing datagrams dropped before they could be sent.
+    pub datagrams_expired_outgoing: u64,
+    /// Incoming datagrams dropped because they were not read in time.
+    pub datagrams_dropped_incoming: u64,
+}
+
+/// The lane a datagram waits in until it is written, see
+/// [`Transport::send_datagram_with_priority`].
+#[cfg(feature = "datagrams")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DatagramPriority {
+    /// Written ahead of every normal datagram still waiting, for urgent control messages such
+    /// as acks, pings and input.
+    High,
+    /// Written once no high priority datagram is waiting, e.g. bulk telemetry.
+    #[default]
+    Normal,
+}
+
+/// Sends handed to a session but not written yet, see [`Transport::queued`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueuedSends {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+/// The sending half of a stream opened with [`Transport::open_bidirectional_stream`].
+///
+/// Writes may be issued before the stream is actually open; they are queued and sent in order.
+/// The stream is finished when the writer is dropped.
+pub struct StreamWriter {
+    write: Box<dyn Fn(Vec<u8>)>,
+    finish: Option<Box<dyn FnOnce()>>,
+}
+
+impl StreamWriter {
+    /// Creates a writer passing chunks to `write` and calling `finish` once it is dropped.
+    pub fn new(write: impl Fn(Vec<u8>) + 'static, finish: impl FnOnce() + 'static) -> Self {
+        Self {
+            write: Box::new(write),
+            finish: Some(Box::new(finish)),
+        }
+    }
+
+    /// Queues `data` to be written on the stream.
+    pub fn write(&self, data: Vec<u8>) {
+        (self.write)(data);
+    }
+
+    /// Finishes the stream once everything written so far has been sent.
+    pub fn finish(self) {}
+}
+
+impl Drop for StreamWriter {
+    fn drop(&mut self) {
+        if let Some(finish) = self.finish.take() {
+            finish();
+        }
+    }
+}
+
+impl fmt::Debug for StreamWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamWriter")
+    }
+}
+
+impl Transport for WebTransportTask {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        WebTransportTask::datagram(self.transport.clone(), data, None, &self.send_options());
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        let expired = self.expired_datagrams.clone();
+        let on_expired = Callback::from(move |_| expired.set(expired.get() + 1));
+        WebTransportTask::datagram(
+            self.transport.clone(),
+            data,
+            Some((deadline, on_expired)),
+            &self.send_options(),
+        );
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        let options = SendOptions {
+            priority: Some(priority),
+            ..self.send_options()
+        };
+        WebTransportTask::datagram(self.transport.clone(), data, None, &options);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        WebTransportTask::unidirectional_stream(self.transport.clone(), data, self.send_options());
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        let options = SendOptions {
+            send_order: Some(send_order),
+            ..self.send_options()
+        };
+        WebTransportTask::unidirectional_stream(self.transport.clone(), data, options);
+    }
+
+    /// Resolves with [`WebTransportError::AlreadyClosed`] if the session ends first.
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        let transport = self.transport.clone();
+        let chunking = self.chunking();
+        self.end
+            .until_closed(async move {
+                WebTransportTask::write_unidirectional_stream(&transport, &data, None, chunking)
+                    .await
+                    .map_err(|e| WebTransportError::StreamError(e.to_string()))
+            })
+            .map(|result| result.unwrap_or(Err(WebTransportError::AlreadyClosed)))
+            .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        WebTransportTask::send_bidirectional_stream_reporting(
+            self.transport.clone(),
+            data,
+            callback,
+            self.send_options(),
+        );
+    }
+
+    /// Resolves with [`WebTransportError::AlreadyClosed`] if the session ends first.
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        self.end
+            .until_closed(WebTransportTask::request(
+                self.transport.clone(),
+                data,
+                SendOptions {
+                    receive_limit: None,
+                    ..self.send_options()
+                },
+            ))
+            .map(|response| response.unwrap_or(Err(WebTransportError::AlreadyClosed)))
+            .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        WebTransportTask::open_bidirectional_stream_reporting(
+            self.transport.clone(),
+            on_data,
+            self.send_options(),
+        )
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_options(&self, data: Vec<u8>, options: &StreamOptions) {
+        WebTransportTask::unidirectional_stream(
+            self.transport.clone(),
+            options.encode(data),
+            self.stream_send_options(options),
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream_with_options(
+        &self,
+        data: Vec<u8>,
+        options: &StreamOptions,
+        callback: Callback<LabeledData>,
+    ) {
+        let send_options = self.stream_send_options(options);
+        WebTransportTask::send_bidirectional_stream_reporting(
+            self.transport.clone(),
+            options.encode(data),
+            options.receiver_reporting(callback, send_options.notification.clone()),
+            send_options,
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream_with_options(
+        &self,
+        options: &StreamOptions,
+        on_data: Callback<LabeledData>,
+    ) -> StreamWriter {
+        let send_options = self.stream_send_options(options);
+        options.writer(WebTransportTask::open_bidirectional_stream_reporting(
+            self.transport.clone(),
+            options.receiver_reporting(on_data, send_options.notification.clone()),
+            send_options,
+        ))
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let transport = self.transport.clone();
+        let options = SendOptions {
+            channel: Some(id),
+            ..self.send_options()
+        };
+        let send = Rc::new(move |data| {
+            WebTransportTask::datagram(transport.clone(), data, None, &options);
+        });
+        self.datagram_router.channel(id, on_message, send)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.datagram_router.subscribe(filter, on_datagram)
+    }
+
+    /// Datagrams dropped by [`Transport::send_datagram_with_deadline`] are counted as expired,
+    /// along with those the browser expired.
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        let expired = self.expired_datagrams.clone();
+        WebTransportTask::stats(self.transport.clone())
+            .map(move |stats| {
+                stats.map(|stats| TransportStats {
+                    datagrams_expired_outgoing: stats.datagrams_expired_outgoing + expired.get(),
+                    ..stats
+                })
+            })
+            .boxed_local()
+    }
+
+    fn queued(&self) -> QueuedSends {
+        self.send_queue.total()
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        self.send_queue.channel(id)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        self.datagram_lanes.set_channel_priority(id, priority);
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        WebTransportTask::close(self)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        (**self).send_datagram(data);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        (**self).send_datagram_with_deadline(data, deadline);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        (**self).send_datagram_with_priority(data, priority);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        (**self).send_unidirectional_stream(data);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        (**self).send_unidirectional_stream_with_order(data, send_order);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        (**self).send_unidirectional_stream_finished(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        (**self).send_bidirectional_stream(data, callback);
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        (**self).request(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        (**self).open_bidirectional_stream(on_data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_options(&self, data: Vec<u8>, options: &StreamOptions) {
+        (**self).send_unidirectional_stream_with_options(data, options);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream_with_options(
+        &self,
+        data: Vec<u8>,
+        options: &StreamOptions,
+        callback: Callback<LabeledData>,
+    ) {
+        (**self).send_bidirectional_stream_with_options(data, options, callback);
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream_with_options(
+        &self,
+        options: &StreamOptions,
+        on_data: Callback<LabeledData>,
+    ) -> StreamWriter {
+        (**self).open_bidirectional_stream_with_options(options, on_data)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        (**self).datagram_channel(id, on_message)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        (**self).subscribe_datagrams(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        (**self).stats()
+    }
+
+    fn queued(&self) -> QueuedSends {
+        (**self).queued()
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        (**self).queued_on_channel(id)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        (**self).set_channel_priority(id, priority);
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        (**self).close()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Rc<T> {
+    #[cfg(feature = "datagrams")]
+    fn send_datagram(&self, data: Vec<u8>) {
+        (**self).send_datagram(data);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_deadline(&self, data: Vec<u8>, deadline: Duration) {
+        (**self).send_datagram_with_deadline(data, deadline);
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn send_datagram_with_priority(&self, data: Vec<u8>, priority: DatagramPriority) {
+        (**self).send_datagram_with_priority(data, priority);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, data: Vec<u8>) {
+        (**self).send_unidirectional_stream(data);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_order(&self, data: Vec<u8>, send_order: i64) {
+        (**self).send_unidirectional_stream_with_order(data, send_order);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        (**self).send_unidirectional_stream_finished(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, data: Vec<u8>, callback: Callback<Vec<u8>>) {
+        (**self).send_bidirectional_stream(data, callback);
+    }
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        (**self).request(data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, on_data: Callback<Vec<u8>>) -> StreamWriter {
+        (**self).open_bidirectional_stream(on_data)
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_with_options(&self, data: Vec<u8>, options: &StreamOptions) {
+        (**self).send_unidirectional_stream_with_options(data, options);
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream_with_options(
+        &self,
+        data: Vec<u8>,
+        options: &StreamOptions,
+        callback: Callback<LabeledData>,
+    ) {
+        (**self).send_bidirectional_stream_with_options(data, options, callback);
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream_with_options(
+        &self,
+        options: &StreamOptions,
+        on_data: Callback<LabeledData>,
+    ) -> StreamWriter {
+        (**self).open_bidirectional_stream_with_options(options, on_data)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        (**self).datagram_channel(id, on_message)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        (**self).subscribe_datagrams(filter, on_datagram)
+    }
+
+    fn stats(&self) -> LocalBoxFuture<'static, Result<TransportStats, WebTransportError>> {
+        (**self).stats()
+    }
+
+    fn queued(&self) -> QueuedSends {
+        (**self).queued()
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        (**self).queued_on_channel(id)
+    }
+
+    #[cfg(feature = "datagrams")]
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        (**self).set_channel_priority(id, priority);
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        (**self).close()
+    }
+}
@@ -0,0 +1,141 @@
+//! Uploading a large payload over several unidirectional streams at once.
+//!
+//! A single stream is limited by its own flow control window, which leaves bandwidth unused on
+//! links with a large bandwidth-delay product. An [`Uploader`] splits the payload into
+//! contiguous segments and sends each one on its own unidirectional stream, framed as
+//!
+//! ```text
+//! upload id (varint) | offset (varint) | total length (varint) | segment bytes
+//! ```
+//!
+//! so that the server can put the payload back together from the offsets, whatever order the
+//! streams arrive in. The upload completes once every stream is finished. Varints are QUIC
+//! varints, see [`crate::varint`].
+
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use std::{cell::Cell, fmt, rc::Rc};
+
+use crate::transport::Transport;
+use crate::varint;
+use crate::webtransport::WebTransportError;
+
+/// Tuning for an [`Uploader`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadConfig {
+    /// Streams opened at most per upload.
+    pub streams: usize,
+    /// Smallest segment worth a stream of its own; smaller payloads use fewer streams.
+    pub min_segment: usize,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            streams: 4,
+            min_segment: 64 * 1024,
+        }
+    }
+}
+
+/// One stream's share of an upload, as decoded by the receiving side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub upload_id: u64,
+    /// Position of `data` in the payload.
+    pub offset: u64,
+    /// Length of the whole payload.
+    pub total_len: u64,
+    pub data: Vec<u8>,
+}
+
+/// Encodes a segment, as sent on one stream.
+pub fn encode_segment(upload_id: u64, offset: u64, total_len: u64, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 24);
+    varint::encode(upload_id, &mut out);
+    varint::encode(offset, &mut out);
+    varint::encode(total_len, &mut out);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Decodes everything received on one stream of an upload.
+pub fn decode_segment(stream: &[u8]) -> Option<Segment> {
+    let mut at = 0;
+    let mut next = || {
+        let (value, len) = varint::decode(&stream[at..])?;
+        at += len;
+        Some(value)
+    };
+    let upload_id = next()?;
+    let offset = next()?;
+    let total_len = next()?;
+    let data = stream[at..].to_vec();
+    let end = offset.checked_add(data.len() as u64)?;
+    if end > total_len {
+        return None;
+    }
+    Some(Segment {
+        upload_id,
+        offset,
+        total_len,
+        data,
+    })
+}
+
+/// Sends payloads split across concurrent unidirectional streams.
+pub struct Uploader<T> {
+    transport: Rc<T>,
+    config: UploadConfig,
+    next_id: Cell<u64>,
+}
+
+impl<T: Transport + 'static> Uploader<T> {
+    pub fn new(transport: Rc<T>, config: UploadConfig) -> Self {
+        Self {
+            transport,
+            config,
+            next_id: Cell::new(0),
+        }
+    }
+
+    /// Uploads `data`. Resolves once every stream is done, with the upload id, or with the
+    /// first error if a stream failed.
+    pub fn upload(&self, data: &[u8]) -> LocalBoxFuture<'static, Result<u64, WebTransportError>> {
+        let upload_id = self.next_id.get();
+        self.next_id.set(upload_id + 1);
+        let total_len = data.len() as u64;
+        let streams =
+            (data.len() / self.config.min_segment.max(1)).clamp(1, self.config.streams.max(1));
+        let segment_len = data.len().div_ceil(streams).max(1);
+        let mut sends: Vec<_> = data
+            .chunks(segment_len)
+            .enumerate()
+            .map(|(index, segment)| {
+                let offset = (index * segment_len) as u64;
+                let stream = encode_segment(upload_id, offset, total_len, segment);
+                self.transport.send_unidirectional_stream_finished(stream)
+            })
+            .collect();
+        if sends.is_empty() {
+            let stream = encode_segment(upload_id, 0, 0, &[]);
+            sends.push(self.transport.send_unidirectional_stream_finished(stream));
+        }
+        future::join_all(sends)
+            .map(move |results| {
+                results
+                    .into_iter()
+                    .collect::<Result<Vec<()>, _>>()
+                    .map(|_| upload_id)
+            })
+            .boxed_local()
+    }
+}
+
+impl<T> fmt::Debug for Uploader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Uploader")
+            .field("config", &self.config)
+            .field("uploads", &self.next_id.get())
+            .finish()
+    }
+}
@@ -0,0 +1,51 @@
+//! QUIC variable-length integers ([RFC 9000 §16](https://www.rfc-editor.org/rfc/rfc9000#section-16)).
+//!
+//! Used wherever this crate prefixes payloads with small identifiers, so the encodings stay
+//! readable by servers that already speak QUIC.
+
+/// The largest value a QUIC varint can hold.
+pub const MAX: u64 = (1 << 62) - 1;
+
+/// Number of bytes `value` takes once encoded.
+pub fn encoded_len(value: u64) -> usize {
+    match value {
+        0..=0x3f => 1,
+        0x40..=0x3fff => 2,
+        0x4000..=0x3fff_ffff => 4,
+        _ => 8,
+    }
+}
+
+/// Number of bytes of a varint whose first byte is `first`.
+pub fn len_from_first_byte(first: u8) -> usize {
+    1 << (first >> 6)
+}
+
+/// Appends `value` to `out`.
+///
+/// # Panics
+///
+/// If `value` is larger than [`MAX`].
+pub fn encode(value: u64, out: &mut Vec<u8>) {
+    assert!(value <= MAX, "{value} does not fit in a QUIC varint");
+    match encoded_len(value) {
+        1 => out.push(value as u8),
+        2 => out.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        4 => out.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => out.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+/// Decodes a varint from the start of `buf`, returning it with the number of bytes it used,
+/// or `None` if `buf` is too short.
+pub fn decode(buf: &[u8]) -> Option<(u64, usize)> {
+    let first = *buf.first()?;
+    let len = len_from_first_byte(first);
+    let bytes = buf.get(..len)?;
+    let value = bytes[1..]
+        .iter()
+        .fold(u64::from(first & 0x3f), |acc, byte| {
+            (acc << 8) | u64::from(*byte)
+        });
+    Some((value, len))
+}
@@ -24,24 +24,78 @@ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
  */
-use anyhow::{anyhow, Error};
-use std::{fmt, rc::Rc};
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use anyhow::anyhow;
+#[cfg(feature = "datagrams")]
+use std::collections::{HashSet, VecDeque};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    time::Duration,
+};
 use thiserror::Error as ThisError;
 use wasm_bindgen_futures::JsFuture;
 use yew::callback::Callback;
+#[cfg(feature = "streams")]
 use yew::platform::pinned::oneshot::channel;
 
-use gloo_console::log;
-use js_sys::{Boolean, JsString, Promise, Reflect, Uint8Array};
-use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use crate::certhash::CertificateHash;
+use crate::channel::DatagramRouter;
+#[cfg(feature = "formats")]
+use crate::error::Error;
+use crate::handler::Handler;
+use crate::limit::ReceiveLimit;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use crate::limit::{OverflowPolicy, ReadGate};
+#[cfg(feature = "datagrams")]
+use crate::logging::DATAGRAMS;
+use crate::logging::SESSION;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use crate::logging::STREAMS;
+#[cfg(feature = "streams")]
+use crate::options::StreamOptions;
+#[cfg(feature = "datagrams")]
+use crate::pool::{BufferPool, PooledBuffer};
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use crate::time::now_ms;
+#[cfg(feature = "datagrams")]
+use crate::transport::DatagramPriority;
+#[cfg(feature = "streams")]
+use crate::transport::StreamWriter;
+use crate::transport::{QueuedSends, TransportStats};
+use crate::worker::ReadWorker;
+#[cfg(feature = "streams")]
+use futures::channel::mpsc;
+use futures::channel::oneshot;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use futures::future::Either;
+use futures::future::{self, abortable, AbortHandle, FutureExt, LocalBoxFuture, Shared};
+#[cfg(feature = "streams")]
+use futures::StreamExt;
+use js_sys::{Array, Function, JsString, Object, Reflect, Uint8Array};
+use std::future::Future;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsCast, JsValue};
+#[cfg(feature = "datagrams")]
+use web_sys::WebTransportDatagramDuplexStream;
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+use web_sys::{ReadableStream, ReadableStreamDefaultReader, WritableStream};
+use web_sys::{
+    WebTransport, WebTransportBidirectionalStream, WebTransportCongestionControl,
+    WebTransportOptions, WebTransportReceiveStream,
+};
+#[cfg(feature = "streams")]
 use web_sys::{
-    ReadableStream, ReadableStreamDefaultReader, WebTransport, WebTransportBidirectionalStream,
-    WebTransportCloseInfo, WebTransportDatagramDuplexStream, WebTransportReceiveStream,
-    WritableStream,
+    WebTransportError as JsWebTransportError, WebTransportErrorOptions,
+    WebTransportSendStreamOptions, WritableStreamDefaultWriter,
 };
 
 /// Represents formatting errors.
-#[derive(Debug, ThisError)]
+#[cfg(feature = "formats")]
+#[derive(Clone, Debug, PartialEq, Eq, ThisError)]
 pub enum FormatError {
     /// Received text for a binary format, e.g. someone sending text
     /// on a WebTransport that is using a binary serialization format, like Cbor.
@@ -60,10 +114,12 @@ pub enum FormatError {
 /// A representation of a value which can be stored and restored as a text.
 ///
 /// Some formats are binary only and can't be serialized to or deserialized
-/// from Text.  Attempting to do so will return an Err(FormatError).
+/// from Text.  Attempting to do so will return an Err([`Error::Format`]).
+#[cfg(feature = "formats")]
 pub type Text = Result<String, Error>;
 
 /// A representation of a value which can be stored and restored as a binary.
+#[cfg(feature = "formats")]
 pub type Binary = Result<Vec<u8>, Error>;
 
 /// The status of a WebTransport connection. Used for status notifications.
@@ -72,40 +128,612 @@ pub enum WebTransportStatus {
     /// Fired when a WebTransport connection has opened.
     Opened,
     /// Fired when a WebTransport connection has closed.
-    Closed(JsValue),
-    /// Fired when a WebTransport connection has failed.
-    Error(JsValue),
+    Closed(CloseInfo),
+    /// Fired when a WebTransport connection could not be established.
+    Error(SessionError),
+    /// Fired when receiving failed on one stream, or a loop receiving datagrams or incoming
+    /// streams stopped with an error. Only the affected stream or loop stops; the session is
+    /// not closed because of it. Also fired for the sends that fail, as the
+    /// [`SendFailurePolicy`] says.
+    StreamError(String),
+}
+
+/// Everything a session passes to its handler when connected with
+/// [`WebTransportService::connect_with_events`], for apps matching on one message in `update()`
+/// rather than handling five callbacks.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WtEvent {
+    /// See [`WebTransportStatus::Opened`].
+    Opened,
+    /// See [`WebTransportStatus::Closed`].
+    Closed(CloseInfo),
+    /// See [`WebTransportStatus::Error`].
+    Error(SessionError),
+    /// See [`WebTransportStatus::StreamError`]. Also fired when reading an incoming
+    /// unidirectional stream fails.
+    StreamError(String),
+    /// A datagram from the server.
+    Datagram(Vec<u8>),
+    /// Data read from a unidirectional stream the server opened. Streams are numbered from 0 in
+    /// the order they arrive.
+    UniStreamData { stream: u64, data: Vec<u8> },
+    /// The server finished the unidirectional stream `stream`.
+    UniStreamFinished { stream: u64 },
+    /// The server opened a bidirectional stream.
+    BidiStreamOpened(WebTransportBidirectionalStream),
+}
+
+impl From<WebTransportStatus> for WtEvent {
+    fn from(status: WebTransportStatus) -> Self {
+        match status {
+            WebTransportStatus::Opened => WtEvent::Opened,
+            WebTransportStatus::Closed(info) => WtEvent::Closed(info),
+            WebTransportStatus::Error(error) => WtEvent::Error(error),
+            WebTransportStatus::StreamError(error) => WtEvent::StreamError(error),
+        }
+    }
+}
+
+/// A status notification along with the label of the session it is about, see
+/// [`WebTransportBuilder::label`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabeledStatus {
+    pub label: Option<Rc<str>>,
+    pub status: WebTransportStatus,
 }
 
-#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
 /// An error encountered by a WebTransport.
 pub enum WebTransportError {
     #[error("{0}")]
     /// An error encountered when creating the WebTransport.
     CreationError(String),
+    #[error("{0}")]
+    /// An error encountered while writing to or reading from a stream.
+    StreamError(String),
+    #[error("{0}")]
+    /// An error encountered while serializing or deserializing a message.
+    SerializationError(String),
+    #[error("timed out")]
+    /// The operation did not complete in time.
+    Timeout,
+    #[error("remote error: {0}")]
+    /// The server reported an error while handling a request.
+    RemoteError(String),
+    #[error("the session is already closed")]
+    /// The session was closed before, by either side.
+    AlreadyClosed,
+    #[error(transparent)]
+    /// The session could not be established.
+    Session(SessionError),
+}
+
+/// How a WebTransport session ended, parsed from the value its `closed` promise settled with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CloseInfo {
+    /// The code the peer closed the session with, if it sent one.
+    pub close_code: Option<u32>,
+    /// The reason the peer gave, or what went wrong if the session was lost. Empty when the
+    /// session was closed locally.
+    pub reason: String,
+}
+
+impl CloseInfo {
+    /// Parses a `WebTransportCloseInfo` dictionary, or the error the `closed` promise was
+    /// rejected with when the session was lost.
+    pub fn from_js(value: &JsValue) -> Self {
+        if !value.is_object() {
+            return Self {
+                close_code: None,
+                reason: value.as_string().unwrap_or_default(),
+            };
+        }
+        let field = |field: &str| Reflect::get(value, &JsString::from(field)).ok();
+        let close_code = field("closeCode")
+            .and_then(|code| code.as_f64())
+            .filter(|code| code.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(code))
+            .map(|code| code as u32);
+        let reason = field("reason")
+            .and_then(|reason| reason.as_string())
+            .or_else(|| field("message").and_then(|message| message.as_string()))
+            .unwrap_or_default();
+        Self { close_code, reason }
+    }
+}
+
+impl fmt::Display for CloseInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.close_code, self.reason.as_str()) {
+            (Some(code), "") => write!(f, "closed with code {code}"),
+            (Some(code), reason) => write!(f, "closed with code {code}: {reason}"),
+            (None, "") => f.write_str("closed"),
+            (None, reason) => write!(f, "closed: {reason}"),
+        }
+    }
+}
+
+/// Why a WebTransport connection could not be established, mapped from the name of the
+/// `DOMException` the browser rejected it with. Each variant holds the exception's message.
+#[derive(Clone, Debug, PartialEq, Eq, ThisError)]
+pub enum SessionError {
+    #[error("network error: {0}")]
+    /// The server could not be reached or the handshake failed (`NetworkError`).
+    Network(String),
+    #[error("security error: {0}")]
+    /// The connection is not allowed, e.g. the certificate was rejected (`SecurityError`).
+    Security(String),
+    #[error("handshake failed: {0}")]
+    /// The server refused the session (`WebTransportError`).
+    Handshake(String),
+    #[error("aborted: {0}")]
+    /// The connection attempt was aborted (`AbortError`).
+    Aborted(String),
+    #[error("timed out: {0}")]
+    /// The connection attempt timed out (`TimeoutError`).
+    Timeout(String),
+    #[error("not supported: {0}")]
+    /// The browser doesn't support what was asked for (`NotSupportedError`).
+    NotSupported(String),
+    #[error("{name}: {message}")]
+    /// Any other exception, or a rejection value that is not an exception, in which case the
+    /// name is empty and the message is the value's debug representation.
+    Other { name: String, message: String },
+}
+
+impl SessionError {
+    /// Maps the value a WebTransport promise was rejected with.
+    pub fn from_js(value: &JsValue) -> Self {
+        let field = |field: &str| {
+            Reflect::get(value, &JsString::from(field))
+                .ok()
+                .and_then(|field| field.as_string())
+        };
+        if !value.is_object() {
+            return Self::other(value);
+        }
+        let Some(name) = field("name") else {
+            return Self::other(value);
+        };
+        let message = field("message").unwrap_or_default();
+        match name.as_str() {
+            "NetworkError" => Self::Network(message),
+            "SecurityError" => Self::Security(message),
+            "WebTransportError" => Self::Handshake(message),
+            "AbortError" => Self::Aborted(message),
+            "TimeoutError" => Self::Timeout(message),
+            "NotSupportedError" => Self::NotSupported(message),
+            _ => Self::Other { name, message },
+        }
+    }
+
+    fn other(value: &JsValue) -> Self {
+        Self::Other {
+            name: String::new(),
+            message: format!("{value:?}"),
+        }
+    }
 }
 
 /// A handle to control the WebTransport connection. Implements `Task` and could be canceled.
+///
+/// Sends made through its [`Transport`](crate::transport::Transport) implementation are
+/// cancelled once the session is closed or the task dropped.
 #[must_use = "the connection will be closed when the task is dropped"]
 pub struct WebTransportTask {
     pub transport: Rc<WebTransport>,
-    #[allow(dead_code)]
-    notification: Callback<WebTransportStatus>,
-    #[allow(dead_code)]
-    listeners: [Promise; 2],
+    url: String,
+    dial_options: DialOptions,
+    label: Option<Rc<str>>,
+    pub(crate) notification: Callback<WebTransportStatus>,
+    /// Stops the status listeners, and releases what they hold, once the task is dropped.
+    listeners: AbortHandle,
+    pub(crate) end: Rc<SessionEnd>,
+    chunking: SendChunking,
+    send_failure: SendFailurePolicy,
+    read_mode: ReadMode,
+    receive_limit: Option<ReceiveLimit>,
+    read_worker: Option<ReadWorker>,
+    stream_acceptance: StreamAcceptance,
+    /// Readers of the incoming stream queues, taken on the first lazy accept.
+    #[cfg(feature = "streams")]
+    unidirectional_acceptor: RefCell<Option<ReadableStreamDefaultReader>>,
+    #[cfg(feature = "streams")]
+    bidirectional_acceptor: RefCell<Option<ReadableStreamDefaultReader>>,
+    #[cfg_attr(not(feature = "datagrams"), allow(dead_code))]
+    pub(crate) datagram_router: DatagramRouter,
+    #[cfg(feature = "datagrams")]
+    pub(crate) datagram_lanes: Rc<DatagramLanes>,
+    #[cfg(feature = "datagrams")]
+    datagram_gate: ReadGate,
+    pub(crate) expired_datagrams: Rc<Cell<u64>>,
+    datagram_max_age: Option<Duration>,
+    pub(crate) send_queue: Rc<SendQueue>,
 }
 
 impl WebTransportTask {
     fn new(
+        url: String,
+        dial_options: DialOptions,
         transport: Rc<WebTransport>,
         notification: Callback<WebTransportStatus>,
-        listeners: [Promise; 2],
+        listeners: AbortHandle,
+        end: Rc<SessionEnd>,
+        datagram_router: DatagramRouter,
     ) -> WebTransportTask {
         WebTransportTask {
             transport,
+            url,
+            dial_options,
+            label: None,
             notification,
             listeners,
+            end,
+            chunking: SendChunking::default(),
+            send_failure: SendFailurePolicy::default(),
+            read_mode: ReadMode::default(),
+            receive_limit: None,
+            read_worker: None,
+            stream_acceptance: StreamAcceptance::default(),
+            #[cfg(feature = "streams")]
+            unidirectional_acceptor: RefCell::new(None),
+            #[cfg(feature = "streams")]
+            bidirectional_acceptor: RefCell::new(None),
+            datagram_router,
+            #[cfg(feature = "datagrams")]
+            datagram_lanes: Rc::default(),
+            #[cfg(feature = "datagrams")]
+            datagram_gate: ReadGate::default(),
+            expired_datagrams: Rc::default(),
+            datagram_max_age: None,
+            send_queue: Rc::default(),
+        }
+    }
+
+    /// The URL the session was opened to, the one that won with
+    /// [`WebTransportBuilder::connect_racing`].
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether the session can carry datagrams. `None` if the browser doesn't tell.
+    pub fn reliability(&self) -> Option<Reliability> {
+        self.attribute("reliability")
+            .and_then(|reliability| Reliability::from_js(&reliability))
+    }
+
+    /// Whether the session supports reliable streams, which every established session does.
+    /// `None` until the session is established, or if the browser doesn't tell.
+    pub fn supports_reliable_streams(&self) -> Option<bool> {
+        match self.reliability()? {
+            Reliability::Pending => None,
+            Reliability::ReliableOnly | Reliability::SupportsUnreliable => Some(true),
+        }
+    }
+
+    /// Whether datagrams were required with [`WebTransportBuilder::require_unreliable`] and
+    /// the established session supports them. `None` if they were not required, the session
+    /// is not established yet, or the browser doesn't tell.
+    pub fn require_unreliable_honored(&self) -> Option<bool> {
+        if !self.dial_options.require_unreliable {
+            return None;
+        }
+        match self.reliability()? {
+            Reliability::Pending => None,
+            reliability => Some(reliability == Reliability::SupportsUnreliable),
+        }
+    }
+
+    /// The congestion control the browser settled on, which may differ from the one asked for
+    /// with [`WebTransportBuilder::congestion_control`]. `None` if the browser doesn't tell.
+    pub fn congestion_control(&self) -> Option<CongestionControl> {
+        self.attribute("congestionControl")
+            .and_then(|congestion_control| CongestionControl::from_js(&congestion_control))
+    }
+
+    /// Reads a string attribute of the session, which older browsers may not have.
+    fn attribute(&self, name: &str) -> Option<String> {
+        Reflect::get(&self.transport, &JsString::from(name))
+            .ok()
+            .and_then(|value| value.as_string())
+    }
+
+    /// The label the session was given, see [`WebTransportBuilder::label`].
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// How payloads sent through the task are chunked.
+    pub fn chunking(&self) -> SendChunking {
+        self.chunking
+    }
+
+    /// What happens to the sends made through the task that fail.
+    pub fn send_failure(&self) -> SendFailurePolicy {
+        self.send_failure
+    }
+
+    /// How streams opened through the task are read.
+    pub fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+
+    /// The cap on received data the application has yet to process, if one was set.
+    pub fn receive_limit(&self) -> Option<&ReceiveLimit> {
+        self.receive_limit.as_ref()
+    }
+
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    pub(crate) fn send_options(&self) -> SendOptions {
+        SendOptions {
+            end: Some(self.end.clone()),
+            #[cfg(feature = "streams")]
+            chunking: self.chunking,
+            #[cfg(feature = "streams")]
+            read_mode: self.read_mode,
+            #[cfg(feature = "streams")]
+            receive_limit: self.receive_limit.clone(),
+            label: self.label.clone(),
+            #[cfg(feature = "streams")]
+            send_order: None,
+            queue: Some(self.send_queue.clone()),
+            channel: None,
+            on_failure: self.send_failure,
+            notification: Some(self.notification.clone()),
+            #[cfg(feature = "datagrams")]
+            datagram_max_age: self.datagram_max_age.map(|max_age| {
+                let expired = self.expired_datagrams.clone();
+                let on_expired = Callback::from(move |_| expired.set(expired.get() + 1));
+                (max_age, on_expired)
+            }),
+            #[cfg(feature = "datagrams")]
+            lanes: Some(self.datagram_lanes.clone()),
+            #[cfg(feature = "datagrams")]
+            priority: None,
+        }
+    }
+
+    /// The send options of a stream opened with `options`: its own label, if it has one,
+    /// replaces the session's.
+    #[cfg(feature = "streams")]
+    pub(crate) fn stream_send_options(&self, options: &StreamOptions) -> SendOptions {
+        let send_options = self.send_options();
+        SendOptions {
+            label: options.label.clone().or(send_options.label),
+            send_order: options.send_order,
+            ..send_options
+        }
+    }
+
+    /// Like [`WebTransportTask::read_incoming_stream`], following the task's read mode and
+    /// receive limit. With a [`ReadWorker`], the stream is read in the worker instead; the read
+    /// mode doesn't apply then, and the receive limit counts the data without pausing reads.
+    #[cfg(feature = "streams")]
+    pub fn read_incoming(
+        &self,
+        stream: ReadableStream,
+        callback: Callback<Vec<u8>>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        self.read_incoming_with_gate(stream, callback, None)
+    }
+
+    /// Like [`WebTransportTask::read_incoming`], pausing the reads while `gate` is paused, e.g.
+    /// while the component showing the stream is hidden. Reads in a [`ReadWorker`] don't
+    /// pause.
+    #[cfg(feature = "streams")]
+    pub fn read_incoming_gated(
+        &self,
+        stream: ReadableStream,
+        callback: Callback<Vec<u8>>,
+        gate: ReadGate,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        self.read_incoming_with_gate(stream, callback, Some(gate))
+    }
+
+    #[cfg(feature = "streams")]
+    fn read_incoming_with_gate(
+        &self,
+        stream: ReadableStream,
+        callback: Callback<Vec<u8>>,
+        gate: Option<ReadGate>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        let read_mode = self.read_mode;
+        let receive_limit = self.receive_limit.clone();
+        if let Some(worker) = &self.read_worker {
+            let overflowed = Rc::new(Cell::new(false));
+            let overflow = overflowed.clone();
+            let reading = worker.read_with(&stream, move |chunk| {
+                let data = Uint8Array::new(&chunk).to_vec();
+                if overflow.get() {
+                    return;
+                }
+                if let Some(limit) = &receive_limit {
+                    if !limit.charge(data.len()) {
+                        overflow.set(true);
+                        return;
+                    }
+                }
+                callback.emit(data);
+            });
+            return async move {
+                reading.await?;
+                if overflowed.get() {
+                    return Err(WebTransportError::StreamError(
+                        "Stopped reading the stream: the receive limit is full".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            .boxed_local();
+        }
+        async move {
+            let readable: ReadableStreamDefaultReader =
+                cast(stream.get_reader(), "a stream reader")?;
+            Self::read_stream(readable, callback, read_mode, receive_limit, gate)
+                .await
+                .map_err(|e| WebTransportError::StreamError(e.to_string()))
+        }
+        .boxed_local()
+    }
+
+    /// How incoming streams are accepted.
+    pub fn stream_acceptance(&self) -> StreamAcceptance {
+        self.stream_acceptance
+    }
+
+    /// With [`StreamAcceptance::Lazy`], takes the next unidirectional stream the server
+    /// opened, waiting for one if there is none yet. Resolves with `None` once the session
+    /// accepts no more streams. The stream is not read: read it with
+    /// [`WebTransportTask::read_incoming`], or decline it with `cancel()`.
+    #[cfg(feature = "streams")]
+    pub fn accept_unidirectional_stream(
+        &self,
+    ) -> LocalBoxFuture<'static, Result<Option<WebTransportReceiveStream>, WebTransportError>> {
+        let acceptor = self.acceptor(&self.unidirectional_acceptor, || {
+            self.transport.incoming_unidirectional_streams()
+        });
+        async move { accept_next(acceptor?, "an incoming unidirectional stream").await }
+            .boxed_local()
+    }
+
+    /// Like [`WebTransportTask::accept_unidirectional_stream`], for bidirectional streams.
+    #[cfg(feature = "streams")]
+    pub fn accept_bidirectional_stream(
+        &self,
+    ) -> LocalBoxFuture<'static, Result<Option<WebTransportBidirectionalStream>, WebTransportError>>
+    {
+        let acceptor = self.acceptor(&self.bidirectional_acceptor, || {
+            self.transport.incoming_bidirectional_streams()
+        });
+        async move { accept_next(acceptor?, "an incoming bidirectional stream").await }
+            .boxed_local()
+    }
+
+    #[cfg(feature = "streams")]
+    fn acceptor(
+        &self,
+        acceptor: &RefCell<Option<ReadableStreamDefaultReader>>,
+        incoming: impl FnOnce() -> ReadableStream,
+    ) -> Result<ReadableStreamDefaultReader, WebTransportError> {
+        if self.stream_acceptance != StreamAcceptance::Lazy {
+            return Err(WebTransportError::StreamError(
+                "Incoming streams are passed to the stream callbacks unless accepted lazily"
+                    .to_string(),
+            ));
         }
+        let mut acceptor = acceptor.borrow_mut();
+        if let Some(reader) = acceptor.as_ref() {
+            return Ok(reader.clone());
+        }
+        let reader: ReadableStreamDefaultReader = cast(incoming().get_reader(), "a stream reader")?;
+        *acceptor = Some(reader.clone());
+        Ok(reader)
+    }
+
+    /// The session's datagram streams, for the application to drive them itself, e.g. to pipe
+    /// them into other web APIs. Fails if incoming datagrams are read by the task, unless that
+    /// was turned off with [`WebTransportBuilder::read_datagrams`].
+    ///
+    /// Sends made through the task lock the writable side while they are written.
+    #[cfg(feature = "datagrams")]
+    pub fn raw_datagrams(&self) -> Result<WebTransportDatagramDuplexStream, WebTransportError> {
+        let datagrams = self.transport.datagrams();
+        unlocked(datagrams.readable(), "Incoming datagrams")?;
+        Ok(datagrams)
+    }
+
+    /// The stream of unidirectional streams the server opens. Fails if they are taken by the
+    /// task, unless they are accepted lazily, see [`StreamAcceptance::Lazy`], and
+    /// [`WebTransportTask::accept_unidirectional_stream`] was never called.
+    #[cfg(feature = "streams")]
+    pub fn raw_incoming_unidirectional_streams(&self) -> Result<ReadableStream, WebTransportError> {
+        unlocked(
+            self.transport.incoming_unidirectional_streams(),
+            "Incoming unidirectional streams",
+        )
+    }
+
+    /// Like [`WebTransportTask::raw_incoming_unidirectional_streams`], for bidirectional
+    /// streams.
+    #[cfg(feature = "streams")]
+    pub fn raw_incoming_bidirectional_streams(&self) -> Result<ReadableStream, WebTransportError> {
+        unlocked(
+            self.transport.incoming_bidirectional_streams(),
+            "Incoming bidirectional streams",
+        )
+    }
+
+    /// Number of datagrams sent with a deadline that were dropped because it passed before
+    /// they could be sent.
+    pub fn expired_datagrams(&self) -> u64 {
+        self.expired_datagrams.get()
+    }
+
+    /// Stops reading incoming datagrams, after the read in progress, until
+    /// [`WebTransportTask::resume_datagrams`]. The browser drops those that arrive meanwhile
+    /// once its queue is full. Datagrams read in a [`ReadWorker`] keep coming.
+    #[cfg(feature = "datagrams")]
+    pub fn pause_datagrams(&self) {
+        log::debug!(target: DATAGRAMS, "pausing incoming datagrams");
+        self.datagram_gate.pause();
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub fn resume_datagrams(&self) {
+        log::debug!(target: DATAGRAMS, "resuming incoming datagrams");
+        self.datagram_gate.resume();
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub fn datagrams_paused(&self) -> bool {
+        self.datagram_gate.is_paused()
+    }
+
+    /// The deadline of the datagrams sent without one of their own, see
+    /// [`WebTransportBuilder::datagram_max_age`].
+    pub fn datagram_max_age(&self) -> Option<Duration> {
+        self.datagram_max_age
+    }
+
+    /// Resolves once the session is established, or with the reason it could not be. Futures
+    /// spawned along with the session can await it before they send anything.
+    pub fn ready(&self) -> impl Future<Output = Result<(), WebTransportError>> + 'static {
+        let ready = JsFuture::from(self.transport.ready());
+        async move {
+            ready
+                .await
+                .map(|_| ())
+                .map_err(|e| WebTransportError::Session(SessionError::from_js(&e)))
+        }
+    }
+
+    /// Resolves once the session is closed, by either side, or lost, with how it ended. Unlike
+    /// the [`WebTransportStatus::Closed`] notification, it can be awaited from any future, such
+    /// as a protocol driver spawned along with the session. A session that failed to open
+    /// resolves with the error as the reason.
+    pub fn closed(&self) -> impl Future<Output = CloseInfo> + 'static {
+        let closed = JsFuture::from(self.transport.closed());
+        async move { CloseInfo::from_js(&closed.await.unwrap_or_else(|e| e)) }
+    }
+
+    /// Closes the session and notifies [`WebTransportStatus::Closed`]. Closing a session that
+    /// is already closed, by either side, does nothing and returns
+    /// [`WebTransportError::AlreadyClosed`].
+    pub fn close(&self) -> Result<(), WebTransportError> {
+        if !self.end.finish() {
+            return Err(WebTransportError::AlreadyClosed);
+        }
+        log::info!(target: SESSION, "closing the session to {}", self.url);
+        let result = close_session(&self.transport);
+        self.notification
+            .emit(WebTransportStatus::Closed(CloseInfo::default()));
+        result
+    }
+}
+
+impl Drop for WebTransportTask {
+    fn drop(&mut self) {
+        self.listeners.abort();
+        self.end.cancel_sends();
     }
 }
 
@@ -119,339 +747,2106 @@ impl fmt::Debug for WebTransportTask {
 #[derive(Default, Debug)]
 pub struct WebTransportService {}
 
+/// Configures a WebTransport connection before opening it.
+///
+/// Handlers are optional. Without `on_datagram`, incoming datagrams are not read, unless
+/// [`WebTransportBuilder::read_datagrams`] says otherwise. Without `on_unidirectional_stream` or
+/// `on_bidirectional_stream`, the streams of that kind the server opens are cancelled as they
+/// arrive, see [`WebTransportBuilder::unhandled_stream_code`], rather than left to pile up.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use yew::Callback;
+/// # use yew_webtransport::webtransport::WebTransportService;
+/// # fn connect(on_datagram: Callback<Vec<u8>>) {
+/// let task = WebTransportService::builder("https://example.com:4433")
+///     .on_datagram(on_datagram)
+///     .send_chunk_size(256 * 1024)
+///     .yield_interval(Duration::from_millis(4))
+///     .connect()
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+#[must_use = "the builder does nothing until `connect` is called"]
+pub struct WebTransportBuilder {
+    pub(crate) url: String,
+    on_datagram: Option<Callback<Vec<u8>>>,
+    on_unidirectional_stream: Option<Callback<WebTransportReceiveStream>>,
+    on_bidirectional_stream: Option<Callback<WebTransportBidirectionalStream>>,
+    read_datagrams: Option<bool>,
+    pub(crate) notification: Callback<WebTransportStatus>,
+    on_labeled_status: Option<Callback<LabeledStatus>>,
+    chunking: SendChunking,
+    send_failure: SendFailurePolicy,
+    datagram_max_age: Option<Duration>,
+    read_mode: ReadMode,
+    receiving: ReceiveOptions,
+    dial_options: DialOptions,
+}
+
+impl WebTransportBuilder {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            on_datagram: None,
+            on_unidirectional_stream: None,
+            on_bidirectional_stream: None,
+            read_datagrams: None,
+            notification: Callback::noop(),
+            on_labeled_status: None,
+            chunking: SendChunking::default(),
+            send_failure: SendFailurePolicy::default(),
+            datagram_max_age: None,
+            read_mode: ReadMode::default(),
+            receiving: ReceiveOptions::default(),
+            dial_options: DialOptions::default(),
+        }
+    }
+
+    pub fn on_datagram(mut self, on_datagram: impl Handler<Vec<u8>>) -> Self {
+        self.on_datagram = Some(on_datagram.into_callback());
+        self
+    }
+
+    pub fn on_unidirectional_stream(
+        mut self,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+    ) -> Self {
+        self.on_unidirectional_stream = Some(on_unidirectional_stream.into_callback());
+        self
+    }
+
+    pub fn on_bidirectional_stream(
+        mut self,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+    ) -> Self {
+        self.on_bidirectional_stream = Some(on_bidirectional_stream.into_callback());
+        self
+    }
+
+    pub fn on_status(mut self, notification: impl Handler<WebTransportStatus>) -> Self {
+        self.notification = notification.into_callback();
+        self
+    }
+
+    /// Passes every status notification to `on_labeled_status` along with the session's
+    /// label, as well as to the callback set with [`WebTransportBuilder::on_status`].
+    pub fn on_labeled_status(mut self, on_labeled_status: impl Handler<LabeledStatus>) -> Self {
+        self.on_labeled_status = Some(on_labeled_status.into_callback());
+        self
+    }
+
+    /// Names the session, to tell its events apart from those of other sessions. The label
+    /// prefixes the errors the session logs, and is passed to the callback set with
+    /// [`WebTransportBuilder::on_labeled_status`].
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.receiving.label = Some(label.into().into());
+        self
+    }
+
+    /// What to do with the datagrams and streams that fail to send, see
+    /// [`SendFailurePolicy`].
+    pub fn on_send_failure(mut self, send_failure: SendFailurePolicy) -> Self {
+        self.send_failure = send_failure;
+        self
+    }
+
+    /// Drops the datagrams still queued `max_age` after they were sent, so that stale ones,
+    /// such as cursor positions held back by congestion, never hit the wire. Datagrams sent
+    /// with [`Transport::send_datagram_with_deadline`](crate::transport::Transport::send_datagram_with_deadline)
+    /// keep their own deadline instead. Drops are counted by
+    /// [`WebTransportTask::expired_datagrams`].
+    pub fn datagram_max_age(mut self, max_age: Duration) -> Self {
+        self.datagram_max_age = Some(max_age);
+        self
+    }
+
+    /// Largest chunk written to a stream at once, see [`SendChunking`].
+    pub fn send_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunking.chunk_size = chunk_size;
+        self
+    }
+
+    /// Longest time spent writing a payload's chunks without yielding to the event loop, see
+    /// [`SendChunking`]. Zero yields between every two chunks.
+    pub fn yield_interval(mut self, yield_interval: Duration) -> Self {
+        self.chunking.yield_interval = yield_interval;
+        self
+    }
+
+    /// How the responses to streams opened through the task are read, see [`ReadMode`].
+    pub fn read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
+    }
+
+    /// Caps the received data the application has yet to process, see
+    /// [`crate::limit`]. Applies to datagrams and to the streams read by the task.
+    pub fn receive_limit(mut self, receive_limit: ReceiveLimit) -> Self {
+        self.receiving.receive_limit = Some(receive_limit);
+        self
+    }
+
+    /// Reads incoming datagrams in `worker`, see [`crate::worker`], as well as the streams
+    /// passed to [`WebTransportTask::read_incoming`]. A receive limit then drops datagrams that
+    /// don't fit whatever its policy.
+    pub fn read_worker(mut self, worker: ReadWorker) -> Self {
+        self.receiving.read_worker = Some(worker);
+        self
+    }
+
+    /// Fails to connect unless the session supports datagrams, rather than falling back to a
+    /// session without them.
+    pub fn require_unreliable(mut self, require_unreliable: bool) -> Self {
+        self.dial_options.require_unreliable = require_unreliable;
+        self
+    }
+
+    /// Asks the browser to tune the session's congestion controller for `congestion_control`.
+    /// Browsers are free to ignore it; [`WebTransportTask::congestion_control`] tells whether
+    /// they did.
+    pub fn congestion_control(mut self, congestion_control: CongestionControl) -> Self {
+        self.dial_options.congestion_control = Some(congestion_control);
+        self
+    }
+
+    /// Whether incoming datagrams are read, which they are by default if `on_datagram` is set.
+    /// Datagrams addressed to a [`DatagramChannel`](crate::channel::DatagramChannel) are only
+    /// delivered if they are read. Left unread, they can be read from
+    /// [`WebTransportTask::raw_datagrams`] instead.
+    pub fn read_datagrams(mut self, read_datagrams: bool) -> Self {
+        self.read_datagrams = Some(read_datagrams);
+        self
+    }
+
+    /// The stream error code incoming streams are cancelled with when there is no handler for
+    /// them, 0 by default.
+    pub fn unhandled_stream_code(mut self, code: u8) -> Self {
+        self.receiving.unhandled_stream_code = code;
+        self
+    }
+
+    /// Accepts a server certificate with one of `hashes` instead of checking it against the
+    /// web PKI, which lets the session reach a server with a self-signed certificate. See
+    /// [`crate::certhash`] for what browsers require of such certificates.
+    pub fn server_certificate_hashes(
+        mut self,
+        hashes: impl IntoIterator<Item = CertificateHash>,
+    ) -> Self {
+        self.dial_options.server_certificate_hashes = hashes.into_iter().collect();
+        self
+    }
+
+    /// Copies the fields of `options` into the `WebTransportOptions` the session is created
+    /// with, for options this builder has no method for yet. Options set through the builder
+    /// take precedence.
+    pub fn raw_options(mut self, options: Object) -> Self {
+        self.dial_options.raw = Some(options);
+        self
+    }
+
+    /// How incoming streams are accepted, see [`StreamAcceptance`].
+    pub fn stream_acceptance(mut self, stream_acceptance: StreamAcceptance) -> Self {
+        self.receiving.stream_acceptance = stream_acceptance;
+        self
+    }
+
+    /// Opens the connection. Sends made through the task's
+    /// [`Transport`](crate::transport::Transport) implementation follow the chunking and read
+    /// mode configured here.
+    pub fn connect(self) -> Result<WebTransportTask, WebTransportError> {
+        let dialed = Dialed::new(&self.url, &self.dial_options)?;
+        self.connect_dialed(dialed)
+    }
+
+    /// Connects to the builder's URL and every one of `alternatives` at once, and keeps the
+    /// first session to be ready, closing the others. Helps when some servers are slow or
+    /// unreachable from where the client is. The status callback doesn't hear about the
+    /// sessions that lost the race: resolves with an error only if every connection failed.
+    ///
+    /// The task's [`url`](WebTransportTask::url) tells which URL won.
+    pub fn connect_racing(
+        self,
+        alternatives: impl IntoIterator<Item = impl Into<String>>,
+    ) -> LocalBoxFuture<'static, Result<WebTransportTask, WebTransportError>> {
+        let urls: Vec<String> = std::iter::once(self.url.clone())
+            .chain(alternatives.into_iter().map(Into::into))
+            .collect();
+        async move {
+            let mut failures = Vec::new();
+            let mut candidates = Vec::new();
+            for url in urls {
+                match Dialed::new(&url, &self.dial_options) {
+                    Ok(dialed) => candidates.push(Some(dialed)),
+                    Err(e) => failures.push(format!("{url}: {e}")),
+                }
+            }
+            let mut racing: Vec<_> = candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(index, candidate)| {
+                    let ready = JsFuture::from(candidate.as_ref()?.transport.ready());
+                    Some(ready.map(move |ready| (index, ready)).boxed_local())
+                })
+                .collect();
+            let mut winner = None;
+            while !racing.is_empty() {
+                let ((index, ready), _, rest) = future::select_all(racing).await;
+                racing = rest;
+                match ready {
+                    Ok(_) => {
+                        winner = candidates[index].take();
+                        break;
+                    }
+                    Err(e) => {
+                        if let Some(lost) = candidates[index].take() {
+                            failures.push(format!("{}: {}", lost.url, SessionError::from_js(&e)));
+                        }
+                    }
+                }
+            }
+            for lost in candidates.into_iter().flatten() {
+                let _ = close_session(&lost.transport);
+            }
+            match winner {
+                Some(dialed) => self.connect_dialed(dialed),
+                None => Err(WebTransportError::CreationError(format!(
+                    "Failed to connect to any URL: {}",
+                    failures.join("; ")
+                ))),
+            }
+        }
+        .boxed_local()
+    }
+
+    fn connect_dialed(self, dialed: Dialed) -> Result<WebTransportTask, WebTransportError> {
+        let datagram_router = DatagramRouter::new();
+        let mut receiving = self.receiving.clone();
+        receiving.skip_datagrams = !self.read_datagrams.unwrap_or(self.on_datagram.is_some());
+        let on_datagram = datagram_router.route(self.on_datagram.unwrap_or_else(Callback::noop));
+        let notification = match self.on_labeled_status {
+            Some(on_labeled_status) => {
+                let on_status = self.notification;
+                let label = self.receiving.label.clone();
+                Callback::from(move |status: WebTransportStatus| {
+                    on_status.emit(status.clone());
+                    on_labeled_status.emit(LabeledStatus {
+                        label: label.clone(),
+                        status,
+                    });
+                })
+            }
+            None => self.notification,
+        };
+        let mut task = WebTransportService::connect_receiving(
+            dialed,
+            move |bytes: &Uint8Array| process_binary(bytes, &on_datagram),
+            datagram_router,
+            receiving,
+            self.on_unidirectional_stream,
+            self.on_bidirectional_stream,
+            notification,
+        )?;
+        task.chunking = self.chunking;
+        task.send_failure = self.send_failure;
+        task.datagram_max_age = self.datagram_max_age;
+        task.read_mode = self.read_mode;
+        task.receive_limit = self.receiving.receive_limit;
+        task.read_worker = self.receiving.read_worker;
+        task.stream_acceptance = self.receiving.stream_acceptance;
+        task.label = self.receiving.label;
+        Ok(task)
+    }
+}
+
+impl fmt::Debug for WebTransportBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebTransportBuilder")
+            .field("url", &self.url)
+            .field("label", &self.receiving.label)
+            .field("chunking", &self.chunking)
+            .field("send_failure", &self.send_failure)
+            .field("datagram_max_age", &self.datagram_max_age)
+            .field("read_mode", &self.read_mode)
+            .field("receive_limit", &self.receiving.receive_limit)
+            .field("read_worker", &self.receiving.read_worker)
+            .field("stream_acceptance", &self.receiving.stream_acceptance)
+            .field("read_datagrams", &self.read_datagrams)
+            .field(
+                "unhandled_stream_code",
+                &self.receiving.unhandled_stream_code,
+            )
+            .field("require_unreliable", &self.dial_options.require_unreliable)
+            .field("congestion_control", &self.dial_options.congestion_control)
+            .field(
+                "server_certificate_hashes",
+                &self.dial_options.server_certificate_hashes,
+            )
+            .field("raw_options", &self.dial_options.raw)
+            .finish()
+    }
+}
+
 impl WebTransportService {
-    /// Connects to a server through a WebTransport connection. Needs two callbacks; one is passed
-    /// data, the other is passed updates about the WebTransport's status.
+    /// Starts configuring a connection to `url`.
+    pub fn builder(url: impl Into<String>) -> WebTransportBuilder {
+        WebTransportBuilder::new(url)
+    }
+
+    /// Connects to a server through a WebTransport connection. The handlers, yew
+    /// [`Callback`]s or plain closures, are passed incoming datagrams and streams, and updates
+    /// about the WebTransport's status.
     pub fn connect(
         url: &str,
-        on_datagram: Callback<Vec<u8>>,
-        on_unidirectional_stream: Callback<WebTransportReceiveStream>,
-        on_bidirectional_stream: Callback<WebTransportBidirectionalStream>,
+        on_datagram: impl Handler<Vec<u8>>,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+        notification: impl Handler<WebTransportStatus>,
+    ) -> Result<WebTransportTask, WebTransportError> {
+        let datagram_router = DatagramRouter::new();
+        let on_datagram = datagram_router.route(on_datagram.into_callback());
+        Self::connect_receiving(
+            Dialed::new(url, &DialOptions::default())?,
+            move |bytes: &Uint8Array| process_binary(bytes, &on_datagram),
+            datagram_router,
+            ReceiveOptions::default(),
+            Some(on_unidirectional_stream.into_callback()),
+            Some(on_bidirectional_stream.into_callback()),
+            notification.into_callback(),
+        )
+    }
+
+    /// Like [`WebTransportService::connect`], with everything passed to a single `on_event`
+    /// handler as a [`WtEvent`]. Unidirectional streams the server opens are read as they
+    /// arrive, chunk by chunk.
+    pub fn connect_with_events(
+        url: &str,
+        on_event: impl Handler<WtEvent>,
+    ) -> Result<WebTransportTask, WebTransportError> {
+        let on_event = on_event.into_callback();
+        #[cfg(feature = "streams")]
+        let on_unidirectional_stream = {
+            let on_event = on_event.clone();
+            let streams = Cell::new(0);
+            Callback::from(move |stream: WebTransportReceiveStream| {
+                let number = streams.get();
+                streams.set(number + 1);
+                let on_event = on_event.clone();
+                let on_data = on_event.reform(move |data| WtEvent::UniStreamData {
+                    stream: number,
+                    data,
+                });
+                wasm_bindgen_futures::spawn_local(async move {
+                    let read = WebTransportTask::read_incoming_stream(
+                        stream.into(),
+                        on_data,
+                        ReadMode::PerChunk,
+                    )
+                    .await;
+                    on_event.emit(match read {
+                        Ok(()) => WtEvent::UniStreamFinished { stream: number },
+                        Err(e) => WtEvent::StreamError(e.to_string()),
+                    });
+                });
+            })
+        };
+        #[cfg(not(feature = "streams"))]
+        let on_unidirectional_stream = Callback::noop();
+        Self::connect(
+            url,
+            on_event.reform(WtEvent::Datagram),
+            on_unidirectional_stream,
+            on_event.reform(WtEvent::BidiStreamOpened),
+            on_event.reform(WtEvent::from),
+        )
+    }
+
+    /// Like [`WebTransportService::connect`], but copies incoming datagrams into buffers taken
+    /// from `pool`, which go back to it once `on_datagram` is done with them. Datagrams
+    /// addressed to a [`DatagramChannel`](crate::channel::DatagramChannel) are still delivered
+    /// as plain vectors.
+    #[cfg(feature = "datagrams")]
+    pub fn connect_pooled(
+        url: &str,
+        pool: BufferPool,
+        on_datagram: impl Handler<PooledBuffer>,
+        on_unidirectional_stream: impl Handler<WebTransportReceiveStream>,
+        on_bidirectional_stream: impl Handler<WebTransportBidirectionalStream>,
+        notification: impl Handler<WebTransportStatus>,
+    ) -> Result<WebTransportTask, WebTransportError> {
+        let datagram_router = DatagramRouter::new();
+        let router = datagram_router.clone();
+        let on_datagram = on_datagram.into_callback();
+        Self::connect_receiving(
+            Dialed::new(url, &DialOptions::default())?,
+            move |bytes: &Uint8Array| {
+                let buffer = process_binary_pooled(bytes, &pool);
+                if let Err(data) = router.dispatch(buffer.into_vec()) {
+                    on_datagram.emit(pool.attach(data));
+                }
+            },
+            datagram_router,
+            ReceiveOptions::default(),
+            Some(on_unidirectional_stream.into_callback()),
+            Some(on_bidirectional_stream.into_callback()),
+            notification.into_callback(),
+        )
+    }
+
+    fn connect_receiving(
+        dialed: Dialed,
+        on_datagram: impl Fn(&Uint8Array) + 'static,
+        datagram_router: DatagramRouter,
+        receiving: ReceiveOptions,
+        on_unidirectional_stream: Option<Callback<WebTransportReceiveStream>>,
+        on_bidirectional_stream: Option<Callback<WebTransportBidirectionalStream>>,
         notification: Callback<WebTransportStatus>,
     ) -> Result<WebTransportTask, WebTransportError> {
-        let ConnectCommon(transport, listeners) = Self::connect_common(url, &notification)?;
+        let (url, dial_options) = (dialed.url.clone(), dialed.options.clone());
+        let ConnectCommon(transport, listeners, end) = Self::connect_common(dialed, &notification);
         let transport = Rc::new(transport);
 
-        Self::start_listening_incoming_datagrams(
-            transport.clone(),
-            transport.datagrams(),
+        let lazy = receiving.stream_acceptance == StreamAcceptance::Lazy;
+        let label = receiving.label.clone();
+        let unhandled_stream_code = receiving.unhandled_stream_code;
+        #[cfg(feature = "datagrams")]
+        let datagram_gate = ReadGate::new();
+        #[cfg(feature = "datagrams")]
+        Self::start_receiving_datagrams(
+            &transport,
             on_datagram,
+            receiving,
+            datagram_gate.clone(),
+            notification.clone(),
         );
-        Self::start_listening_incoming_unidirectional_streams(
-            transport.clone(),
-            transport.incoming_unidirectional_streams(),
+        #[cfg(not(feature = "datagrams"))]
+        let _ = (on_datagram, receiving);
+
+        #[cfg(feature = "streams")]
+        if !lazy {
+            Self::start_listening_incoming_unidirectional_streams(
+                transport.incoming_unidirectional_streams(),
+                on_unidirectional_stream,
+                unhandled_stream_code,
+                notification.clone(),
+                label.clone(),
+            );
+            Self::start_listening_incoming_bidirectional_streams(
+                transport.incoming_bidirectional_streams(),
+                on_bidirectional_stream,
+                unhandled_stream_code,
+                notification.clone(),
+                label,
+            );
+        }
+        #[cfg(not(feature = "streams"))]
+        let _ = (
             on_unidirectional_stream,
+            on_bidirectional_stream,
+            lazy,
+            label,
+            unhandled_stream_code,
         );
 
-        Self::start_listening_incoming_bidirectional_streams(
-            transport.clone(),
-            transport.incoming_bidirectional_streams(),
-            on_bidirectional_stream,
+        #[allow(unused_mut)]
+        let mut task = WebTransportTask::new(
+            url,
+            dial_options,
+            transport,
+            notification,
+            listeners,
+            end,
+            datagram_router,
         );
+        #[cfg(feature = "datagrams")]
+        {
+            task.datagram_gate = datagram_gate;
+        }
+        Ok(task)
+    }
 
-        Ok(WebTransportTask::new(transport, notification, listeners))
+    #[cfg(feature = "datagrams")]
+    fn start_receiving_datagrams(
+        transport: &WebTransport,
+        on_datagram: impl Fn(&Uint8Array) + 'static,
+        receiving: ReceiveOptions,
+        gate: ReadGate,
+        notification: Callback<WebTransportStatus>,
+    ) {
+        let ReceiveOptions {
+            receive_limit,
+            read_worker,
+            label,
+            skip_datagrams,
+            ..
+        } = receiving;
+        if skip_datagrams {
+            return;
+        }
+        match read_worker {
+            Some(worker) => Self::start_reading_datagrams_in_worker(
+                &worker,
+                transport.datagrams(),
+                on_datagram,
+                receive_limit,
+                notification,
+            ),
+            None => Self::start_listening_incoming_datagrams(
+                transport.datagrams(),
+                on_datagram,
+                receive_limit,
+                gate,
+                notification,
+                label,
+            ),
+        }
     }
 
+    #[cfg(feature = "streams")]
     fn start_listening_incoming_unidirectional_streams(
-        transport: Rc<WebTransport>,
         incoming_streams: ReadableStream,
-        callback: Callback<WebTransportReceiveStream>,
+        callback: Option<Callback<WebTransportReceiveStream>>,
+        unhandled_stream_code: u8,
+        notification: Callback<WebTransportStatus>,
+        label: Option<Rc<str>>,
     ) {
-        let read_result: ReadableStreamDefaultReader =
-            incoming_streams.get_reader().unchecked_into();
+        let reader: ReadableStreamDefaultReader =
+            match cast(incoming_streams.get_reader(), "a stream reader") {
+                Ok(reader) => reader,
+                Err(e) => {
+                    return report_stream_error(
+                        Some(&notification),
+                        label.as_deref(),
+                        e.to_string(),
+                    )
+                }
+            };
         wasm_bindgen_futures::spawn_local(async move {
             loop {
-                let read_result = JsFuture::from(read_result.read()).await;
-                match read_result {
+                match read_next(&reader).await {
+                    Ok(ReadResult::Value(value)) if value.is_object() => {
+                        match cast::<WebTransportReceiveStream>(
+                            value,
+                            "an incoming unidirectional stream",
+                        ) {
+                            Ok(stream) => match &callback {
+                                Some(callback) => {
+                                    log::debug!(
+                                        target: STREAMS,
+                                        "accepted an incoming unidirectional stream"
+                                    );
+                                    callback.emit(stream)
+                                }
+                                None => {
+                                    log::debug!(
+                                        target: STREAMS,
+                                        "cancelling an unhandled incoming unidirectional stream"
+                                    );
+                                    let _ = stream
+                                        .cancel_with_reason(&stream_error(unhandled_stream_code));
+                                }
+                            },
+                            Err(e) => notification.emit(WebTransportStatus::StreamError(format!(
+                                "Ignoring malformed incoming unidirectional stream: {e}"
+                            ))),
+                        }
+                    }
+                    Ok(ReadResult::Value(value)) => {
+                        notification.emit(WebTransportStatus::StreamError(format!(
+                            "Ignoring malformed incoming unidirectional stream {value:?}"
+                        )));
+                    }
+                    Ok(ReadResult::Done) => break,
                     Err(e) => {
-                        log!("Failed to read incoming unidirectional streams", &e);
-                        let mut reason = WebTransportCloseInfo::default();
-                        reason.reason(
-                            format!("Failed to read incoming unidirectional streams {e:?}")
-                                .as_str(),
+                        log_error(
+                            STREAMS,
+                            label.as_deref(),
+                            &format!("Failed to read incoming unidirectional streams {e}"),
                         );
-                        transport.close_with_close_info(&reason);
+                        notification.emit(WebTransportStatus::StreamError(format!(
+                            "Failed to read incoming unidirectional streams {e}"
+                        )));
                         break;
                     }
-                    Ok(result) => {
-                        let done = Reflect::get(&result, &JsString::from("done"))
-                            .unwrap()
-                            .unchecked_into::<Boolean>();
-                        if let Ok(value) = Reflect::get(&result, &JsString::from("value")) {
-                            if value.is_undefined() {
-                                break;
-                            }
-                            let value: WebTransportReceiveStream = value.unchecked_into();
-                            callback.emit(value);
-                        }
-                        if done.is_truthy() {
-                            break;
-                        }
-                    }
                 }
             }
         });
     }
 
+    #[cfg(feature = "datagrams")]
     fn start_listening_incoming_datagrams(
-        transport: Rc<WebTransport>,
         datagrams: WebTransportDatagramDuplexStream,
-        callback: Callback<Vec<u8>>,
+        on_datagram: impl Fn(&Uint8Array) + 'static,
+        receive_limit: Option<ReceiveLimit>,
+        gate: ReadGate,
+        notification: Callback<WebTransportStatus>,
+        label: Option<Rc<str>>,
     ) {
         let incoming_datagrams: ReadableStreamDefaultReader =
-            datagrams.readable().get_reader().unchecked_into();
+            match cast(datagrams.readable().get_reader(), "a datagram reader") {
+                Ok(reader) => reader,
+                Err(e) => {
+                    return report_stream_error(
+                        Some(&notification),
+                        label.as_deref(),
+                        e.to_string(),
+                    )
+                }
+            };
         wasm_bindgen_futures::spawn_local(async move {
             loop {
-                let read_result = JsFuture::from(incoming_datagrams.read()).await;
-                match read_result {
-                    Err(e) => {
-                        let mut reason = WebTransportCloseInfo::default();
-                        reason.reason(format!("Failed to read incoming datagrams {e:?}").as_str());
-                        transport.close_with_close_info(&reason);
-                        break;
+                gate.opened().await;
+                if let Some(limit) = &receive_limit {
+                    if limit.policy() == OverflowPolicy::Backpressure {
+                        limit.room().await;
                     }
-                    Ok(result) => {
-                        let done = Reflect::get(&result, &JsString::from("done"))
-                            .unwrap()
-                            .unchecked_into::<Boolean>();
-                        if done.is_truthy() {
-                            break;
+                }
+                match read_next(&incoming_datagrams).await {
+                    Ok(ReadResult::Value(value)) => match value.dyn_into::<Uint8Array>() {
+                        Ok(value) => {
+                            let len = value.length() as usize;
+                            let admitted = match &receive_limit {
+                                // Already waited for room, the datagram is delivered whatever
+                                // its size.
+                                Some(limit) if limit.policy() == OverflowPolicy::Backpressure => {
+                                    limit.charge(len)
+                                }
+                                Some(limit) => limit.admit(len),
+                                None => true,
+                            };
+                            if admitted {
+                                log::trace!(target: DATAGRAMS, "received a datagram of {len} bytes");
+                                on_datagram(&value);
+                            } else if receive_limit.as_ref().map(ReceiveLimit::policy)
+                                == Some(OverflowPolicy::Error)
+                            {
+                                notification.emit(WebTransportStatus::StreamError(
+                                    "Stopped reading datagrams: the receive limit is full"
+                                        .to_string(),
+                                ));
+                                break;
+                            }
                         }
-                        let value: Uint8Array = Reflect::get(&result, &JsString::from("value"))
-                            .unwrap()
-                            .unchecked_into();
-                        process_binary(&value, &callback);
+                        Err(value) => {
+                            notification.emit(WebTransportStatus::StreamError(format!(
+                                "Ignoring malformed datagram {value:?}"
+                            )));
+                        }
+                    },
+                    Ok(ReadResult::Done) => break,
+                    Err(e) => {
+                        log::warn!(target: DATAGRAMS, "Failed to read incoming datagrams {e}");
+                        notification.emit(WebTransportStatus::StreamError(format!(
+                            "Failed to read incoming datagrams {e}"
+                        )));
+                        break;
                     }
                 }
             }
         });
     }
 
-    fn start_listening_incoming_bidirectional_streams(
-        transport: Rc<WebTransport>,
+    #[cfg(feature = "datagrams")]
+    fn start_reading_datagrams_in_worker(
+        worker: &ReadWorker,
+        datagrams: WebTransportDatagramDuplexStream,
+        on_datagram: impl Fn(&Uint8Array) + 'static,
+        receive_limit: Option<ReceiveLimit>,
+        notification: Callback<WebTransportStatus>,
+    ) {
+        let reading = worker.read_with(&datagrams.readable(), move |datagram| {
+            let datagram = Uint8Array::new(&datagram);
+            let admitted = match &receive_limit {
+                Some(limit) => limit.admit(datagram.length() as usize),
+                None => true,
+            };
+            if admitted {
+                on_datagram(&datagram);
+            }
+        });
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = reading.await {
+                notification.emit(WebTransportStatus::StreamError(format!(
+                    "Failed to read incoming datagrams {e}"
+                )));
+            }
+        });
+    }
+
+    #[cfg(feature = "streams")]
+    fn start_listening_incoming_bidirectional_streams(
         streams: ReadableStream,
-        callback: Callback<WebTransportBidirectionalStream>,
+        callback: Option<Callback<WebTransportBidirectionalStream>>,
+        unhandled_stream_code: u8,
+        notification: Callback<WebTransportStatus>,
+        label: Option<Rc<str>>,
     ) {
-        let read_result: ReadableStreamDefaultReader = streams.get_reader().unchecked_into();
+        let reader: ReadableStreamDefaultReader =
+            match cast(streams.get_reader(), "a stream reader") {
+                Ok(reader) => reader,
+                Err(e) => {
+                    return report_stream_error(
+                        Some(&notification),
+                        label.as_deref(),
+                        e.to_string(),
+                    )
+                }
+            };
         wasm_bindgen_futures::spawn_local(async move {
             loop {
-                let read_result = JsFuture::from(read_result.read()).await;
-                match read_result {
+                match read_next(&reader).await {
+                    Ok(ReadResult::Value(value)) if value.is_object() => {
+                        match cast::<WebTransportBidirectionalStream>(
+                            value,
+                            "an incoming bidirectional stream",
+                        ) {
+                            Ok(stream) => match &callback {
+                                Some(callback) => {
+                                    log::debug!(
+                                        target: STREAMS,
+                                        "accepted an incoming bidirectional stream"
+                                    );
+                                    callback.emit(stream)
+                                }
+                                None => {
+                                    log::debug!(
+                                        target: STREAMS,
+                                        "cancelling an unhandled incoming bidirectional stream"
+                                    );
+                                    let reason = stream_error(unhandled_stream_code);
+                                    let _ = stream.readable().cancel_with_reason(&reason);
+                                    let _ = stream.writable().abort_with_reason(&reason);
+                                }
+                            },
+                            Err(e) => notification.emit(WebTransportStatus::StreamError(format!(
+                                "Ignoring malformed incoming bidirectional stream: {e}"
+                            ))),
+                        }
+                    }
+                    Ok(ReadResult::Value(value)) => {
+                        notification.emit(WebTransportStatus::StreamError(format!(
+                            "Ignoring malformed incoming bidirectional stream {value:?}"
+                        )));
+                    }
+                    Ok(ReadResult::Done) => break,
                     Err(e) => {
-                        let mut reason = WebTransportCloseInfo::default();
-                        reason.reason(
-                            format!("Failed to read incoming unidirectional streams {e:?}")
-                                .as_str(),
+                        log::warn!(
+                            target: STREAMS,
+                            "Failed to read incoming bidirectional streams {e}"
                         );
-                        transport.close_with_close_info(&reason);
+                        notification.emit(WebTransportStatus::StreamError(format!(
+                            "Failed to read incoming bidirectional streams {e}"
+                        )));
                         break;
                     }
-                    Ok(result) => {
-                        let done = Reflect::get(&result, &JsString::from("done"))
-                            .unwrap()
-                            .unchecked_into::<Boolean>();
-                        if let Ok(value) = Reflect::get(&result, &JsString::from("value")) {
-                            if value.is_undefined() {
-                                break;
-                            }
-                            let value: WebTransportBidirectionalStream = value.unchecked_into();
-                            callback.emit(value);
-                        }
-                        if done.is_truthy() {
-                            break;
-                        }
-                    }
                 }
             }
         });
     }
 
     fn connect_common(
-        url: &str,
+        dialed: Dialed,
         notification: &Callback<WebTransportStatus>,
-    ) -> Result<ConnectCommon, WebTransportError> {
-        let transport = WebTransport::new(url);
+    ) -> ConnectCommon {
+        let transport = dialed.transport;
+        let url = dialed.url;
+        log::info!(target: SESSION, "connecting to {url}");
+
+        let ready = JsFuture::from(transport.ready());
+        let closed = JsFuture::from(transport.closed());
+        let notify = notification.clone();
+        let end = SessionEnd::new();
+        let finished = end.clone();
+        // Futures rather than `Closure`s, so that the listeners are released when the session
+        // closes, or when the task is dropped and they are aborted.
+        let (listen, listeners) = abortable(async move {
+            if let Err(e) = ready.await {
+                if finished.finish() {
+                    let error = SessionError::from_js(&e);
+                    log::warn!(target: SESSION, "session to {url} failed: {error}");
+                    notify.emit(WebTransportStatus::Error(error));
+                }
+                return;
+            }
+            log::info!(target: SESSION, "session to {url} opened");
+            notify.emit(WebTransportStatus::Opened);
+            let reason = closed.await.unwrap_or_else(|e| e);
+            if finished.finish() {
+                let info = CloseInfo::from_js(&reason);
+                log::info!(target: SESSION, "session to {url} closed: {info:?}");
+                notify.emit(WebTransportStatus::Closed(info));
+            }
+        });
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = listen.await;
+        });
+
+        ConnectCommon(transport, listeners, end)
+    }
+}
+struct ConnectCommon(WebTransport, AbortHandle, Rc<SessionEnd>);
+
+/// A session being opened to `url`.
+struct Dialed {
+    url: String,
+    transport: WebTransport,
+    options: DialOptions,
+}
+
+impl Dialed {
+    fn new(url: &str, options: &DialOptions) -> Result<Self, WebTransportError> {
+        let transport = if *options == DialOptions::default() {
+            WebTransport::new(url)
+        } else {
+            WebTransport::new_with_options(url, &options.to_js())
+        };
         let transport = transport.map_err(|e| {
             WebTransportError::CreationError(format!("Failed to create WebTransport: {e:?}"))
         })?;
+        Ok(Self {
+            url: url.to_string(),
+            transport,
+            options: options.clone(),
+        })
+    }
+}
 
-        let notify = notification.clone();
+/// What is asked of the browser when opening a session.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DialOptions {
+    require_unreliable: bool,
+    congestion_control: Option<CongestionControl>,
+    server_certificate_hashes: Vec<CertificateHash>,
+    /// Fields copied as is into the `WebTransportOptions`, before the typed ones.
+    raw: Option<Object>,
+}
 
-        let opened_closure = Closure::wrap(Box::new(move |_| {
-            notify.emit(WebTransportStatus::Opened);
-        }) as Box<dyn FnMut(JsValue)>);
-        let notify = notification.clone();
-        let closed_closure = Closure::wrap(Box::new(move |e: JsValue| {
-            notify.emit(WebTransportStatus::Closed(e));
-        }) as Box<dyn FnMut(JsValue)>);
-        let ready = transport
-            .ready()
-            .then(&opened_closure)
-            .catch(&closed_closure);
-        let closed = transport
-            .closed()
-            .then(&closed_closure)
-            .catch(&closed_closure);
-        // forget closures, this is a minor leak but it prevents weird issues downstream
-        opened_closure.forget();
-        closed_closure.forget();
+impl DialOptions {
+    fn to_js(&self) -> WebTransportOptions {
+        let mut options = WebTransportOptions::new();
+        if let Some(raw) = &self.raw {
+            Object::assign(&options, raw);
+        }
+        if self.require_unreliable {
+            options.require_unreliable(true);
+        }
+        if let Some(congestion_control) = self.congestion_control {
+            options.congestion_control(congestion_control.to_js());
+        }
+        if !self.server_certificate_hashes.is_empty() {
+            let hashes: Array = self
+                .server_certificate_hashes
+                .iter()
+                .map(CertificateHash::to_js)
+                .collect();
+            options.server_certificate_hashes(&hashes);
+        }
+        options
+    }
+}
 
-        {
-            let listeners = [ready, closed];
-            Ok(ConnectCommon(transport, listeners))
+/// Whether a session can carry unreliable data, as reported by the browser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reliability {
+    /// The session is not established yet.
+    Pending,
+    /// Only streams are available, e.g. over an HTTP/2 fallback: datagrams can't be sent.
+    ReliableOnly,
+    /// Datagrams are available as well as streams.
+    SupportsUnreliable,
+}
+
+impl Reliability {
+    fn from_js(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(Self::Pending),
+            "reliable-only" => Some(Self::ReliableOnly),
+            "supports-unreliable" => Some(Self::SupportsUnreliable),
+            _ => None,
         }
     }
 }
-struct ConnectCommon(WebTransport, [Promise; 2]);
+
+/// What the congestion controller of a session optimizes for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CongestionControl {
+    /// Whatever the browser picks.
+    #[default]
+    Default,
+    Throughput,
+    LowLatency,
+}
+
+impl CongestionControl {
+    fn from_js(value: &str) -> Option<Self> {
+        match value {
+            "default" => Some(Self::Default),
+            "throughput" => Some(Self::Throughput),
+            "low-latency" => Some(Self::LowLatency),
+            _ => None,
+        }
+    }
+
+    fn to_js(self) -> WebTransportCongestionControl {
+        match self {
+            Self::Default => WebTransportCongestionControl::Default,
+            Self::Throughput => WebTransportCongestionControl::Throughput,
+            Self::LowLatency => WebTransportCongestionControl::LowLatency,
+        }
+    }
+}
+
+/// How a connection's incoming data is received.
+#[derive(Clone, Default)]
+struct ReceiveOptions {
+    receive_limit: Option<ReceiveLimit>,
+    read_worker: Option<ReadWorker>,
+    stream_acceptance: StreamAcceptance,
+    /// Prefixes the errors logged while receiving.
+    label: Option<Rc<str>>,
+    /// Leaves incoming datagrams unread, for the application to read them.
+    skip_datagrams: bool,
+    /// Incoming streams without a handler are cancelled with this code.
+    unhandled_stream_code: u8,
+}
+
+/// The end of a session, shared by its task and status listeners.
+pub(crate) struct SessionEnd {
+    /// Set once the final status has been notified, so that it is notified only once.
+    notified: Cell<bool>,
+    /// Dropped when the session ends, which cancels the sends still in flight.
+    cancel_sends: Cell<Option<oneshot::Sender<()>>>,
+    #[cfg_attr(not(any(feature = "datagrams", feature = "streams")), allow(dead_code))]
+    sends_cancelled: Shared<oneshot::Receiver<()>>,
+}
+
+impl SessionEnd {
+    fn new() -> Rc<Self> {
+        let (cancel_sends, sends_cancelled) = oneshot::channel();
+        Rc::new(Self {
+            notified: Cell::new(false),
+            cancel_sends: Cell::new(Some(cancel_sends)),
+            sends_cancelled: sends_cancelled.shared(),
+        })
+    }
+
+    /// Ends the session. Returns whether it was still going, in which case the caller notifies
+    /// how it ended.
+    fn finish(&self) -> bool {
+        self.cancel_sends();
+        !self.notified.replace(true)
+    }
+
+    fn cancel_sends(&self) {
+        self.cancel_sends.take();
+    }
+
+    /// Runs `send` until it completes, or until the session ends, in which case `send` is
+    /// dropped and `None` returned.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    pub(crate) fn until_closed<F: Future>(
+        &self,
+        send: F,
+    ) -> impl Future<Output = Option<F::Output>> {
+        let cancelled = self.sends_cancelled.clone();
+        async move {
+            futures::pin_mut!(send);
+            match future::select(send, cancelled).await {
+                Either::Left((output, _)) => Some(output),
+                Either::Right(_) => None,
+            }
+        }
+    }
+}
+
+/// What to do when sending a datagram, or writing to a stream, fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SendFailurePolicy {
+    /// Logs the failure. A datagram or unidirectional stream that fails to send closes the
+    /// session, a bidirectional stream that fails is reported as
+    /// [`WebTransportStatus::StreamError`].
+    #[default]
+    Log,
+    /// Drops the failed send without a word, and leaves the session open.
+    Drop,
+    /// Reports the failure as [`WebTransportStatus::StreamError`], and leaves the session open.
+    Report,
+    /// Sends again, up to `attempts` more times, waiting `backoff` before the first retry and
+    /// twice as long before each following one, then reports the failure like
+    /// [`SendFailurePolicy::Report`]. What was written to a long-lived stream is not sent
+    /// again, nor is a bidirectional stream whose callback was passed part of the response:
+    /// their failures are reported right away.
+    Retry { attempts: u32, backoff: Duration },
+}
+
+/// Runs `send` until it succeeds, retrying it if `options` say so and `retryable` still holds,
+/// and handles its failure otherwise, see [`fail_send`].
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+async fn send_with_policy<F: Future<Output = Result<(), anyhow::Error>>>(
+    transport: &WebTransport,
+    options: &SendOptions,
+    target: &str,
+    closes_session: bool,
+    retryable: impl Fn() -> bool,
+    mut send: impl FnMut() -> F,
+) {
+    let mut retries = 0;
+    let error = loop {
+        let Err(error) = send().await else {
+            return;
+        };
+        match options.on_failure {
+            SendFailurePolicy::Retry { attempts, backoff } if retries < attempts && retryable() => {
+                let delay = backoff.saturating_mul(1 << retries.min(16));
+                log::debug!(target: target, "retrying a failed send in {delay:?}: {error}");
+                yew::platform::time::sleep(delay).await;
+                retries += 1;
+            }
+            _ => break error,
+        }
+    };
+    fail_send(
+        transport,
+        options,
+        target,
+        closes_session,
+        error.to_string(),
+    );
+}
+
+/// Handles a send that failed for good as `options` say. Under [`SendFailurePolicy::Log`], a
+/// failure that `closes_session` closes it, and any other is reported.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+fn fail_send(
+    transport: &WebTransport,
+    options: &SendOptions,
+    target: &str,
+    closes_session: bool,
+    error: String,
+) {
+    let label = options.label.as_deref();
+    match options.on_failure {
+        SendFailurePolicy::Drop => {}
+        SendFailurePolicy::Log if closes_session => {
+            log_error(target, label, &error);
+            let _ = close_session(transport);
+        }
+        _ => {
+            match label {
+                Some(label) => log::warn!(target: target, "[{label}] {error}"),
+                None => log::warn!(target: target, "{error}"),
+            }
+            if let Some(notification) = &options.notification {
+                notification.emit(WebTransportStatus::StreamError(error));
+            }
+        }
+    }
+}
+
+/// How large payloads are written to streams.
+///
+/// A payload is written in chunks of at most `chunk_size` bytes, and the writer yields to the
+/// event loop between two chunks once `yield_interval` has passed since it last did, so that
+/// sending a multi-megabyte payload doesn't freeze the page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SendChunking {
+    pub chunk_size: usize,
+    pub yield_interval: Duration,
+}
+
+impl Default for SendChunking {
+    fn default() -> Self {
+        Self {
+            chunk_size: 64 * 1024,
+            yield_interval: Duration::from_millis(8),
+        }
+    }
+}
+
+/// How incoming stream data is passed to its callback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Every chunk the browser hands over is passed on its own, as soon as it is read.
+    #[default]
+    PerChunk,
+    /// Every chunk already available is read before calling back, and they are passed at once,
+    /// concatenated, up to `max_batch` bytes per call. Chunks are only held back until the
+    /// browser has no more of them queued, so this adds no latency, but it saves a callback per
+    /// chunk on bulk downloads.
+    Batched { max_batch: usize },
+}
+
+impl ReadMode {
+    /// Batches of up to 1 MiB.
+    pub fn batched() -> Self {
+        ReadMode::Batched {
+            max_batch: 1024 * 1024,
+        }
+    }
+}
+
+/// When the streams the server opens are taken from the session.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StreamAcceptance {
+    /// Every incoming stream is taken as soon as it arrives and passed, unread, to the
+    /// `on_unidirectional_stream` or `on_bidirectional_stream` callback.
+    #[default]
+    Eager,
+    /// Incoming streams wait in the session's queue until the application takes them with
+    /// [`WebTransportTask::accept_unidirectional_stream`] or
+    /// [`WebTransportTask::accept_bidirectional_stream`]; the stream callbacks are not called.
+    /// Streams left in the queue count against the server's stream limit, so a server that
+    /// opens streams faster than they are accepted is held back.
+    Lazy,
+}
+
+/// What the sends made through a task inherit from it.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+#[derive(Clone, Default)]
+pub(crate) struct SendOptions {
+    /// Cancels the send when the session ends.
+    pub(crate) end: Option<Rc<SessionEnd>>,
+    #[cfg(feature = "streams")]
+    pub(crate) chunking: SendChunking,
+    /// How the streams opened by the send are read.
+    #[cfg(feature = "streams")]
+    pub(crate) read_mode: ReadMode,
+    #[cfg(feature = "streams")]
+    pub(crate) receive_limit: Option<ReceiveLimit>,
+    /// Prefixes the errors logged by the send.
+    pub(crate) label: Option<Rc<str>>,
+    /// The send order of the stream opened by the send, if any.
+    #[cfg(feature = "streams")]
+    pub(crate) send_order: Option<i64>,
+    /// Counts the send until it is written.
+    pub(crate) queue: Option<Rc<SendQueue>>,
+    /// The datagram channel the send is made on, if any.
+    pub(crate) channel: Option<u64>,
+    pub(crate) on_failure: SendFailurePolicy,
+    /// Where failures are reported.
+    pub(crate) notification: Option<Callback<WebTransportStatus>>,
+    /// The deadline of datagrams sent without one of their own, and where they go once it
+    /// passed.
+    #[cfg(feature = "datagrams")]
+    pub(crate) datagram_max_age: Option<(Duration, Callback<Vec<u8>>)>,
+    /// Where datagrams wait for their turn to be written.
+    #[cfg(feature = "datagrams")]
+    pub(crate) lanes: Option<Rc<DatagramLanes>>,
+    /// The lane of the datagram sent, if not the one of its channel.
+    #[cfg(feature = "datagrams")]
+    pub(crate) priority: Option<DatagramPriority>,
+}
+
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+impl SendOptions {
+    fn enqueue(&self, bytes: usize) -> Option<Queued> {
+        self.queue
+            .as_ref()
+            .map(|queue| queue.push(self.channel, bytes))
+    }
+}
+
+/// Orders the datagrams of a session waiting for backpressure to ease: they are written one
+/// at a time, every high priority datagram before the normal ones.
+#[cfg(feature = "datagrams")]
+#[derive(Default)]
+pub(crate) struct DatagramLanes {
+    /// Whether a datagram has the turn.
+    busy: Cell<bool>,
+    high: RefCell<VecDeque<oneshot::Sender<DatagramTurn>>>,
+    normal: RefCell<VecDeque<oneshot::Sender<DatagramTurn>>>,
+    /// Channels sent at high priority.
+    high_channels: RefCell<HashSet<u64>>,
+}
+
+#[cfg(feature = "datagrams")]
+impl DatagramLanes {
+    pub(crate) fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        let mut high_channels = self.high_channels.borrow_mut();
+        match priority {
+            DatagramPriority::High => high_channels.insert(id),
+            DatagramPriority::Normal => high_channels.remove(&id),
+        };
+    }
+
+    /// The lane of a datagram sent with `options`.
+    fn priority(&self, options: &SendOptions) -> DatagramPriority {
+        match (options.priority, options.channel) {
+            (Some(priority), _) => priority,
+            (None, Some(id)) if self.high_channels.borrow().contains(&id) => DatagramPriority::High,
+            _ => DatagramPriority::Normal,
+        }
+    }
+
+    /// Waits until the datagram is next to be written in its lane. The turn passes on when
+    /// the returned guard is dropped.
+    async fn turn(self: &Rc<Self>, priority: DatagramPriority) -> DatagramTurn {
+        if !self.busy.replace(true) {
+            return DatagramTurn {
+                lanes: Some(self.clone()),
+            };
+        }
+        let (sender, receiver) = oneshot::channel();
+        match priority {
+            DatagramPriority::High => self.high.borrow_mut().push_back(sender),
+            DatagramPriority::Normal => self.normal.borrow_mut().push_back(sender),
+        }
+        receiver.await.unwrap_or(DatagramTurn { lanes: None })
+    }
+
+    /// Gives the turn to the next datagram waiting, skipping those whose send was cancelled.
+    fn pass_turn(self: &Rc<Self>) {
+        loop {
+            let next = self.high.borrow_mut().pop_front();
+            let Some(next) = next.or_else(|| self.normal.borrow_mut().pop_front()) else {
+                self.busy.set(false);
+                return;
+            };
+            let turn = DatagramTurn {
+                lanes: Some(self.clone()),
+            };
+            match next.send(turn) {
+                Ok(()) => return,
+                Err(mut turn) => turn.lanes = None,
+            }
+        }
+    }
+}
+
+/// The turn of a datagram to be written, see [`DatagramLanes::turn`].
+#[cfg(feature = "datagrams")]
+struct DatagramTurn {
+    lanes: Option<Rc<DatagramLanes>>,
+}
+
+#[cfg(feature = "datagrams")]
+impl Drop for DatagramTurn {
+    fn drop(&mut self) {
+        if let Some(lanes) = self.lanes.take() {
+            lanes.pass_turn();
+        }
+    }
+}
+
+/// Counts the sends of a session that are not written yet, in total and per datagram channel.
+#[derive(Default)]
+pub(crate) struct SendQueue {
+    total: Cell<QueuedSends>,
+    #[cfg_attr(not(any(feature = "datagrams", feature = "streams")), allow(dead_code))]
+    channels: RefCell<HashMap<u64, QueuedSends>>,
+}
+
+impl SendQueue {
+    /// Counts a send of `bytes` until the returned guard is dropped.
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    fn push(self: &Rc<Self>, channel: Option<u64>, bytes: usize) -> Queued {
+        let bytes = bytes as u64;
+        self.update(channel, |queued| {
+            queued.messages += 1;
+            queued.bytes += bytes;
+        });
+        Queued {
+            queue: self.clone(),
+            channel,
+            bytes,
+        }
+    }
+
+    #[cfg(any(feature = "datagrams", feature = "streams"))]
+    fn update(&self, channel: Option<u64>, update: impl Fn(&mut QueuedSends)) {
+        let mut total = self.total.get();
+        update(&mut total);
+        self.total.set(total);
+        if let Some(id) = channel {
+            let mut channels = self.channels.borrow_mut();
+            let queued = channels.entry(id).or_default();
+            update(queued);
+            if queued.messages == 0 {
+                channels.remove(&id);
+            }
+        }
+    }
+
+    pub(crate) fn total(&self) -> QueuedSends {
+        self.total.get()
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub(crate) fn channel(&self, id: u64) -> QueuedSends {
+        self.channels.borrow().get(&id).copied().unwrap_or_default()
+    }
+}
+
+/// A send counted by a [`SendQueue`] until it is dropped.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+pub(crate) struct Queued {
+    queue: Rc<SendQueue>,
+    channel: Option<u64>,
+    bytes: u64,
+}
+
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+impl Drop for Queued {
+    fn drop(&mut self) {
+        let bytes = self.bytes;
+        self.queue.update(self.channel, |queued| {
+            queued.messages -= 1;
+            queued.bytes -= bytes;
+        });
+    }
+}
+
+/// Opens a bidirectional stream, competing for bandwidth according to `send_order` if given.
+#[cfg(feature = "streams")]
+fn create_bidirectional_stream(transport: &WebTransport, send_order: Option<i64>) -> JsFuture {
+    let stream = match send_order {
+        Some(send_order) => {
+            let mut options = WebTransportSendStreamOptions::new();
+            options.send_order(Some(send_order as f64));
+            transport.create_bidirectional_stream_with_options(&options)
+        }
+        None => transport.create_bidirectional_stream(),
+    };
+    JsFuture::from(stream)
+}
+
+/// Writes `data` to `writer` following `chunking`.
+#[cfg(feature = "streams")]
+async fn write_chunked(
+    writer: &WritableStreamDefaultWriter,
+    data: &[u8],
+    chunking: SendChunking,
+) -> Result<(), anyhow::Error> {
+    let mut yielded_at = now_ms();
+    let yield_interval = chunking.yield_interval.as_secs_f64() * 1000.0;
+    for (index, chunk) in data.chunks(chunking.chunk_size.max(1)).enumerate() {
+        if index > 0 && now_ms() - yielded_at >= yield_interval {
+            yew::platform::time::sleep(Duration::ZERO).await;
+            yielded_at = now_ms();
+        }
+        JsFuture::from(writer.ready())
+            .await
+            .map_err(|e| anyhow!("Error getting writer ready {:?}", e))?;
+        JsFuture::from(writer.write_with_chunk(&Uint8Array::from(chunk)))
+            .await
+            .map_err(|e| anyhow!("Error writing to stream: {:?}", e))?;
+    }
+    Ok(())
+}
+
+/// Spawns `send`, to be cancelled when the session ends if `end` is given.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+fn spawn_send(end: Option<&SessionEnd>, send: impl Future<Output = ()> + 'static) {
+    match end {
+        Some(end) => wasm_bindgen_futures::spawn_local(end.until_closed(send).map(|_| ())),
+        None => wasm_bindgen_futures::spawn_local(send),
+    }
+}
+
+/// Casts an object received from the browser. With the `debug-validate` feature, the cast is
+/// checked and an object of another type is reported as `what` being expected; otherwise the
+/// object is trusted to be a `T`.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+fn cast<T: JsCast>(value: impl JsCast, what: &str) -> Result<T, WebTransportError> {
+    #[cfg(feature = "debug-validate")]
+    {
+        value.dyn_into::<T>().map_err(|value| {
+            WebTransportError::StreamError(format!("expected {what}, got {:?}", value.as_ref()))
+        })
+    }
+    #[cfg(not(feature = "debug-validate"))]
+    {
+        let _ = what;
+        Ok(value.unchecked_into())
+    }
+}
+
+/// Calls `WebTransport.close()`, turning the exception thrown for a session that is already
+/// closed into [`WebTransportError::AlreadyClosed`].
+pub(crate) fn close_session(transport: &WebTransport) -> Result<(), WebTransportError> {
+    let close = Reflect::get(transport, &JsString::from("close"))
+        .ok()
+        .and_then(|close| close.dyn_into::<Function>().ok())
+        .ok_or(WebTransportError::AlreadyClosed)?;
+    close
+        .call0(transport)
+        .map(|_| ())
+        .map_err(|_| WebTransportError::AlreadyClosed)
+}
 
 pub fn process_binary(bytes: &Uint8Array, callback: &Callback<Vec<u8>>) {
     let data = bytes.to_vec();
     callback.emit(data);
 }
 
+/// Copies `bytes` into a buffer taken from `pool`.
+#[cfg(feature = "datagrams")]
+pub fn process_binary_pooled(bytes: &Uint8Array, pool: &BufferPool) -> PooledBuffer {
+    let mut buffer = pool.take(bytes.length() as usize);
+    bytes.copy_to(&mut buffer);
+    buffer
+}
+
+/// The outcome of a `ReadableStreamDefaultReader.read()`.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+enum ReadResult {
+    Value(JsValue),
+    Done,
+}
+
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+#[wasm_bindgen]
+extern "C" {
+    /// The `{ done, value }` object a `ReadableStreamDefaultReader.read()` resolves with. The
+    /// web-sys `ReadableStreamReadResult` dictionary can only be built, not read.
+    #[wasm_bindgen(extends = js_sys::Object)]
+    type ReadResultObject;
+
+    #[wasm_bindgen(method, getter)]
+    fn done(this: &ReadResultObject) -> JsValue;
+
+    #[wasm_bindgen(method, getter)]
+    fn value(this: &ReadResultObject) -> JsValue;
+}
+
+/// Reads the next value from `reader`. The `{ done, value }` object the read resolves with is
+/// checked rather than trusted, so that a malformed one is an error instead of a panic.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+async fn read_next(reader: &ReadableStreamDefaultReader) -> Result<ReadResult, WebTransportError> {
+    let result = JsFuture::from(reader.read())
+        .await
+        .map_err(|e| WebTransportError::StreamError(format!("{e:?}")))?;
+    if !result.is_object() {
+        return Err(WebTransportError::StreamError(format!(
+            "Malformed read result {result:?}"
+        )));
+    }
+    let result: ReadResultObject = result.unchecked_into();
+    if result.done().is_truthy() {
+        return Ok(ReadResult::Done);
+    }
+    let value = result.value();
+    if value.is_undefined() {
+        return Err(WebTransportError::StreamError(format!(
+            "Malformed read result {:?}",
+            JsValue::from(result)
+        )));
+    }
+    Ok(ReadResult::Value(value))
+}
+
+/// The reason a stream is cancelled with, carrying `code` to the peer.
+#[cfg(feature = "streams")]
+fn stream_error(code: u8) -> JsValue {
+    let mut options = WebTransportErrorOptions::new();
+    options.stream_error_code(Some(code));
+    JsWebTransportError::new_with_message_and_options("unhandled stream", &options)
+        .map(JsValue::from)
+        .unwrap_or(JsValue::UNDEFINED)
+}
+
+/// Returns `stream` unless it is being read already.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+fn unlocked(stream: ReadableStream, what: &str) -> Result<ReadableStream, WebTransportError> {
+    if stream.locked() {
+        return Err(WebTransportError::StreamError(format!(
+            "{what} are already being read"
+        )));
+    }
+    Ok(stream)
+}
+
+/// Reports the failure of a single stream, which leaves the session open.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+pub(crate) fn report_stream_error(
+    notification: Option<&Callback<WebTransportStatus>>,
+    label: Option<&str>,
+    error: String,
+) {
+    match label {
+        Some(label) => log::warn!(target: STREAMS, "[{label}] {error}"),
+        None => log::warn!(target: STREAMS, "{error}"),
+    }
+    if let Some(notification) = notification {
+        notification.emit(WebTransportStatus::StreamError(error));
+    }
+}
+
+/// Logs an `error` that ends the session, prefixed with the label of the session.
+#[cfg(any(feature = "datagrams", feature = "streams"))]
+pub(crate) fn log_error(target: &str, label: Option<&str>, error: &str) {
+    match label {
+        Some(label) => log::error!(target: target, "[{label}] {error}"),
+        None => log::error!(target: target, "{error}"),
+    }
+}
+
 impl WebTransportTask {
     /// Sends data to a WebTransport connection.
+    #[cfg(feature = "datagrams")]
     pub fn send_datagram(transport: Rc<WebTransport>, data: Vec<u8>) {
-        wasm_bindgen_futures::spawn_local(async move {
-            let transport = transport.clone();
-            let result: Result<(), anyhow::Error> = {
-                let transport = transport.clone();
-                async move {
-                    let stream = transport.datagrams();
-                    let stream: WritableStream = stream.writable();
-                    if stream.locked() {
-                        return Err(anyhow::anyhow!("Stream is locked"));
-                    }
-                    let writer = stream.get_writer().map_err(|e| anyhow!("{:?}", e))?;
-                    let data = Uint8Array::from(data.as_slice());
-                    JsFuture::from(writer.ready())
-                        .await
-                        .map_err(|e| anyhow!("{:?}", e))?;
-                    JsFuture::from(writer.write_with_chunk(&data))
-                        .await
-                        .map_err(|e| anyhow!("{:?}", e))?;
-                    writer.release_lock();
-                    Ok(())
-                }
-            }
-            .await;
-            if let Err(e) = result {
-                let e = e.to_string();
-                log!("error: ", e);
-                transport.close();
-            }
+        Self::datagram(transport, data, None, &SendOptions::default());
+    }
+
+    /// Like [`WebTransportTask::send_datagram`], but gives up on `data` if it could not be
+    /// handed to the browser within `deadline`, e.g. because of backpressure, and passes it to
+    /// `on_expired` instead.
+    #[cfg(feature = "datagrams")]
+    pub fn send_datagram_with_deadline(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        deadline: Duration,
+        on_expired: Callback<Vec<u8>>,
+    ) {
+        Self::datagram(
+            transport,
+            data,
+            Some((deadline, on_expired)),
+            &SendOptions::default(),
+        );
+    }
+
+    #[cfg(feature = "datagrams")]
+    pub(crate) fn datagram(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        deadline: Option<(Duration, Callback<Vec<u8>>)>,
+        options: &SendOptions,
+    ) {
+        let queued_at = now_ms();
+        let queued = options.enqueue(data.len());
+        let deadline = deadline.or_else(|| options.datagram_max_age.clone());
+        let lane = options
+            .lanes
+            .clone()
+            .map(|lanes| (lanes.priority(options), lanes));
+        let options = options.clone();
+        log::trace!(target: DATAGRAMS, "sending a datagram of {} bytes", data.len());
+        spawn_send(options.end.clone().as_deref(), async move {
+            let _queued = queued;
+            let deadline = deadline.as_ref();
+            let lane = lane.as_ref();
+            let send = || Self::write_datagram(&transport, &data, queued_at, deadline, lane);
+            send_with_policy(&transport, &options, DATAGRAMS, true, || true, send).await;
         });
     }
 
-    pub fn send_unidirectional_stream(transport: Rc<WebTransport>, data: Vec<u8>) {
-        wasm_bindgen_futures::spawn_local(async move {
-            let transport = transport.clone();
-            let result: Result<(), anyhow::Error> = {
-                let transport = transport.clone();
-                async move {
-                    let _ = JsFuture::from(transport.ready())
-                        .await
-                        .map_err(|e| anyhow!("{:?}", e))?;
-                    let stream = JsFuture::from(transport.create_unidirectional_stream()).await;
-                    let stream: WritableStream = stream
-                        .map_err(|e| anyhow!("failed to create Writeable stream {:?}", e))?
-                        .unchecked_into();
-                    let writer = stream
-                        .get_writer()
-                        .map_err(|e| anyhow!("Error getting writer {:?}", e))?;
-                    let data = Uint8Array::from(data.as_slice());
-                    JsFuture::from(writer.ready())
-                        .await
-                        .map_err(|e| anyhow!("Error getting writer ready {:?}", e))?;
-                    let _ = JsFuture::from(writer.write_with_chunk(&data))
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Error writing to stream: {:?}", e))?;
-                    writer.release_lock();
-                    JsFuture::from(stream.close())
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Error closing stream {:?}", e))?;
-                    Ok(())
-                }
-            }
-            .await;
-            if let Err(e) = result {
-                let e = e.to_string();
-                log!("error: ", e);
-                transport.close();
+    /// Writes `data` as a datagram once it has the turn in its `lane`, unless `deadline`
+    /// passed since it was queued at `queued_at`, in which case it is passed to the deadline's
+    /// callback instead.
+    #[cfg(feature = "datagrams")]
+    async fn write_datagram(
+        transport: &WebTransport,
+        data: &[u8],
+        queued_at: f64,
+        deadline: Option<&(Duration, Callback<Vec<u8>>)>,
+        lane: Option<&(DatagramPriority, Rc<DatagramLanes>)>,
+    ) -> Result<(), anyhow::Error> {
+        let _turn = match lane {
+            Some((priority, lanes)) => Some(lanes.turn(*priority).await),
+            None => None,
+        };
+        let stream = transport.datagrams();
+        let stream: WritableStream = stream.writable();
+        if stream.locked() {
+            return Err(anyhow::anyhow!("Stream is locked"));
+        }
+        let writer = stream.get_writer().map_err(|e| anyhow!("{:?}", e))?;
+        JsFuture::from(writer.ready())
+            .await
+            .map_err(|e| anyhow!("{:?}", e))?;
+        if let Some((deadline, on_expired)) = deadline {
+            if now_ms() - queued_at > deadline.as_secs_f64() * 1000.0 {
+                writer.release_lock();
+                on_expired.emit(data.to_vec());
+                return Ok(());
             }
+        }
+        let data = Uint8Array::from(data);
+        JsFuture::from(writer.write_with_chunk(&data))
+            .await
+            .map_err(|e| anyhow!("{:?}", e))?;
+        writer.release_lock();
+        Ok(())
+    }
+
+    #[cfg(feature = "streams")]
+    pub fn send_unidirectional_stream(transport: Rc<WebTransport>, data: Vec<u8>) {
+        Self::unidirectional_stream(transport, data, SendOptions::default());
+    }
+
+    /// Like [`WebTransportTask::send_unidirectional_stream`], but competes for bandwidth with
+    /// the session's other streams according to `send_order`: streams with a higher send order
+    /// are sent first.
+    #[cfg(feature = "streams")]
+    pub fn send_unidirectional_stream_with_order(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        send_order: i64,
+    ) {
+        let options = SendOptions {
+            send_order: Some(send_order),
+            ..SendOptions::default()
+        };
+        Self::unidirectional_stream(transport, data, options);
+    }
+
+    #[cfg(feature = "streams")]
+    pub(crate) fn unidirectional_stream(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        options: SendOptions,
+    ) {
+        let chunking = options.chunking;
+        let send_order = options.send_order;
+        let queued = options.enqueue(data.len());
+        log::debug!(
+            target: STREAMS,
+            "opening a unidirectional stream for {} bytes",
+            data.len()
+        );
+        spawn_send(options.end.clone().as_deref(), async move {
+            let _queued = queued;
+            let send =
+                || Self::write_unidirectional_stream(&transport, &data, send_order, chunking);
+            send_with_policy(&transport, &options, STREAMS, true, || true, send).await;
         });
     }
 
+    /// Sends `data` on a new unidirectional stream and resolves once the stream is finished.
+    /// Unlike [`WebTransportTask::send_unidirectional_stream`], a failure only affects this
+    /// stream and is returned.
+    #[cfg(feature = "streams")]
+    pub async fn send_unidirectional_stream_finished(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+    ) -> Result<(), WebTransportError> {
+        Self::write_unidirectional_stream(&transport, &data, None, SendChunking::default())
+            .await
+            .map_err(|e| WebTransportError::StreamError(e.to_string()))
+    }
+
+    #[cfg(feature = "streams")]
+    pub(crate) async fn write_unidirectional_stream(
+        transport: &WebTransport,
+        data: &[u8],
+        send_order: Option<i64>,
+        chunking: SendChunking,
+    ) -> Result<(), anyhow::Error> {
+        let _ = JsFuture::from(transport.ready())
+            .await
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let stream = match send_order {
+            Some(send_order) => {
+                let mut options = WebTransportSendStreamOptions::new();
+                options.send_order(Some(send_order as f64));
+                transport.create_unidirectional_stream_with_options(&options)
+            }
+            None => transport.create_unidirectional_stream(),
+        };
+        let stream = JsFuture::from(stream).await;
+        let stream: WritableStream = cast(
+            stream.map_err(|e| anyhow!("failed to create Writeable stream {:?}", e))?,
+            "a writable stream",
+        )?;
+        let writer = stream
+            .get_writer()
+            .map_err(|e| anyhow!("Error getting writer {:?}", e))?;
+        write_chunked(&writer, data, chunking).await?;
+        writer.release_lock();
+        JsFuture::from(stream.close())
+            .await
+            .map_err(|e| anyhow::anyhow!("Error closing stream {:?}", e))?;
+        Ok(())
+    }
+
+    /// Sends `data` on a new bidirectional stream and passes every chunk received on it to
+    /// `callback`. A failure only affects this stream, and is logged.
+    #[cfg(feature = "streams")]
     pub fn send_bidirectional_stream(
         transport: Rc<WebTransport>,
         data: Vec<u8>,
         callback: Callback<Vec<u8>>,
     ) {
-        wasm_bindgen_futures::spawn_local(async move {
-            let transport = transport.clone();
+        Self::send_bidirectional_stream_reporting(
+            transport,
+            data,
+            callback,
+            SendOptions::default(),
+        );
+    }
+
+    /// Like [`WebTransportTask::send_bidirectional_stream`], handling a failure as `options`
+    /// say, and reporting it to their notification callback if they have one.
+    #[cfg(feature = "streams")]
+    pub(crate) fn send_bidirectional_stream_reporting(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        callback: Callback<Vec<u8>>,
+        options: SendOptions,
+    ) {
+        // Once part of the response reached `callback`, sending again would deliver it twice.
+        let received = Rc::new(Cell::new(false));
+        let callback = {
+            let received = received.clone();
+            Callback::from(move |chunk| {
+                received.set(true);
+                callback.emit(chunk);
+            })
+        };
+        spawn_send(options.end.clone().as_deref(), async move {
+            let send = || {
+                let options = options.clone();
+                Self::bidirectional_stream(
+                    transport.clone(),
+                    data.clone(),
+                    callback.clone(),
+                    options,
+                )
+            };
+            let retryable = || !received.get();
+            send_with_policy(&transport, &options, STREAMS, false, retryable, send).await;
+        });
+    }
+
+    /// Sends `data` on a new bidirectional stream and resolves with everything the server
+    /// wrote back once it finishes the stream.
+    #[cfg(feature = "streams")]
+    pub async fn request_bidirectional_stream(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, WebTransportError> {
+        Self::request(transport, data, SendOptions::default()).await
+    }
+
+    #[cfg(feature = "streams")]
+    pub(crate) async fn request(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        options: SendOptions,
+    ) -> Result<Vec<u8>, WebTransportError> {
+        let response = Rc::new(RefCell::new(Vec::new()));
+        let collect = {
+            let response = response.clone();
+            Callback::from(move |chunk: Vec<u8>| response.borrow_mut().extend(chunk))
+        };
+        Self::bidirectional_stream(transport, data, collect, options)
+            .await
+            .map_err(|e| WebTransportError::StreamError(e.to_string()))?;
+        let response = response.take();
+        Ok(response)
+    }
+
+    /// Reads the session's counters with `getStats()`. Counters the browser doesn't report are
+    /// left at zero.
+    pub async fn stats(transport: Rc<WebTransport>) -> Result<TransportStats, WebTransportError> {
+        let stats = JsFuture::from(transport.get_stats())
+            .await
+            .map_err(|e| WebTransportError::StreamError(format!("{:?}", e)))?;
+        let number = |object: &JsValue, key: &str| {
+            Reflect::get(object, &JsValue::from_str(key))
+                .ok()
+                .and_then(|value| value.as_f64())
+        };
+        let count = |object: &JsValue, key: &str| number(object, key).unwrap_or_default() as u64;
+        let datagrams =
+            Reflect::get(&stats, &JsValue::from_str("datagrams")).unwrap_or(JsValue::UNDEFINED);
+        Ok(TransportStats {
+            bytes_sent: count(&stats, "bytesSent"),
+            bytes_received: count(&stats, "bytesReceived"),
+            packets_sent: count(&stats, "packetsSent"),
+            packets_lost: count(&stats, "packetsLost"),
+            smoothed_rtt: number(&stats, "smoothedRtt")
+                .map(|ms| Duration::from_secs_f64(ms.max(0.0) / 1000.0)),
+            datagrams_lost_outgoing: count(&datagrams, "lostOutgoing"),
+            datagrams_expired_outgoing: count(&datagrams, "expiredOutgoing"),
+            datagrams_dropped_incoming: count(&datagrams, "droppedIncoming"),
+        })
+    }
+
+    /// Opens a bidirectional stream that stays open until the returned writer is dropped.
+    /// Every chunk received on it is passed to `callback`. A failure only affects this stream,
+    /// and is logged.
+    #[cfg(feature = "streams")]
+    pub fn open_bidirectional_stream(
+        transport: Rc<WebTransport>,
+        callback: Callback<Vec<u8>>,
+    ) -> StreamWriter {
+        Self::open_bidirectional_stream_reporting(transport, callback, SendOptions::default())
+    }
+
+    /// Like [`WebTransportTask::open_bidirectional_stream`], handling a failure to write as
+    /// `options` say, and reporting failures to their notification callback if they have one.
+    #[cfg(feature = "streams")]
+    pub(crate) fn open_bidirectional_stream_reporting(
+        transport: Rc<WebTransport>,
+        callback: Callback<Vec<u8>>,
+        options: SendOptions,
+    ) -> StreamWriter {
+        let (sender, mut receiver) = mpsc::unbounded::<(Vec<u8>, Option<Queued>)>();
+        let queue = SendOptions {
+            channel: None,
+            ..options.clone()
+        };
+        let failure = options.clone();
+        let SendOptions {
+            end,
+            chunking,
+            read_mode,
+            receive_limit,
+            label,
+            send_order,
+            notification,
+            ..
+        } = options;
+        let send_end = end.clone();
+        spawn_send(send_end.as_deref(), async move {
             let result: Result<(), anyhow::Error> = {
                 let transport = transport.clone();
                 async move {
-                    let stream = JsFuture::from(transport.create_bidirectional_stream()).await;
-                    let stream: WebTransportBidirectionalStream =
-                        stream.map_err(|e| anyhow!("{:?}", e))?.unchecked_into();
+                    let stream = create_bidirectional_stream(&transport, send_order).await;
+                    let stream: WebTransportBidirectionalStream = cast(
+                        stream.map_err(|e| anyhow!("{:?}", e))?,
+                        "a bidirectional stream",
+                    )?;
                     let readable: ReadableStreamDefaultReader =
-                        stream.readable().get_reader().unchecked_into();
-                    let (sender, receiver) = channel();
-                    wasm_bindgen_futures::spawn_local(async move {
-                        loop {
-                            let read_result = JsFuture::from(readable.read()).await;
-                            match read_result {
-                                Err(e) => {
-                                    let mut reason = WebTransportCloseInfo::default();
-                                    reason.reason(
-                                        format!("Failed to read incoming stream {e:?}").as_str(),
-                                    );
-                                    transport.close_with_close_info(&reason);
-                                    break;
-                                }
-                                Ok(result) => {
-                                    let done = Reflect::get(&result, &JsString::from("done"))
-                                        .unwrap()
-                                        .unchecked_into::<Boolean>();
-                                    if done.is_truthy() {
-                                        break;
-                                    }
-                                    let value: Uint8Array =
-                                        Reflect::get(&result, &JsString::from("value"))
-                                            .unwrap()
-                                            .unchecked_into();
-                                    process_binary(&value, &callback);
-                                }
-                            }
+                        cast(stream.readable().get_reader(), "a stream reader")?;
+                    spawn_send(end.as_deref(), async move {
+                        let read =
+                            Self::read_stream(readable, callback, read_mode, receive_limit, None);
+                        if let Err(e) = read.await {
+                            report_stream_error(
+                                notification.as_ref(),
+                                label.as_deref(),
+                                e.to_string(),
+                            );
                         }
-                        sender.send(true).unwrap();
                     });
                     let writer = stream
                         .writable()
                         .get_writer()
                         .map_err(|e| anyhow!("{:?}", e))?;
-
-                    JsFuture::from(writer.ready())
-                        .await
-                        .map_err(|e| anyhow!("{:?}", e))?;
-                    let data = Uint8Array::from(data.as_slice());
-                    let _ = JsFuture::from(writer.write_with_chunk(&data))
-                        .await
-                        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+                    while let Some((data, _queued)) = receiver.next().await {
+                        write_chunked(&writer, &data, chunking).await?;
+                    }
                     JsFuture::from(writer.close())
                         .await
-                        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
-                    let _ = receiver.await?;
+                        .map_err(|e| anyhow!("{:?}", e))?;
                     Ok(())
                 }
             }
             .await;
             if let Err(e) = result {
-                let e = e.to_string();
-                log!("error: {}", e);
-                transport.close();
+                fail_send(&transport, &failure, STREAMS, false, e.to_string());
             }
         });
+        let writer = sender.clone();
+        StreamWriter::new(
+            move |data: Vec<u8>| {
+                let queued = queue.enqueue(data.len());
+                let _ = writer.unbounded_send((data, queued));
+            },
+            move || sender.close_channel(),
+        )
+    }
+
+    #[cfg(feature = "streams")]
+    async fn bidirectional_stream(
+        transport: Rc<WebTransport>,
+        data: Vec<u8>,
+        callback: Callback<Vec<u8>>,
+        options: SendOptions,
+    ) -> Result<(), anyhow::Error> {
+        let queued = options.enqueue(data.len());
+        let SendOptions {
+            chunking,
+            read_mode,
+            receive_limit,
+            send_order,
+            ..
+        } = options;
+        let stream = create_bidirectional_stream(&transport, send_order).await;
+        let stream: WebTransportBidirectionalStream = cast(
+            stream.map_err(|e| anyhow!("{:?}", e))?,
+            "a bidirectional stream",
+        )?;
+        let readable: ReadableStreamDefaultReader =
+            cast(stream.readable().get_reader(), "a stream reader")?;
+        let (sender, receiver) = channel();
+        wasm_bindgen_futures::spawn_local(async move {
+            let read = Self::read_stream(readable, callback, read_mode, receive_limit, None);
+            let _ = sender.send(read.await);
+        });
+        let writer = stream
+            .writable()
+            .get_writer()
+            .map_err(|e| anyhow!("{:?}", e))?;
+        write_chunked(&writer, &data, chunking).await?;
+        JsFuture::from(writer.close())
+            .await
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        drop(queued);
+        receiver.await?
+    }
+
+    /// Passes everything read from `stream` to `callback`, as `read_mode` says, until the peer
+    /// finishes it. Use it on the streams passed to `on_unidirectional_stream`, or the readable
+    /// side of those passed to `on_bidirectional_stream`. If reading fails, the stream is
+    /// cancelled.
+    #[cfg(feature = "streams")]
+    pub async fn read_incoming_stream(
+        stream: ReadableStream,
+        callback: Callback<Vec<u8>>,
+        read_mode: ReadMode,
+    ) -> Result<(), WebTransportError> {
+        let readable: ReadableStreamDefaultReader = cast(stream.get_reader(), "a stream reader")?;
+        Self::read_stream(readable, callback, read_mode, None, None)
+            .await
+            .map_err(|e| WebTransportError::StreamError(e.to_string()))
+    }
+
+    /// Passes what is read from `readable` to `callback`, as `read_mode` says, until the peer
+    /// finishes the stream. If reading fails, the stream is cancelled.
+    #[cfg(feature = "streams")]
+    async fn read_stream(
+        readable: ReadableStreamDefaultReader,
+        callback: Callback<Vec<u8>>,
+        read_mode: ReadMode,
+        receive_limit: Option<ReceiveLimit>,
+        gate: Option<ReadGate>,
+    ) -> Result<(), anyhow::Error> {
+        let result = Self::read_chunks(
+            &readable,
+            &callback,
+            read_mode,
+            receive_limit.as_ref(),
+            gate.as_ref(),
+        )
+        .await;
+        if result.is_err() {
+            let _ = readable.cancel();
+        }
+        result
+    }
+
+    #[cfg(feature = "streams")]
+    async fn read_chunks(
+        readable: &ReadableStreamDefaultReader,
+        callback: &Callback<Vec<u8>>,
+        read_mode: ReadMode,
+        limit: Option<&ReceiveLimit>,
+        gate: Option<&ReadGate>,
+    ) -> Result<(), anyhow::Error> {
+        let max_batch = match read_mode {
+            ReadMode::PerChunk => 0,
+            ReadMode::Batched { max_batch } => max_batch,
+        };
+        let mut batch = Vec::new();
+        if let Some(gate) = gate {
+            gate.opened().await;
+        }
+        let mut next = read_chunk(readable).boxed_local();
+        loop {
+            // With a batch started, a chunk counts as available if the read resolves before a
+            // zero delay timer fires: a queued chunk resolves it in a microtask, which runs first.
+            let read = if batch.is_empty() {
+                next.await
+            } else {
+                let timer = yew::platform::time::sleep(Duration::ZERO).boxed_local();
+                match future::select(next, timer).await {
+                    Either::Left((read, _)) => read,
+                    Either::Right((_, pending)) => {
+                        deliver(std::mem::take(&mut batch), callback, limit)?;
+                        next = pending;
+                        continue;
+                    }
+                }
+            };
+            let chunk = match read {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) if batch.is_empty() => return Ok(()),
+                Ok(None) => return deliver(batch, callback, limit),
+                Err(e) => {
+                    if !batch.is_empty() {
+                        deliver(batch, callback, limit)?;
+                    }
+                    return Err(e);
+                }
+            };
+            if max_batch == 0 {
+                deliver(chunk.to_vec(), callback, limit)?;
+            } else {
+                let start = batch.len();
+                batch.resize(start + chunk.length() as usize, 0);
+                chunk.copy_to(&mut batch[start..]);
+                if batch.len() >= max_batch {
+                    deliver(std::mem::take(&mut batch), callback, limit)?;
+                }
+            }
+            // Leaving the stream unread makes the server wait, through flow control, until the
+            // application catches up.
+            if let Some(limit) = limit {
+                if limit.policy() != OverflowPolicy::Error && limit.is_full() {
+                    if !batch.is_empty() {
+                        deliver(std::mem::take(&mut batch), callback, Some(limit))?;
+                    }
+                    limit.room().await;
+                }
+            }
+            if let Some(gate) = gate.filter(|gate| gate.is_paused()) {
+                if !batch.is_empty() {
+                    deliver(std::mem::take(&mut batch), callback, limit)?;
+                }
+                gate.opened().await;
+            }
+            next = read_chunk(readable).boxed_local();
+        }
+    }
+}
+
+/// Passes stream data to `callback`, counting it against `limit`.
+#[cfg(feature = "streams")]
+fn deliver(
+    data: Vec<u8>,
+    callback: &Callback<Vec<u8>>,
+    limit: Option<&ReceiveLimit>,
+) -> Result<(), anyhow::Error> {
+    if let Some(limit) = limit {
+        if !limit.charge(data.len()) {
+            return Err(anyhow!(
+                "Stopped reading the stream: the receive limit is full"
+            ));
+        }
+    }
+    log::trace!(target: STREAMS, "received {} bytes on a stream", data.len());
+    callback.emit(data);
+    Ok(())
+}
+
+/// Takes the next stream from the queue of incoming streams read by `acceptor`.
+#[cfg(feature = "streams")]
+async fn accept_next<T: JsCast>(
+    acceptor: ReadableStreamDefaultReader,
+    what: &str,
+) -> Result<Option<T>, WebTransportError> {
+    match read_next(&acceptor).await? {
+        ReadResult::Value(value) => cast(value, what).map(Some),
+        ReadResult::Done => Ok(None),
+    }
+}
+
+/// Reads the next chunk of a stream, or `None` once the peer finished it.
+#[cfg(feature = "streams")]
+async fn read_chunk(
+    readable: &ReadableStreamDefaultReader,
+) -> Result<Option<Uint8Array>, anyhow::Error> {
+    match read_next(readable).await {
+        Ok(ReadResult::Value(value)) => value
+            .dyn_into::<Uint8Array>()
+            .map(Some)
+            .map_err(|value| anyhow!("Malformed stream chunk {:?}", value)),
+        Ok(ReadResult::Done) => Ok(None),
+        Err(e) => Err(anyhow!("Failed to read incoming stream {}", e)),
     }
 }
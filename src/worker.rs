@@ -0,0 +1,237 @@
+//! Reading streams in a web worker.
+//!
+//! Reading a busy stream on the main thread competes with rendering for time. A [`ReadWorker`]
+//! takes over `ReadableStream`s, which browsers can transfer to a worker, reads them there, and
+//! posts every chunk back as an `ArrayBuffer` transferred rather than copied. Chunks can then be
+//! handed to other web APIs, a `VideoDecoder` for instance, without ever being copied into wasm
+//! memory.
+//!
+//! The worker runs a small script built into the crate, so no separate file has to be served.
+//! A single worker can read the streams of several sessions.
+
+use futures::channel::oneshot;
+use js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    rc::{Rc, Weak},
+};
+use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, MessageEvent, ReadableStream, Url, Worker};
+use yew::callback::Callback;
+
+use crate::webtransport::WebTransportError;
+
+/// Reads every stream it is sent, posting back `{ id, chunk }` for each chunk, then
+/// `{ id, done }` or `{ id, error }`.
+const WORKER_SCRIPT: &str = r#"
+self.onmessage = async ({ data: { id, stream } }) => {
+  const reader = stream.getReader();
+  try {
+    for (;;) {
+      const { done, value } = await reader.read();
+      if (done) break;
+      const whole = value.byteOffset === 0 && value.byteLength === value.buffer.byteLength;
+      const chunk = whole ? value.buffer : value.slice().buffer;
+      self.postMessage({ id, chunk }, [chunk]);
+    }
+    self.postMessage({ id, done: true });
+  } catch (e) {
+    self.postMessage({ id, error: String(e) });
+  }
+};
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    /// A message posted by the worker script.
+    #[wasm_bindgen(extends = js_sys::Object)]
+    type WorkerMessage;
+
+    #[wasm_bindgen(method, getter)]
+    fn id(this: &WorkerMessage) -> u32;
+
+    #[wasm_bindgen(method, getter)]
+    fn chunk(this: &WorkerMessage) -> JsValue;
+
+    #[wasm_bindgen(method, getter)]
+    fn error(this: &WorkerMessage) -> JsValue;
+}
+
+struct Reading {
+    on_chunk: Rc<dyn Fn(ArrayBuffer)>,
+    finished: oneshot::Sender<Result<(), String>>,
+}
+
+struct WorkerState {
+    worker: Worker,
+    url: String,
+    next_id: Cell<u32>,
+    readings: RefCell<HashMap<u32, Reading>>,
+    on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl WorkerState {
+    fn receive(&self, message: WorkerMessage) {
+        let id = message.id();
+        if let Ok(chunk) = message.chunk().dyn_into::<ArrayBuffer>() {
+            let on_chunk = self
+                .readings
+                .borrow()
+                .get(&id)
+                .map(|reading| reading.on_chunk.clone());
+            if let Some(on_chunk) = on_chunk {
+                on_chunk(chunk);
+            }
+            return;
+        }
+        let Some(reading) = self.readings.borrow_mut().remove(&id) else {
+            return;
+        };
+        let error = message.error();
+        let result = if error.is_undefined() {
+            Ok(())
+        } else {
+            Err(error.as_string().unwrap_or_else(|| format!("{error:?}")))
+        };
+        let _ = reading.finished.send(result);
+    }
+}
+
+impl Drop for WorkerState {
+    fn drop(&mut self) {
+        self.worker.set_onmessage(None);
+        self.worker.terminate();
+        let _ = Url::revoke_object_url(&self.url);
+    }
+}
+
+/// A web worker reading streams off the main thread.
+///
+/// Cloning a `ReadWorker` yields another handle to the same worker, which is terminated once
+/// every handle is dropped, abandoning the streams it was reading.
+#[derive(Clone)]
+pub struct ReadWorker {
+    state: Rc<WorkerState>,
+}
+
+impl ReadWorker {
+    /// Starts the worker.
+    pub fn new() -> Result<Self, WebTransportError> {
+        let creation_error =
+            |e: JsValue| WebTransportError::CreationError(format!("Failed to start worker: {e:?}"));
+        let script = Array::of1(&JsValue::from_str(WORKER_SCRIPT));
+        let mut options = BlobPropertyBag::new();
+        options.type_("text/javascript");
+        let blob =
+            Blob::new_with_str_sequence_and_options(&script, &options).map_err(creation_error)?;
+        let url = Url::create_object_url_with_blob(&blob).map_err(creation_error)?;
+        let worker = match Worker::new(&url) {
+            Ok(worker) => worker,
+            Err(e) => {
+                let _ = Url::revoke_object_url(&url);
+                return Err(creation_error(e));
+            }
+        };
+        let state = Rc::new_cyclic(|state: &Weak<WorkerState>| {
+            let receiver = state.clone();
+            WorkerState {
+                worker,
+                url,
+                next_id: Cell::new(0),
+                readings: RefCell::new(HashMap::new()),
+                on_message: Closure::new(move |event: MessageEvent| {
+                    if let Some(state) = receiver.upgrade() {
+                        state.receive(event.data().unchecked_into());
+                    }
+                }),
+            }
+        });
+        state
+            .worker
+            .set_onmessage(Some(state.on_message.as_ref().unchecked_ref()));
+        Ok(Self { state })
+    }
+
+    /// Transfers `stream` to the worker, which reads it until the peer finishes it. Every chunk
+    /// is passed to `on_chunk`. Resolves once the stream is finished, or with the error that
+    /// stopped reading it.
+    ///
+    /// The stream must not be locked: once transferred, it can no longer be used on this
+    /// thread.
+    pub fn read(
+        &self,
+        stream: &ReadableStream,
+        on_chunk: Callback<ArrayBuffer>,
+    ) -> impl Future<Output = Result<(), WebTransportError>> + 'static {
+        self.read_with(stream, move |chunk| on_chunk.emit(chunk))
+    }
+
+    /// Like [`ReadWorker::read`], copying every chunk into a vector.
+    pub fn read_bytes(
+        &self,
+        stream: &ReadableStream,
+        on_data: Callback<Vec<u8>>,
+    ) -> impl Future<Output = Result<(), WebTransportError>> + 'static {
+        self.read_with(stream, move |chunk| {
+            on_data.emit(Uint8Array::new(&chunk).to_vec())
+        })
+    }
+
+    pub(crate) fn read_with(
+        &self,
+        stream: &ReadableStream,
+        on_chunk: impl Fn(ArrayBuffer) + 'static,
+    ) -> impl Future<Output = Result<(), WebTransportError>> + 'static {
+        let id = self.state.next_id.get();
+        self.state.next_id.set(id.wrapping_add(1));
+        let (finished, result) = oneshot::channel();
+        self.state.readings.borrow_mut().insert(
+            id,
+            Reading {
+                on_chunk: Rc::new(on_chunk),
+                finished,
+            },
+        );
+        let posted = self.post(id, stream);
+        if posted.is_err() {
+            self.state.readings.borrow_mut().remove(&id);
+        }
+        async move {
+            posted?;
+            match result.await {
+                Ok(result) => result.map_err(WebTransportError::StreamError),
+                Err(_) => Err(WebTransportError::StreamError(
+                    "The read worker was dropped".to_string(),
+                )),
+            }
+        }
+    }
+
+    fn post(&self, id: u32, stream: &ReadableStream) -> Result<(), WebTransportError> {
+        let message = Object::new();
+        let post_error =
+            |e: JsValue| WebTransportError::StreamError(format!("Failed to transfer stream {e:?}"));
+        Reflect::set(&message, &"id".into(), &id.into()).map_err(post_error)?;
+        Reflect::set(&message, &"stream".into(), stream).map_err(post_error)?;
+        self.state
+            .worker
+            .post_message_with_transfer(&message, &Array::of1(stream))
+            .map_err(post_error)
+    }
+
+    /// Number of streams being read.
+    pub fn reading(&self) -> usize {
+        self.state.readings.borrow().len()
+    }
+}
+
+impl fmt::Debug for ReadWorker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadWorker")
+            .field("reading", &self.reading())
+            .finish()
+    }
+}
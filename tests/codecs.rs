@@ -0,0 +1,516 @@
+//! Property tests for the framing codecs, fed with arbitrarily split, truncated, oversized and
+//! interleaved input, as a misbehaving peer could send. The `fuzz` directory runs the same
+//! decoders under `cargo fuzz`.
+#![cfg(all(not(target_arch = "wasm32"), feature = "streams"))]
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+#[cfg(feature = "encryption")]
+use yew_webtransport::crypto::{ChannelCipher, ChannelCryptoError, ChannelKey};
+#[cfg(feature = "checksum")]
+use yew_webtransport::framing::Checksum;
+use yew_webtransport::framing::{Deframer, Framing, FramingError, MessagePart};
+#[cfg(feature = "formats")]
+use yew_webtransport::graphql::{GraphqlMessage, GraphqlRequest};
+use yew_webtransport::mux::{encode_frame, FrameDecoder};
+use yew_webtransport::pubsub::TopicMessage;
+use yew_webtransport::reliable::{encode_message, MessageDecoder};
+use yew_webtransport::upload::{decode_segment, encode_segment};
+use yew_webtransport::varint;
+
+/// Splits `data` into chunks ending at `cuts`, taken modulo its length.
+fn split(data: &[u8], cuts: &[usize]) -> Vec<Vec<u8>> {
+    let mut cuts: Vec<usize> = cuts
+        .iter()
+        .map(|cut| cut % (data.len() + 1))
+        .chain([0, data.len()])
+        .collect();
+    cuts.sort_unstable();
+    cuts.windows(2)
+        .map(|bounds| data[bounds[0]..bounds[1]].to_vec())
+        .collect()
+}
+
+fn messages() -> impl Strategy<Value = Vec<Vec<u8>>> {
+    vec(vec(any::<u8>(), 0..300), 0..20)
+}
+
+fn framings() -> impl Strategy<Value = Framing> {
+    prop_oneof![
+        Just(Framing::Varint),
+        Just(Framing::U32),
+        Just(Framing::Protobuf),
+        Just(Framing::Cobs),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn varint_round_trips(value in 0..=varint::MAX) {
+        let mut out = Vec::new();
+        varint::encode(value, &mut out);
+        prop_assert_eq!(out.len(), varint::encoded_len(value));
+        prop_assert_eq!(varint::len_from_first_byte(out[0]), out.len());
+        prop_assert_eq!(varint::decode(&out), Some((value, out.len())));
+    }
+
+    #[test]
+    fn truncated_varint_is_incomplete(value in 0..=varint::MAX, cut in any::<usize>()) {
+        let mut out = Vec::new();
+        varint::encode(value, &mut out);
+        let cut = cut % out.len();
+        prop_assert_eq!(varint::decode(&out[..cut]), None);
+    }
+
+    #[test]
+    fn varint_decodes_any_bytes(bytes in vec(any::<u8>(), 0..16)) {
+        if let Some((value, len)) = varint::decode(&bytes) {
+            prop_assert!(value <= varint::MAX);
+            prop_assert!(len <= bytes.len());
+            prop_assert_eq!(len, varint::len_from_first_byte(bytes[0]));
+        }
+    }
+
+    #[test]
+    fn messages_survive_any_chunking(
+        messages in messages(),
+        cuts in vec(any::<usize>(), 0..40),
+    ) {
+        let mut stream = Vec::new();
+        for message in &messages {
+            encode_message(message, &mut stream);
+        }
+        let mut decoder = MessageDecoder::new();
+        let mut decoded = Vec::new();
+        for chunk in split(&stream, &cuts) {
+            decoder.push(&chunk);
+            while let Some(message) = decoder.next_message() {
+                decoded.push(message);
+            }
+        }
+        prop_assert_eq!(decoded, messages);
+    }
+
+    #[test]
+    fn truncated_messages_are_held_back(messages in messages(), cut in any::<usize>()) {
+        let mut stream = Vec::new();
+        let mut ends = Vec::new();
+        for message in &messages {
+            encode_message(message, &mut stream);
+            ends.push(stream.len());
+        }
+        let cut = cut % (stream.len() + 1);
+        let mut decoder = MessageDecoder::new();
+        decoder.push(&stream[..cut]);
+        let complete = ends.iter().filter(|&&end| end <= cut).count();
+        for message in &messages[..complete] {
+            let next = decoder.next_message();
+            prop_assert_eq!(next.as_ref(), Some(message));
+        }
+        prop_assert_eq!(decoder.next_message(), None);
+    }
+
+    #[test]
+    fn oversized_message_length_waits(
+        len in (u32::MAX as u64)..=varint::MAX,
+        tail in vec(any::<u8>(), 0..64),
+    ) {
+        let mut stream = Vec::new();
+        varint::encode(len, &mut stream);
+        stream.extend_from_slice(&tail);
+        let mut decoder = MessageDecoder::new();
+        decoder.push(&stream);
+        prop_assert_eq!(decoder.next_message(), None);
+    }
+
+    #[test]
+    fn message_decoder_takes_any_bytes(chunks in vec(vec(any::<u8>(), 0..64), 0..16)) {
+        let mut decoder = MessageDecoder::new();
+        let mut total = 0;
+        for chunk in &chunks {
+            decoder.push(chunk);
+            total += chunk.len();
+            while let Some(message) = decoder.next_message() {
+                prop_assert!(message.len() < total);
+            }
+        }
+    }
+
+    #[test]
+    fn interleaved_frames_keep_their_channel_and_order(
+        frames in vec((0..8u64, vec(any::<u8>(), 0..200)), 0..30),
+        cuts in vec(any::<usize>(), 0..40),
+    ) {
+        let mut stream = Vec::new();
+        for (id, payload) in &frames {
+            encode_frame(*id, payload, &mut stream);
+        }
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = Vec::new();
+        for chunk in split(&stream, &cuts) {
+            decoder.push(&chunk);
+            while let Some(frame) = decoder.next_frame() {
+                decoded.push(frame);
+            }
+        }
+        prop_assert_eq!(decoder.buffered(), 0);
+        prop_assert_eq!(decoded, frames);
+    }
+
+    #[test]
+    fn truncated_frame_stays_buffered(
+        id in 0..=varint::MAX,
+        payload in vec(any::<u8>(), 0..200),
+        cut in any::<usize>(),
+    ) {
+        let mut stream = Vec::new();
+        encode_frame(id, &payload, &mut stream);
+        let cut = cut % stream.len();
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&stream[..cut]);
+        prop_assert_eq!(decoder.next_frame(), None);
+        prop_assert_eq!(decoder.buffered(), cut);
+        decoder.push(&stream[cut..]);
+        prop_assert_eq!(decoder.next_frame(), Some((id, payload)));
+    }
+
+    #[test]
+    fn frame_decoder_takes_any_bytes(chunks in vec(vec(any::<u8>(), 0..64), 0..16)) {
+        let mut decoder = FrameDecoder::new();
+        for chunk in &chunks {
+            decoder.push(chunk);
+            while decoder.next_frame().is_some() {}
+        }
+    }
+
+    #[test]
+    fn topic_messages_round_trip(
+        kind in 0..3u8,
+        topic in ".{0,40}",
+        payload in vec(any::<u8>(), 0..300),
+    ) {
+        let message = match kind {
+            0 => TopicMessage::Subscribe(topic),
+            1 => TopicMessage::Unsubscribe(topic),
+            _ => TopicMessage::Publish { topic, payload },
+        };
+        prop_assert_eq!(TopicMessage::decode(&message.encode()), Some(message));
+    }
+
+    #[test]
+    fn topic_message_decoder_takes_any_bytes(data in vec(any::<u8>(), 0..64)) {
+        if let Some(message) = TopicMessage::decode(&data) {
+            prop_assert_eq!(TopicMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[cfg(feature = "formats")]
+    #[test]
+    fn graphql_messages_round_trip(
+        kind in 0..8u8,
+        id in ".{0,20}",
+        text in ".{0,40}",
+        with_payload in any::<bool>(),
+    ) {
+        let mut map = serde_json::Map::new();
+        map.insert("text".to_string(), text.clone().into());
+        let payload = with_payload.then(|| map.clone());
+        let message = match kind {
+            0 => GraphqlMessage::ConnectionInit(payload),
+            1 => GraphqlMessage::ConnectionAck(payload),
+            2 => GraphqlMessage::Ping(payload),
+            3 => GraphqlMessage::Pong(payload),
+            4 => GraphqlMessage::Subscribe {
+                id,
+                payload: GraphqlRequest {
+                    query: text.clone(),
+                    operation_name: with_payload.then(|| text.clone()),
+                    variables: payload.clone(),
+                    extensions: payload,
+                },
+            },
+            5 => GraphqlMessage::Next { id, payload: map.into() },
+            6 => GraphqlMessage::Error { id, payload: vec![map.into()] },
+            _ => GraphqlMessage::Complete { id },
+        };
+        prop_assert_eq!(GraphqlMessage::decode(&message.encode()), Some(message));
+    }
+
+    #[cfg(feature = "formats")]
+    #[test]
+    fn graphql_message_decoder_takes_any_json(
+        kind in prop::sample::select(vec![
+            "connection_init", "connection_ack", "ping", "pong",
+            "subscribe", "next", "error", "complete", "other",
+        ]),
+        id in prop::sample::select(vec!["\"1\"", "1", "null", "[]"]),
+        payload in prop::sample::select(vec![
+            "null", "1", "\"a\"", "[]", "[1]", "{}", "{\"query\":\"q\"}",
+            "{\"query\":1}", "{\"query\":\"q\",\"variables\":[]}",
+            "{\"query\":\"q\",\"operationName\":null,\"extensions\":{}}",
+        ]),
+    ) {
+        let data = format!(r#"{{"type":"{kind}","id":{id},"payload":{payload}}}"#);
+        if let Some(message) = GraphqlMessage::decode(data.as_bytes()) {
+            prop_assert_eq!(GraphqlMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[cfg(feature = "formats")]
+    #[test]
+    fn graphql_message_decoder_takes_any_bytes(data in vec(any::<u8>(), 0..64)) {
+        if let Some(message) = GraphqlMessage::decode(&data) {
+            prop_assert_eq!(GraphqlMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn segments_reassemble_in_any_order(
+        upload_id in 0..=varint::MAX,
+        payload in vec(any::<u8>(), 0..2000),
+        cuts in vec(any::<usize>(), 0..8),
+        order in any::<u64>(),
+    ) {
+        let mut offset = 0;
+        let mut streams: Vec<Vec<u8>> = split(&payload, &cuts)
+            .into_iter()
+            .map(|segment| {
+                let stream =
+                    encode_segment(upload_id, offset, payload.len() as u64, &segment);
+                offset += segment.len() as u64;
+                stream
+            })
+            .collect();
+        let len = streams.len();
+        streams.rotate_left(order as usize % len);
+
+        let mut reassembled = vec![0; payload.len()];
+        let mut received = 0;
+        for stream in &streams {
+            let segment = decode_segment(stream).unwrap();
+            prop_assert_eq!(segment.upload_id, upload_id);
+            prop_assert_eq!(segment.total_len, payload.len() as u64);
+            let start = segment.offset as usize;
+            reassembled[start..start + segment.data.len()].copy_from_slice(&segment.data);
+            received += segment.data.len();
+        }
+        prop_assert_eq!(received, payload.len());
+        prop_assert_eq!(reassembled, payload);
+    }
+
+    #[test]
+    fn segment_past_the_end_is_rejected(
+        offset in 0..1_000_000u64,
+        data in vec(any::<u8>(), 1..200),
+        short_by in 1..200u64,
+    ) {
+        let end = offset + data.len() as u64;
+        let total_len = end.saturating_sub(short_by);
+        let stream = encode_segment(7, offset, total_len, &data);
+        prop_assert_eq!(decode_segment(&stream), None);
+    }
+
+    #[test]
+    fn truncated_segment_header_is_rejected(
+        upload_id in 0..=varint::MAX,
+        offset in 0..=varint::MAX,
+        cut in any::<usize>(),
+    ) {
+        let stream = encode_segment(upload_id, offset, varint::MAX, &[]);
+        let cut = cut % stream.len();
+        prop_assert_eq!(decode_segment(&stream[..cut]), None);
+    }
+
+    #[test]
+    fn segment_decoder_takes_any_bytes(stream in vec(any::<u8>(), 0..64)) {
+        if let Some(segment) = decode_segment(&stream) {
+            prop_assert!(segment.offset + segment.data.len() as u64 <= segment.total_len);
+        }
+    }
+
+    #[test]
+    fn framed_messages_survive_any_chunking(
+        framing in framings(),
+        messages in messages(),
+        cuts in vec(any::<usize>(), 0..40),
+    ) {
+        let mut stream = Vec::new();
+        for message in &messages {
+            framing.encode(message, &mut stream);
+        }
+        let mut deframer = Deframer::new(framing);
+        let mut decoded = Vec::new();
+        for chunk in split(&stream, &cuts) {
+            deframer.push(&chunk);
+            while let Some(message) = deframer.next_message().unwrap() {
+                decoded.push(message);
+            }
+        }
+        prop_assert_eq!(decoded, messages);
+        prop_assert_eq!(deframer.buffered(), 0);
+    }
+
+    #[test]
+    fn large_messages_come_in_parts(
+        framing in framings(),
+        messages in messages(),
+        threshold in 0..300usize,
+        cuts in vec(any::<usize>(), 0..40),
+    ) {
+        let mut stream = Vec::new();
+        for message in &messages {
+            framing.encode(message, &mut stream);
+        }
+        let mut deframer = Deframer::new(framing);
+        let mut decoded = Vec::new();
+        let mut large = None::<(u64, Vec<u8>)>;
+        for chunk in split(&stream, &cuts) {
+            deframer.push(&chunk);
+            while let Some(part) = deframer.next_part(threshold).unwrap() {
+                match part {
+                    MessagePart::Whole(message) => {
+                        prop_assert!(large.is_none());
+                        prop_assert!(framing == Framing::Cobs || message.len() <= threshold);
+                        decoded.push(message);
+                    }
+                    MessagePart::Start { len } => {
+                        prop_assert!(large.is_none());
+                        prop_assert!(len > threshold as u64);
+                        large = Some((len, Vec::new()));
+                    }
+                    MessagePart::Body { data, last } => {
+                        let (len, mut message) = large.take().unwrap();
+                        message.extend_from_slice(&data);
+                        prop_assert_eq!(last, message.len() as u64 == len);
+                        if last {
+                            decoded.push(message);
+                        } else {
+                            large = Some((len, message));
+                        }
+                    }
+                }
+            }
+        }
+        prop_assert_eq!(decoded, messages);
+        prop_assert_eq!(deframer.buffered(), 0);
+    }
+
+    #[test]
+    fn overlong_protobuf_prefix_is_rejected(extra in 0..8usize) {
+        let mut deframer = Deframer::new(Framing::Protobuf);
+        deframer.push(&vec![0x80; 10 + extra]);
+        prop_assert_eq!(deframer.next_message(), Err(FramingError::MalformedLength));
+        prop_assert_eq!(deframer.buffered(), 0);
+    }
+
+    #[test]
+    fn deframer_takes_any_bytes(
+        framing in framings(),
+        stream in vec(any::<u8>(), 0..64),
+    ) {
+        let mut deframer = Deframer::new(framing);
+        deframer.push(&stream);
+        while let Ok(Some(message)) = deframer.next_message() {
+            prop_assert!(message.len() < stream.len());
+        }
+        prop_assert!(deframer.buffered() <= stream.len());
+    }
+
+    #[test]
+    fn cobs_frames_end_at_their_only_zero(message in vec(any::<u8>(), 0..600)) {
+        let mut frame = Vec::new();
+        Framing::Cobs.encode(&message, &mut frame);
+        prop_assert_eq!(frame.iter().position(|&byte| byte == 0), Some(frame.len() - 1));
+        prop_assert!(frame.len() <= message.len() + message.len() / 254 + 2);
+    }
+
+    #[test]
+    fn malformed_cobs_frame_spares_the_next(message in vec(any::<u8>(), 0..300)) {
+        let mut deframer = Deframer::new(Framing::Cobs);
+        deframer.push(&[0, 5, 1, 0]);
+        let mut frame = Vec::new();
+        Framing::Cobs.encode(&message, &mut frame);
+        deframer.push(&frame);
+        prop_assert_eq!(deframer.next_message(), Err(FramingError::MalformedCobs));
+        prop_assert_eq!(deframer.next_message(), Ok(Some(message)));
+    }
+
+    #[cfg(feature = "checksum")]
+    #[test]
+    fn checksummed_messages_round_trip(
+        checksum in prop_oneof![Just(Checksum::Crc32), Just(Checksum::Xxh3)],
+        message in vec(any::<u8>(), 0..300),
+    ) {
+        let mut checked = message.clone();
+        checksum.append(&mut checked);
+        prop_assert_eq!(checksum.verify(checked), Ok(message));
+    }
+
+    #[cfg(feature = "checksum")]
+    #[test]
+    fn corrupted_messages_fail_their_checksum(
+        checksum in prop_oneof![Just(Checksum::Crc32), Just(Checksum::Xxh3)],
+        message in vec(any::<u8>(), 0..300),
+        bit in any::<usize>(),
+        cut in any::<usize>(),
+    ) {
+        let mut checked = message;
+        checksum.append(&mut checked);
+        let mut flipped = checked.clone();
+        let bit = bit % (flipped.len() * 8);
+        flipped[bit / 8] ^= 1 << (bit % 8);
+        prop_assert_eq!(checksum.verify(flipped), Err(FramingError::ChecksumMismatch));
+        let cut = cut % checked.len();
+        checked.truncate(cut);
+        prop_assert_eq!(checksum.verify(checked), Err(FramingError::ChecksumMismatch));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn sealed_messages_open_on_their_channel_only(
+        key in any::<[u8; 32]>(),
+        channel in 0..=varint::MAX,
+        other_channel in 0..=varint::MAX,
+        message in vec(any::<u8>(), 0..300),
+    ) {
+        let cipher = ChannelCipher::new(ChannelKey::new(1, key));
+        let sealed = cipher.seal(channel, &message);
+        prop_assert_eq!(cipher.open(channel, &sealed), Ok(message));
+        if other_channel != channel {
+            prop_assert_eq!(
+                cipher.open(other_channel, &sealed),
+                Err(ChannelCryptoError::Authentication)
+            );
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn tampered_messages_fail_to_open(
+        message in vec(any::<u8>(), 0..300),
+        bit in any::<usize>(),
+    ) {
+        let cipher = ChannelCipher::new(ChannelKey::new(1, [7; 32]));
+        let mut sealed = cipher.seal(0, &message);
+        let bit = bit % ((sealed.len() - 1) * 8);
+        // The first byte is the key id, flipping it would be reported as an unknown key.
+        sealed[1 + bit / 8] ^= 1 << (bit % 8);
+        prop_assert_eq!(cipher.open(0, &sealed), Err(ChannelCryptoError::Authentication));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn rotated_keys_open_messages_in_flight(message in vec(any::<u8>(), 0..300)) {
+        let sender = ChannelCipher::new(ChannelKey::new(1, [1; 32]));
+        let receiver = ChannelCipher::new(ChannelKey::new(1, [1; 32]));
+        receiver.on_unknown_key(|id| (id == 2).then(|| ChannelKey::new(2, [2; 32])));
+        let in_flight = sender.seal(0, &message);
+        sender.rotate(ChannelKey::new(2, [2; 32]));
+        let rotated = sender.seal(0, &message);
+        prop_assert_eq!(receiver.open(0, &rotated), Ok(message.clone()));
+        prop_assert_eq!(receiver.open(0, &in_flight), Ok(message.clone()));
+        prop_assert_eq!(sender.open(0, &in_flight), Ok(message));
+        sender.rotate(ChannelKey::new(3, [3; 32]));
+        prop_assert_eq!(sender.open(0, &in_flight), Err(ChannelCryptoError::UnknownKey(1)));
+    }
+}
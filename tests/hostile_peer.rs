@@ -0,0 +1,158 @@
+//! Datagram helpers fed with malformed or extreme input from the peer, which must neither hang
+//! nor panic.
+#![cfg(all(not(target_arch = "wasm32"), feature = "datagrams"))]
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use yew::Callback;
+use yew_webtransport::jitter::{JitterBuffer, JitterConfig, JitterMode};
+use yew_webtransport::mock::{MockWebTransport, MockWebTransportTask};
+use yew_webtransport::nack::{NackConfig, NackReceiver};
+use yew_webtransport::time::ManualScheduler;
+use yew_webtransport::timesync::{RttHistogram, TimeSync, TimeSyncConfig};
+use yew_webtransport::varint;
+
+const DATA: u64 = 1;
+const CONTROL: u64 = 2;
+const CLOCK: u64 = 3;
+
+fn connect(server: &MockWebTransport) -> MockWebTransportTask {
+    let task = server
+        .connect(
+            "https://example.com",
+            Callback::noop(),
+            Callback::noop(),
+            Callback::noop(),
+            Callback::noop(),
+        )
+        .unwrap();
+    server.open();
+    task
+}
+
+/// A datagram for channel `channel` carrying `values` as varints.
+fn datagram(channel: u64, values: &[u64]) -> Vec<u8> {
+    let mut data = Vec::new();
+    varint::encode(channel, &mut data);
+    for &value in values {
+        varint::encode(value, &mut data);
+    }
+    data
+}
+
+#[test]
+fn nack_receiver_skips_a_huge_sequence_jump() {
+    let server = MockWebTransport::new();
+    let task = connect(&server);
+    let config = NackConfig::default();
+    let max_missing = config.max_missing;
+    let receiver = NackReceiver::with_scheduler(
+        &task,
+        DATA,
+        CONTROL,
+        config,
+        Callback::noop(),
+        Rc::new(ManualScheduler::new()),
+    );
+
+    server.receive_datagram(datagram(DATA, &[0]));
+    server.receive_datagram(datagram(DATA, &[varint::MAX]));
+
+    assert_eq!(receiver.missing(), max_missing);
+    let stats = receiver.stats();
+    assert_eq!(stats.frames_received, 2);
+    assert_eq!(stats.frames_lost, varint::MAX - 1 - max_missing as u64);
+}
+
+/// A time sync answer on channel [`CLOCK`].
+fn answer(t0: f64, t1: f64, t2: f64) -> Vec<u8> {
+    let mut data = datagram(CLOCK, &[]);
+    data.push(1);
+    for time in [t0, t1, t2] {
+        data.extend_from_slice(&time.to_be_bytes());
+    }
+    data
+}
+
+#[test]
+fn time_sync_ignores_non_finite_answers() {
+    let server = MockWebTransport::new();
+    let task = connect(&server);
+    let updates = Rc::new(Cell::new(0));
+    let on_offset = {
+        let updates = updates.clone();
+        Callback::from(move |_| updates.set(updates.get() + 1))
+    };
+    let sync = TimeSync::with_scheduler(
+        &task,
+        CLOCK,
+        TimeSyncConfig::default(),
+        on_offset,
+        Rc::new(ManualScheduler::new()),
+    );
+
+    server.receive_datagram(answer(0.0, f64::INFINITY, 0.0));
+    server.receive_datagram(answer(0.0, 0.0, f64::NAN));
+    server.receive_datagram(answer(-f64::MAX, 0.0, f64::MAX));
+    assert_eq!(updates.get(), 0);
+    assert_eq!(sync.estimate(), None);
+    assert_eq!(sync.rtt_stats(), None);
+
+    server.receive_datagram(answer(0.0, 10.0, 10.0));
+    assert_eq!(updates.get(), 1);
+    assert_eq!(sync.rtt_stats().unwrap().samples, 1);
+}
+
+#[test]
+fn rtt_histogram_ignores_delays_out_of_range() {
+    let mut histogram = RttHistogram::new(8);
+    histogram.record(f64::INFINITY);
+    histogram.record(f64::MAX);
+    assert_eq!(histogram.stats(), None);
+
+    histogram.record(20.0);
+    assert_eq!(histogram.stats().unwrap().max_ms, 20.0);
+}
+
+#[test]
+fn default_rtt_histogram_is_bounded() {
+    let mut histogram = RttHistogram::default();
+    for round_trip in 0..1000 {
+        histogram.record(round_trip as f64);
+    }
+    let stats = histogram.stats().unwrap();
+    assert_eq!(stats.samples, TimeSyncConfig::default().rtt_history);
+    assert_eq!(stats.max_ms, 999.0);
+}
+
+#[test]
+fn jitter_buffer_survives_wild_timestamps() {
+    let clock = ManualScheduler::new();
+    let max_delay = Duration::from_millis(500);
+    let config = JitterConfig {
+        mode: JitterMode::Adaptive {
+            min_delay: Duration::from_millis(20),
+            max_delay,
+        },
+        ..JitterConfig::default()
+    };
+    let played = Rc::new(Cell::new(0));
+    let on_playout = {
+        let played = played.clone();
+        Callback::from(move |()| played.set(played.get() + 1))
+    };
+    let buffer = JitterBuffer::with_scheduler(config, on_playout, Rc::new(clock.clone()));
+
+    for sequence in 0..32 {
+        let timestamp = if sequence % 2 == 0 {
+            Duration::ZERO
+        } else {
+            Duration::MAX
+        };
+        buffer.push(sequence, timestamp, ());
+    }
+    assert_eq!(buffer.stats().delay, max_delay);
+
+    clock.advance(max_delay);
+    assert!(played.get() > 0);
+}
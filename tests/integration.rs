@@ -0,0 +1,141 @@
+//! End to end tests against the echo server in `examples/echo-server`.
+//!
+//! These run in a headless browser; use `tests/run-integration.sh`, which starts the server,
+//! points Chrome at it and runs `wasm-pack test`.
+#![cfg(target_arch = "wasm32")]
+
+use std::time::Duration;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use futures::{future, pin_mut, FutureExt, StreamExt};
+use js_sys::{Boolean, JsString, Reflect, Uint8Array};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+use web_sys::{ReadableStream, ReadableStreamDefaultReader};
+use yew::platform::time::sleep;
+use yew::Callback;
+use yew_webtransport::webtransport::{WebTransportService, WebTransportStatus, WebTransportTask};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const ECHO_SERVER_URL: &str = match option_env!("ECHO_SERVER_URL") {
+    Some(url) => url,
+    None => "https://127.0.0.1:4433",
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Session {
+    task: WebTransportTask,
+    datagrams: UnboundedReceiver<Vec<u8>>,
+    unidirectional_streams: UnboundedReceiver<ReadableStream>,
+    statuses: UnboundedReceiver<WebTransportStatus>,
+}
+
+fn forward<T: 'static>() -> (Callback<T>, UnboundedReceiver<T>) {
+    let (sender, receiver) = unbounded();
+    let callback = Callback::from(move |value| {
+        let _ = sender.unbounded_send(value);
+    });
+    (callback, receiver)
+}
+
+async fn next<T>(receiver: &mut UnboundedReceiver<T>) -> T {
+    let timeout = sleep(TIMEOUT).fuse();
+    let next = receiver.next().fuse();
+    pin_mut!(timeout, next);
+    match future::select(next, timeout).await {
+        future::Either::Left((Some(value), _)) => value,
+        future::Either::Left((None, _)) => panic!("channel closed"),
+        future::Either::Right(_) => panic!("timed out after {TIMEOUT:?}"),
+    }
+}
+
+async fn connect() -> Session {
+    let (on_datagram, datagrams) = forward();
+    let (on_unidirectional_stream, unidirectional_streams) = forward();
+    let (notification, mut statuses) = forward();
+    let task = WebTransportService::connect(
+        ECHO_SERVER_URL,
+        on_datagram,
+        on_unidirectional_stream.reform(|stream: web_sys::WebTransportReceiveStream| {
+            stream.unchecked_into::<ReadableStream>()
+        }),
+        Callback::noop(),
+        notification,
+    )
+    .expect("failed to create the session");
+    assert_eq!(next(&mut statuses).await, WebTransportStatus::Opened);
+    Session {
+        task,
+        datagrams,
+        unidirectional_streams,
+        statuses,
+    }
+}
+
+async fn read_to_end(stream: ReadableStream) -> Vec<u8> {
+    let reader: ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+    let mut data = Vec::new();
+    loop {
+        let result = JsFuture::from(reader.read()).await.expect("read failed");
+        let done = Reflect::get(&result, &JsString::from("done"))
+            .unwrap()
+            .unchecked_into::<Boolean>();
+        if done.is_truthy() {
+            return data;
+        }
+        let chunk: Uint8Array = Reflect::get(&result, &JsString::from("value"))
+            .unwrap()
+            .unchecked_into();
+        data.extend(chunk.to_vec());
+    }
+}
+
+#[wasm_bindgen_test]
+async fn connects() {
+    let session = connect().await;
+    session.task.transport.close();
+}
+
+#[wasm_bindgen_test]
+async fn echoes_datagrams() {
+    let mut session = connect().await;
+    WebTransportTask::send_datagram(session.task.transport.clone(), b"ping".to_vec());
+    assert_eq!(next(&mut session.datagrams).await, b"ping".to_vec());
+}
+
+#[wasm_bindgen_test]
+async fn echoes_unidirectional_streams() {
+    let mut session = connect().await;
+    WebTransportTask::send_unidirectional_stream(session.task.transport.clone(), b"uni".to_vec());
+    let stream = next(&mut session.unidirectional_streams).await;
+    assert_eq!(read_to_end(stream).await, b"uni".to_vec());
+}
+
+#[wasm_bindgen_test]
+async fn echoes_bidirectional_streams() {
+    let session = connect().await;
+    let (on_chunk, mut chunks) = forward();
+    WebTransportTask::send_bidirectional_stream(
+        session.task.transport.clone(),
+        b"bidi".to_vec(),
+        on_chunk,
+    );
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        received.extend(next(&mut chunks).await);
+    }
+    assert_eq!(received, b"bidi".to_vec());
+}
+
+#[wasm_bindgen_test]
+async fn reports_close() {
+    let mut session = connect().await;
+    session.task.transport.close();
+    assert!(matches!(
+        next(&mut session.statuses).await,
+        WebTransportStatus::Closed(_)
+    ));
+}
@@ -0,0 +1,32 @@
+//! Native sessions, against a peer that never answers so that no server is needed.
+#![cfg(all(not(target_arch = "wasm32"), feature = "native"))]
+
+use futures::channel::oneshot;
+use futures::FutureExt;
+use std::net::UdpSocket;
+use yew::platform::Runtime;
+use yew::Callback;
+use yew_webtransport::native::{NativeOptions, WebTransportService};
+
+#[test]
+fn dropping_the_task_closes_the_session() {
+    let silent_peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://{}/", silent_peer.local_addr().unwrap());
+    let (done, result) = oneshot::channel();
+    Runtime::default().spawn_pinned(move || async move {
+        let task = WebTransportService::connect_with_options(
+            &url,
+            NativeOptions::default(),
+            Callback::noop(),
+            Callback::noop(),
+            Callback::noop(),
+            Callback::noop(),
+        )
+        .unwrap();
+        let transport = task.transport.clone();
+        drop(task);
+        let _ = done.send(transport.ready().now_or_never());
+    });
+    let ready = futures::executor::block_on(result).unwrap();
+    assert_eq!(ready, Some(Err("session is closed".to_string())));
+}
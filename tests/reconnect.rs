@@ -0,0 +1,323 @@
+//! Reconnect logic driven against a mocked server behind the network simulator, with scripted
+//! failures and a manual clock, so that every delay can be asserted exactly.
+#![cfg(all(not(target_arch = "wasm32"), feature = "datagrams"))]
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use futures::future;
+use yew::Callback;
+use yew_webtransport::mock::{MockWebTransport, MockWebTransportTask, SentMessage};
+use yew_webtransport::reconnect::{ReconnectPolicy, ReconnectState, ReconnectingSession};
+use yew_webtransport::sim::{NetworkConditions, NetworkSimulator, SimulatedTransport};
+use yew_webtransport::time::{ManualScheduler, Scheduler};
+use yew_webtransport::transport::Transport;
+use yew_webtransport::webtransport::{CloseInfo, SessionError, WebTransportStatus};
+
+type Session = ReconnectingSession<SimulatedTransport<MockWebTransportTask>>;
+
+const LATENCY: Duration = Duration::from_millis(50);
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_delay: ms(100),
+        max_delay: ms(1000),
+        max_attempts,
+    }
+}
+
+/// The delay before the next attempt of a session waiting to reconnect.
+fn next_delay(session: &Session) -> Duration {
+    policy(None).delay(session.attempts())
+}
+
+/// A server reached through a simulated network, recording when the client connects and what
+/// status notifications it is passed.
+struct Harness {
+    clock: ManualScheduler,
+    server: MockWebTransport,
+    /// Connection attempts left to refuse.
+    refusals: Rc<Cell<u32>>,
+    attempts: Rc<RefCell<Vec<Duration>>>,
+    statuses: Rc<RefCell<Vec<(Duration, WebTransportStatus)>>>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        Self {
+            clock: ManualScheduler::new(),
+            server: MockWebTransport::new(),
+            refusals: Rc::default(),
+            attempts: Rc::default(),
+            statuses: Rc::default(),
+        }
+    }
+
+    fn connect(&self, policy: ReconnectPolicy) -> Session {
+        let scheduler: Rc<dyn Scheduler> = Rc::new(self.clock.clone());
+        let sim = NetworkSimulator::with_scheduler(
+            NetworkConditions::with_latency(LATENCY),
+            scheduler.clone(),
+        );
+        let notification = {
+            let clock = self.clock.clone();
+            let statuses = self.statuses.clone();
+            Callback::from(move |status| statuses.borrow_mut().push((clock.now(), status)))
+        };
+        let server = self.server.clone();
+        let refusals = self.refusals.clone();
+        let attempts = self.attempts.clone();
+        let clock = self.clock.clone();
+        ReconnectingSession::with_scheduler(policy, notification, scheduler, move |notification| {
+            attempts.borrow_mut().push(clock.now());
+            if refusals.get() > 0 {
+                refusals.set(refusals.get() - 1);
+                server.fail_next_connect("connection refused");
+            }
+            let task = server.connect(
+                "https://example.com:4433",
+                sim.incoming_datagrams(Callback::noop()),
+                sim.incoming(Callback::noop()),
+                sim.incoming(Callback::noop()),
+                sim.incoming(notification),
+            );
+            future::ready(task.map(|task| sim.wrap(task)))
+        })
+    }
+
+    fn attempts(&self) -> Vec<Duration> {
+        self.attempts.borrow().clone()
+    }
+
+    fn statuses(&self) -> Vec<(Duration, WebTransportStatus)> {
+        self.statuses.borrow().clone()
+    }
+
+    fn lose_session(&self) {
+        self.server.close_with(CloseInfo {
+            close_code: Some(0),
+            reason: "server restarting".to_string(),
+        });
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_the_maximum() {
+    let harness = Harness::new();
+    harness.refusals.set(6);
+    let session = harness.connect(policy(None));
+    assert_eq!(session.state(), ReconnectState::Waiting);
+
+    harness.clock.advance(ms(10_000));
+    let expected: Vec<Duration> = [0, 100, 300, 700, 1500, 2500, 3500]
+        .into_iter()
+        .map(ms)
+        .collect();
+    assert_eq!(harness.attempts(), expected);
+    assert_eq!(session.state(), ReconnectState::Connecting);
+    assert_eq!(session.attempts(), 6);
+
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+    assert_eq!(session.state(), ReconnectState::Open);
+    assert_eq!(session.attempts(), 0);
+    assert_eq!(session.reconnects(), 0);
+    assert!(session.session().is_some());
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let harness = Harness::new();
+    harness.refusals.set(u32::MAX);
+    let session = harness.connect(policy(Some(3)));
+
+    harness.clock.run_until_idle();
+    let expected: Vec<Duration> = [0, 100, 300, 700].into_iter().map(ms).collect();
+    assert_eq!(harness.attempts(), expected);
+    assert_eq!(session.state(), ReconnectState::GaveUp);
+    assert_eq!(harness.clock.pending(), 0);
+    assert!(session.session().is_none());
+}
+
+#[test]
+fn failed_handshakes_back_off_like_refused_connections() {
+    let harness = Harness::new();
+    let session = harness.connect(policy(None));
+
+    // Each handshake fails once the failure crossed the simulated network.
+    for _ in 0..3 {
+        harness
+            .server
+            .fail(SessionError::Handshake("unknown path".to_string()));
+        harness.clock.advance(LATENCY);
+        assert_eq!(session.state(), ReconnectState::Waiting);
+        harness.clock.advance(next_delay(&session));
+    }
+    let expected: Vec<Duration> = [0, 150, 400, 850].into_iter().map(ms).collect();
+    assert_eq!(harness.attempts(), expected);
+    assert_eq!(session.state(), ReconnectState::Connecting);
+}
+
+#[test]
+fn status_sequence_across_a_lost_session() {
+    let harness = Harness::new();
+    let session = harness.connect(policy(None));
+    harness.server.open();
+    assert_eq!(session.state(), ReconnectState::Connecting);
+    harness.clock.advance(LATENCY);
+    assert_eq!(session.state(), ReconnectState::Open);
+
+    harness.clock.advance(ms(950));
+    harness.lose_session();
+    harness.clock.advance(LATENCY);
+    assert_eq!(session.state(), ReconnectState::Waiting);
+    assert!(session.session().is_none());
+
+    harness.clock.advance(ms(100));
+    assert_eq!(harness.attempts(), vec![ms(0), ms(1150)]);
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+
+    let closed = WebTransportStatus::Closed(CloseInfo {
+        close_code: Some(0),
+        reason: "server restarting".to_string(),
+    });
+    assert_eq!(
+        harness.statuses(),
+        vec![
+            (ms(50), WebTransportStatus::Opened),
+            (ms(1050), closed),
+            (ms(1200), WebTransportStatus::Opened),
+        ]
+    );
+    assert_eq!(session.state(), ReconnectState::Open);
+    assert_eq!(session.reconnects(), 1);
+}
+
+#[test]
+fn attempts_reset_once_the_session_opens() {
+    let harness = Harness::new();
+    harness.refusals.set(2);
+    let session = harness.connect(policy(None));
+    harness.clock.advance(ms(300));
+    assert_eq!(harness.attempts(), vec![ms(0), ms(100), ms(300)]);
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+    assert_eq!(session.attempts(), 0);
+
+    harness.lose_session();
+    harness.clock.advance(LATENCY);
+    harness.clock.advance(ms(100));
+    assert_eq!(harness.attempts().last(), Some(&ms(500)));
+}
+
+#[test]
+fn closing_stops_reconnecting() {
+    let harness = Harness::new();
+    harness.refusals.set(u32::MAX);
+    let session = harness.connect(policy(None));
+    harness.clock.advance(ms(100));
+    assert_eq!(session.state(), ReconnectState::Waiting);
+
+    session.close().unwrap();
+    harness.clock.advance(ms(10_000));
+    assert_eq!(session.state(), ReconnectState::Closed);
+    assert_eq!(harness.attempts(), vec![ms(0), ms(100)]);
+    assert!(session.close().is_err());
+}
+
+#[test]
+fn closing_an_open_session_does_not_reconnect() {
+    let harness = Harness::new();
+    let session = harness.connect(policy(None));
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+
+    session.close().unwrap();
+    assert!(harness.server.is_closed());
+    harness.lose_session();
+    harness.clock.run_until_idle();
+    assert_eq!(session.state(), ReconnectState::Closed);
+    assert_eq!(harness.attempts(), vec![ms(0)]);
+}
+
+#[test]
+fn messages_queued_offline_flush_in_order() {
+    let harness = Harness::new();
+    let session = harness.connect(policy(None));
+    // Messages sent while there is no session wait in the outbox, which is flushed by the
+    // restored setup as soon as a session opens.
+    let outbox: Rc<RefCell<Vec<Vec<u8>>>> = Rc::default();
+    let send = |message: &str| match session.state() {
+        ReconnectState::Open => session
+            .session()
+            .unwrap()
+            .send_datagram(message.as_bytes().to_vec()),
+        _ => outbox.borrow_mut().push(message.as_bytes().to_vec()),
+    };
+    let _flush = {
+        let outbox = outbox.clone();
+        session.restore(move |session| {
+            for message in outbox.borrow_mut().drain(..) {
+                session.send_datagram(message);
+            }
+        })
+    };
+
+    send("first");
+    send("second");
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+    send("third");
+    harness.clock.advance(LATENCY);
+    let datagram = |message: &str| SentMessage::Datagram(message.as_bytes().to_vec());
+    assert_eq!(
+        harness.server.take_sent(),
+        vec![datagram("first"), datagram("second"), datagram("third")]
+    );
+
+    harness.lose_session();
+    harness.clock.advance(LATENCY);
+    send("fourth");
+    send("fifth");
+    harness.clock.advance(ms(100));
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+    send("sixth");
+    harness.clock.advance(LATENCY);
+    assert_eq!(
+        harness.server.sent(),
+        vec![datagram("fourth"), datagram("fifth"), datagram("sixth")]
+    );
+    assert!(outbox.borrow().is_empty());
+}
+
+#[test]
+fn flush_before_loss_reaches_the_first_session() {
+    let harness = Harness::new();
+    let session = harness.connect(policy(None));
+    let _greeting = session.restore(|session| session.send_datagram(b"hello".to_vec()));
+    harness.server.open();
+    harness.clock.advance(LATENCY);
+    harness.clock.advance(LATENCY);
+    assert_eq!(
+        harness.server.take_sent(),
+        vec![SentMessage::Datagram(b"hello".to_vec())]
+    );
+
+    harness.lose_session();
+    harness.clock.advance(LATENCY + ms(100));
+    harness.server.open();
+    harness.clock.advance(LATENCY * 2);
+    assert_eq!(
+        harness.server.sent(),
+        vec![SentMessage::Datagram(b"hello".to_vec())]
+    );
+}
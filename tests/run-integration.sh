@@ -0,0 +1,38 @@
+#!/usr/bin/env bash
+# Runs the browser integration tests against a freshly started echo server.
+#
+# Requires openssl, wasm-pack and Chrome with a matching chromedriver on the PATH.
+set -euo pipefail
+
+ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+PORT="${ECHO_SERVER_PORT:-4433}"
+WORK="$(mktemp -d)"
+trap 'kill ${SERVER_PID:-} 2>/dev/null || true; rm -rf "$WORK"' EXIT
+
+# A short lived ECDSA certificate, as browsers require for self-signed WebTransport servers.
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes -days 10 \
+    -subj "/CN=localhost" -addext "subjectAltName=DNS:localhost,IP:127.0.0.1" \
+    -keyout "$WORK/key.pem" -out "$WORK/cert.pem" 2>/dev/null
+SPKI="$(openssl x509 -pubkey -noout -in "$WORK/cert.pem" \
+    | openssl pkey -pubin -outform der \
+    | openssl dgst -sha256 -binary | base64)"
+
+cargo run --quiet --manifest-path "$ROOT/examples/echo-server/Cargo.toml" -- \
+    "$PORT" "$WORK/cert.pem" "$WORK/key.pem" &
+SERVER_PID=$!
+sleep 2
+
+cat > "$ROOT/webdriver.json" <<JSON
+{
+  "goog:chromeOptions": {
+    "args": [
+      "--origin-to-force-quic-on=127.0.0.1:$PORT",
+      "--ignore-certificate-errors-spki-list=$SPKI"
+    ]
+  }
+}
+JSON
+
+cd "$ROOT"
+ECHO_SERVER_URL="https://127.0.0.1:$PORT" RUSTFLAGS=--cfg=web_sys_unstable_apis \
+    wasm-pack test --headless --chrome --test integration
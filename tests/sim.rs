@@ -0,0 +1,154 @@
+//! The network simulator in front of a session that sorts channel datagrams into priority
+//! lanes, checking that what the wrapped session is told about its channels still applies and
+//! that what it reports about its streams still reaches the application.
+#![cfg(all(not(target_arch = "wasm32"), feature = "datagrams"))]
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    time::Duration,
+};
+
+#[cfg(feature = "streams")]
+use futures::future::{FutureExt, LocalBoxFuture};
+use yew::Callback;
+use yew_webtransport::channel::{
+    DatagramChannel, DatagramFilter, DatagramRouter, DatagramSubscription,
+};
+use yew_webtransport::sim::{NetworkConditions, NetworkSimulator};
+use yew_webtransport::time::{ManualScheduler, Scheduler};
+#[cfg(feature = "streams")]
+use yew_webtransport::transport::StreamWriter;
+use yew_webtransport::transport::{DatagramPriority, QueuedSends, Transport};
+use yew_webtransport::webtransport::WebTransportError;
+
+const LATENCY: Duration = Duration::from_millis(50);
+
+/// A session recording the lane every channel datagram is sent in, and counting them as
+/// queued on their channel.
+#[derive(Default)]
+struct LaneRecorder {
+    router: DatagramRouter,
+    high: Rc<RefCell<HashSet<u64>>>,
+    sent: Rc<RefCell<Vec<(u64, DatagramPriority)>>>,
+    queued: Rc<RefCell<HashMap<u64, QueuedSends>>>,
+}
+
+impl Transport for LaneRecorder {
+    fn send_datagram(&self, _data: Vec<u8>) {}
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream(&self, _data: Vec<u8>) {}
+
+    #[cfg(feature = "streams")]
+    fn send_unidirectional_stream_finished(
+        &self,
+        _data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<(), WebTransportError>> {
+        let error = WebTransportError::StreamError("reset by peer".to_string());
+        Box::pin(futures::future::ready(Err(error)))
+    }
+
+    #[cfg(feature = "streams")]
+    fn send_bidirectional_stream(&self, _data: Vec<u8>, _callback: Callback<Vec<u8>>) {}
+
+    #[cfg(feature = "streams")]
+    fn request(
+        &self,
+        _data: Vec<u8>,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, WebTransportError>> {
+        Box::pin(futures::future::pending())
+    }
+
+    #[cfg(feature = "streams")]
+    fn open_bidirectional_stream(&self, _on_data: Callback<Vec<u8>>) -> StreamWriter {
+        StreamWriter::new(|_| {}, || {})
+    }
+
+    fn datagram_channel(&self, id: u64, on_message: Callback<Vec<u8>>) -> DatagramChannel {
+        let high = self.high.clone();
+        let sent = self.sent.clone();
+        let queued = self.queued.clone();
+        let send = Rc::new(move |data: Vec<u8>| {
+            let priority = if high.borrow().contains(&id) {
+                DatagramPriority::High
+            } else {
+                DatagramPriority::Normal
+            };
+            sent.borrow_mut().push((id, priority));
+            let mut queued = queued.borrow_mut();
+            let queued = queued.entry(id).or_default();
+            queued.messages += 1;
+            queued.bytes += data.len() as u64;
+        });
+        self.router.channel(id, on_message, send)
+    }
+
+    fn subscribe_datagrams(
+        &self,
+        filter: Option<DatagramFilter>,
+        on_datagram: Callback<Vec<u8>>,
+    ) -> DatagramSubscription {
+        self.router.subscribe(filter, on_datagram)
+    }
+
+    fn queued_on_channel(&self, id: u64) -> QueuedSends {
+        self.queued.borrow().get(&id).copied().unwrap_or_default()
+    }
+
+    fn set_channel_priority(&self, id: u64, priority: DatagramPriority) {
+        let mut high = self.high.borrow_mut();
+        match priority {
+            DatagramPriority::High => high.insert(id),
+            DatagramPriority::Normal => high.remove(&id),
+        };
+    }
+
+    fn close(&self) -> Result<(), WebTransportError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn simulated_channels_keep_their_priority_and_counters() {
+    let clock = ManualScheduler::new();
+    let scheduler: Rc<dyn Scheduler> = Rc::new(clock.clone());
+    let sim = NetworkSimulator::with_scheduler(NetworkConditions::with_latency(LATENCY), scheduler);
+    let recorder = LaneRecorder::default();
+    let sent = recorder.sent.clone();
+    let transport = sim.wrap(recorder);
+
+    let audio = transport.datagram_channel(1, Callback::noop());
+    let chat = transport.datagram_channel(2, Callback::noop());
+    transport.set_channel_priority(1, DatagramPriority::High);
+    audio.send(b"frame");
+    chat.send(b"hello");
+    assert!(sent.borrow().is_empty());
+
+    clock.advance(LATENCY);
+    assert_eq!(
+        *sent.borrow(),
+        [(1, DatagramPriority::High), (2, DatagramPriority::Normal)]
+    );
+    assert_eq!(transport.queued_on_channel(1).messages, 1);
+    assert_eq!(transport.queued_on_channel(2).messages, 1);
+}
+
+#[cfg(feature = "streams")]
+#[test]
+fn simulated_finished_streams_report_the_wrapped_outcome() {
+    let clock = ManualScheduler::new();
+    let scheduler: Rc<dyn Scheduler> = Rc::new(clock.clone());
+    let sim = NetworkSimulator::with_scheduler(NetworkConditions::with_latency(LATENCY), scheduler);
+    let transport = sim.wrap(LaneRecorder::default());
+
+    let mut finished = transport.send_unidirectional_stream_finished(b"state".to_vec());
+    assert!((&mut finished).now_or_never().is_none());
+
+    clock.advance(LATENCY);
+    assert!(matches!(
+        finished.now_or_never(),
+        Some(Err(WebTransportError::StreamError(_)))
+    ));
+}
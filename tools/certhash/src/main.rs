@@ -0,0 +1,65 @@
+//! Prints the SHA-256 hash of a certificate, for clients to accept a self-signed server through
+//! `serverCertificateHashes`.
+//!
+//! Usage: `cargo run -p certhash -- [--base64] <cert.pem>`
+//!
+//! The hash is printed in hex, or in base64 with `--base64`. Both are accepted by
+//! `yew_webtransport::certhash::CertificateHash`. A file holding a chain gets one line per
+//! certificate, the server's first. Files that aren't PEM are hashed as DER.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const END: &str = "-----END CERTIFICATE-----";
+
+fn main() -> Result<()> {
+    let mut base64 = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--base64" => base64 = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => bail!("unexpected argument {arg:?}"),
+        }
+    }
+    let path = path.context("usage: certhash [--base64] <cert.pem>")?;
+    let contents = std::fs::read(&path).with_context(|| format!("failed to read {path}"))?;
+
+    for der in certificates(&contents)? {
+        let hash = Sha256::digest(der);
+        if base64 {
+            println!("{}", STANDARD.encode(hash));
+        } else {
+            let hex: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+            println!("{hex}");
+        }
+    }
+    Ok(())
+}
+
+/// The DER encoded certificates of a PEM file, or `contents` itself if it isn't PEM.
+fn certificates(contents: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let Ok(text) = std::str::from_utf8(contents) else {
+        return Ok(vec![contents.to_vec()]);
+    };
+    if !text.contains(BEGIN) {
+        return Ok(vec![contents.to_vec()]);
+    }
+    let mut certificates = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) {
+        let body = &rest[start + BEGIN.len()..];
+        let end = body.find(END).context("unterminated certificate")?;
+        let encoded: String = body[..end].split_whitespace().collect();
+        certificates.push(
+            STANDARD
+                .decode(encoded)
+                .context("invalid base64 in certificate")?,
+        );
+        rest = &body[end + END.len()..];
+    }
+    Ok(certificates)
+}
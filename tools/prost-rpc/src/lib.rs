@@ -0,0 +1,111 @@
+//! Generates a client calling the services of `.proto` files over WebTransport, from a build
+//! script, along with the messages prost generates.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() -> std::io::Result<()> {
+//!     prost_build::Config::new()
+//!         .service_generator(Box::new(yew_webtransport_prost::ClientGenerator::new()))
+//!         .compile_protos(&["proto/greeter.proto"], &["proto"])
+//! }
+//! ```
+//!
+//! A service `Greeter` gets a `GreeterClient<T>`, made from a
+//! `yew_webtransport::rpc::RpcClient<T>`, with a method per RPC, in snake case, taking the
+//! request message and resolving with the response:
+//!
+//! ```ignore
+//! let greeter = GreeterClient::new(RpcClient::new(session));
+//! let reply = greeter.say_hello(&HelloRequest { name: "yew".into() }).await?;
+//! ```
+//!
+//! Every call goes through `RpcClient::call_prost`, which needs the `prost` feature of
+//! yew-webtransport, with `/package.Service/Method` as the method name, the path gRPC uses, so
+//! a Rust server can dispatch on it. Streaming RPCs have no equivalent in the RPC layer, and
+//! are left out of the client.
+
+use std::fmt::Write;
+
+use prost_build::{Service, ServiceGenerator};
+
+/// Generates the clients, see the [crate documentation](crate).
+#[derive(Clone, Debug)]
+pub struct ClientGenerator {
+    crate_path: String,
+}
+
+impl Default for ClientGenerator {
+    fn default() -> Self {
+        Self {
+            crate_path: "::yew_webtransport".to_string(),
+        }
+    }
+}
+
+impl ClientGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path yew-webtransport is reached by in the generated code, `::yew_webtransport`
+    /// unless it is renamed or re-exported.
+    pub fn crate_path(mut self, crate_path: impl Into<String>) -> Self {
+        self.crate_path = crate_path.into();
+        self
+    }
+}
+
+impl ServiceGenerator for ClientGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        let krate = &self.crate_path;
+        let client = format!("{}Client", service.name);
+        let path = match service.package.as_str() {
+            "" => service.proto_name.clone(),
+            package => format!("{package}.{}", service.proto_name),
+        };
+        service.comments.append_with_indent(0, buf);
+        if !service.comments.leading.is_empty() {
+            buf.push_str("///\n");
+        }
+        let _ = writeln!(
+            buf,
+            "/// Calls the `{path}` service over WebTransport.
+pub struct {client}<T> {{
+    rpc: {krate}::rpc::RpcClient<T>,
+}}
+
+impl<T> ::core::clone::Clone for {client}<T> {{
+    fn clone(&self) -> Self {{
+        Self {{ rpc: self.rpc.clone() }}
+    }}
+}}
+
+impl<T: {krate}::transport::Transport + 'static> {client}<T> {{
+    pub fn new(rpc: {krate}::rpc::RpcClient<T>) -> Self {{
+        Self {{ rpc }}
+    }}
+
+    pub fn rpc(&self) -> &{krate}::rpc::RpcClient<T> {{
+        &self.rpc
+    }}"
+        );
+        for method in &service.methods {
+            if method.client_streaming || method.server_streaming {
+                continue;
+            }
+            buf.push('\n');
+            method.comments.append_with_indent(1, buf);
+            let _ = writeln!(
+                buf,
+                "    pub fn {name}(&self, request: &{input}) -> {krate}::rpc::RpcCall<{output}> {{
+        self.rpc.call_prost(\"/{path}/{proto_name}\", request)
+    }}",
+                name = method.name,
+                input = method.input_type,
+                output = method.output_type,
+                proto_name = method.proto_name,
+            );
+        }
+        buf.push_str("}\n");
+    }
+}